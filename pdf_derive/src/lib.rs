@@ -0,0 +1,229 @@
+//! `#[derive(Object)]` for structs that are dictionaries in PDF: each field is the entry of the
+//! key given with `#[pdf(key="...")]`. See `pdf::object::Object` for the attributes.
+//!
+//! Both directions are generated: `from_primitive` reads the entries, `to_primitive` and
+//! `serialize` write them (entries that are `null`, like `None`, are left out). A `Dictionary`
+//! field with `#[pdf(other)]` gets the entries of no other field, and writes them back.
+//!
+//! The generated code uses `Object`, `Resolve`, `check_type`, `Primitive`, `Dictionary`, `Result`
+//! and `ResultExt` of the `pdf` crate unqualified - they have to be in scope where the derive is
+//! used (`use pdf::object::*; use pdf::primitive::*; use pdf::{Result, ResultExt};`).
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use quote::Tokens;
+use syn::{Attribute, Body, DeriveInput, Field, Ident, Lit, MetaItem, NestedMetaItem, VariantData};
+
+#[proc_macro_derive(Object, attributes(pdf))]
+pub fn object(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+    impl_object(&ast).parse().unwrap()
+}
+
+/// The items of the `#[pdf(..)]` attributes in `attrs`.
+fn pdf_items(attrs: &[Attribute]) -> Vec<&MetaItem> {
+    let mut items = Vec::new();
+    for attr in attrs {
+        if let MetaItem::List (ref ident, ref nested) = attr.value {
+            if ident.as_ref() == "pdf" {
+                for item in nested {
+                    match *item {
+                        NestedMetaItem::MetaItem (ref item) => items.push(item),
+                        NestedMetaItem::Literal (ref lit) =>
+                            panic!("#[derive(Object)]: unexpected literal {:?} in #[pdf(..)]", lit),
+                    }
+                }
+            }
+        }
+    }
+    items
+}
+
+/// The `#[pdf(..)]` attribute of the struct.
+#[derive(Default)]
+struct GlobalAttrs {
+    /// `Type="..."`: the expected `/Type` (not checked with `Type=false`, or without `Type`)
+    type_name: Option<String>,
+    /// `Subtype="..."`: the expected `/Subtype`
+    subtype: Option<String>,
+}
+impl GlobalAttrs {
+    fn from_attrs(attrs: &[Attribute]) -> GlobalAttrs {
+        let mut result = GlobalAttrs::default();
+        for item in pdf_items(attrs) {
+            match *item {
+                MetaItem::NameValue (ref ident, Lit::Str (ref value, _)) if ident.as_ref() == "Type" =>
+                    result.type_name = Some(value.clone()),
+                MetaItem::NameValue (ref ident, Lit::Bool (false)) if ident.as_ref() == "Type" => {}
+                MetaItem::NameValue (ref ident, Lit::Str (ref value, _)) if ident.as_ref() == "Subtype" =>
+                    result.subtype = Some(value.clone()),
+                ref item => panic!("#[derive(Object)]: unknown struct attribute {:?}", item),
+            }
+        }
+        result
+    }
+    /// The entries that are checked when reading, and written: `(key, name)`.
+    fn type_entries(&self) -> Vec<(&'static str, &str)> {
+        let mut entries = Vec::new();
+        if let Some(ref name) = self.type_name {
+            entries.push(("Type", name.as_str()));
+        }
+        if let Some(ref name) = self.subtype {
+            entries.push(("Subtype", name.as_str()));
+        }
+        entries
+    }
+}
+
+/// The `#[pdf(..)]` attribute of a field.
+struct FieldAttrs {
+    /// `key="..."` - empty for `other`
+    key: String,
+    /// `default="expr"`: the value if the key is missing
+    default: Option<String>,
+    /// `other`: the entries that aren't any other field's
+    other: bool,
+}
+impl FieldAttrs {
+    fn from_field(field: &Field) -> FieldAttrs {
+        let mut key = None;
+        let mut default = None;
+        let mut other = false;
+        for item in pdf_items(&field.attrs) {
+            match *item {
+                MetaItem::NameValue (ref ident, Lit::Str (ref value, _)) if ident.as_ref() == "key" =>
+                    key = Some(value.clone()),
+                MetaItem::NameValue (ref ident, Lit::Str (ref value, _)) if ident.as_ref() == "default" =>
+                    default = Some(value.clone()),
+                MetaItem::Word (ref ident) if ident.as_ref() == "other" => other = true,
+                ref item => panic!("#[derive(Object)]: unknown field attribute {:?}", item),
+            }
+        }
+        if other {
+            if key.is_some() || default.is_some() {
+                panic!("#[derive(Object)]: field {:?} has #[pdf(other)] and a key", field.ident);
+            }
+            return FieldAttrs {key: String::new(), default: None, other: true};
+        }
+        FieldAttrs {
+            key: key.unwrap_or_else(|| panic!("#[derive(Object)]: field {:?} needs #[pdf(key=\"...\")]", field.ident)),
+            default: default,
+            other: false,
+        }
+    }
+}
+
+fn impl_object(ast: &DeriveInput) -> Tokens {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let fields = match ast.body {
+        Body::Struct (VariantData::Struct (ref fields)) => fields,
+        _ => panic!("#[derive(Object)] is only for structs with named fields"),
+    };
+    let attrs = GlobalAttrs::from_attrs(&ast.attrs);
+    let (other, fields): (Vec<(&Field, FieldAttrs)>, Vec<(&Field, FieldAttrs)>) = fields.iter()
+        .map(|field| (field, FieldAttrs::from_field(field)))
+        .partition(|&(_, ref attrs)| attrs.other);
+    if other.len() > 1 {
+        panic!("#[derive(Object)]: more than one field with #[pdf(other)]");
+    }
+    let other = other.first().map(|&(field, _)| field.ident.as_ref().unwrap());
+
+    // (following `ParseOptions::type_check`)
+    let type_checks: Vec<Tokens> = attrs.type_entries().into_iter().map(|(key, expected)| quote! {
+        check_type(&__dict, #key, #expected, __resolve.options())?;
+    }).collect();
+
+    // Each field is read into a variable of its name, so that defaults can use earlier fields.
+    let reads: Vec<Tokens> = fields.iter().map(|&(field, ref attrs)| {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let key = &attrs.key;
+        let missing = match attrs.default {
+            // (the expression is pasted as it is)
+            Some(ref expr) => {
+                let expr = Ident::new(expr.as_str());
+                quote! { #expr }
+            }
+            None => quote! {
+                <#ty as Object>::from_missing(__resolve)
+                    .chain_err(|| format!("Dictionary entry /{}", #key))?
+            },
+        };
+        quote! {
+            let #ident: #ty = match __dict.remove(#key) {
+                Some(p) => <#ty as Object>::from_primitive(p, __resolve)
+                    .chain_err(|| format!("Dictionary entry /{}", #key))?,
+                None => #missing,
+            };
+        }
+    }).collect();
+    // What is left of the dictionary - without the checked type names.
+    let read_other = match other {
+        Some(ident) => {
+            let type_keys: Vec<&str> = attrs.type_entries().into_iter().map(|(key, _)| key).collect();
+            quote! {
+                #(__dict.remove(#type_keys);)*
+                let #ident: Dictionary = __dict;
+            }
+        }
+        None => quote! {},
+    };
+    let inits: Vec<Tokens> = fields.iter().map(|&(field, _)| field.ident.as_ref().unwrap())
+        .chain(other)
+        .map(|ident| quote! { #ident: #ident })
+        .collect();
+
+    // (the fields replace entries of `other` with the same keys)
+    let write_other = match other {
+        Some(ident) => quote! {
+            for (key, val) in self.#ident.iter() {
+                __dict.insert(key.clone(), val.clone());
+            }
+        },
+        None => quote! {},
+    };
+    let type_writes: Vec<Tokens> = attrs.type_entries().into_iter().map(|(key, name)| quote! {
+        __dict.insert(#key, Primitive::Name (#name.into()));
+    }).collect();
+    let writes: Vec<Tokens> = fields.iter().map(|&(field, ref attrs)| {
+        let ident = field.ident.as_ref().unwrap();
+        let key = &attrs.key;
+        quote! {
+            match Object::to_primitive(&self.#ident).chain_err(|| format!("Dictionary entry /{}", #key))? {
+                Primitive::Null => { __dict.remove(#key); }
+                p => { __dict.insert(#key, p); }
+            }
+        }
+    }).collect();
+
+    quote! {
+        impl #impl_generics Object for #name #ty_generics #where_clause {
+            fn serialize<W: ::std::io::Write>(&self, out: &mut W) -> ::std::io::Result<()> {
+                match Object::to_primitive(self) {
+                    Ok(p) => Object::serialize(&p, out),
+                    Err(e) => Err(::std::io::Error::new(::std::io::ErrorKind::Other, e.to_string())),
+                }
+            }
+            fn from_primitive(__p: Primitive, __resolve: &Resolve) -> Result<Self> {
+                #[allow(unused_mut)]
+                let mut __dict = <Dictionary as Object>::from_primitive(__p, __resolve)?;
+                #(#type_checks)*
+                #(#reads)*
+                #read_other
+                Ok(#name { #(#inits),* })
+            }
+            fn to_primitive(&self) -> Result<Primitive> {
+                let mut __dict = Dictionary::new();
+                #write_other
+                #(#type_writes)*
+                #(#writes)*
+                Ok(Primitive::Dictionary (__dict))
+            }
+        }
+    }
+}
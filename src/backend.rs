@@ -1,10 +1,13 @@
 use memmap::{Mmap, Protection};
 use err::*;
 use std::fs::File;
-use std::io::Read;
-use parser::Lexer;
-use parser::{read_xref_and_trailer_at, parse_indirect_object, parse};
-use xref::{XRef, XRefTable};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Mutex;
+use std::borrow::Cow;
+use std::{cmp, str};
+use parser::{Lexer, ParseOptions, find_substr};
+use parser::{read_xref_and_trailer_at, parse_xref_stream_and_trailer, parse_indirect_object, parse_with_lexer};
+use xref::{XRef, XRefTable, XRefSection};
 use primitive::{Primitive, Dictionary};
 use object::*;
 
@@ -15,103 +18,377 @@ use std::ops::{
     Range,
 };
 
+/// Where `startxref` is looked for first: the end of the file.
+const TAIL_SIZE: usize = 1024;
+/// The first part of the data that `Backend::parse_at` tries to parse.
+const WINDOW_SIZE: usize = 4096;
 
+/// Random access to the data of a PDF file. `File` and the parser only use `read_at` and `len`,
+/// so the data may be in memory, mapped, in a file, or remote.
 pub trait Backend: Sized {
     fn open(path: &str) -> Result<Self>;
-    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]>;
-    fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]>;
+    /// Reads `len` bytes at `offset`, or fewer at the end of the file. Reading at an offset
+    /// past the end fails with `ReadOutOfBounds`. Backends that have the data in memory should
+    /// return it borrowed.
+    fn read_at(&self, offset: usize, len: usize) -> Result<Cow<[u8]>>;
     fn len(&self) -> usize;
 
+    /// `read_at` with a range.
+    fn read<T: IndexRange>(&self, range: T) -> Result<Cow<[u8]>> {
+        let r = range.to_range(self.len());
+        self.read_at(r.start, r.end.saturating_sub(r.start))
+    }
+
     /// Returns the value of startxref (currently only used internally!)
+    ///
+    /// `startxref` should be in the last 1024 bytes, but some files have junk appended after
+    /// `%%EOF`, so ever larger parts of the end of the file are searched.
     fn locate_xref_offset(&self) -> Result<usize> {
         // locate the xref offset at the end of the file
         // `\nPOS\n%%EOF` where POS is the position encoded as base 10 integer.
         // u64::MAX has 20 digits + \n\n(2) + %%EOF(5) = 27 bytes max.
-
-        let mut lexer = Lexer::new(self.read(..)?);
-        lexer.set_pos_from_end(0);
-        lexer.seek_substr_back(b"startxref")?;
-        Ok(lexer.next()?.to::<usize>()?)
+        let len = self.len();
+        let mut size = TAIL_SIZE;
+        loop {
+            let start = len.saturating_sub(size);
+            if let Some(offset) = find_startxref(&self.read(start ..)?) {
+                if offset >= len {
+                    bail!("startxref {} is beyond the end of the file ({} bytes)", offset, len);
+                }
+                return Ok(offset);
+            }
+            if start == 0 {
+                bail!(ErrorKind::NotFound {word: "startxref".into()});
+            }
+            size *= 8;
+        }
+    }
+    /// Runs `parse` on the data from `pos`. So that backends which load data on demand need not
+    /// load the rest of the file, `parse` first gets a small part of it, and, if it fails, ever
+    /// larger parts.
+    fn parse_at<T, F>(&self, pos: usize, parse: F) -> Result<T>
+        where F: Fn(&mut Lexer) -> Result<T>
+    {
+        let len = self.len();
+        let mut size = WINDOW_SIZE;
+        loop {
+            let end = cmp::min(pos.saturating_add(size), len);
+            let data = self.read_at(pos, end.saturating_sub(pos))?;
+            let mut lexer = Lexer::new(&data);
+            match parse(&mut lexer) {
+                Ok(t) => return Ok(t),
+                Err(e) => if end == len {
+                    return Err(e);
+                }
+            }
+            size *= 8;
+        }
     }
     /// Used internally by File, but could also be useful for applications that want to look at the raw PDF objects.
+    ///
+    /// Follows the `/Prev` chain of incrementally updated files: the newest section of each
+    /// object wins. The returned trailer is the newest one.
     fn read_xref_table_and_trailer(&self) -> Result<(XRefTable, Dictionary)> {
-        let xref_offset = self.locate_xref_offset()?;
-        let mut lexer = Lexer::new(self.read(xref_offset..)?);
-        
-        let (xref_sections, trailer) = read_xref_and_trailer_at(&mut lexer, NO_RESOLVE)?;
-        
-        let highest_id = trailer.get("Size")
-            .ok_or_else(|| ErrorKind::EntryNotFound {key: "Size"})?
-            .clone().as_integer()?;
-
-        let mut refs = XRefTable::new(highest_id as ObjNr);
-        for section in xref_sections {
-            refs.add_entries_from(section);
-        }
-        
-        let mut prev_trailer = {
-            match trailer.get("Prev") {
-                Some(p) => Some(p.as_integer()?),
-                None => None
+        build_xref_table(self.read_revisions()?, self.len() as ObjNr)
+    }
+    /// The revisions of the document, following the `/Prev` chain from the last one: the
+    /// original document first, then each incremental update.
+    fn read_revisions(&self) -> Result<Vec<Revision>> {
+        let mut revisions = Vec::new();
+        let mut next = Some(self.locate_xref_offset()?);
+        while let Some(xref_offset) = next {
+            if revisions.iter().any(|rev: &Revision| rev.xref_offset == xref_offset) {
+                bail!("Cycle in /Prev chain (at {})", xref_offset);
             }
-        };
-        println!("READ XREF AND TABLE");
-        while let Some(prev_xref_offset) = prev_trailer {
-            let mut lexer = Lexer::new(self.read(prev_xref_offset as usize..)?);
-            let (xref_sections, trailer) = read_xref_and_trailer_at(&mut lexer, NO_RESOLVE)?;
-            
-            for section in xref_sections {
-                refs.add_entries_from(section);
+            let (xref_sections, trailer) = self.parse_at(xref_offset, |lexer| read_xref_and_trailer_at(lexer, NO_RESOLVE))?;
+            next = get_prev(&trailer)?;
+            revisions.push(self.read_revision(xref_offset, xref_sections, trailer)?);
+        }
+        revisions.reverse();
+        Ok(revisions)
+    }
+    /// Completes one revision. In hybrid files, the trailer of an xref table can point to an
+    /// additional xref stream (`/XRefStm`), whose entries take precedence over the table.
+    fn read_revision(&self, xref_offset: usize, sections: Vec<XRefSection>, trailer: Dictionary) -> Result<Revision> {
+        let mut all_sections = Vec::new();
+        if let Some(p) = trailer.get("XRefStm") {
+            let pos = p.clone().as_integer()? as usize;
+            all_sections.extend(self.parse_at(pos, |lexer| parse_xref_stream_and_trailer(lexer, NO_RESOLVE))?.0);
+        }
+        all_sections.extend(sections);
+
+        // The revision ends after the `%%EOF` that follows its xref section (or, if the file
+        // is truncated, at the end of the data).
+        let end = self.parse_at(xref_offset, |lexer| {
+            let data = lexer.get_remaining_slice();
+            match find_substr(data, b"%%EOF") {
+                Some(i) => {
+                    let mut end = i + 5;
+                    if data.get(end) == Some(&b'\r') {
+                        end += 1;
+                    }
+                    if data.get(end) == Some(&b'\n') {
+                        end += 1;
+                    }
+                    Ok(end)
+                }
+                None => bail!(ErrorKind::NotFound {word: "%%EOF".into()}),
             }
-            
-            prev_trailer = {
-                match trailer.get("Prev") {
-                    Some(p) => Some(p.as_integer()?),
-                    None => None
+        }).map(|end| xref_offset + end).unwrap_or(self.len());
+
+        Ok(Revision {
+            xref_offset: xref_offset,
+            end:        end,
+            trailer:    trailer,
+            sections:   all_sections,
+        })
+    }
+    /// Repair mode for files with a missing or corrupt xref table: rebuilds it by scanning the
+    /// whole file for object headers (`N G obj`), and the contents of object streams. The
+    /// trailer is the last `trailer` dictionary, or the dictionary of the last xref stream, or
+    /// else made up from the last `/Type /Catalog` object.
+    fn reconstruct_xref_table(&self) -> Result<(XRefTable, Dictionary)> {
+        let data = self.read(..)?;
+        let data: &[u8] = &data;
+        let mut objects = Vec::new();
+        let mut trailer = None;
+
+        let mut pos = 0;
+        while pos < data.len() {
+            if pos == 0 || is_whitespace(data[pos - 1]) {
+                if let Some((id, gen, end)) = match_obj_header(&data[pos ..]) {
+                    objects.push((id, gen, pos));
+                    pos += end;
+                    continue;
+                }
+                if data[pos ..].starts_with(b"trailer") {
+                    let mut lexer = Lexer::new(&data[pos + 7 ..]);
+                    if let Ok(Primitive::Dictionary (dict)) = parse_with_lexer(&mut lexer, NO_RESOLVE) {
+                        trailer = Some(dict);
+                    }
                 }
+            }
+            pos += 1;
+        }
+
+        // (a file has fewer objects than bytes - larger numbers are damage)
+        let max_id = data.len() as ObjNr;
+        objects.retain(|&(id, _, _)| id < max_id);
+        let size = objects.iter().map(|&(id, _, _)| id + 1).max().unwrap_or(1);
+        let mut refs = XRefTable::with_max_id(size, max_id);
+        // Later objects are newer (incremental updates are appended)
+        for &(id, gen, pos) in objects.iter().rev() {
+            refs.set_if_invalid(id, XRef::Raw {pos: pos, gen_nr: gen});
+        }
+
+        // Second pass: object streams, xref streams and the catalog
+        let mut root = None;
+        let mut xref_stream_trailer = None;
+        for &(id, gen, pos) in &objects {
+            let mut lexer = Lexer::new(&data[pos ..]);
+            let obj = match parse_indirect_object(&mut lexer, &|r| self.resolve(&refs, r)) {
+                Ok((_, obj)) => obj,
+                Err(_) => continue
+            };
+            let kind = match obj {
+                Primitive::Stream (ref s) => s.info.get("Type").cloned(),
+                Primitive::Dictionary (ref d) => d.get("Type").cloned(),
+                _ => None
             };
+            match kind {
+                Some(Primitive::Name (ref t)) if t == "ObjStm" => {
+                    let stream = ObjectStream::from_primitive(obj, &|r| self.resolve(&refs, r));
+                    if let Ok(stream) = stream {
+                        for index in 0 .. stream.n_objects() {
+                            let nr = stream.get_object_nr(index).unwrap();
+                            refs.set_if_invalid(nr, XRef::Stream {stream_id: id, index: index});
+                        }
+                    }
+                }
+                Some(Primitive::Name (ref t)) if t == "XRef" => {
+                    if let Primitive::Stream (s) = obj {
+                        xref_stream_trailer = Some(s.info);
+                    }
+                }
+                Some(Primitive::Name (ref t)) if t == "Catalog" => {
+                    root = Some(PlainRef {id: id, gen: gen});
+                }
+                _ => {}
+            }
         }
+
+        let mut trailer = match trailer.or(xref_stream_trailer) {
+            Some(trailer) => trailer,
+            None => {
+                let mut trailer = Dictionary::new();
+                match root {
+                    Some(root) => trailer.insert("Root", Primitive::Reference (root)),
+                    None => bail!("Xref reconstruction: no trailer and no catalog found"),
+                };
+                trailer
+            }
+        };
+        trailer.insert("Size", Primitive::Integer (refs.len() as i32));
+        trailer.remove("Prev");
         Ok((refs, trailer))
     }
     /// File needs this because it need a resolve function to parse the trailer before the
     /// File has been created. However, it could also be useful for applications that are dealing with
     /// objects manually.
     fn resolve(&self, refs: &XRefTable, r: PlainRef) -> Result<Primitive> {
+        self.resolve_with_options(refs, r, &ParseOptions::default())
+    }
+    /// Like `resolve`, but parses with `options`.
+    fn resolve_with_options(&self, refs: &XRefTable, r: PlainRef, options: &ParseOptions) -> Result<Primitive> {
+        guard_cycle(r, || match self.resolve_unguarded(refs, r, options)? {
+            // an object that is just a reference to another one
+            Primitive::Reference (next) => self.resolve_with_options(refs, next, options),
+            p => Ok(p)
+        })
+    }
+    /// `resolve_with_options` without checking for circular references (see `guard_cycle`).
+    fn resolve_unguarded(&self, refs: &XRefTable, r: PlainRef, options: &ParseOptions) -> Result<Primitive> {
+        let resolve = Resolver::new(|r| self.resolve_with_options(refs, r, options), *options);
         match refs.get(r.id)? {
-            XRef::Raw {pos, ..} => {
-                let mut lexer = Lexer::new(self.read(pos..)?);
-                Ok(parse_indirect_object(&mut lexer, &|r| self.resolve(refs, r))?.1)
+            XRef::Raw {pos, gen_nr} => {
+                check_generation(r, gen_nr, options)?;
+                Ok(self.parse_at(pos, |lexer| parse_indirect_object(lexer, &resolve))?.1)
                 // ^ NOTE: using self.resolve is tentative.. don't know if it leads to problems
             }
             XRef::Stream {stream_id, index} => {
-                let obj_stream = self.resolve(refs, PlainRef {id: stream_id, gen: 0 /* TODO what gen nr? */})?;
-                let obj_stream = ObjectStream::from_primitive(obj_stream, &|r| self.resolve(refs, r))?;
-                let slice = obj_stream.get_object_slice(index)?;
-                parse(slice, &|r| self.resolve(refs, r))
+                // objects in object streams always have generation 0
+                check_generation(r, 0, options)?;
+                let obj_stream = self.resolve_with_options(refs, PlainRef {id: stream_id, gen: 0 /* TODO what gen nr? */}, options)?;
+                let obj_stream = ObjectStream::from_primitive(obj_stream, &resolve)?;
+                obj_stream.get_object_at(r.id, index, &resolve)
             }
             XRef::Free {..} => bail!(ErrorKind::FreeObject {obj_nr: r.id}),
-            XRef::Promised => unimplemented!(),
+            XRef::Promised => bail!(ErrorKind::PromisedObject {obj_nr: r.id}),
             XRef::Invalid => bail!(ErrorKind::NullRef {obj_nr: r.id}),
         }
     }
 }
 
+/// One revision of a document: the original document, or an incremental update.
+#[derive(Debug)]
+pub struct Revision {
+    /// Offset of the xref section (table or stream)
+    pub xref_offset: usize,
+    /// Offset of the end of the revision, after its `%%EOF`. The data up to here is the
+    /// document as of this revision (what a signature made in it covers).
+    pub end:        usize,
+    pub trailer:    Dictionary,
+    /// The xref sections of this revision, including those of a `/XRefStm`.
+    pub sections:   Vec<XRefSection>,
+}
+
+/// The xref table of the document made up of `revisions` (oldest first), and the trailer of
+/// the last of them. Object numbers from `max_id` on are left out (see
+/// `XRefTable::with_max_id`) - the length of the file is a bound.
+pub fn build_xref_table(mut revisions: Vec<Revision>, max_id: ObjNr) -> Result<(XRefTable, Dictionary)> {
+    let last = match revisions.pop() {
+        Some(last) => last,
+        None => bail!("No xref section"),
+    };
+    let highest_id = last.trailer.get_int("Size")?;
+
+    let mut refs = XRefTable::with_max_id(cmp::max(highest_id, 0) as ObjNr, max_id);
+    refs.add_revision(last.sections);
+    for revision in revisions.into_iter().rev() {
+        refs.add_revision(revision.sections);
+    }
+    Ok((refs, last.trailer))
+}
+
+/// Checks that reference `r` has the generation `gen` of the xref entry. A different generation
+/// means that the object `r` referred to has been deleted (and the number reused). In lenient
+/// mode, the current object is used anyway.
+pub fn check_generation(r: PlainRef, gen: GenNr, options: &ParseOptions) -> Result<()> {
+    if r.gen != gen {
+        if options.strict {
+            bail!(ErrorKind::GenerationMismatch {obj_nr: r.id, gen: r.gen, found: gen});
+        }
+    }
+    Ok(())
+}
+
+fn is_whitespace(b: u8) -> bool {
+    match b {
+        b' ' | b'\t' | b'\r' | b'\n' | b'\x0C' | b'\0' => true,
+        _ => false
+    }
+}
+/// Matches `N G obj` at the start of `data`. Returns the numbers and the length of the match.
+fn match_obj_header(data: &[u8]) -> Option<(ObjNr, GenNr, usize)> {
+    fn number(data: &[u8], pos: usize) -> Option<(u64, usize)> {
+        let len = data[pos ..].iter().take_while(|&&b| b'0' <= b && b <= b'9').count();
+        if len == 0 || len > 10 {
+            return None;
+        }
+        let n = data[pos .. pos + len].iter().fold(0, |n, &b| n * 10 + (b - b'0') as u64);
+        Some((n, pos + len))
+    }
+    fn spaces(data: &[u8], pos: usize) -> usize {
+        pos + data[pos ..].iter().take_while(|&&b| is_whitespace(b)).count()
+    }
+    let (id, pos) = match number(data, 0) {
+        Some(r) => r,
+        None => return None
+    };
+    let (gen, pos) = match number(data, spaces(data, pos)) {
+        Some(r) => r,
+        None => return None
+    };
+    let pos = spaces(data, pos);
+    if gen > 65535 || !data[pos ..].starts_with(b"obj") {
+        return None;
+    }
+    Some((id, gen as GenNr, pos + 3))
+}
+
+/// Finds the last `startxref` in `data` that is followed by a number, and returns the number.
+fn find_startxref(data: &[u8]) -> Option<usize> {
+    let mut end = data.len();
+    while let Some(pos) = data[.. end].windows(9).rposition(|w| w == b"startxref") {
+        let rest = &data[pos + 9 ..];
+        let spaces = rest.iter().take_while(|&&b| is_whitespace(b)).count();
+        let digits = rest[spaces ..].iter().take_while(|&&b| b'0' <= b && b <= b'9').count();
+        if digits > 0 && digits <= 20 {
+            let number = str::from_utf8(&rest[spaces .. spaces + digits]).unwrap();
+            if let Ok(n) = number.parse() {
+                return Some(n);
+            }
+        }
+        end = pos;
+    }
+    None
+}
+
+fn get_prev(trailer: &Dictionary) -> Result<Option<usize>> {
+    Ok(match trailer.get("Prev") {
+        Some(p) => Some(p.clone().as_integer()? as usize),
+        None => None
+    })
+}
+
+
+/// The part of a file of `file_len` bytes that `read_at(offset, len)` reads.
+pub fn read_range(offset: usize, len: usize, file_len: usize) -> Result<Range<usize>> {
+    if offset > file_len {
+        bail!(ErrorKind::ReadOutOfBounds {offset: offset, len: file_len});
+    }
+    Ok(offset .. cmp::min(offset.saturating_add(len), file_len))
+}
 
 impl Backend for Mmap {
     fn open(path: &str) -> Result<Mmap> {
         Ok(Mmap::open_path(path, Protection::Read)?)
     }
-    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
-        let r = range.to_range(self.len());
-        Ok(unsafe {
+    fn read_at(&self, offset: usize, len: usize) -> Result<Cow<[u8]>> {
+        let r = read_range(offset, len, self.len())?;
+        Ok(Cow::Borrowed(unsafe {
             &self.as_slice()[r]
-        })
-    }
-    fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]> {
-        let r = range.to_range(self.len());
-        Ok(unsafe {
-            &mut self.as_mut_slice()[r]
-        })
+        }))
     }
     fn len(&self) -> usize {
         self.len()
@@ -126,13 +403,9 @@ impl Backend for Vec<u8> {
         f.read_to_end(&mut buf)?;
         Ok(buf)
     }
-    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
-        let r = range.to_range(self.len());
-        Ok(&self[r])
-    }
-    fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]> {
-        let r = range.to_range(self.len());
-        Ok(&mut self[r])
+    fn read_at(&self, offset: usize, len: usize) -> Result<Cow<[u8]>> {
+        let r = read_range(offset, len, self.len())?;
+        Ok(Cow::Borrowed(&self[r]))
     }
     fn len(&self) -> usize {
         self.len()
@@ -140,6 +413,34 @@ impl Backend for Vec<u8> {
 }
 
 
+/// Reads the file on demand, without loading or mapping all of it.
+pub struct FileBackend {
+    file:   Mutex<File>,
+    len:    usize,
+}
+impl Backend for FileBackend {
+    fn open(path: &str) -> Result<FileBackend> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        Ok(FileBackend {
+            file:   Mutex::new(file),
+            len:    len,
+        })
+    }
+    fn read_at(&self, offset: usize, len: usize) -> Result<Cow<[u8]>> {
+        let r = read_range(offset, len, self.len)?;
+        let mut data = vec![0; r.end - r.start];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(r.start as u64))?;
+        file.read_exact(&mut data)?;
+        Ok(Cow::Owned(data))
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+
 
 /// `IndexRange` is implemented by Rust's built-in range types, produced
 /// by range syntax like `..`, `a..`, `..b` or `c..d`.
@@ -1,32 +1,58 @@
 /// PDF content streams.
-use file::Reader;
-use file::Primitive;
-
 use std;
+use std::io;
 use std::fmt::{Display, Formatter};
-use err::*;
 use std::mem::swap;
-use file::lexer::Lexer;
+use err::*;
+use object::*;
+use primitive::{Primitive, Dictionary, PdfString};
+use parser::{Lexer, parse_with_lexer};
 
 /// Operation in a PDF content stream.
 #[derive(Debug, Clone)]
 pub struct Operation {
-	pub operator: String,
-	pub operands: Vec<Primitive>,
+    pub operator: String,
+    pub operands: Vec<Primitive>,
 }
 
 impl Operation {
-	pub fn new(operator: String, operands: Vec<Primitive>) -> Operation {
-		Operation{
-			operator: operator,
-			operands: operands,
-		}
-	}
+    pub fn new(operator: String, operands: Vec<Primitive>) -> Operation {
+        Operation{
+            operator: operator,
+            operands: operands,
+        }
+    }
+    /// Writes the operation as in a content stream: the operands, then the operator. An inline
+    /// image (`BI`, with the image dictionary and the data as operands) is written with `ID`
+    /// and `EI`.
+    pub fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        if self.operator == "BI" && self.operands.len() == 2 {
+            writeln!(out, "BI")?;
+            if let Primitive::Dictionary (ref dict) = self.operands[0] {
+                for (key, value) in dict.iter() {
+                    key.serialize(out)?;
+                    out.write_all(b" ")?;
+                    value.serialize(out)?;
+                    writeln!(out, "")?;
+                }
+            }
+            out.write_all(b"ID ")?;
+            if let Primitive::String (ref data) = self.operands[1] {
+                out.write_all(data.as_bytes())?;
+            }
+            return writeln!(out, "\nEI");
+        }
+        for operand in &self.operands {
+            operand.serialize(out)?;
+            out.write_all(b" ")?;
+        }
+        writeln!(out, "{}", self.operator)
+    }
 }
 
 
 /// Represents a PDF content stream - a `Vec` of `Operator`s
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Content {
     pub operations: Vec<Operation>,
 }
@@ -34,13 +60,18 @@ pub struct Content {
 impl Content {
     pub fn parse_from(data: &[u8]) -> Result<Content> {
         let mut lexer = Lexer::new(data);
+        // content streams have no indirect objects
+        let resolve = |r: PlainRef| -> Result<Primitive> {
+            bail!("Reference {} {} R in a content stream", r.id, r.gen)
+        };
 
         let mut content = Content {operations: Vec::new()};
         let mut buffer = Vec::new();
 
-        loop {
+        // (stops at the end of the data)
+        while lexer.peek().is_ok() {
             let backup_pos = lexer.get_pos();
-            let obj = Reader::parse_direct_object(&mut lexer);
+            let obj = parse_with_lexer(&mut lexer, &resolve);
             match obj {
                 Ok(obj) => {
                     // Operand
@@ -49,21 +80,73 @@ impl Content {
                 Err(_) => {
                     // It's not an object/operand - treat it as an operator.
                     lexer.set_pos(backup_pos);
-                    let operator = lexer.next()?.as_string();
+                    let operator = lexer.next()?.to_string();
+                    if operator == "BI" {
+                        content.operations.push(parse_inline_image(&mut lexer, &resolve)?);
+                        buffer.clear();
+                        continue;
+                    }
                     let mut operation = Operation::new(operator, Vec::new());
                     // Give operands to operation and empty buffer.
                     swap(&mut buffer, &mut operation.operands);
-                    content.operations.push(operation.clone());
+                    content.operations.push(operation);
                 }
             }
             if lexer.get_pos() > data.len() {
                 bail!(ErrorKind::ContentReadPastBoundary);
-            } else if lexer.get_pos() == data.len() {
-                break;
             }
         }
         Ok(content)
     }
+
+    /// The content stream data of the operations.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for operation in &self.operations {
+            // (writing to a Vec doesn't fail)
+            let _ = operation.serialize(&mut data);
+        }
+        data
+    }
+}
+
+/// Reads an inline image after `BI`: the entries of the image dictionary up to `ID`, and the data
+/// up to `EI`, into a `BI` operation.
+fn parse_inline_image(lexer: &mut Lexer, resolve: &Resolve) -> Result<Operation> {
+    let mut dict = Dictionary::new();
+    loop {
+        if lexer.peek()?.equals(b"ID") {
+            break;
+        }
+        let key = parse_with_lexer(lexer, resolve)?.to_name()?;
+        let value = parse_with_lexer(lexer, resolve)?;
+        dict.insert(key, value);
+    }
+    // `ID` and a single whitespace character, then the data
+    let rest = lexer.get_remaining_slice();
+    let id = match rest.windows(2).position(|w| w == b"ID") {
+        Some(id) => id,
+        None => bail!(ErrorKind::EOF),
+    };
+    let start = id + 3;
+    // `EI`, between whitespace (or at the end)
+    let is_space = |b: u8| b" \t\r\n\x0C\x00".contains(&b);
+    let mut end = start;
+    loop {
+        if end + 2 > rest.len() {
+            bail!(ErrorKind::EOF);
+        }
+        if &rest[end .. end + 2] == b"EI" && end > 0 && is_space(rest[end - 1])
+            && (end + 2 == rest.len() || is_space(rest[end + 2])) {
+            break;
+        }
+        end += 1;
+    }
+    // (`ID EI` is an image without data: the whitespace after `ID` is also the one before `EI`)
+    let data = if end > start { rest[start .. end - 1].to_vec() } else { Vec::new() };
+    let pos = lexer.get_pos();
+    lexer.set_pos(pos + end + 2);
+    Ok(Operation::new("BI".into(), vec![Primitive::Dictionary (dict), Primitive::String (PdfString::new(data))]))
 }
 
 
@@ -81,7 +164,7 @@ impl Display for Operation {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         write!(f, "Operation: {} (", self.operator)?;
         for operand in &self.operands {
-            write!(f, "{}, ", operand)?;
+            write!(f, "{:?}, ", operand)?;
         }
         write!(f, ")\n")
     }
@@ -0,0 +1,126 @@
+//! Copying objects from one document into another - the basis of merging and splitting.
+
+use std::collections::HashMap;
+use err::*;
+use object::*;
+use primitive::{Primitive, Dictionary, PdfStream};
+use backend::Backend;
+use file::File;
+
+/// Copies objects of `source` - with all objects they refer to - into other documents. The
+/// copied objects are added with new object numbers, and the references are changed to them.
+///
+/// Each object is copied only once: objects that are used by several copied objects (like a
+/// font used on several pages) stay shared, and objects that refer to each other are fine.
+///
+/// ```ignore
+/// let mut copier = Copier::new(&source);
+/// let page = copier.copy_page(page_ref, &mut dest)?;
+/// // set `/Parent` of `page` and add it to the `/Kids` of a page tree node of `dest`
+/// ```
+pub struct Copier<'a, B: Backend + 'a> {
+    source:     &'a File<B>,
+    /// New reference of each copied object
+    copied:     HashMap<PlainRef, PlainRef>,
+    /// Objects that have a new number, but are not copied yet
+    pending:    Vec<(PlainRef, PlainRef)>,
+}
+
+impl<'a, B: Backend> Copier<'a, B> {
+    pub fn new(source: &'a File<B>) -> Copier<'a, B> {
+        Copier {
+            source:     source,
+            copied:     HashMap::new(),
+            pending:    Vec::new(),
+        }
+    }
+    /// The reference in the destination of the source object `r`, if it has been copied.
+    pub fn get_copy(&self, r: PlainRef) -> Option<PlainRef> {
+        self.copied.get(&r).cloned()
+    }
+
+    /// Copies `p` and everything it refers to into `dest`, and returns the copy of `p` (which
+    /// refers to the copies). References to free or non-existing objects become `null`.
+    pub fn copy<C: Backend>(&mut self, p: &Primitive, dest: &mut File<C>) -> Result<Primitive> {
+        let copy = self.renumber(p, dest);
+        while let Some((old, new)) = self.pending.pop() {
+            let p = match self.source.deref(Ref::<Primitive>::new(old)) {
+                Ok(p) => self.renumber(&p, dest),
+                Err(Error(ErrorKind::FreeObject {..}, _))
+                | Err(Error(ErrorKind::NullRef {..}, _))
+                | Err(Error(ErrorKind::UnspecifiedXRefEntry {..}, _)) => Primitive::Null,
+                Err(e) => return Err(e).chain_err(|| format!("Copying object {} {} R", old.id, old.gen)),
+            };
+            dest.update(new.id, p);
+        }
+        Ok(copy)
+    }
+    /// Copies the object `r` into `dest` (unless it has been copied already), and returns the
+    /// reference to the copy.
+    pub fn copy_ref<C: Backend>(&mut self, r: PlainRef, dest: &mut File<C>) -> Result<PlainRef> {
+        match self.copy(&Primitive::Reference (r), dest)? {
+            Primitive::Reference (r) => Ok(r),
+            _ => unreachable!()
+        }
+    }
+    /// Copies the page `r` with the attributes it inherits, but without `/Parent` - which would
+    /// copy the whole page tree of the source.
+    pub fn copy_page<C: Backend>(&mut self, r: PlainRef, dest: &mut File<C>) -> Result<PlainRef> {
+        if let Some(copy) = self.get_copy(r) {
+            return Ok(copy);
+        }
+        let mut page = {
+            let source = self.source;
+            let resolve = Resolver::new(|r| source.deref(Ref::<Primitive>::new(r)), *source.get_parse_options());
+            let mut page = Dictionary::from_primitive(Primitive::Reference (r), &resolve)?;
+            inherit_page_attributes(&mut page, &resolve)?;
+            page
+        };
+        page.remove("Parent");
+
+        // (references back to the page, e.g. `/P` of annotations, are to the copy)
+        let copy = dest.add_primitive(Primitive::Null);
+        self.copied.insert(r, copy);
+        let p = self.copy(&Primitive::Dictionary (page), dest)?;
+        dest.update(copy.id, p);
+        Ok(copy)
+    }
+
+    /// `p` with the references changed to the copies - new objects get a number and are
+    /// added to `pending`.
+    fn renumber<C: Backend>(&mut self, p: &Primitive, dest: &mut File<C>) -> Primitive {
+        match *p {
+            Primitive::Reference (r) => {
+                if let Some(copy) = self.get_copy(r) {
+                    return Primitive::Reference (copy);
+                }
+                let copy = dest.add_primitive(Primitive::Null);
+                self.copied.insert(r, copy);
+                self.pending.push((r, copy));
+                Primitive::Reference (copy)
+            }
+            Primitive::Array (ref array) => {
+                let mut copy = Vec::with_capacity(array.len());
+                for p in array {
+                    copy.push(self.renumber(p, dest));
+                }
+                Primitive::Array (copy)
+            }
+            Primitive::Dictionary (ref dict) => Primitive::Dictionary (self.renumber_dict(dict, dest)),
+            Primitive::Stream (ref stream) => {
+                // (`/Length` is often a reference to an object after the stream, not needed)
+                let mut info = stream.info.clone();
+                info.insert("Length", stream.data.len() as i32);
+                Primitive::Stream (PdfStream {info: self.renumber_dict(&info, dest), data: stream.data.clone()})
+            }
+            ref p => p.clone()
+        }
+    }
+    fn renumber_dict<C: Backend>(&mut self, dict: &Dictionary, dest: &mut File<C>) -> Dictionary {
+        let mut copy = Dictionary::new();
+        for (key, val) in dict.iter() {
+            copy.insert(key.clone(), self.renumber(val, dest));
+        }
+        copy
+    }
+}
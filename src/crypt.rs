@@ -0,0 +1,910 @@
+//! Encryption of strings and streams (PDF reference, section 3.5).
+
+use std::cmp;
+use std::collections::HashMap;
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+use crypto::rc4::Rc4;
+use crypto::sha2::{Sha256, Sha384, Sha512};
+use crypto::aessafe::{AesSafe128Decryptor, AesSafe256Decryptor, AesSafe128Encryptor, AesSafe256Encryptor};
+use crypto::symmetriccipher::{BlockDecryptor, BlockEncryptor, SynchronousStreamCipher};
+use rand::{OsRng, Rng};
+use err::*;
+use object::*;
+use primitive::{Primitive, Dictionary, PdfString, PdfStream};
+
+/// The encryption dictionary (`/Encrypt` of the trailer) - read with `File::encryption`.
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct CryptDict {
+    /// The security handler, `Standard` for the password-based one
+    #[pdf(key="Filter")]
+    pub filter:             String,
+    #[pdf(key="SubFilter")]
+    pub sub_filter:         Option<String>,
+    /// The algorithm: 1 and 2 are RC4 (with keys up to 40 and 128 bits), 4 and 5 use the crypt
+    /// filters of `/CF` (PDF 1.5, and AES-256 of PDF 2.0)
+    #[pdf(key="V", default="0")]
+    pub v:                  i32,
+    /// The length of the key in bits, for `V` 2
+    #[pdf(key="Length", default="40")]
+    pub bits:               i32,
+    /// The crypt filters, by name (`V` 4 and 5)
+    #[pdf(key="CF")]
+    pub crypt_filters:      Option<Dictionary>,
+    /// The crypt filter of the streams, by default `Identity`
+    #[pdf(key="StmF")]
+    pub stream_filter:      Option<String>,
+    /// The crypt filter of the strings, by default `Identity`
+    #[pdf(key="StrF")]
+    pub string_filter:      Option<String>,
+    /// The crypt filter of the embedded files, by default that of the streams (PDF 1.6)
+    #[pdf(key="EFF")]
+    pub embedded_file_filter: Option<String>,
+
+    // the entries of the standard security handler
+    /// The revision of the standard security handler
+    #[pdf(key="R")]
+    pub revision:           i32,
+    /// `/O`, made from the owner password
+    #[pdf(key="O")]
+    pub owner_hash:         PdfString,
+    /// `/U`, made from the user password
+    #[pdf(key="U")]
+    pub user_hash:          PdfString,
+    /// The permission flags - see `Permissions`
+    #[pdf(key="P")]
+    pub permission_flags:   i32,
+    /// `/OE`: the file key, encrypted with a key made from the owner password (revisions 5 and 6)
+    #[pdf(key="OE")]
+    pub owner_key:          Option<PdfString>,
+    /// `/UE`: the file key, encrypted with a key made from the user password (revisions 5 and 6)
+    #[pdf(key="UE")]
+    pub user_key:           Option<PdfString>,
+    /// `/Perms`: the permission flags, encrypted with the file key (revisions 5 and 6)
+    #[pdf(key="Perms")]
+    pub encrypted_permissions: Option<PdfString>,
+    /// Whether the metadata streams are encrypted (`V` 4 and 5)
+    #[pdf(key="EncryptMetadata", default="true")]
+    pub encrypt_metadata:   bool,
+}
+
+impl CryptDict {
+    /// The permissions of the `/P` flags.
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_flags(self.permission_flags, self.revision)
+    }
+}
+
+/// What a user of an encrypted document may do, by the `/P` flags of the standard security
+/// handler - which viewers are asked, but can't be made, to honor. The owner password gives all
+/// permissions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Permissions {
+    /// Print the document (bit 3)
+    pub can_print:          bool,
+    /// Print it at full quality, rather than as low-resolution images (bit 12)
+    pub can_print_high_quality: bool,
+    /// Change it, other than by the permissions below (bit 4)
+    pub can_modify:         bool,
+    /// Copy or otherwise extract text and images (bit 5)
+    pub can_copy:           bool,
+    /// Add or change annotations, and fill in forms (bit 6)
+    pub can_annotate:       bool,
+    /// Fill in form fields, including signature fields (bit 9)
+    pub can_fill_forms:     bool,
+    /// Extract text and images for accessibility (bit 10)
+    pub can_extract_for_accessibility: bool,
+    /// Insert, rotate and delete pages, and create bookmarks and thumbnails (bit 11)
+    pub can_assemble:       bool,
+}
+
+impl Permissions {
+    /// All permissions - as for a document that isn't encrypted.
+    pub fn all() -> Permissions {
+        Permissions::from_flags(-1, 3)
+    }
+    /// The permissions of the flags `p` of a standard security handler of revision `revision`.
+    /// Revision 2 has only bits 3 to 6: the others follow `can_print`, `can_annotate`,
+    /// `can_copy` and `can_modify`.
+    pub fn from_flags(p: i32, revision: i32) -> Permissions {
+        let bit = |n: u32| p & (1 << (n - 1)) != 0;
+        if revision < 3 {
+            return Permissions {
+                can_print:          bit(3),
+                can_print_high_quality: bit(3),
+                can_modify:         bit(4),
+                can_copy:           bit(5),
+                can_annotate:       bit(6),
+                can_fill_forms:     bit(6),
+                can_extract_for_accessibility: bit(5),
+                can_assemble:       bit(4),
+            };
+        }
+        Permissions {
+            can_print:          bit(3),
+            can_print_high_quality: bit(3) && bit(12),
+            can_modify:         bit(4),
+            can_copy:           bit(5),
+            can_annotate:       bit(6),
+            // (annotating includes filling in forms)
+            can_fill_forms:     bit(6) || bit(9),
+            can_extract_for_accessibility: bit(10),
+            can_assemble:       bit(11),
+        }
+    }
+    /// The `/P` flags of these permissions, for revision 3 and later.
+    pub fn to_flags(&self) -> i32 {
+        // bits 7, 8 and 13 to 32 are set
+        let mut p = 0xFFFF_F0C0_u32;
+        for &(allowed, bit) in &[
+            (self.can_print, 3), (self.can_modify, 4), (self.can_copy, 5), (self.can_annotate, 6),
+            (self.can_fill_forms, 9), (self.can_extract_for_accessibility, 10), (self.can_assemble, 11),
+            (self.can_print_high_quality, 12),
+        ] {
+            if allowed {
+                p |= 1 << (bit - 1);
+            }
+        }
+        p as i32
+    }
+}
+
+/// How `File::save_encrypted_to` encrypts a document, with the standard security handler.
+#[derive(Debug, Clone)]
+pub struct EncryptionOptions {
+    /// `RC4` (with a 128-bit key, revision 3), `AES128` (revision 4) or `AES256` (revision 6)
+    pub method:         CryptMethod,
+    /// The password to open the document with - anyone can if it is empty.
+    pub user_password:  Vec<u8>,
+    /// The password for all permissions - the user password if it is empty.
+    pub owner_password: Vec<u8>,
+    pub permissions:    Permissions,
+}
+
+impl EncryptionOptions {
+    /// Encryption with `method` and the passwords, with all permissions.
+    pub fn new(method: CryptMethod, user_password: &[u8], owner_password: &[u8]) -> EncryptionOptions {
+        EncryptionOptions {
+            method:         method,
+            user_password:  user_password.to_vec(),
+            owner_password: owner_password.to_vec(),
+            permissions:    Permissions::all(),
+        }
+    }
+}
+
+/// Decrypts the strings and streams of an encrypted document - and encrypts them for writing.
+pub trait SecurityHandler {
+    /// Decrypts the data of the stream object `id`. `filter` is the name of the crypt filter
+    /// given by a `/Crypt` filter of the stream, `None` means the default for streams (`/StmF`).
+    fn decrypt_stream(&self, filter: Option<&str>, id: PlainRef, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypts a string contained in the object `id`.
+    fn decrypt_string(&self, id: PlainRef, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypts the data of the embedded file stream `id` that has no `/Crypt` filter - with
+    /// the default for embedded files (`/EFF`), which is that for streams unless given.
+    fn decrypt_embedded_file(&self, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt_stream(None, id, data)
+    }
+
+    /// Whether metadata streams are encrypted too (`/EncryptMetadata`).
+    fn encrypts_metadata(&self) -> bool {
+        true
+    }
+
+    /// Encrypts the strings and the stream data of `p`, the object `id` - for writing it. This
+    /// is the reverse of `decrypt_object`, without crypt filters of the streams.
+    fn encrypt_object(&self, id: PlainRef, p: Primitive) -> Result<Primitive>;
+}
+
+/// Decrypts the strings and the stream data of `p`, the object `id` as read from the file - so
+/// that objects are parsed from the decrypted ones. A stream is decrypted with its `/Crypt`
+/// filter, which is taken out of its filters then - or else with the default crypt filter for
+/// embedded files or for streams. Xref streams aren't encrypted, and neither are metadata
+/// streams if the handler says so.
+pub fn decrypt_object(handler: &SecurityHandler, id: PlainRef, p: Primitive) -> Result<Primitive> {
+    Ok(match p {
+        Primitive::String (s) => Primitive::String (PdfString::new(handler.decrypt_string(id, s.as_bytes())?)),
+        Primitive::Array (array) => Primitive::Array (array.into_iter()
+            .map(|p| decrypt_object(handler, id, p))
+            .collect::<Result<Vec<_>>>()?),
+        Primitive::Dictionary (dict) => Primitive::Dictionary (decrypt_dict(handler, id, dict)?),
+        Primitive::Stream (stream) => {
+            let (unencrypted, embedded_file) = stream_encryption(&stream.info, handler.encrypts_metadata());
+            if unencrypted {
+                return Ok(Primitive::Stream (stream));
+            }
+            let mut info = decrypt_dict(handler, id, stream.info)?;
+            let data = match take_crypt_filter(&mut info) {
+                Some(filter) => handler.decrypt_stream(Some(filter.as_str()), id, &stream.data)?,
+                None if embedded_file => handler.decrypt_embedded_file(id, &stream.data)?,
+                None => handler.decrypt_stream(None, id, &stream.data)?,
+            };
+            // AES removes the initialization vector and the padding: /Length is that of the data
+            // as it is now
+            info.insert("Length", Primitive::Integer (data.len() as i32));
+            Primitive::Stream (PdfStream {
+                data:   data,
+                info:   info,
+            })
+        }
+        p => p
+    })
+}
+fn decrypt_dict(handler: &SecurityHandler, id: PlainRef, dict: Dictionary) -> Result<Dictionary> {
+    let mut decrypted = Dictionary::new();
+    for (key, val) in dict.iter() {
+        let val = if unencrypted_entry(&dict, key) { val.clone() } else { decrypt_object(handler, id, val.clone())? };
+        decrypted.insert(key.clone(), val);
+    }
+    Ok(decrypted)
+}
+
+/// Whether a stream with the dictionary `info` isn't encrypted at all - an xref stream, or a
+/// metadata stream unless `encrypt_metadata` - and whether it is an embedded file.
+fn stream_encryption(info: &Dictionary, encrypt_metadata: bool) -> (bool, bool) {
+    match info.get("Type") {
+        Some(&Primitive::Name (ref t)) => (
+            t == "XRef" || (t == "Metadata" && !encrypt_metadata),
+            t == "EmbeddedFile"
+        ),
+        _ => (false, false)
+    }
+}
+
+/// Whether the entry `key` of `dict` isn't encrypted: `/Contents` of a signature dictionary,
+/// which is filled in after the file is written.
+fn unencrypted_entry(dict: &Dictionary, key: &str) -> bool {
+    key == "Contents" && match dict.get("Type") {
+        Some(&Primitive::Name (ref t)) => t == "Sig" || t == "DocTimeStamp",
+        _ => false
+    }
+}
+
+/// Takes a `/Crypt` filter - which has to come first - out of `/Filter` and `/DecodeParms` of
+/// the stream dictionary `info`, and returns the name of its crypt filter (by default
+/// `Identity`).
+fn take_crypt_filter(info: &mut Dictionary) -> Option<String> {
+    let in_array = match info.get("Filter") {
+        Some(&Primitive::Name (ref name)) if name == "Crypt" => false,
+        Some(&Primitive::Array (ref filters)) => match filters.first() {
+            Some(&Primitive::Name (ref name)) if name == "Crypt" => true,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let params = if in_array {
+        if let Some(Primitive::Array (mut filters)) = info.remove("Filter") {
+            filters.remove(0);
+            info.insert("Filter", filters);
+        }
+        match info.remove("DecodeParms") {
+            Some(Primitive::Array (mut params)) => {
+                let first = if params.is_empty() { None } else { Some(params.remove(0)) };
+                info.insert("DecodeParms", params);
+                first
+            }
+            _ => None
+        }
+    } else {
+        info.remove("Filter");
+        info.remove("DecodeParms")
+    };
+    let name = match params {
+        Some(Primitive::Dictionary (ref params)) => match params.get("Name") {
+            Some(&Primitive::Name (ref name)) => Some(name.clone()),
+            _ => None
+        },
+        _ => None
+    };
+    Some(name.unwrap_or_else(|| "Identity".into()))
+}
+
+/// How a crypt filter encrypts (`/CFM` of the crypt filter).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CryptMethod {
+    /// `None`: not at all - as the `Identity` filter
+    None,
+    /// `V2`: RC4
+    RC4,
+    /// `AESV2`: AES-128 in CBC mode, with the initialization vector before the data (PDF 1.6)
+    AES128,
+    /// `AESV3`: AES-256 in CBC mode, with the file key for all objects (PDF 2.0)
+    AES256,
+}
+
+impl CryptMethod {
+    /// The method of the crypt filter dictionary `filter` (an entry of `/CF`).
+    pub fn from_filter(filter: &Dictionary) -> Result<CryptMethod> {
+        match filter.get("CFM") {
+            Some(&Primitive::Name (ref name)) => match name.as_str() {
+                "None" => Ok(CryptMethod::None),
+                "V2" => Ok(CryptMethod::RC4),
+                "AESV2" => Ok(CryptMethod::AES128),
+                "AESV3" => Ok(CryptMethod::AES256),
+                name => bail!(ErrorKind::UnsupportedSecurityHandler {reason: format!("crypt filter method /{}", name)}),
+            },
+            Some(p) => bail!(ErrorKind::UnexpectedPrimitive {expected: "Name", found: p.get_debug_name()}),
+            None => Ok(CryptMethod::None),
+        }
+    }
+}
+
+/// Which password a document was opened with (see `File::password_kind`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordKind {
+    /// The user password - which may be empty, so that anyone can open the document
+    User,
+    /// The owner password, which gives all permissions
+    Owner,
+}
+
+/// The padding of passwords to 32 bytes.
+const PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// The standard (password-based) security handler: RC4 and AES-128 (revisions 2 to 4), and
+/// AES-256 (revisions 5 and 6).
+pub struct StandardSecurityHandler {
+    /// The file key
+    key:            Vec<u8>,
+    /// The crypt filters of `/CF`, by name
+    filters:        HashMap<String, CryptMethod>,
+    stream_method:  CryptMethod,
+    string_method:  CryptMethod,
+    embedded_file_method: CryptMethod,
+    password_kind:  PasswordKind,
+    encrypt_metadata: bool,
+}
+
+impl StandardSecurityHandler {
+    /// The handler of the document with the encryption dictionary `dict` and the permanent
+    /// identifier `id` (the first string of `/ID`), opened with `password` - the user or the
+    /// owner password. For revisions 5 and 6 the password is UTF-8.
+    ///
+    /// Fails with `ErrorKind::WrongPassword` if it is neither, and with
+    /// `ErrorKind::UnsupportedSecurityHandler` if the document is encrypted in a way that isn't
+    /// supported.
+    pub fn new(dict: &CryptDict, id: &[u8], password: &[u8], resolve: &Resolve) -> Result<StandardSecurityHandler> {
+        if dict.filter != "Standard" {
+            bail!(ErrorKind::UnsupportedSecurityHandler {reason: format!("/Filter /{}", dict.filter)});
+        }
+        let mut filters = HashMap::new();
+        let (key_len, stream_method, string_method, embedded_file_method) = match dict.v {
+            1 => (5, CryptMethod::RC4, CryptMethod::RC4, CryptMethod::RC4),
+            2 => {
+                check_min("Length", dict.bits, 40)?;
+                check_max("Length", dict.bits, 128)?;
+                if dict.bits % 8 != 0 {
+                    bail!(ErrorKind::InvalidEntry {key: "Length", reason: format!("{} bits", dict.bits)});
+                }
+                (dict.bits as usize / 8, CryptMethod::RC4, CryptMethod::RC4, CryptMethod::RC4)
+            }
+            4 | 5 => {
+                if let Some(ref crypt_filters) = dict.crypt_filters {
+                    for (name, filter) in crypt_filters.iter() {
+                        let filter = Dictionary::from_primitive(filter.clone(), resolve)?;
+                        let method = CryptMethod::from_filter(&filter)
+                            .chain_err(|| format!("Crypt filter /{}", name))?;
+                        filters.insert(name.clone(), method);
+                    }
+                }
+                let stream_method = filter_method(&filters, dict.stream_filter.as_ref())?;
+                let string_method = filter_method(&filters, dict.string_filter.as_ref())?;
+                let embedded_file_method = match dict.embedded_file_filter {
+                    Some(ref name) => filter_method(&filters, Some(name))?,
+                    None => stream_method,
+                };
+                (if dict.v == 5 { 32 } else { 16 }, stream_method, string_method, embedded_file_method)
+            }
+            v => bail!(ErrorKind::UnsupportedSecurityHandler {reason: format!("/V {}", v)}),
+        };
+        // the 256-bit keys of `/V 5` are those of revisions 5 and 6, the MD5-based keys of the
+        // older revisions have at most 16 bytes
+        if (dict.v == 5) != (dict.revision == 5 || dict.revision == 6) {
+            bail!(ErrorKind::UnsupportedSecurityHandler {reason: format!("/V {} with revision {}", dict.v, dict.revision)});
+        }
+        if dict.revision <= 4 && key_len > 16 {
+            bail!(ErrorKind::InvalidEntry {key: "Length", reason: format!("{} bytes for revision {}", key_len, dict.revision)});
+        }
+        let (key, password_kind) = match dict.revision {
+            2 ... 4 => {
+                if let Some(key) = check_user_password(dict, id, password, key_len) {
+                    (key, PasswordKind::User)
+                } else {
+                    // the owner password gives the user password
+                    let user_password = user_password_from_owner(dict, password, key_len);
+                    match check_user_password(dict, id, &user_password, key_len) {
+                        Some(key) => (key, PasswordKind::Owner),
+                        None => bail!(ErrorKind::WrongPassword),
+                    }
+                }
+            }
+            5 | 6 => aes256_file_key(dict, password)?,
+            r => bail!(ErrorKind::UnsupportedSecurityHandler {reason: format!("revision {}", r)}),
+        };
+        Ok(StandardSecurityHandler {
+            key:            key,
+            filters:        filters,
+            stream_method:  stream_method,
+            string_method:  string_method,
+            embedded_file_method: embedded_file_method,
+            password_kind:  password_kind,
+            encrypt_metadata: dict.encrypt_metadata,
+        })
+    }
+    /// Which password the document was opened with.
+    pub fn password_kind(&self) -> PasswordKind {
+        self.password_kind
+    }
+
+    /// A handler for encrypting a new document with the identifier `id` (the first string of
+    /// `/ID`) as `options` say, and its encryption dictionary.
+    pub fn create(options: &EncryptionOptions, id: &[u8]) -> Result<(StandardSecurityHandler, Dictionary)> {
+        let owner_password = match options.owner_password.len() {
+            0 => &options.user_password,
+            _ => &options.owner_password,
+        };
+        let p = options.permissions.to_flags();
+        let (v, revision, key_len) = match options.method {
+            CryptMethod::RC4 => (2, 3, 16),
+            CryptMethod::AES128 => (4, 4, 16),
+            CryptMethod::AES256 => (5, 6, 32),
+            CryptMethod::None => bail!("Encryption without a method"),
+        };
+        let mut dict = Dictionary::new()
+            .with("Filter", Primitive::Name ("Standard".into()))
+            .with("V", v)
+            .with("R", revision)
+            .with("Length", key_len as i32 * 8)
+            .with("P", p);
+        if v >= 4 {
+            let cfm = if v == 4 { "AESV2" } else { "AESV3" };
+            let filter = Dictionary::new()
+                .with("CFM", Primitive::Name (cfm.into()))
+                .with("AuthEvent", Primitive::Name ("DocOpen".into()))
+                .with("Length", key_len as i32);
+            dict.insert("CF", Dictionary::new().with("StdCF", filter));
+            dict.insert("StmF", Primitive::Name ("StdCF".into()));
+            dict.insert("StrF", Primitive::Name ("StdCF".into()));
+        }
+
+        let key = if revision == 6 {
+            let key = random_bytes(32)?;
+            // the hashes of the passwords with their salts, and the key encrypted with each
+            let salts = random_bytes(32)?;
+            let mut user_hash = password_hash(revision, &options.user_password, &salts[.. 8], &[]);
+            user_hash.extend_from_slice(&salts[.. 16]);
+            let user_key_hash = password_hash(revision, &options.user_password, &salts[8 .. 16], &[]);
+            let mut owner_hash = password_hash(revision, owner_password, &salts[16 .. 24], &user_hash);
+            owner_hash.extend_from_slice(&salts[16 .. 32]);
+            let owner_key_hash = password_hash(revision, owner_password, &salts[24 .. 32], &user_hash);
+            dict.insert("UE", PdfString::new(cbc_encrypt(&user_key_hash, &[0; 16], &key)));
+            dict.insert("OE", PdfString::new(cbc_encrypt(&owner_key_hash, &[0; 16], &key)));
+            dict.insert("U", PdfString::new(user_hash));
+            dict.insert("O", PdfString::new(owner_hash));
+            // the flags, "T" for encrypted metadata, and "adb" (then 4 random bytes)
+            let mut perms = vec![p as u8, (p >> 8) as u8, (p >> 16) as u8, (p >> 24) as u8, 0xFF, 0xFF, 0xFF, 0xFF];
+            perms.extend_from_slice(b"Tadb");
+            perms.extend_from_slice(&random_bytes(4)?);
+            dict.insert("Perms", PdfString::new(cbc_encrypt(&key, &[0; 16], &perms)));
+            key
+        } else {
+            let owner_hash = owner_hash(revision, owner_password, &options.user_password, key_len);
+            let crypt_dict = CryptDict {
+                filter:             "Standard".into(),
+                sub_filter:         None,
+                v:                  v,
+                bits:               key_len as i32 * 8,
+                crypt_filters:      None,
+                stream_filter:      None,
+                string_filter:      None,
+                embedded_file_filter: None,
+                revision:           revision,
+                owner_hash:         PdfString::new(owner_hash.clone()),
+                user_hash:          PdfString::new(Vec::new()),
+                permission_flags:   p,
+                owner_key:          None,
+                user_key:           None,
+                encrypted_permissions: None,
+                encrypt_metadata:   true,
+            };
+            let key = file_key(&crypt_dict, id, &options.user_password, key_len);
+            // (16 bytes that don't count)
+            let mut user_hash = user_hash(&key, id, revision);
+            user_hash.extend_from_slice(&[0; 16]);
+            dict.insert("U", PdfString::new(user_hash));
+            dict.insert("O", PdfString::new(owner_hash));
+            key
+        };
+        let method = options.method;
+        Ok((StandardSecurityHandler {
+            key:            key,
+            filters:        HashMap::new(),
+            stream_method:  method,
+            string_method:  method,
+            embedded_file_method: method,
+            password_kind:  PasswordKind::Owner,
+            encrypt_metadata: true,
+        }, dict))
+    }
+    fn encrypt_dict(&self, id: PlainRef, dict: Dictionary) -> Result<Dictionary> {
+        let mut encrypted = Dictionary::new();
+        for (key, val) in dict.iter() {
+            let val = if unencrypted_entry(&dict, key) { val.clone() } else { self.encrypt_object(id, val.clone())? };
+            encrypted.insert(key.clone(), val);
+        }
+        Ok(encrypted)
+    }
+    fn encrypt(&self, method: CryptMethod, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(match method {
+            CryptMethod::None => data.to_vec(),
+            CryptMethod::RC4 => rc4(&self.object_key(id, false), data),
+            CryptMethod::AES128 => aes_cbc_encrypt(&self.object_key(id, true), data)?,
+            CryptMethod::AES256 => aes_cbc_encrypt(&self.key, data)?,
+        })
+    }
+
+    /// The key of the object `id`, for RC4 or - with `aes` - AES-128.
+    fn object_key(&self, id: PlainRef, aes: bool) -> Vec<u8> {
+        let mut md5 = Md5::new();
+        md5.input(&self.key);
+        md5.input(&[id.id as u8, (id.id >> 8) as u8, (id.id >> 16) as u8, id.gen as u8, (id.gen >> 8) as u8]);
+        if aes {
+            md5.input(b"sAlT");
+        }
+        let mut hash = [0; 16];
+        md5.result(&mut hash);
+        hash[.. cmp::min(self.key.len() + 5, 16)].to_vec()
+    }
+    fn decrypt(&self, method: CryptMethod, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        match method {
+            CryptMethod::None => Ok(data.to_vec()),
+            CryptMethod::RC4 => Ok(rc4(&self.object_key(id, false), data)),
+            CryptMethod::AES128 => aes_cbc_decrypt(&self.object_key(id, true), data),
+            CryptMethod::AES256 => aes_cbc_decrypt(&self.key, data),
+        }
+    }
+}
+
+impl SecurityHandler for StandardSecurityHandler {
+    fn decrypt_stream(&self, filter: Option<&str>, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        let method = match filter {
+            Some("Identity") => CryptMethod::None,
+            Some(name) => match self.filters.get(name) {
+                Some(&method) => method,
+                None => bail!("Crypt filter /{} not found in /CF", name),
+            },
+            None => self.stream_method,
+        };
+        self.decrypt(method, id, data)
+    }
+    fn decrypt_string(&self, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt(self.string_method, id, data)
+    }
+    fn decrypt_embedded_file(&self, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt(self.embedded_file_method, id, data)
+    }
+    fn encrypts_metadata(&self) -> bool {
+        self.encrypt_metadata
+    }
+    fn encrypt_object(&self, id: PlainRef, p: Primitive) -> Result<Primitive> {
+        Ok(match p {
+            Primitive::String (s) => Primitive::String (PdfString::new(self.encrypt(self.string_method, id, s.as_bytes())?)),
+            Primitive::Array (array) => Primitive::Array (array.into_iter()
+                .map(|p| self.encrypt_object(id, p))
+                .collect::<Result<Vec<_>>>()?),
+            Primitive::Dictionary (dict) => Primitive::Dictionary (self.encrypt_dict(id, dict)?),
+            Primitive::Stream (stream) => {
+                let (unencrypted, embedded_file) = stream_encryption(&stream.info, self.encrypt_metadata);
+                if unencrypted {
+                    return Ok(Primitive::Stream (stream));
+                }
+                let method = if embedded_file { self.embedded_file_method } else { self.stream_method };
+                Primitive::Stream (PdfStream {
+                    data:   self.encrypt(method, id, &stream.data)?,
+                    info:   self.encrypt_dict(id, stream.info)?,
+                })
+            }
+            p => p
+        })
+    }
+}
+
+/// The method of the crypt filter `name` (`/StmF`, `/StrF` or `/EFF`), by default `Identity`.
+fn filter_method(filters: &HashMap<String, CryptMethod>, name: Option<&String>) -> Result<CryptMethod> {
+    match name.map(|name| name.as_str()) {
+        None | Some("Identity") => Ok(CryptMethod::None),
+        Some(name) => match filters.get(name) {
+            Some(&method) => Ok(method),
+            None => bail!("Crypt filter /{} not found in /CF", name),
+        },
+    }
+}
+
+/// `password` padded (or truncated) to 32 bytes.
+fn pad_password(password: &[u8]) -> [u8; 32] {
+    let n = cmp::min(password.len(), 32);
+    let mut padded = [0; 32];
+    padded[.. n].copy_from_slice(&password[.. n]);
+    padded[n ..].copy_from_slice(&PADDING[.. 32 - n]);
+    padded
+}
+
+fn md5(data: &[&[u8]]) -> [u8; 16] {
+    let mut md5 = Md5::new();
+    for part in data {
+        md5.input(part);
+    }
+    let mut hash = [0; 16];
+    md5.result(&mut hash);
+    hash
+}
+
+/// The file key of `key_len` bytes for the user password `password`.
+fn file_key(dict: &CryptDict, id: &[u8], password: &[u8], key_len: usize) -> Vec<u8> {
+    let p = dict.permission_flags;
+    let owner_hash = dict.owner_hash.as_bytes();
+    let mut digest = Md5::new();
+    digest.input(&pad_password(password));
+    digest.input(&owner_hash[.. cmp::min(owner_hash.len(), 32)]);
+    digest.input(&[p as u8, (p >> 8) as u8, (p >> 16) as u8, (p >> 24) as u8]);
+    digest.input(id);
+    if dict.revision >= 4 && !dict.encrypt_metadata {
+        digest.input(&[0xFF; 4]);
+    }
+    let mut hash = [0; 16];
+    digest.result(&mut hash);
+    if dict.revision >= 3 {
+        for _ in 0 .. 50 {
+            hash = md5(&[&hash[.. key_len]]);
+        }
+    }
+    hash[.. key_len].to_vec()
+}
+
+/// The file key for the user password `password` of revisions 2 to 4 - `None` if it is the
+/// wrong one.
+fn check_user_password(dict: &CryptDict, id: &[u8], password: &[u8], key_len: usize) -> Option<Vec<u8>> {
+    let key = file_key(dict, id, password, key_len);
+    // the first 16 bytes of `/U` are checked (for revision 2, all 32)
+    let user_hash = user_hash(&key, id, dict.revision);
+    let n = if dict.revision == 2 { 32 } else { 16 };
+    if dict.user_hash.as_bytes().len() < n || dict.user_hash.as_bytes()[.. n] != user_hash[.. n] {
+        return None;
+    }
+    Some(key)
+}
+
+/// The key that `/O` of revisions 2 to 4 is encrypted with: made from the owner password.
+fn owner_key(revision: i32, owner_password: &[u8], key_len: usize) -> Vec<u8> {
+    let mut hash = md5(&[&pad_password(owner_password)]);
+    if revision >= 3 {
+        for _ in 0 .. 50 {
+            hash = md5(&[&hash]);
+        }
+    }
+    hash[.. key_len].to_vec()
+}
+
+/// `/O` of revisions 2 to 4: the padded user password, encrypted with the owner key (for
+/// revisions 3 and 4, 20 times - with the key XORed with 0 to 19).
+fn owner_hash(revision: i32, owner_password: &[u8], user_password: &[u8], key_len: usize) -> Vec<u8> {
+    let key = owner_key(revision, owner_password, key_len);
+    let mut hash = rc4(&key, &pad_password(user_password));
+    if revision >= 3 {
+        for i in 1 .. 20 {
+            let key: Vec<u8> = key.iter().map(|&b| b ^ i).collect();
+            hash = rc4(&key, &hash);
+        }
+    }
+    hash
+}
+
+/// The (padded) user password that `/O` was made from, if `password` is the owner password of
+/// revisions 2 to 4.
+fn user_password_from_owner(dict: &CryptDict, password: &[u8], key_len: usize) -> Vec<u8> {
+    let key = owner_key(dict.revision, password, key_len);
+    let owner_hash = dict.owner_hash.as_bytes();
+    let owner_hash = &owner_hash[.. cmp::min(owner_hash.len(), 32)];
+    if dict.revision == 2 {
+        return rc4(&key, owner_hash);
+    }
+    // `/O` was encrypted 20 times, with the key XORed with 0 to 19
+    let mut user_password = owner_hash.to_vec();
+    for i in (0 .. 20).rev() {
+        let key: Vec<u8> = key.iter().map(|&b| b ^ i).collect();
+        user_password = rc4(&key, &user_password);
+    }
+    user_password
+}
+
+/// `/U` for the file key `key` - of which only the first 16 bytes count for revisions 3 and 4.
+fn user_hash(key: &[u8], id: &[u8], revision: i32) -> Vec<u8> {
+    if revision == 2 {
+        return rc4(key, &PADDING);
+    }
+    let mut hash = rc4(key, &md5(&[&PADDING, id]));
+    for i in 1 .. 20 {
+        let key: Vec<u8> = key.iter().map(|&b| b ^ i).collect();
+        hash = rc4(&key, &hash);
+    }
+    hash
+}
+
+/// The file key of revisions 5 and 6: `/UE` decrypted with the hash of the user password
+/// `password` if it matches `/U`, or `/OE` with the hash of the owner password if it matches
+/// `/O`. The key is checked with `/Perms`.
+fn aes256_file_key(dict: &CryptDict, password: &[u8]) -> Result<(Vec<u8>, PasswordKind)> {
+    // (at most 127 bytes of the password count)
+    let password = &password[.. cmp::min(password.len(), 127)];
+    // the hashes of the passwords, and the salts for checking them and for the keys
+    let user_hash = dict.user_hash.as_bytes();
+    let owner_hash = dict.owner_hash.as_bytes();
+    if user_hash.len() < 48 {
+        bail!(ErrorKind::InvalidEntry {key: "U", reason: format!("{} bytes instead of 48", user_hash.len())});
+    }
+    if owner_hash.len() < 48 {
+        bail!(ErrorKind::InvalidEntry {key: "O", reason: format!("{} bytes instead of 48", owner_hash.len())});
+    }
+    let user_hash = &user_hash[.. 48];
+    let (key_hash, encrypted_key, password_kind) = if password_hash(dict.revision, password, &user_hash[32 .. 40], &[]) == &user_hash[.. 32] {
+        let key_hash = password_hash(dict.revision, password, &user_hash[40 .. 48], &[]);
+        (key_hash, dict.user_key.as_ref(), PasswordKind::User)
+    } else if password_hash(dict.revision, password, &owner_hash[32 .. 40], user_hash) == &owner_hash[.. 32] {
+        let key_hash = password_hash(dict.revision, password, &owner_hash[40 .. 48], user_hash);
+        (key_hash, dict.owner_key.as_ref(), PasswordKind::Owner)
+    } else {
+        bail!(ErrorKind::WrongPassword);
+    };
+    let key = match encrypted_key {
+        Some(key) if key.as_bytes().len() == 32 => cbc_decrypt(&key_hash, &[0; 16], key.as_bytes()),
+        _ => bail!(ErrorKind::InvalidEntry {
+            key: if password_kind == PasswordKind::User { "UE" } else { "OE" },
+            reason: "missing or not 32 bytes".into()
+        }),
+    };
+
+    if let Some(ref perms) = dict.encrypted_permissions {
+        let perms = perms.as_bytes();
+        if perms.len() < 16 {
+            bail!(ErrorKind::InvalidEntry {key: "Perms", reason: format!("{} bytes instead of 16", perms.len())});
+        }
+        let mut block = [0; 16];
+        AesSafe256Decryptor::new(&key).decrypt_block(&perms[.. 16], &mut block);
+        let p = dict.permission_flags;
+        if &block[9 .. 12] != b"adb" || block[.. 4] != [p as u8, (p >> 8) as u8, (p >> 16) as u8, (p >> 24) as u8] {
+            bail!(ErrorKind::InvalidEntry {key: "Perms", reason: "doesn't match the file key and /P".into()});
+        }
+    }
+    Ok((key, password_kind))
+}
+
+/// The hash of `password` with `salt` - and, for the owner password, `/U` as `user_hash`: SHA-256
+/// for revision 5, and for revision 6 the hash hardened by rounds of AES and SHA-2 of PDF 2.0.
+fn password_hash(revision: i32, password: &[u8], salt: &[u8], user_hash: &[u8]) -> Vec<u8> {
+    let mut hash = digest(Sha256::new(), &[password, salt, user_hash]);
+    if revision == 5 {
+        return hash;
+    }
+    let mut round = 0;
+    loop {
+        let mut data = Vec::with_capacity(64 * (password.len() + hash.len() + user_hash.len()));
+        for _ in 0 .. 64 {
+            data.extend_from_slice(password);
+            data.extend_from_slice(&hash);
+            data.extend_from_slice(user_hash);
+        }
+        let encrypted = cbc_encrypt(&hash[.. 16], &hash[16 .. 32], &data);
+        // the next hash function by the first 16 bytes, as a number modulo 3
+        hash = match encrypted[.. 16].iter().map(|&b| b as u32).sum::<u32>() % 3 {
+            0 => digest(Sha256::new(), &[&encrypted]),
+            1 => digest(Sha384::new(), &[&encrypted]),
+            _ => digest(Sha512::new(), &[&encrypted]),
+        };
+        round += 1;
+        // at least 64 rounds, until the last byte is at most the number of rounds - 32
+        if round >= 64 && encrypted[encrypted.len() - 1] as u32 <= round - 32 {
+            break;
+        }
+    }
+    hash.truncate(32);
+    hash
+}
+
+fn digest<D: Digest>(mut digest: D, data: &[&[u8]]) -> Vec<u8> {
+    for part in data {
+        digest.input(part);
+    }
+    let mut hash = vec![0; digest.output_bytes()];
+    digest.result(&mut hash);
+    hash
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0; data.len()];
+    Rc4::new(key).process(data, &mut out);
+    out
+}
+
+/// Decrypts `data` - the initialization vector and the encrypted data - with AES in CBC mode,
+/// and removes the padding. The key is 16 or 32 bytes long.
+fn aes_cbc_decrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 16 || data.len() % 16 != 0 {
+        bail!("AES-encrypted data of {} bytes is not made of 16-byte blocks", data.len());
+    }
+    let (iv, data) = data.split_at(16);
+    let mut out = cbc_decrypt(key, iv, data);
+    // the padding: 1 to 16 bytes, each the number of them (kept if it isn't valid)
+    let n = out.last().cloned().unwrap_or(0) as usize;
+    if 1 <= n && n <= 16 && n <= out.len() && out[out.len() - n ..].iter().all(|&b| b as usize == n) {
+        let len = out.len() - n;
+        out.truncate(len);
+    }
+    Ok(out)
+}
+
+/// Decrypts the whole blocks of `data` with AES in CBC mode, with the key `key` (16 or 32 bytes)
+/// and the initialization vector `iv`.
+fn cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+    let aes: Box<BlockDecryptor> = match key.len() {
+        16 => Box::new(AesSafe128Decryptor::new(key)),
+        _ => Box::new(AesSafe256Decryptor::new(key)),
+    };
+    let mut out = Vec::with_capacity(data.len());
+    let mut previous = iv;
+    let mut block = [0; 16];
+    for chunk in data.chunks(16).filter(|chunk| chunk.len() == 16) {
+        aes.decrypt_block(chunk, &mut block);
+        out.extend(block.iter().zip(previous).map(|(&b, &p)| b ^ p));
+        previous = chunk;
+    }
+    out
+}
+
+/// Encrypts `data` with AES in CBC mode and a random initialization vector, which comes first,
+/// with padding. The key is 16 or 32 bytes long.
+fn aes_cbc_encrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let iv = random_bytes(16)?;
+    let n = 16 - data.len() % 16;
+    let mut padded = Vec::with_capacity(data.len() + n);
+    padded.extend_from_slice(data);
+    padded.extend(::std::iter::repeat(n as u8).take(n));
+    let mut out = iv.clone();
+    out.extend(cbc_encrypt(key, &iv, &padded));
+    Ok(out)
+}
+
+/// Encrypts `data` (whole blocks) with AES in CBC mode, without padding. The key is 16 or 32
+/// bytes long.
+fn cbc_encrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+    let aes: Box<BlockEncryptor> = match key.len() {
+        16 => Box::new(AesSafe128Encryptor::new(key)),
+        _ => Box::new(AesSafe256Encryptor::new(key)),
+    };
+    let mut out = Vec::with_capacity(data.len());
+    let mut block = [0; 16];
+    block.copy_from_slice(iv);
+    for chunk in data.chunks(16) {
+        for (b, &d) in block.iter_mut().zip(chunk) {
+            *b ^= d;
+        }
+        let previous = block;
+        aes.encrypt_block(&previous, &mut block);
+        out.extend_from_slice(&block);
+    }
+    out
+}
+
+/// `n` random bytes - for keys, salts and initialization vectors - from the random number
+/// generator of the operating system.
+pub fn random_bytes(n: usize) -> Result<Vec<u8>> {
+    let mut rng = OsRng::new().chain_err(|| "No random number generator for the encryption")?;
+    let mut bytes = vec![0; n];
+    rng.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
@@ -0,0 +1,155 @@
+//! Comparing two documents object by object - for example two outputs of a PDF generator.
+
+use std::collections::{BTreeMap, BTreeSet};
+use err::*;
+use object::*;
+use primitive::{Primitive, Dictionary, PdfStream, PdfString};
+use backend::Backend;
+use file::File;
+
+/// How an object differs between two documents. Objects are matched by object number.
+#[derive(Debug)]
+pub enum ObjectDiff {
+    /// Only in the new document
+    Added (PlainRef),
+    /// Only in the old document
+    Removed (PlainRef),
+    /// In both, with different contents
+    Changed (PlainRef, Vec<Difference>),
+}
+
+/// A difference inside an object.
+#[derive(Debug)]
+pub struct Difference {
+    /// Where the values are in the object: dictionary keys and array indices, like
+    /// `/Resources/Font` or `/Kids[2]`. Empty for the object itself, and `stream` for the
+    /// (decoded) data of a stream - which is given as a string.
+    pub path:   String,
+    /// `None` if the entry is missing
+    pub old:    Option<Primitive>,
+    pub new:    Option<Primitive>,
+}
+
+/// The objects that differ between `old` and `new`, in the order of object numbers.
+///
+/// Streams are compared decoded (when their filters are supported), so the same data encoded
+/// differently is the same. Object streams and xref streams - the structure of the file, not of
+/// the document - are left out.
+pub fn diff<B: Backend, C: Backend>(old: &File<B>, new: &File<C>) -> Result<Vec<ObjectDiff>> {
+    let old_refs: BTreeMap<ObjNr, PlainRef> = old.object_refs().into_iter().map(|r| (r.id, r)).collect();
+    let new_refs: BTreeMap<ObjNr, PlainRef> = new.object_refs().into_iter().map(|r| (r.id, r)).collect();
+    let ids: BTreeSet<ObjNr> = old_refs.keys().chain(new_refs.keys()).cloned().collect();
+
+    let mut diffs = Vec::new();
+    for id in ids {
+        let old_object = match old_refs.get(&id) {
+            Some(&r) => normalized_object(old, r)?.map(|p| (r, p)),
+            None => None
+        };
+        let new_object = match new_refs.get(&id) {
+            Some(&r) => normalized_object(new, r)?.map(|p| (r, p)),
+            None => None
+        };
+        match (old_object, new_object) {
+            (Some((r, _)), None) => diffs.push(ObjectDiff::Removed (r)),
+            (None, Some((r, _))) => diffs.push(ObjectDiff::Added (r)),
+            (Some((_, a)), Some((r, b))) => {
+                let mut differences = Vec::new();
+                compare(String::new(), &a, &b, &mut differences);
+                if !differences.is_empty() {
+                    diffs.push(ObjectDiff::Changed (r, differences));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    Ok(diffs)
+}
+
+/// The object `r` of `file`, with its data decoded if it is a stream (without `/Length`, which
+/// is the length of the encoded data). `None` for object and xref streams.
+fn normalized_object<B: Backend>(file: &File<B>, r: PlainRef) -> Result<Option<Primitive>> {
+    let p = file.deref(Ref::<Primitive>::new(r))
+        .chain_err(|| format!("Comparing object {} {} R", r.id, r.gen))?;
+    let mut stream = match p {
+        Primitive::Stream (stream) => stream,
+        p => return Ok(Some(p)),
+    };
+    match stream.info.get("Type") {
+        Some(&Primitive::Name (ref t)) if t == "ObjStm" || t == "XRef" => return Ok(None),
+        _ => {}
+    }
+    let resolve = Resolver::new(|r| file.deref(Ref::<Primitive>::new(r)), *file.get_parse_options());
+    let decoded = Stream::<Dictionary>::from_primitive(Primitive::Stream (stream.clone()), &resolve)
+        .and_then(|mut decoded| file.decode_stream(&mut decoded).map(|_| decoded));
+    let decoded = match decoded {
+        Ok(decoded) => decoded,
+        // compare the encoded data
+        Err(_) => {
+            stream.info.remove("Length");
+            return Ok(Some(Primitive::Stream (stream)));
+        }
+    };
+    let mut info = decoded.info.clone();
+    // filters that aren't decoded (image codecs)
+    let filters: Vec<Primitive> = decoded.get_filters().iter()
+        .map(|f| Primitive::Name (f.name().into()))
+        .collect();
+    if !filters.is_empty() {
+        info.insert("Filter", filters);
+    }
+    Ok(Some(Primitive::Stream (PdfStream {info: info, data: decoded.get_data_raw().to_vec()})))
+}
+
+/// Adds the differences between `old` and `new` at `path` to `out`.
+fn compare(path: String, old: &Primitive, new: &Primitive, out: &mut Vec<Difference>) {
+    match (old, new) {
+        (&Primitive::Dictionary (ref a), &Primitive::Dictionary (ref b)) => compare_dicts(&path, a, b, out),
+        (&Primitive::Array (ref a), &Primitive::Array (ref b)) if a.len() == b.len() => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                compare(format!("{}[{}]", path, i), a, b, out);
+            }
+        }
+        (&Primitive::Stream (ref a), &Primitive::Stream (ref b)) => {
+            compare_dicts(&path, &a.info, &b.info, out);
+            if a.data != b.data {
+                out.push(Difference {
+                    path:   format!("{}stream", path),
+                    old:    Some(Primitive::String (PdfString::new(a.data.clone()))),
+                    new:    Some(Primitive::String (PdfString::new(b.data.clone()))),
+                });
+            }
+        }
+        _ => {
+            if serialized(old) != serialized(new) {
+                out.push(Difference {
+                    path:   path,
+                    old:    Some(old.clone()),
+                    new:    Some(new.clone()),
+                });
+            }
+        }
+    }
+}
+fn compare_dicts(path: &str, old: &Dictionary, new: &Dictionary, out: &mut Vec<Difference>) {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let key_path = format!("{}/{}", path, key);
+        match (old.get(key), new.get(key)) {
+            (Some(a), Some(b)) => compare(key_path, a, b, out),
+            (a, b) => out.push(Difference {
+                path:   key_path,
+                old:    a.cloned(),
+                new:    b.cloned(),
+            }),
+        }
+    }
+}
+/// Scalars (and arrays of different lengths) are the same if they are written the same - so
+/// `1` and `1.0` are.
+fn serialized(p: &Primitive) -> Vec<u8> {
+    let mut data = Vec::new();
+    // (writing to a Vec doesn't fail)
+    let _ = p.serialize(&mut data);
+    data
+}
@@ -0,0 +1,303 @@
+//! `CCITTFaxDecode`: Group 3 (one- and two-dimensional) and Group 4 fax decoding, as specified
+//! in ITU-T T.4 and T.6.
+
+use err::*;
+use std::{cmp, mem};
+use super::CCITTFaxDecodeParams;
+
+/// (code length, code, run length) of white runs, sorted by code length.
+const WHITE_CODES: &'static [(u8, u16, u16)] = &[
+    (4, 0b0111, 2), (4, 0b1000, 3), (4, 0b1011, 4), (4, 0b1100, 5), (4, 0b1110, 6), (4, 0b1111, 7),
+    (5, 0b10011, 8), (5, 0b10100, 9), (5, 0b00111, 10), (5, 0b01000, 11), (5, 0b11011, 64),
+    (5, 0b10010, 128), (6, 0b000111, 1), (6, 0b001000, 12), (6, 0b000011, 13), (6, 0b110100, 14),
+    (6, 0b110101, 15), (6, 0b101010, 16), (6, 0b101011, 17), (6, 0b010111, 192),
+    (6, 0b011000, 1664), (7, 0b0100111, 18), (7, 0b0001100, 19), (7, 0b0001000, 20),
+    (7, 0b0010111, 21), (7, 0b0000011, 22), (7, 0b0000100, 23), (7, 0b0101000, 24),
+    (7, 0b0101011, 25), (7, 0b0010011, 26), (7, 0b0100100, 27), (7, 0b0011000, 28),
+    (7, 0b0110111, 256), (8, 0b00110101, 0), (8, 0b00000010, 29), (8, 0b00000011, 30),
+    (8, 0b00011010, 31), (8, 0b00011011, 32), (8, 0b00010010, 33), (8, 0b00010011, 34),
+    (8, 0b00010100, 35), (8, 0b00010101, 36), (8, 0b00010110, 37), (8, 0b00010111, 38),
+    (8, 0b00101000, 39), (8, 0b00101001, 40), (8, 0b00101010, 41), (8, 0b00101011, 42),
+    (8, 0b00101100, 43), (8, 0b00101101, 44), (8, 0b00000100, 45), (8, 0b00000101, 46),
+    (8, 0b00001010, 47), (8, 0b00001011, 48), (8, 0b01010010, 49), (8, 0b01010011, 50),
+    (8, 0b01010100, 51), (8, 0b01010101, 52), (8, 0b00100100, 53), (8, 0b00100101, 54),
+    (8, 0b01011000, 55), (8, 0b01011001, 56), (8, 0b01011010, 57), (8, 0b01011011, 58),
+    (8, 0b01001010, 59), (8, 0b01001011, 60), (8, 0b00110010, 61), (8, 0b00110011, 62),
+    (8, 0b00110100, 63), (8, 0b00110110, 320), (8, 0b00110111, 384), (8, 0b01100100, 448),
+    (8, 0b01100101, 512), (8, 0b01101000, 576), (8, 0b01100111, 640), (9, 0b011001100, 704),
+    (9, 0b011001101, 768), (9, 0b011010010, 832), (9, 0b011010011, 896), (9, 0b011010100, 960),
+    (9, 0b011010101, 1024), (9, 0b011010110, 1088), (9, 0b011010111, 1152), (9, 0b011011000, 1216),
+    (9, 0b011011001, 1280), (9, 0b011011010, 1344), (9, 0b011011011, 1408), (9, 0b010011000, 1472),
+    (9, 0b010011001, 1536), (9, 0b010011010, 1600), (9, 0b010011011, 1728),
+    (11, 0b00000001000, 1792), (11, 0b00000001100, 1856), (11, 0b00000001101, 1920),
+    (12, 0b000000010010, 1984), (12, 0b000000010011, 2048), (12, 0b000000010100, 2112),
+    (12, 0b000000010101, 2176), (12, 0b000000010110, 2240), (12, 0b000000010111, 2304),
+    (12, 0b000000011100, 2368), (12, 0b000000011101, 2432), (12, 0b000000011110, 2496),
+    (12, 0b000000011111, 2560),
+];
+/// (code length, code, run length) of black runs, sorted by code length.
+const BLACK_CODES: &'static [(u8, u16, u16)] = &[
+    (2, 0b11, 2), (2, 0b10, 3), (3, 0b010, 1), (3, 0b011, 4), (4, 0b0011, 5), (4, 0b0010, 6),
+    (5, 0b00011, 7), (6, 0b000101, 8), (6, 0b000100, 9), (7, 0b0000100, 10), (7, 0b0000101, 11),
+    (7, 0b0000111, 12), (8, 0b00000100, 13), (8, 0b00000111, 14), (9, 0b000011000, 15),
+    (10, 0b0000110111, 0), (10, 0b0000010111, 16), (10, 0b0000011000, 17), (10, 0b0000001000, 18),
+    (10, 0b0000001111, 64), (11, 0b00001100111, 19), (11, 0b00001101000, 20),
+    (11, 0b00001101100, 21), (11, 0b00000110111, 22), (11, 0b00000101000, 23),
+    (11, 0b00000010111, 24), (11, 0b00000011000, 25), (11, 0b00000001000, 1792),
+    (11, 0b00000001100, 1856), (11, 0b00000001101, 1920), (12, 0b000011001010, 26),
+    (12, 0b000011001011, 27), (12, 0b000011001100, 28), (12, 0b000011001101, 29),
+    (12, 0b000001101000, 30), (12, 0b000001101001, 31), (12, 0b000001101010, 32),
+    (12, 0b000001101011, 33), (12, 0b000011010010, 34), (12, 0b000011010011, 35),
+    (12, 0b000011010100, 36), (12, 0b000011010101, 37), (12, 0b000011010110, 38),
+    (12, 0b000011010111, 39), (12, 0b000001101100, 40), (12, 0b000001101101, 41),
+    (12, 0b000011011010, 42), (12, 0b000011011011, 43), (12, 0b000001010100, 44),
+    (12, 0b000001010101, 45), (12, 0b000001010110, 46), (12, 0b000001010111, 47),
+    (12, 0b000001100100, 48), (12, 0b000001100101, 49), (12, 0b000001010010, 50),
+    (12, 0b000001010011, 51), (12, 0b000000100100, 52), (12, 0b000000110111, 53),
+    (12, 0b000000111000, 54), (12, 0b000000100111, 55), (12, 0b000000101000, 56),
+    (12, 0b000001011000, 57), (12, 0b000001011001, 58), (12, 0b000000101011, 59),
+    (12, 0b000000101100, 60), (12, 0b000001011010, 61), (12, 0b000001100110, 62),
+    (12, 0b000001100111, 63), (12, 0b000011001000, 128), (12, 0b000011001001, 192),
+    (12, 0b000001011011, 256), (12, 0b000000110011, 320), (12, 0b000000110100, 384),
+    (12, 0b000000110101, 448), (12, 0b000000010010, 1984), (12, 0b000000010011, 2048),
+    (12, 0b000000010100, 2112), (12, 0b000000010101, 2176), (12, 0b000000010110, 2240),
+    (12, 0b000000010111, 2304), (12, 0b000000011100, 2368), (12, 0b000000011101, 2432),
+    (12, 0b000000011110, 2496), (12, 0b000000011111, 2560), (13, 0b0000001101100, 512),
+    (13, 0b0000001101101, 576), (13, 0b0000001001010, 640), (13, 0b0000001001011, 704),
+    (13, 0b0000001001100, 768), (13, 0b0000001001101, 832), (13, 0b0000001110010, 896),
+    (13, 0b0000001110011, 960), (13, 0b0000001110100, 1024), (13, 0b0000001110101, 1088),
+    (13, 0b0000001110110, 1152), (13, 0b0000001110111, 1216), (13, 0b0000001010010, 1280),
+    (13, 0b0000001010011, 1344), (13, 0b0000001010100, 1408), (13, 0b0000001010101, 1472),
+    (13, 0b0000001011010, 1536), (13, 0b0000001011011, 1600), (13, 0b0000001100100, 1664),
+    (13, 0b0000001100101, 1728),
+];
+
+/// Bit reader which yields zeros past the end of the data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    fn bit(&self, offset: usize) -> u16 {
+        let pos = self.pos + offset;
+        match self.data.get(pos / 8) {
+            Some(&byte) => ((byte >> (7 - pos % 8)) & 1) as u16,
+            None => 0
+        }
+    }
+    fn peek(&self, len: usize) -> u16 {
+        (0..len).fold(0, |code, i| (code << 1) | self.bit(i))
+    }
+    fn skip(&mut self, len: usize) {
+        self.pos += len;
+    }
+    fn align(&mut self) {
+        self.pos = (self.pos + 7) / 8 * 8;
+    }
+    fn bits_left(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.pos)
+    }
+    /// Whether only zero bits (padding) are left.
+    fn rest_is_zero(&self) -> bool {
+        let byte = self.pos / 8;
+        if byte >= self.data.len() {
+            return true;
+        }
+        (self.data[byte] & (0xff >> (self.pos % 8))) == 0 && self.data[byte + 1 ..].iter().all(|&b| b == 0)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Mode {
+    Pass,
+    Horizontal,
+    /// Offset of a1 relative to b1
+    Vertical (i32),
+}
+const MODE_CODES: &'static [(u8, u16, Mode)] = &[
+    (1, 0b1, Mode::Vertical (0)),
+    (3, 0b001, Mode::Horizontal),
+    (3, 0b011, Mode::Vertical (1)),
+    (3, 0b010, Mode::Vertical (-1)),
+    (4, 0b0001, Mode::Pass),
+    (6, 0b000011, Mode::Vertical (2)),
+    (6, 0b000010, Mode::Vertical (-2)),
+    (7, 0b0000011, Mode::Vertical (3)),
+    (7, 0b0000010, Mode::Vertical (-3)),
+];
+
+fn read_mode(r: &mut Reader) -> Result<Mode> {
+    let found = MODE_CODES.iter().find(|&&(len, code, _)| r.peek(len as usize) == code).cloned();
+    match found {
+        Some((len, _, mode)) => {
+            r.skip(len as usize);
+            Ok(mode)
+        }
+        None if r.peek(7) == 0b0000001 => bail!("CCITTFaxDecode: uncompressed mode is not supported"),
+        None => bail!("CCITTFaxDecode: invalid mode code at bit {}", r.pos),
+    }
+}
+
+/// Reads make-up codes followed by a terminating code.
+fn read_run(r: &mut Reader, codes: &[(u8, u16, u16)]) -> Result<i32> {
+    let mut total = 0;
+    loop {
+        let found = codes.iter().find(|&&(len, code, _)| r.peek(len as usize) == code).cloned();
+        let run = match found {
+            Some((len, _, run)) => {
+                r.skip(len as usize);
+                run
+            }
+            None => bail!("CCITTFaxDecode: invalid run length code at bit {}", r.pos),
+        };
+        total += run as i32;
+        if run < 64 {
+            return Ok(total);
+        }
+    }
+}
+
+/// Skips an EOL code: at least 11 zero bits (including fill) followed by a one.
+fn skip_eol(r: &mut Reader) -> bool {
+    let left = r.bits_left();
+    let mut zeros = 0;
+    while zeros < left && r.bit(zeros) == 0 {
+        zeros += 1;
+    }
+    if zeros >= 11 && zeros < left {
+        r.skip(zeros + 1);
+        true
+    } else {
+        false
+    }
+}
+
+// A line is represented by its changing elements: the positions where the color changes.
+// Lines start white, so changes to black have even indices.
+
+fn decode_1d(r: &mut Reader, columns: i32, changes: &mut Vec<i32>) -> Result<()> {
+    changes.clear();
+    let mut a0 = 0;
+    let mut white = true;
+    while a0 < columns {
+        let run = read_run(r, if white { WHITE_CODES } else { BLACK_CODES })?;
+        a0 = cmp::min(a0 + run, columns);
+        changes.push(a0);
+        white = !white;
+    }
+    Ok(())
+}
+
+fn decode_2d(r: &mut Reader, reference: &[i32], columns: i32, changes: &mut Vec<i32>) -> Result<()> {
+    changes.clear();
+    // changing elements past the end of the reference line are at `columns`
+    let b = |i: usize| reference.get(i).cloned().unwrap_or(columns);
+
+    // a0 starts on an imaginary white element in front of the line
+    let mut a0 = -1;
+    let mut white = true;
+    let mut j = 0;
+    while a0 < columns {
+        while j < reference.len() && reference[j] <= a0 {
+            j += 1;
+        }
+        // b1: first changing element right of a0 with the color opposite to a0
+        let i = if (j % 2 == 0) == white { j } else { j + 1 };
+        let (b1, b2) = (b(i), b(i + 1));
+
+        match read_mode(r)? {
+            Mode::Pass => a0 = b2,
+            Mode::Horizontal => {
+                let (first, second) = if white {
+                    (WHITE_CODES, BLACK_CODES)
+                } else {
+                    (BLACK_CODES, WHITE_CODES)
+                };
+                let a1 = cmp::min(cmp::max(a0, 0) + read_run(r, first)?, columns);
+                let a2 = cmp::min(a1 + read_run(r, second)?, columns);
+                changes.push(a1);
+                changes.push(a2);
+                a0 = a2;
+            }
+            Mode::Vertical (offset) => {
+                let a1 = b1 + offset;
+                if a1 < cmp::max(a0, 0) || a1 > columns {
+                    bail!("CCITTFaxDecode: vertical mode out of line at bit {}", r.pos);
+                }
+                changes.push(a1);
+                a0 = a1;
+                white = !white;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Appends one row of packed pixels. 0 bits are black, unless `black_is_1`.
+fn write_row(changes: &[i32], columns: i32, black_is_1: bool, out: &mut Vec<u8>) {
+    let start = out.len();
+    let row_len = (columns as usize + 7) / 8;
+    out.resize(start + row_len, if black_is_1 { 0 } else { 0xff });
+
+    let row = &mut out[start ..];
+    for pair in changes.chunks(2) {
+        let from = pair[0] as usize;
+        let to = pair.get(1).cloned().unwrap_or(columns) as usize;
+        for x in from .. to {
+            row[x / 8] ^= 0x80 >> (x % 8);
+        }
+    }
+}
+
+pub fn decode(data: &[u8], params: &CCITTFaxDecodeParams) -> Result<Vec<u8>> {
+    let columns = params.columns;
+    if columns < 1 {
+        bail!("CCITTFaxDecode: invalid /Columns {}", columns);
+    }
+    let rows = if params.rows > 0 { Some(params.rows as usize) } else { None };
+
+    let mut r = Reader {data: data, pos: 0};
+    let mut out = Vec::with_capacity((columns as usize + 7) / 8 * rows.unwrap_or(64));
+    let mut reference = Vec::new();
+    let mut changes = Vec::new();
+    let mut n_rows = 0;
+
+    while rows.map(|rows| n_rows < rows).unwrap_or(true) {
+        if params.encoded_byte_align {
+            r.align();
+        }
+        let mut eols = 0;
+        while skip_eol(&mut r) {
+            eols += 1;
+        }
+        // EOFB (two EOLs) ends Group 4 data, RTC (six EOLs) Group 3 data.
+        let end_of_block = if params.k < 0 { eols >= 2 } else { eols >= 6 };
+        if params.end_of_block && end_of_block || r.rest_is_zero() {
+            break;
+        }
+
+        let one_dimensional = match params.k {
+            k if k < 0 => false,
+            0 => true,
+            // tag bit in front of each line
+            _ => {
+                let tag = r.peek(1) == 1;
+                r.skip(1);
+                tag
+            }
+        };
+        if one_dimensional {
+            decode_1d(&mut r, columns, &mut changes)?;
+        } else {
+            decode_2d(&mut r, &reference, columns, &mut changes)?;
+        }
+        while changes.last().map(|&c| c >= columns).unwrap_or(false) {
+            changes.pop();
+        }
+
+        write_row(&changes, columns, params.black_is_1, &mut out);
+        mem::swap(&mut reference, &mut changes);
+        n_rows += 1;
+    }
+    Ok(out)
+}
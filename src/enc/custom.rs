@@ -0,0 +1,33 @@
+//! Filters that are not part of the PDF standard, registered by the application.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use err::*;
+use primitive::Dictionary;
+
+/// Decoder (and optionally encoder) of a non-standard filter, see `register_filter`.
+pub trait CustomFilter: Send + Sync {
+    /// Undoes the filter. `params` is the `/DecodeParms` dictionary of the filter (empty if
+    /// there is none).
+    fn decode(&self, data: &[u8], params: &Dictionary) -> Result<Vec<u8>>;
+
+    /// Applies the filter.
+    fn encode(&self, _data: &[u8], _params: &Dictionary) -> Result<Vec<u8>> {
+        bail!("Encoding is not supported by this filter")
+    }
+}
+
+lazy_static! {
+    static ref CUSTOM_FILTERS: RwLock<HashMap<String, Arc<CustomFilter>>> = RwLock::new(HashMap::new());
+}
+
+/// Registers `filter` for the `/Filter` name `name` (replacing an earlier registration). Streams
+/// with filters that are neither standard nor registered fail to parse.
+pub fn register_filter(name: &str, filter: Arc<CustomFilter>) {
+    CUSTOM_FILTERS.write().unwrap().insert(name.into(), filter);
+}
+
+/// The filter registered for `name`.
+pub fn custom_filter(name: &str) -> Option<Arc<CustomFilter>> {
+    CUSTOM_FILTERS.read().unwrap().get(name).cloned()
+}
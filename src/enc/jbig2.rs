@@ -0,0 +1,117 @@
+//! `JBIG2Decode`: segment parsing of embedded JBIG2 streams. The actual decoding is left to
+//! external decoders, which get the segments of the `JBIG2Globals` stream followed by those
+//! of the image stream.
+
+use err::*;
+use byteorder::{BigEndian, ByteOrder};
+use super::ImageInfo;
+
+/// Segment type of page information segments
+pub const PAGE_INFORMATION: u8 = 48;
+
+/// A segment (header and data) of a JBIG2 stream.
+#[derive(Debug, Clone)]
+pub struct Segment<'a> {
+    pub number: u32,
+    pub kind: u8,
+    /// Numbers of the segments that this one refers to
+    pub referred_to: Vec<u32>,
+    pub page: u32,
+    /// Header and data: the complete segment, as it appears in the stream
+    pub raw: &'a [u8],
+    pub data: &'a [u8],
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.pos + n > self.data.len() {
+            bail!(ErrorKind::EOF);
+        }
+        let slice = &self.data[self.pos .. self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16> {
+        Ok(BigEndian::read_u16(self.take(2)?))
+    }
+    fn u32(&mut self) -> Result<u32> {
+        Ok(BigEndian::read_u32(self.take(4)?))
+    }
+}
+
+/// Splits JBIG2 data in the embedded organization (7.4 in ISO 14492) into segments.
+pub fn segments(data: &[u8]) -> Result<Vec<Segment>> {
+    let mut r = Reader {data: data, pos: 0};
+    let mut segments = Vec::new();
+    while r.pos < data.len() {
+        let start = r.pos;
+        let number = r.u32()?;
+        let flags = r.u8()?;
+        let kind = flags & 0x3f;
+        let long_page = flags & 0x40 != 0;
+
+        // referred-to segment count and retention flags
+        let count_byte = r.u8()?;
+        let count = match count_byte >> 5 {
+            7 => {
+                r.pos -= 1;
+                let count = r.u32()? & 0x1fff_ffff;
+                // retention flags: one bit per referred-to segment and one for this segment
+                r.take((count as usize + 1 + 7) / 8)?;
+                count
+            }
+            5 | 6 => bail!("JBIG2: invalid referred-to segment count"),
+            n => n as u32,
+        };
+        let mut referred_to = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            referred_to.push(match number {
+                0 ... 256 => r.u8()? as u32,
+                257 ... 65536 => r.u16()? as u32,
+                _ => r.u32()?,
+            });
+        }
+        let page = if long_page { r.u32()? } else { r.u8()? as u32 };
+        let len = r.u32()?;
+        if len == 0xffff_ffff {
+            bail!("JBIG2: segments of unknown length are not supported");
+        }
+        let header_end = r.pos;
+        r.take(len as usize)?;
+
+        segments.push(Segment {
+            number: number,
+            kind: kind,
+            referred_to: referred_to,
+            page: page,
+            raw: &data[start .. r.pos],
+            data: &data[header_end .. r.pos],
+        });
+    }
+    Ok(segments)
+}
+
+/// Reads the size from the page information segment.
+pub fn info(data: &[u8]) -> Result<ImageInfo> {
+    for segment in segments(data)? {
+        if segment.kind == PAGE_INFORMATION {
+            if segment.data.len() < 8 {
+                bail!("JBIG2: truncated page information segment");
+            }
+            return Ok(ImageInfo {
+                width: BigEndian::read_u32(segment.data),
+                height: BigEndian::read_u32(&segment.data[4..]),
+                components: 1,
+                bits_per_component: 1,
+            });
+        }
+    }
+    bail!("JBIG2: no page information segment found")
+}
@@ -0,0 +1,1063 @@
+//! Stream filters: decoding and encoding of stream data.
+use inflate::InflateStream;
+use deflate::deflate_bytes_zlib;
+use err::*;
+use std::{io, mem, cmp};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use byteorder::{BigEndian, ByteOrder};
+
+use object::{Object, Resolve, Stream};
+use primitive::{Primitive, Dictionary};
+
+mod ccitt;
+mod reader;
+mod custom;
+pub mod jbig2;
+
+pub use self::reader::decode_reader;
+pub use self::custom::{CustomFilter, register_filter, custom_filter};
+
+
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct LZWFlateParams {
+    #[pdf(key="Predictor", default="1")]
+    pub predictor: i32,
+    #[pdf(key="Colors", default="1")]
+    pub n_components: i32,
+    #[pdf(key="BitsPerComponent", default="8")]
+    pub bits_per_component: i32,
+    #[pdf(key="Columns", default="1")]
+    pub columns: i32,
+    #[pdf(key="EarlyChange", default="1")]
+    pub early_change: i32,
+}
+impl Default for LZWFlateParams {
+    fn default() -> LZWFlateParams {
+        LZWFlateParams {
+            predictor: 1,
+            n_components: 1,
+            bits_per_component: 8,
+            columns: 1,
+            early_change: 1,
+        }
+    }
+}
+
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct DCTDecodeParams {
+    // TODO The default value of ColorTransform is 1 if the image has three components and 0 otherwise.
+    // 0:   No transformation.
+    // 1:   If the image has three color components, transform RGB values to YUV before encoding and from YUV to RGB after decoding.
+    //      If the image has four components, transform CMYK values to YUVK before encoding and from YUVK to CMYK after decoding.
+    //      This option is ignored if the image has one or two color components.
+    #[pdf(key="ColorTransform")]
+    pub color_transform: Option<i32>,
+}
+
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct CCITTFaxDecodeParams {
+    /// < 0: pure two-dimensional (Group 4), 0: one-dimensional (Group 3),
+    /// > 0: mixed one- and two-dimensional (Group 3, 2-D)
+    #[pdf(key="K", default="0")]
+    pub k: i32,
+    #[pdf(key="EndOfLine", default="false")]
+    pub end_of_line: bool,
+    #[pdf(key="EncodedByteAlign", default="false")]
+    pub encoded_byte_align: bool,
+    #[pdf(key="Columns", default="1728")]
+    pub columns: i32,
+    /// 0 if unknown - the data is then decoded until end-of-block or end of data.
+    #[pdf(key="Rows", default="0")]
+    pub rows: i32,
+    #[pdf(key="EndOfBlock", default="true")]
+    pub end_of_block: bool,
+    #[pdf(key="BlackIs1", default="false")]
+    pub black_is_1: bool,
+    #[pdf(key="DamagedRowsBeforeError", default="0")]
+    pub damaged_rows_before_error: i32,
+}
+
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct JBIG2DecodeParams {
+    /// Segments that are shared between several JBIG2 images.
+    #[pdf(key="JBIG2Globals")]
+    pub globals: Option<Stream<()>>,
+}
+impl JBIG2DecodeParams {
+    /// The segments of the `JBIG2Globals` stream followed by those of `data`: what an
+    /// external decoder (e.g. `jbig2dec -e`) needs to decode the image.
+    pub fn embedded_data(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        if let Some(ref globals) = self.globals {
+            out.extend_from_slice(&globals.get_data()?);
+        }
+        out.extend_from_slice(data);
+        Ok(out)
+    }
+}
+
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct CryptFilterParams {
+    /// Name of the crypt filter in the `/CF` dictionary of the security handler.
+    #[pdf(key="Name", default="String::from(\"Identity\")")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamFilter {
+    ASCIIHexDecode,
+    ASCII85Decode,
+    LZWDecode (LZWFlateParams),
+    FlateDecode (LZWFlateParams),
+    /// JPEG 2000
+    JPXDecode,
+    DCTDecode (DCTDecodeParams),
+    CCITTFaxDecode (CCITTFaxDecodeParams),
+    JBIG2Decode (JBIG2DecodeParams),
+    /// Decryption by the security handler of the document. The objects of an encrypted document
+    /// are decrypted as they are read, which takes this filter out - so if it is left, it is
+    /// the `Identity` filter, or the document couldn't be decrypted.
+    Crypt (CryptFilterParams),
+    /// A filter registered with `register_filter`.
+    Custom {name: String, params: Dictionary},
+}
+impl StreamFilter {
+    pub fn from_kind_and_params(kind: &str, params: Dictionary, r: &Resolve) -> Result<StreamFilter> {
+       let params = Primitive::Dictionary (params);
+       Ok(
+       match kind {
+           "ASCIIHexDecode" => StreamFilter::ASCIIHexDecode,
+           "ASCII85Decode" => StreamFilter::ASCII85Decode,
+           "LZWDecode" => StreamFilter::LZWDecode (LZWFlateParams::from_primitive(params, r)?),
+           "FlateDecode" => StreamFilter::FlateDecode (LZWFlateParams::from_primitive(params, r)?),
+           "JPXDecode" => StreamFilter::JPXDecode,
+           "DCTDecode" => StreamFilter::DCTDecode (DCTDecodeParams::from_primitive(params, r)?),
+           "CCITTFaxDecode" => StreamFilter::CCITTFaxDecode (CCITTFaxDecodeParams::from_primitive(params, r)?),
+           "JBIG2Decode" => StreamFilter::JBIG2Decode (JBIG2DecodeParams::from_primitive(params, r)?),
+           "Crypt" => StreamFilter::Crypt (CryptFilterParams::from_primitive(params, r)?),
+           name if custom_filter(name).is_some() => StreamFilter::Custom {
+               name: name.into(),
+               params: Dictionary::from_primitive(params, r)?,
+           },
+           _ => bail!("Unrecognized filter type"),
+       } 
+       )
+    }
+    /// The name of the filter, as used in `/Filter`.
+    pub fn name(&self) -> &str {
+        match *self {
+            StreamFilter::ASCIIHexDecode => "ASCIIHexDecode",
+            StreamFilter::ASCII85Decode => "ASCII85Decode",
+            StreamFilter::LZWDecode (_) => "LZWDecode",
+            StreamFilter::FlateDecode (_) => "FlateDecode",
+            StreamFilter::JPXDecode => "JPXDecode",
+            StreamFilter::DCTDecode (_) => "DCTDecode",
+            StreamFilter::CCITTFaxDecode (_) => "CCITTFaxDecode",
+            StreamFilter::JBIG2Decode (_) => "JBIG2Decode",
+            StreamFilter::Crypt (_) => "Crypt",
+            StreamFilter::Custom {ref name, ..} => name,
+        }
+    }
+    /// Writes the parameters of the filter, as used in `/DecodeParms` (`null` if the filter
+    /// has none).
+    pub fn serialize_params<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            StreamFilter::LZWDecode (ref p) | StreamFilter::FlateDecode (ref p) => p.serialize(out),
+            StreamFilter::DCTDecode (ref p) => p.serialize(out),
+            StreamFilter::CCITTFaxDecode (ref p) => p.serialize(out),
+            StreamFilter::JBIG2Decode (ref p) => p.serialize(out),
+            StreamFilter::Crypt (ref p) => p.serialize(out),
+            StreamFilter::Custom {ref params, ..} if params.len() == 0 => write!(out, "null"),
+            StreamFilter::Custom {ref params, ..} => params.serialize(out),
+            StreamFilter::ASCIIHexDecode | StreamFilter::ASCII85Decode | StreamFilter::JPXDecode
+                => write!(out, "null"),
+        }
+    }
+    /// Image codecs (`DCTDecode`, `JPXDecode`, `JBIG2Decode`) are not undone by
+    /// `Stream::decode()`, because their payload is usually more useful to an image decoder
+    /// than the raw pixels.
+    pub fn is_image_codec(&self) -> bool {
+        match *self {
+            StreamFilter::DCTDecode (_) | StreamFilter::JPXDecode | StreamFilter::JBIG2Decode (_) => true,
+            _ => false
+        }
+    }
+}
+
+/// Limits for the size of decoded stream data, against decompression bombs (a few bytes of
+/// FlateDecode data can expand to gigabytes).
+#[derive(Debug)]
+pub struct DecodeLimits {
+    /// Maximum size of the decoded data of one stream.
+    pub max_stream_size: usize,
+    /// Maximum size of the decoded data of all streams decoded with these limits together (e.g.
+    /// all streams of a document).
+    pub max_total_size: usize,
+    total: AtomicUsize,
+}
+impl DecodeLimits {
+    pub fn new(max_stream_size: usize, max_total_size: usize) -> DecodeLimits {
+        DecodeLimits {
+            max_stream_size: max_stream_size,
+            max_total_size: max_total_size,
+            total: AtomicUsize::new(0),
+        }
+    }
+    pub fn unlimited() -> DecodeLimits {
+        DecodeLimits::new(usize::max_value(), usize::max_value())
+    }
+    /// Size of the data decoded so far.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+    /// The size that the next decoded stream may have: `max_stream_size`, or less if the total
+    /// is almost reached.
+    pub fn stream_limit(&self) -> usize {
+        cmp::min(self.max_stream_size, self.max_total_size.saturating_sub(self.total()))
+    }
+    /// Counts `size` decoded bytes towards the total.
+    pub fn add(&self, size: usize) -> Result<()> {
+        let total = self.total.fetch_add(size, Ordering::Relaxed).saturating_add(size);
+        if total > self.max_total_size {
+            bail!(ErrorKind::DecodeLimit {limit: self.max_total_size});
+        }
+        Ok(())
+    }
+}
+impl Default for DecodeLimits {
+    /// 256 MiB per stream, no limit for the total.
+    fn default() -> DecodeLimits {
+        DecodeLimits::new(256 << 20, usize::max_value())
+    }
+}
+
+/// Image properties as found in the header of an image codec payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub components: u8,
+    pub bits_per_component: u8,
+}
+
+/// Reads the image properties of `data` encoded with the image codec `filter`.
+pub fn image_info(data: &[u8], filter: &StreamFilter) -> Result<ImageInfo> {
+    match *filter {
+        StreamFilter::DCTDecode (_) => dct_info(data),
+        StreamFilter::JBIG2Decode (_) => jbig2::info(data),
+        StreamFilter::JPXDecode => jpx_info(data),
+        ref filter => bail!("{:?} is not an image codec", filter),
+    }
+}
+
+/// Finds the SOF (start of frame) segment of a JPEG stream.
+fn dct_info(data: &[u8]) -> Result<ImageInfo> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        bail!("DCTDecode: missing SOI marker");
+    }
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            bail!("DCTDecode: expected marker at {}", pos);
+        }
+        let marker = data[pos + 1];
+        // fill bytes
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        let len = BigEndian::read_u16(&data[pos + 2 ..]) as usize;
+        match marker {
+            0xC0 ... 0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                let sof = &data[pos + 4 .. cmp::min(pos + 2 + len, data.len())];
+                if sof.len() < 6 {
+                    bail!("DCTDecode: truncated SOF segment");
+                }
+                return Ok(ImageInfo {
+                    bits_per_component: sof[0],
+                    height: BigEndian::read_u16(&sof[1..]) as u32,
+                    width: BigEndian::read_u16(&sof[3..]) as u32,
+                    components: sof[5],
+                });
+            }
+            // start of scan: the frame header should have come before
+            0xDA => break,
+            _ => pos += 2 + len
+        }
+    }
+    bail!("DCTDecode: no SOF segment found")
+}
+
+/// JPX data is either a JP2 file (a sequence of boxes) or a bare JPEG 2000 codestream.
+fn jpx_info(data: &[u8]) -> Result<ImageInfo> {
+    if data.starts_with(&[0xFF, 0x4F, 0xFF, 0x51]) {
+        return j2k_info(data);
+    }
+    // Boxes: length (4 bytes, including the header), type (4 bytes) and contents.
+    // `jp2h` is a superbox containing the image header box `ihdr`.
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let len = BigEndian::read_u32(&data[pos ..]) as usize;
+        let (header_len, len) = match len {
+            0 => (8, data.len() - pos),
+            1 if pos + 16 <= data.len() => (16, BigEndian::read_u64(&data[pos + 8 ..]) as usize),
+            len => (8, len),
+        };
+        if len < header_len {
+            bail!("JPXDecode: invalid box length at {}", pos);
+        }
+        match &data[pos + 4 .. pos + 8] {
+            b"jp2h" => {
+                pos += header_len;
+                continue;
+            }
+            b"ihdr" => {
+                let ihdr = &data[pos + header_len .. cmp::min(pos + len, data.len())];
+                if ihdr.len() < 11 {
+                    bail!("JPXDecode: truncated ihdr box");
+                }
+                return Ok(ImageInfo {
+                    height: BigEndian::read_u32(ihdr),
+                    width: BigEndian::read_u32(&ihdr[4..]),
+                    components: BigEndian::read_u16(&ihdr[8..]) as u8,
+                    // 255: varying bit depth (given in the bpcc box)
+                    bits_per_component: (ihdr[10] & 0x7f) + 1,
+                });
+            }
+            b"jp2c" => return j2k_info(&data[pos + header_len ..]),
+            _ => pos += len,
+        }
+    }
+    bail!("JPXDecode: no image header found")
+}
+/// Reads the SIZ marker segment of a JPEG 2000 codestream.
+fn j2k_info(data: &[u8]) -> Result<ImageInfo> {
+    if !data.starts_with(&[0xFF, 0x4F, 0xFF, 0x51]) || data.len() < 4 + 39 {
+        bail!("JPXDecode: invalid codestream");
+    }
+    let siz = &data[6 ..];
+    let x = BigEndian::read_u32(&siz[2..]);
+    let y = BigEndian::read_u32(&siz[6..]);
+    let x_offset = BigEndian::read_u32(&siz[10..]);
+    let y_offset = BigEndian::read_u32(&siz[14..]);
+    let components = BigEndian::read_u16(&siz[34..]);
+    Ok(ImageInfo {
+        width: x.saturating_sub(x_offset),
+        height: y.saturating_sub(y_offset),
+        components: components as u8,
+        // of the first component
+        bits_per_component: (siz[36] & 0x7f) + 1,
+    })
+}
+
+#[cfg(feature = "jpeg")]
+fn dct_decode(data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
+    use jpeg_decoder::Decoder;
+    // The decoder applies the color transform itself, as indicated by the Adobe marker.
+    let mut decoder = Decoder::new(data);
+    decoder.decode().map_err(|e| Error::from(format!("DCTDecode: {}", e)))
+}
+#[cfg(not(feature = "jpeg"))]
+fn dct_decode(_data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
+    bail!(ErrorKind::UnsupportedFilter {filter: "DCTDecode", feature: "jpeg"})
+}
+
+fn decode_nibble(c: u8) -> Option<u8> {
+    match c {
+        n @ b'0' ... b'9' => Some(n - b'0'),
+        a @ b'a' ... b'f' => Some(a - b'a' + 0xa),
+        a @ b'A' ... b'F' => Some(a - b'A' + 0xA),
+        _ => None
+    }
+}
+
+#[inline]
+fn is_whitespace(c: u8) -> bool {
+    match c {
+        b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ' => true,
+        _ => false
+    }
+}
+
+/// Whitespace is ignored, `>` marks the end of data. An odd last digit is padded with 0.
+fn decode_hex(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 2);
+    let mut high: Option<u8> = None;
+    for (pos, &c) in data.iter().enumerate() {
+        if is_whitespace(c) {
+            continue;
+        }
+        if c == b'>' {
+            break;
+        }
+        let nibble = decode_nibble(c)
+            .ok_or_else(|| ErrorKind::HexDecode {pos: pos, bytes: [c, data.get(pos + 1).cloned().unwrap_or(0)]})?;
+        high = match high {
+            Some(high) => {
+                out.push(high << 4 | nibble);
+                None
+            }
+            None => Some(nibble)
+        };
+    }
+    if let Some(high) = high {
+        out.push(high << 4);
+    }
+    Ok(out)
+}
+
+fn encode_hex(data: &[u8]) -> Vec<u8> {
+    const DIGITS: &'static [u8; 16] = b"0123456789ABCDEF";
+    let mut out = Vec::with_capacity(data.len() * 2 + data.len() / 32 + 1);
+    for (i, &b) in data.iter().enumerate() {
+        if i > 0 && i % 32 == 0 {
+            out.push(b'\n');
+        }
+        out.push(DIGITS[(b >> 4) as usize]);
+        out.push(DIGITS[(b & 0xf) as usize]);
+    }
+    out.push(b'>');
+    out
+}
+
+#[inline]
+fn sym_85(byte: u8) -> Option<u8> {
+    match byte {
+        b @ 0x21 ... 0x75 => Some(b - 0x21),
+        _ => None
+    }
+}
+
+/// Whitespace is ignored, `z` stands for four zero bytes and `~>` marks the end of data.
+fn decode_85(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 5 * 4 + 4);
+
+    let mut group = [0u8; 5];
+    let mut n = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let c = data[pos];
+        pos += 1;
+        if is_whitespace(c) {
+            continue;
+        }
+        match c {
+            b'~' => break,
+            b'z' if n == 0 => out.extend_from_slice(&[0; 4]),
+            c => {
+                group[n] = sym_85(c).ok_or(ErrorKind::Ascii85Decode {pos: pos - 1, byte: c})?;
+                n += 1;
+                if n == 5 {
+                    out.extend_from_slice(&word_85(&group)?);
+                    n = 0;
+                }
+            }
+        }
+    }
+    // The final partial group of n characters encodes n-1 bytes
+    match n {
+        0 => {}
+        1 => bail!(ErrorKind::Ascii85TailError),
+        n => {
+            for sym in &mut group[n ..] {
+                *sym = b'u' - 0x21;
+            }
+            out.extend_from_slice(&word_85(&group)?[.. n - 1]);
+        }
+    }
+    Ok(out)
+}
+fn word_85(group: &[u8; 5]) -> Result<[u8; 4]> {
+    let q = group.iter().fold(0u64, |q, &sym| q * 85 + sym as u64);
+    if q > 0xffff_ffff {
+        bail!(ErrorKind::Ascii85TailError);
+    }
+    let q = q as u32;
+    Ok([(q >> 24) as u8, (q >> 16) as u8, (q >> 8) as u8, q as u8])
+}
+
+fn encode_85(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 5 + 7);
+    for (i, chunk) in data.chunks(4).enumerate() {
+        if i > 0 && i % 16 == 0 {
+            out.push(b'\n');
+        }
+        let mut word = [0u8; 4];
+        word[.. chunk.len()].copy_from_slice(chunk);
+        let mut q = BigEndian::read_u32(&word);
+        if q == 0 && chunk.len() == 4 {
+            out.push(b'z');
+            continue;
+        }
+        let mut group = [0u8; 5];
+        for sym in group.iter_mut().rev() {
+            *sym = (q % 85) as u8 + 0x21;
+            q /= 85;
+        }
+        out.extend_from_slice(&group[.. chunk.len() + 1]);
+    }
+    out.extend_from_slice(b"~>");
+    out
+}
+
+
+fn flate_decode(data: &[u8], params: &LZWFlateParams, max_size: usize) -> Result<Vec<u8>> {
+    // First flate decode
+    let mut inflater = InflateStream::from_zlib();
+    let mut out = Vec::<u8>::new();
+    let mut n = 0;
+    while n < data.len() {
+        let res = inflater.update(&data[n..]);
+        let (num_bytes_read, result) = res?;
+        // end of the zlib stream - ignore trailing data
+        if num_bytes_read == 0 && result.is_empty() {
+            break;
+        }
+        n += num_bytes_read;
+        out.extend(result);
+        if out.len() > max_size {
+            bail!(ErrorKind::DecodeLimit {limit: max_size});
+        }
+    }
+
+    // Then undo the predictor
+    unpredict(out, params)
+}
+
+fn flate_encode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    let data = predict(data, params)?;
+    Ok(deflate_bytes_zlib(&data))
+}
+
+/// Reads MSB-first codes of varying bit length.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> BitReader<'a> {
+    fn read(&mut self, len: usize) -> Option<u16> {
+        if self.pos + len > self.data.len() * 8 {
+            return None;
+        }
+        let mut code = 0;
+        for _ in 0..len {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            code = (code << 1) | bit as u16;
+            self.pos += 1;
+        }
+        Some(code)
+    }
+}
+/// Writes MSB-first codes of varying bit length.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    n: u32,
+}
+impl BitWriter {
+    fn write(&mut self, code: u16, len: u32) {
+        self.acc = (self.acc << len) | code as u32;
+        self.n += len;
+        while self.n >= 8 {
+            self.n -= 8;
+            self.out.push((self.acc >> self.n) as u8);
+        }
+        self.acc &= (1 << self.n) - 1;
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push((self.acc << (8 - self.n)) as u8);
+        }
+        self.out
+    }
+}
+
+const LZW_CLEAR: u16 = 256;
+const LZW_EOD: u16 = 257;
+const LZW_FIRST: u16 = 258;
+const LZW_MAX: u16 = 4096;
+
+/// Code length in bits, given the next code the decoder will assign. With `/EarlyChange 1`
+/// (the default), the length is increased one code early.
+fn lzw_code_len(next_code: u16, early_change: bool) -> u32 {
+    match next_code + early_change as u16 {
+        0 ... 511 => 9,
+        512 ... 1023 => 10,
+        1024 ... 2047 => 11,
+        _ => 12
+    }
+}
+
+fn lzw_decode(data: &[u8], params: &LZWFlateParams, max_size: usize) -> Result<Vec<u8>> {
+    let early_change = params.early_change != 0;
+    let mut reader = BitReader {data: data, pos: 0};
+    let mut out = Vec::with_capacity(data.len() * 3);
+
+    // Every table entry is a substring of the output: (start, length).
+    let mut table: Vec<(usize, usize)> = Vec::with_capacity((LZW_MAX - LZW_FIRST) as usize);
+    let mut prev: Option<(usize, usize)> = None;
+    let mut code_len = 9;
+
+    while let Some(code) = reader.read(code_len) {
+        match code {
+            LZW_CLEAR => {
+                table.clear();
+                prev = None;
+                code_len = 9;
+                continue;
+            }
+            LZW_EOD => break,
+            _ => {}
+        }
+        let start = out.len();
+        if code < LZW_CLEAR {
+            out.push(code as u8);
+        } else if ((code - LZW_FIRST) as usize) < table.len() {
+            let (s, len) = table[(code - LZW_FIRST) as usize];
+            for i in s .. s + len {
+                let b = out[i];
+                out.push(b);
+            }
+        } else if (code - LZW_FIRST) as usize == table.len() {
+            // The code that is just being defined: previous string + its first byte
+            let (s, len) = match prev {
+                Some(p) => p,
+                None => bail!("LZWDecode: code {} without preceding code", code),
+            };
+            for i in s .. s + len {
+                let b = out[i];
+                out.push(b);
+            }
+            let b = out[s];
+            out.push(b);
+        } else {
+            bail!("LZWDecode: invalid code {}", code);
+        }
+
+        if let Some((s, len)) = prev {
+            if table.len() < (LZW_MAX - LZW_FIRST) as usize {
+                // previous string + first byte of this one, which directly follows it
+                table.push((s, len + 1));
+            }
+        }
+        prev = Some((start, out.len() - start));
+        code_len = lzw_code_len(LZW_FIRST + table.len() as u16, early_change) as usize;
+        if out.len() > max_size {
+            bail!(ErrorKind::DecodeLimit {limit: max_size});
+        }
+    }
+
+    unpredict(out, params)
+}
+
+fn lzw_encode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    let data = predict(data, params)?;
+    let early_change = params.early_change != 0;
+    // The decoder lags one entry behind the encoder.
+    let code_len = |next_code: u16| lzw_code_len(cmp::max(LZW_FIRST, next_code - 1), early_change);
+
+    let mut writer = BitWriter {out: Vec::with_capacity(data.len() / 2), acc: 0, n: 0};
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = LZW_FIRST;
+    let mut current: Option<u16> = None;
+
+    writer.write(LZW_CLEAR, 9);
+    for &b in &data {
+        let prefix = match current {
+            Some(prefix) => prefix,
+            None => {
+                current = Some(b as u16);
+                continue;
+            }
+        };
+        let known = table.get(&(prefix, b)).cloned();
+        if let Some(code) = known {
+            current = Some(code);
+            continue;
+        }
+        writer.write(prefix, code_len(next_code));
+        table.insert((prefix, b), next_code);
+        next_code += 1;
+        if next_code == LZW_MAX {
+            writer.write(LZW_CLEAR, code_len(next_code));
+            table.clear();
+            next_code = LZW_FIRST;
+        }
+        current = Some(b as u16);
+    }
+    if let Some(prefix) = current {
+        writer.write(prefix, code_len(next_code));
+        // the decoder adds an entry after reading it
+        next_code += 1;
+    }
+    writer.write(LZW_EOD, code_len(next_code));
+    Ok(writer.finish())
+}
+
+impl LZWFlateParams {
+    /// Number of bytes in one row of samples (without the PNG predictor byte).
+    fn row_len(&self) -> Result<usize> {
+        let bits = self.n_components.checked_mul(self.bits_per_component)
+            .and_then(|bits| bits.checked_mul(self.columns))
+            .and_then(|bits| bits.checked_add(7));
+        match bits {
+            Some(bits) => Ok(bits as usize / 8),
+            None => bail!("Columns/Colors ({}/{}) in DecodeParms are too large", self.columns, self.n_components),
+        }
+    }
+    /// Number of bytes per complete pixel, rounded up to at least one (after `row_len` succeeded).
+    fn pixel_len(&self) -> usize {
+        cmp::max(1, (self.n_components * self.bits_per_component) as usize / 8)
+    }
+    fn check(&self) -> Result<()> {
+        match self.bits_per_component {
+            1 | 2 | 4 | 8 | 16 => {},
+            n => bail!("Invalid BitsPerComponent {} in DecodeParms", n),
+        }
+        if self.columns < 1 || self.n_components < 1 {
+            bail!("Invalid Columns/Colors ({}/{}) in DecodeParms", self.columns, self.n_components);
+        }
+        Ok(())
+    }
+}
+
+/// Undoes the predictor (`/Predictor` in `/DecodeParms`) of LZW and Flate filtered data.
+fn unpredict(data: Vec<u8>, params: &LZWFlateParams) -> Result<Vec<u8>> {
+    match params.predictor {
+        1 => Ok(data),
+        2 => {
+            params.check()?;
+            tiff_unpredict(data, params)
+        }
+        10 ... 15 => {
+            params.check()?;
+            png_unpredict(&data, params)
+        }
+        n => bail!(ErrorKind::UnsupportedPredictor {n: n})
+    }
+}
+/// Applies the predictor prior to LZW or Flate encoding.
+fn predict(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    match params.predictor {
+        1 => Ok(data.to_vec()),
+        2 => {
+            params.check()?;
+            tiff_predict(data.to_vec(), params)
+        }
+        10 ... 15 => {
+            params.check()?;
+            png_predict(data, params)
+        }
+        n => bail!(ErrorKind::UnsupportedPredictor {n: n})
+    }
+}
+
+/// PNG predictors: every row starts with a byte giving the `PredictorType` of the row.
+fn png_unpredict(inp: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    // (a row longer than the data is cut off in the output anyway - don't allocate more)
+    let row_len = cmp::min(params.row_len()?, inp.len());
+    let bpp = params.pixel_len();
+
+    let mut out = Vec::with_capacity(inp.len() / (row_len + 1) * row_len);
+    let mut prev_row = vec![0; row_len];
+    let mut row = vec![0; row_len];
+    let mut row_in = vec![0; row_len];
+
+    for chunk in inp.chunks(row_len + 1) {
+        let predictor = PredictorType::from_u8(chunk[0])?;
+        // A truncated last row is padded with zeros
+        let n = chunk.len() - 1;
+        row_in[.. n].copy_from_slice(&chunk[1..]);
+        for b in &mut row_in[n ..] {
+            *b = 0;
+        }
+        unfilter(predictor, bpp, &prev_row, &row_in, &mut row);
+        out.extend_from_slice(&row[.. n]);
+        mem::swap(&mut prev_row, &mut row);
+    }
+    Ok(out)
+}
+fn png_predict(inp: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    // (as in `png_unpredict`)
+    let row_len = cmp::min(params.row_len()?, cmp::max(1, inp.len()));
+    let bpp = params.pixel_len();
+    // 10 = None, 11 = Sub, ... 14 = Paeth. For 15 (optimum) we just pick Paeth for every row.
+    let method = PredictorType::from_u8(cmp::min(params.predictor - 10, 4) as u8)?;
+
+    let mut out = Vec::with_capacity(inp.len() / row_len * (row_len + 1) + row_len + 1);
+    let mut prev_row = vec![0; row_len];
+    let mut row = vec![0; row_len];
+
+    for chunk in inp.chunks(row_len) {
+        row[.. chunk.len()].copy_from_slice(chunk);
+        for b in &mut row[chunk.len() ..] {
+            *b = 0;
+        }
+        filter(method, bpp, &prev_row, &mut row);
+        out.push(method as u8);
+        out.extend_from_slice(&row[.. chunk.len()]);
+        prev_row[.. chunk.len()].copy_from_slice(chunk);
+    }
+    Ok(out)
+}
+
+/// Reads sample `i` of `bits` bits from `row`
+fn get_sample(row: &[u8], i: usize, bits: usize) -> u16 {
+    match bits {
+        16 => BigEndian::read_u16(&row[2 * i ..]),
+        8 => row[i] as u16,
+        _ => {
+            let bit = i * bits;
+            let shift = 8 - bits - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << bits) - 1) as u8) as u16
+        }
+    }
+}
+/// Writes sample `i` of `bits` bits to `row`
+fn set_sample(row: &mut [u8], i: usize, bits: usize, val: u16) {
+    match bits {
+        16 => BigEndian::write_u16(&mut row[2 * i ..], val),
+        8 => row[i] = val as u8,
+        _ => {
+            let bit = i * bits;
+            let shift = 8 - bits - bit % 8;
+            let mask = (((1 << bits) - 1) as u8) << shift;
+            row[bit / 8] = (row[bit / 8] & !mask) | (((val as u8) << shift) & mask);
+        }
+    }
+}
+
+/// TIFF predictor 2: each sample is stored as the difference to the same component of the
+/// previous pixel in the row.
+fn tiff_unpredict(mut data: Vec<u8>, params: &LZWFlateParams) -> Result<Vec<u8>> {
+    let row_len = params.row_len()?;
+    for row in data.chunks_mut(row_len) {
+        if row.len() < row_len {
+            break;
+        }
+        tiff_unpredict_row(row, params);
+    }
+    Ok(data)
+}
+fn tiff_unpredict_row(row: &mut [u8], params: &LZWFlateParams) {
+    let bits = params.bits_per_component as usize;
+    let colors = params.n_components as usize;
+    let samples = (params.columns * params.n_components) as usize;
+    let mask = ((1u32 << bits) - 1) as u16;
+
+    for i in colors .. samples {
+        let val = get_sample(row, i, bits).wrapping_add(get_sample(row, i - colors, bits)) & mask;
+        set_sample(row, i, bits, val);
+    }
+}
+fn tiff_predict(mut data: Vec<u8>, params: &LZWFlateParams) -> Result<Vec<u8>> {
+    let row_len = params.row_len()?;
+    let bits = params.bits_per_component as usize;
+    let colors = params.n_components as usize;
+    let samples = (params.columns * params.n_components) as usize;
+    let mask = ((1u32 << bits) - 1) as u16;
+
+    for row in data.chunks_mut(row_len) {
+        if row.len() < row_len {
+            break;
+        }
+        // Backwards, so that the previous sample is still the original value
+        for i in (colors .. samples).rev() {
+            let val = get_sample(row, i, bits).wrapping_sub(get_sample(row, i - colors, bits)) & mask;
+            set_sample(row, i, bits, val);
+        }
+    }
+    Ok(data)
+}
+
+
+pub fn decode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
+    decode_limited(data, filter, usize::max_value())
+}
+
+/// Like `decode`, but fails with `ErrorKind::DecodeLimit` if the decoded data would be larger
+/// than `max_size`. The compressing filters stop as soon as the limit is exceeded.
+pub fn decode_limited(data: &[u8], filter: &StreamFilter, max_size: usize) -> Result<Vec<u8>> {
+    let out = match *filter {
+        StreamFilter::ASCIIHexDecode => decode_hex(data),
+        StreamFilter::ASCII85Decode => decode_85(data),
+        StreamFilter::LZWDecode (ref params) => lzw_decode(data, params, max_size),
+        StreamFilter::FlateDecode (ref params) => flate_decode(data, params, max_size),
+        StreamFilter::JPXDecode => bail!("JPXDecode: decoding is not supported - see Stream::image_data"),
+        StreamFilter::DCTDecode (ref params) => dct_decode(data, params),
+        StreamFilter::CCITTFaxDecode (ref params) => ccitt::decode(data, params),
+        StreamFilter::JBIG2Decode (_) => bail!("JBIG2Decode: decoding is not supported - see JBIG2DecodeParams::embedded_data"),
+        StreamFilter::Crypt (ref params) => match params.name.as_str() {
+            "Identity" => Ok(data.to_vec()),
+            name => bail!(ErrorKind::NoSecurityHandler {filter: name.into()}),
+        },
+        StreamFilter::Custom {ref name, ref params} => match custom_filter(name) {
+            Some(filter) => filter.decode(data, params),
+            None => bail!("Filter /{} is not registered", name),
+        },
+    }?;
+    if out.len() > max_size {
+        bail!(ErrorKind::DecodeLimit {limit: max_size});
+    }
+    Ok(out)
+}
+
+/// Inverse of `decode`: applies `filter` to `data`.
+pub fn encode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
+    match *filter {
+        StreamFilter::ASCIIHexDecode => Ok(encode_hex(data)),
+        StreamFilter::ASCII85Decode => Ok(encode_85(data)),
+        StreamFilter::LZWDecode (ref params) => lzw_encode(data, params),
+        StreamFilter::FlateDecode (ref params) => flate_encode(data, params),
+        StreamFilter::Custom {ref name, ref params} => match custom_filter(name) {
+            Some(filter) => filter.encode(data, params),
+            None => bail!("Filter /{} is not registered", name),
+        },
+        ref filter => bail!("Encoding with {:?} is not supported", filter),
+    }
+}
+
+
+/*
+ * Predictor - copied and adapted from PNG crate..
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum PredictorType {
+    NoFilter = 0,
+    Sub = 1,
+    Up = 2,
+    Avg = 3,
+    Paeth = 4
+}
+
+impl PredictorType {  
+    /// u8 -> Self. Temporary solution until Rust provides a canonical one.
+    pub fn from_u8(n: u8) -> Result<PredictorType> {
+        match n {
+            n if n <= 4 => Ok(unsafe { mem::transmute(n) }),
+            n => Err(ErrorKind::IncorrectPredictorType {n}.into())
+        }
+    }
+}
+
+fn filter_paeth(a: u8, b: u8, c: u8) -> u8 {
+    let ia = a as i16;
+    let ib = b as i16;
+    let ic = c as i16;
+
+    let p = ia + ib - ic;
+
+    let pa = (p - ia).abs();
+    let pb = (p - ib).abs();
+    let pc = (p - ic).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+pub fn unfilter(filter: PredictorType, bpp: usize, prev: &[u8], inp: &[u8], out: &mut [u8]) {
+    use self::PredictorType::*;
+    let len = inp.len();
+    assert_eq!(len, out.len());
+    assert_eq!(len, prev.len());
+    // (a truncated last row can be shorter than a pixel)
+    let bpp = cmp::min(bpp, len);
+
+    match filter {
+        NoFilter => {
+            for i in 0..len {
+                out[i] = inp[i];
+            }
+        }
+        Sub => {
+            for i in 0..bpp {
+                out[i] = inp[i];
+            }
+            for i in bpp..len {
+                out[i] = inp[i].wrapping_add(out[i - bpp]);
+            }
+        }
+        Up => {
+            for i in 0..len {
+                out[i] = inp[i].wrapping_add(prev[i]);
+            }
+        }
+        Avg => {
+            for i in 0..bpp {
+                out[i] = inp[i].wrapping_add(prev[i] / 2);
+            }
+
+            for i in bpp..len {
+                out[i] = inp[i].wrapping_add(
+                    ((out[i - bpp] as i16 + prev[i] as i16) / 2) as u8
+                );
+            }
+        }
+        Paeth => {
+            for i in 0..bpp {
+                out[i] = inp[i].wrapping_add(
+                    filter_paeth(0, prev[i], 0)
+                );
+            }
+
+            for i in bpp..len {
+                out[i] = inp[i].wrapping_add(
+                    filter_paeth(out[i - bpp], prev[i], prev[i - bpp])
+                );
+            }
+        }
+    }
+}
+
+pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut [u8]) {
+    use self::PredictorType::*;
+    let len  = current.len();
+    // (as in `unfilter`)
+    let bpp = cmp::min(bpp, len);
+
+    match method {
+        NoFilter => (),
+        Sub => {
+            for i in (bpp..len).rev() {
+                current[i] = current[i].wrapping_sub(current[i - bpp]);
+            }
+        }
+        Up => {
+            for i in 0..len {
+                current[i] = current[i].wrapping_sub(previous[i]);
+            }
+        }
+        Avg => {
+            for i in (bpp..len).rev() {
+                current[i] = current[i].wrapping_sub(((current[i - bpp] as u16 + previous[i] as u16) / 2) as u8);
+            }
+
+            for i in 0..bpp {
+                current[i] = current[i].wrapping_sub(previous[i] / 2);
+            }
+        }
+        Paeth => {
+            for i in (bpp..len).rev() {
+                current[i] = current[i].wrapping_sub(filter_paeth(current[i - bpp], previous[i], previous[i - bpp]));
+            }
+
+            for i in 0..bpp {
+                current[i] = current[i].wrapping_sub(filter_paeth(0, previous[i], 0));
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Lazy decoding: `io::Read` adapters that undo a filter while reading.
+
+use std::io::{self, Read, Cursor};
+use std::mem;
+use inflate::InflateStream;
+use super::*;
+
+/// Wraps `inner` in a reader that undoes `filter`. FlateDecode (with and without predictor) is
+/// decoded incrementally; for the other filters, `inner` is read to the end and decoded at the
+/// first read.
+pub fn decode_reader<'a>(inner: Box<Read + 'a>, filter: &StreamFilter) -> Box<Read + 'a> {
+    match *filter {
+        StreamFilter::FlateDecode (ref params) => {
+            let flate: Box<Read + 'a> = Box::new(FlateReader::new(inner));
+            match params.predictor {
+                1 => flate,
+                _ => Box::new(PredictorReader::new(flate, params.clone())),
+            }
+        }
+        ref filter => Box::new(BufferedReader {
+            inner: Some(inner),
+            filter: filter.clone(),
+            decoded: Cursor::new(Vec::new()),
+        }),
+    }
+}
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Copies from `pending` (starting at `*pos`) to `buf`.
+fn drain(pending: &[u8], pos: &mut usize, buf: &mut [u8]) -> usize {
+    let n = ::std::cmp::min(buf.len(), pending.len() - *pos);
+    buf[.. n].copy_from_slice(&pending[*pos .. *pos + n]);
+    *pos += n;
+    n
+}
+
+
+struct FlateReader<R> {
+    inner: R,
+    inflater: InflateStream,
+    input: Vec<u8>,
+    in_pos: usize,
+    in_len: usize,
+    output: Vec<u8>,
+    out_pos: usize,
+    done: bool,
+}
+impl<R: Read> FlateReader<R> {
+    fn new(inner: R) -> FlateReader<R> {
+        FlateReader {
+            inner: inner,
+            inflater: InflateStream::from_zlib(),
+            input: vec![0; 1 << 14],
+            in_pos: 0,
+            in_len: 0,
+            output: Vec::new(),
+            out_pos: 0,
+            done: false,
+        }
+    }
+}
+impl<R: Read> Read for FlateReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.out_pos < self.output.len() {
+                return Ok(drain(&self.output, &mut self.out_pos, buf));
+            }
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            if self.in_pos == self.in_len {
+                self.in_len = self.inner.read(&mut self.input)?;
+                self.in_pos = 0;
+                if self.in_len == 0 {
+                    self.done = true;
+                    continue;
+                }
+            }
+            let (n, out) = self.inflater.update(&self.input[self.in_pos .. self.in_len])
+                .map_err(invalid_data)?;
+            if n == 0 && out.is_empty() {
+                // end of the zlib stream - ignore trailing data
+                self.done = true;
+            }
+            self.in_pos += n;
+            self.output.clear();
+            self.output.extend_from_slice(out);
+            self.out_pos = 0;
+        }
+    }
+}
+
+/// Undoes a PNG or TIFF predictor, one row at a time.
+///
+/// The row buffers only grow as far as there is data: `/Columns` can be much larger than it.
+struct PredictorReader<R> {
+    inner: R,
+    params: LZWFlateParams,
+    row_in: Vec<u8>,
+    prev_row: Vec<u8>,
+    row: Vec<u8>,
+    row_pos: usize,
+    row_end: usize,
+    /// `params.row_len()`, once `params` have been checked
+    row_len: Option<usize>,
+}
+impl<R: Read> PredictorReader<R> {
+    fn new(inner: R, params: LZWFlateParams) -> PredictorReader<R> {
+        PredictorReader {
+            inner: inner,
+            row_in: Vec::new(),
+            prev_row: Vec::new(),
+            row: Vec::new(),
+            row_pos: 0,
+            row_end: 0,
+            row_len: None,
+            params: params,
+        }
+    }
+    /// Decodes the next row. Returns false at the end of data.
+    fn next_row(&mut self) -> io::Result<bool> {
+        let row_len = match self.row_len {
+            Some(row_len) => row_len,
+            None => {
+                self.params.check().map_err(invalid_data)?;
+                let row_len = self.params.row_len().map_err(invalid_data)?;
+                self.row_len = Some(row_len);
+                row_len
+            }
+        };
+        match self.params.predictor {
+            2 => {
+                self.row.clear();
+                (&mut self.inner).take(row_len as u64).read_to_end(&mut self.row)?;
+                let n = self.row.len();
+                if n == row_len {
+                    tiff_unpredict_row(&mut self.row, &self.params);
+                }
+                self.row_end = n;
+            }
+            10 ... 15 => {
+                self.row_in.clear();
+                (&mut self.inner).take(row_len as u64 + 1).read_to_end(&mut self.row_in)?;
+                let n = self.row_in.len();
+                if n == 0 {
+                    return Ok(false);
+                }
+                let predictor = PredictorType::from_u8(self.row_in[0]).map_err(invalid_data)?;
+                mem::swap(&mut self.prev_row, &mut self.row);
+                // Only the last row can be truncated; above the first one, there are zeros.
+                self.prev_row.resize(n - 1, 0);
+                self.row.resize(n - 1, 0);
+                unfilter(predictor, self.params.pixel_len(), &self.prev_row, &self.row_in[1 ..], &mut self.row);
+                self.row_end = n - 1;
+            }
+            n => return Err(invalid_data(ErrorKind::UnsupportedPredictor {n: n}))
+        }
+        self.row_pos = 0;
+        Ok(self.row_end > 0)
+    }
+}
+impl<R: Read> Read for PredictorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.row_pos == self.row_end && !self.next_row()? {
+            return Ok(0);
+        }
+        let n = ::std::cmp::min(buf.len(), self.row_end - self.row_pos);
+        buf[.. n].copy_from_slice(&self.row[self.row_pos .. self.row_pos + n]);
+        self.row_pos += n;
+        Ok(n)
+    }
+}
+
+/// Decodes everything at the first read.
+struct BufferedReader<'a> {
+    inner: Option<Box<Read + 'a>>,
+    filter: StreamFilter,
+    decoded: Cursor<Vec<u8>>,
+}
+impl<'a> Read for BufferedReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(mut inner) = self.inner.take() {
+            let mut data = Vec::new();
+            inner.read_to_end(&mut data)?;
+            self.decoded = Cursor::new(decode(&data, &self.filter).map_err(invalid_data)?);
+        }
+        self.decoded.read(buf)
+    }
+}
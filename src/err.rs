@@ -1,4 +1,4 @@
-use object::ObjNr;
+use object::{ObjNr, GenNr};
 error_chain! {
     // The type defined for this error. These are the conventional
     // and recommended names, but they can be arbitrarily chosen.
@@ -57,6 +57,10 @@ error_chain! {
             description("Erroneous 'type' field in xref stream - expected 0, 1 or 2")
             display("Erroneous 'type' field in xref stream - expected 0, 1 or 2, found {}", found)
         }
+        StreamLength {length: usize, found: usize} {
+            description("/Length of stream doesn't match its data")
+            display("/Length of stream is {}, but its data has {} bytes", length, found)
+        }
         ContentReadPastBoundary {
             description("Parsing read past boundary of Contents.")
         }
@@ -69,10 +73,38 @@ error_chain! {
         Ascii85TailError  {
             description("Ascii85 tail error")
         }
+        Ascii85Decode {pos: usize, byte: u8} {
+            description("Ascii85 decode error")
+            display("Ascii85 decode error. Position {}, byte {}", pos, byte)
+        }
         IncorrectPredictorType {n: u8} {
             description("Failed to convert u8 into PredictorType")
             display("Failed to convert '{}' into PredictorType", n)
         }
+        UnsupportedFilter {filter: &'static str, feature: &'static str} {
+            description("Decoding with this filter is not enabled")
+            display("Decoding {} requires the '{}' feature", filter, feature)
+        }
+        NoSecurityHandler {filter: String} {
+            description("Crypt filter needs the security handler of the document")
+            display("Crypt filter /{} needs the security handler of the document", filter)
+        }
+        WrongPassword {
+            description("Wrong password")
+            display("The document is encrypted, and none of the passwords is right")
+        }
+        UnsupportedSecurityHandler {reason: String} {
+            description("The security handler of the document is not supported")
+            display("Unsupported security handler: {}", reason)
+        }
+        DecodeLimit {limit: usize} {
+            description("Decoded stream data exceeds the limit")
+            display("Decoded stream data exceeds the limit of {} bytes", limit)
+        }
+        UnsupportedPredictor {n: i32} {
+            description("Unsupported /Predictor in DecodeParms")
+            display("Unsupported /Predictor {} in DecodeParms", n)
+        }
         //////////////////
         // Dictionary
         EntryNotFound{key: &'static str} {
@@ -82,6 +114,10 @@ error_chain! {
         WrongDictionaryType {expected: String, found: String} {
             display("Expected dictionary /Type = {}. Found /Type = {}.", expected, found)
         }
+        InvalidEntry {key: &'static str, reason: String} {
+            description("Dictionary entry has an invalid value.")
+            display("Invalid /{}: {}", key, reason)
+        }
         //////////////////
         // Misc
         FreeObject {obj_nr: u64} {
@@ -92,21 +128,41 @@ error_chain! {
             description("Tried to dereference non-existing object.")
             display("Tried to dereference non-existing object nr {}.", obj_nr)
         }
+        PromisedObject {obj_nr: u64} {
+            description("Tried to dereference an object that is allocated, but not written yet.")
+            display("Tried to dereference object nr {}, which is allocated, but not written yet.", obj_nr)
+        }
 
         UnexpectedPrimitive {expected: &'static str, found: &'static str} {
             description("Expected a certain primitive kind, found another.")
             display("Expected {}, found {}.", expected, found)
         }
+        NumberOutOfRange {number: f64, expected: &'static str} {
+            description("Number can't be converted to the expected type.")
+            display("{} is not a valid {}.", number, expected)
+        }
         /*
         WrongObjectType {expected: &'static str, found: &'static str} {
             description("Function called on object of wrong type.")
             display("Expected {}, found {}.", expected, found)
         }
         */
+        GenerationMismatch {obj_nr: ObjNr, gen: GenNr, found: GenNr} {
+            description("Generation number of reference doesn't match the xref entry.")
+            display("Reference {} {} R, but object {} has generation {}.", obj_nr, gen, obj_nr, found)
+        }
+        CircularReference {obj_nr: ObjNr} {
+            description("Object refers to itself.")
+            display("Object {} refers to itself (directly or indirectly).", obj_nr)
+        }
         ObjStmOutOfBounds {index: usize, max: usize} {
             description("Object stream index out of bounds.")
             display("Object stream index out of bounds ({}/{}).", index, max)
         }
+        ObjStmObjectNotFound {obj_nr: ObjNr, stream_id: ObjNr} {
+            description("Object not found in object stream.")
+            display("Object {} not found in object stream {}.", obj_nr, stream_id)
+        }
         PageOutOfBounds {page_nr: i32, max: i32} {
             description("Page out of bounds.")
             display("Page out of bounds ({}/{}).", page_nr, max)
@@ -115,11 +171,26 @@ error_chain! {
             description("The page requested could not be found in the page tree.")
             display("Page {} could not be found in the page tree.", page_nr)
         }
+        WrongPageCount {count: i32, found: i32} {
+            description("/Count of a page tree node is not the number of pages in it.")
+            display("Page tree node with /Count {} has {} pages.", count, found)
+        }
+        RevisionOutOfBounds {revision: usize, max: usize} {
+            description("Revision out of bounds.")
+            display("Revision out of bounds ({}/{}).", revision, max)
+        }
         UnspecifiedXRefEntry {id: ObjNr} {
             description("Entry in xref table unspecified")
             display("Entry {} in xref table unspecified", id)
         }
 
+        //////////
+        // Backend
+        ReadOutOfBounds {offset: usize, len: usize} {
+            description("Read past the end of the file.")
+            display("Read at {} past the end of the file ({} bytes).", offset, len)
+        }
+
 
     }
 }
@@ -1,12 +1,77 @@
 //! This is kind of the entry-point of the type-safe PDF functionality.
-use std::{str};
+use std::{str, cmp};
+use std::io::{self, Write};
 use std::marker::PhantomData;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
 use err::*;
 use object::*;
-use xref::{XRef, XRefTable};
-use primitive::{Primitive, Dictionary, PdfString};
-use backend::Backend;
+use enc::{encode, StreamFilter, LZWFlateParams, DecodeLimits};
+use xref::{XRef, XRefTable, ObjectLayout, ObjectLocation};
+use primitive::{Primitive, Dictionary, PdfString, PdfStream};
+use backend::{Backend, Revision, build_xref_table, check_generation};
+use parser::{ParseOptions, PdfVersion, parse_header, parse_indirect_object};
+use linearization::{LinearizationDict, PageOffsetHints, read_linearization, read_first_page, read_page_offset_hints};
+use visit::{Visitor, walk};
+use content::Content;
+use signature::{Signature, SigningOptions, Verification, read_signatures};
+use crypt::{CryptDict, Permissions, PasswordKind, SecurityHandler, StandardSecurityHandler, EncryptionOptions, decrypt_object, random_bytes};
+use rayon::prelude::*;
+
+/// The security handler for the encryption dictionary `dict`, opened with the empty user
+/// password - or else with the first right one that `passwords` gives.
+fn open_security_handler(dict: &Dictionary, id: &[PdfString], resolve: &Resolve, passwords: &mut FnMut() -> Option<Vec<u8>>)
+    -> Result<StandardSecurityHandler>
+{
+    let dict = CryptDict::from_primitive(Primitive::Dictionary (dict.clone()), resolve)?;
+    let id: &[u8] = match id.first() {
+        Some(id) => id.as_bytes(),
+        None => &[]
+    };
+    let mut password = Vec::new();
+    loop {
+        match StandardSecurityHandler::new(&dict, id, &password, resolve) {
+            Err(Error (ErrorKind::WrongPassword, _)) => match passwords() {
+                Some(next) => password = next,
+                None => bail!(ErrorKind::WrongPassword),
+            },
+            result => return result
+        }
+    }
+}
+
+/// Writes `obj` as indirect object `id`.
+fn write_indirect<T: Object>(out: &mut Vec<u8>, id: PlainRef, obj: &T) -> io::Result<()> {
+    writeln!(out, "{} {} obj", id.id, id.gen)?;
+    obj.serialize(out)?;
+    writeln!(out, "\nendobj")
+}
+
+/// `p` with the references changed to the ones in `numbers` - and references to other objects
+/// (which are missing) to `null`.
+fn renumber(p: Primitive, numbers: &HashMap<PlainRef, PlainRef>) -> Primitive {
+    match p {
+        Primitive::Reference (r) => match numbers.get(&r) {
+            Some(&r) => Primitive::Reference (r),
+            None => Primitive::Null,
+        },
+        Primitive::Array (array) => Primitive::Array (array.into_iter().map(|p| renumber(p, numbers)).collect()),
+        Primitive::Dictionary (dict) => Primitive::Dictionary (renumber_dict(dict, numbers)),
+        Primitive::Stream (stream) => Primitive::Stream (PdfStream {
+            info:   renumber_dict(stream.info, numbers),
+            data:   stream.data,
+        }),
+        p => p
+    }
+}
+fn renumber_dict(dict: Dictionary, numbers: &HashMap<PlainRef, PlainRef>) -> Dictionary {
+    let mut renumbered = Dictionary::new();
+    for (key, val) in dict.iter() {
+        renumbered.insert(key.clone(), renumber(val.clone(), numbers));
+    }
+    renumbered
+}
 
 pub struct PromisedRef<T> {
     inner:      PlainRef,
@@ -76,11 +141,97 @@ fn update_pages(pages: &mut PageTree, mut offset: i32, page_nr: i32, page: Page)
 }
 
 
+/// Default for `File::set_cache_limit`.
+const DEFAULT_CACHE_LIMIT: usize = 10000;
+/// Number of decoded object streams that are kept.
+const OBJECT_STREAM_CACHE_LIMIT: usize = 100;
+
+/// Parsed objects (or decoded object streams), of which the oldest are dropped when there are
+/// more than `limit`.
+struct ObjectCache<K, V> {
+    objects:    HashMap<K, V>,
+    order:      VecDeque<K>,
+    limit:      usize,
+}
+impl<K: Copy + Eq + Hash, V> ObjectCache<K, V> {
+    fn new(limit: usize) -> ObjectCache<K, V> {
+        ObjectCache {
+            objects:    HashMap::new(),
+            order:      VecDeque::new(),
+            limit:      limit,
+        }
+    }
+    fn get(&self, r: K) -> Option<&V> {
+        self.objects.get(&r)
+    }
+    fn insert(&mut self, r: K, p: V) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.objects.insert(r, p).is_none() {
+            self.order.push_back(r);
+        }
+        self.shrink();
+    }
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.shrink();
+    }
+    fn shrink(&mut self) {
+        while self.order.len() > self.limit {
+            if let Some(r) = self.order.pop_front() {
+                self.objects.remove(&r);
+            }
+        }
+    }
+    fn clear(&mut self) {
+        self.objects.clear();
+        self.order.clear();
+    }
+}
+
+/// Objects that are larger than this when serialized are not put in object streams by
+/// `File::save_to`.
+const MAX_COMPRESSED_OBJECT_SIZE: usize = 4096;
+/// Maximum number of objects per object stream written by `File::save_to`.
+const MAX_OBJECTS_PER_STREAM: usize = 200;
+
 pub struct File<B: Backend> {
     backend:    B,
     trailer:    Trailer,
+    /// The trailer dictionary as read - `save_to` needs the references in it.
+    raw_trailer: Dictionary,
     refs:       XRefTable,
-    changes:    HashMap<ObjNr, Primitive>
+    changes:    HashMap<ObjNr, Primitive>,
+    /// Limits for the decoded size of the streams of this document.
+    decode_limits: DecodeLimits,
+    options:    ParseOptions,
+    /// The version in the header
+    version:    PdfVersion,
+    /// Decoded object streams, by object number
+    object_streams: Mutex<ObjectCache<ObjNr, Arc<ObjectStream>>>,
+    cache:      Mutex<ObjectCache<PlainRef, Primitive>>,
+    linearization: Option<LinearizationDict>,
+    /// The security handler, if the document is encrypted and it could be opened
+    security_handler: Option<Box<SecurityHandler + Send + Sync>>,
+    password_kind: Option<PasswordKind>,
+}
+
+/// A `File` as `Resolve` (from `File::resolver`), with the parse options and the decode limits
+/// of the file.
+pub struct FileResolver<'a, B: Backend + 'a> {
+    file:   &'a File<B>,
+}
+impl<'a, B: Backend> Resolve for FileResolver<'a, B> {
+    fn resolve(&self, r: PlainRef) -> Result<Primitive> {
+        self.file.resolve(r)
+    }
+    fn options(&self) -> &ParseOptions {
+        &self.file.options
+    }
+    fn decode_limits(&self) -> Option<&DecodeLimits> {
+        Some(&self.file.decode_limits)
+    }
 }
 
 impl<B: Backend> File<B> {
@@ -88,61 +239,1387 @@ impl<B: Backend> File<B> {
         File {
             backend:    b,
             trailer:    Trailer::default(),
+            raw_trailer: Dictionary::default(),
             refs:       XRefTable::new(1), // the root object,
-            changes:    HashMap::new()
+            changes:    HashMap::new(),
+            decode_limits: DecodeLimits::default(),
+            options:    ParseOptions::default(),
+            version:    PdfVersion::new(1, 5),
+            object_streams: Mutex::new(ObjectCache::new(OBJECT_STREAM_CACHE_LIMIT)),
+            cache:      Mutex::new(ObjectCache::new(DEFAULT_CACHE_LIMIT)),
+            linearization: None,
+            security_handler: None,
+            password_kind: None,
         }
     }
     pub fn open(path: &str) -> Result<File<B>> {
-        let backend = B::open(path)?;
-        let (refs, trailer) = backend.read_xref_table_and_trailer()?;
-        let trailer = Trailer::from_primitive(Primitive::Dictionary(trailer), &|r| backend.resolve(&refs, r))?;
-        eprintln!("XREFS {:?}", refs);
-        //
-        
-        Ok(File {
+        File::open_with_options(path, ParseOptions::default())
+    }
+    /// Opens the file at `path`, and parses all its objects with `options`.
+    pub fn open_with_options(path: &str, options: ParseOptions) -> Result<File<B>> {
+        File::from_backend(B::open(path)?, options)
+    }
+    /// Opens an encrypted document with the user or owner password `password`. (The empty
+    /// user password is tried first, like by `open`.)
+    pub fn open_with_password(path: &str, password: &[u8], options: ParseOptions) -> Result<File<B>> {
+        File::from_backend_with_password(B::open(path)?, password, options)
+    }
+    /// Opens the document with `backend`. If it is encrypted, the empty user password is tried -
+    /// see `from_backend_with_passwords` for others.
+    pub fn from_backend(backend: B, options: ParseOptions) -> Result<File<B>> {
+        File::from_backend_with_passwords(backend, options, || None)
+    }
+    /// `from_backend` with the user or owner password `password`.
+    pub fn from_backend_with_password(backend: B, password: &[u8], options: ParseOptions) -> Result<File<B>> {
+        let mut password = Some(password.to_vec());
+        File::from_backend_with_passwords(backend, options, || password.take())
+    }
+    /// Opens the document with `backend`. If it is encrypted and the user password isn't empty,
+    /// `passwords` is asked for passwords (user or owner ones - see `password_kind`) until one of
+    /// them is right, or it returns `None`: then opening fails with `ErrorKind::WrongPassword`.
+    ///
+    /// A document that is encrypted in a way that isn't supported fails with
+    /// `ErrorKind::UnsupportedSecurityHandler` - or, if not strict, is opened without decrypting.
+    /// A broken xref table is reconstructed by scanning for objects, if not strict.
+    pub fn from_backend_with_passwords<F>(backend: B, options: ParseOptions, mut passwords: F) -> Result<File<B>>
+        where F: FnMut() -> Option<Vec<u8>>
+    {
+        let (refs, raw_trailer) = match backend.read_xref_table_and_trailer() {
+            Ok(r) => r,
+            Err(e) if options.strict => return Err(e),
+            // (a broken xref table, or the offsets in it)
+            Err(e) => match backend.reconstruct_xref_table() {
+                Ok(r) => r,
+                Err(reconstruction) => return Err(e).chain_err(||
+                    format!("Reconstructing the xref table failed too: {}", reconstruction)),
+            }
+        };
+        File::from_xref_table(backend, refs, raw_trailer, options, &mut passwords)
+    }
+    /// Opens the document as of revision `revision` (see `revisions()`): later incremental
+    /// updates are ignored.
+    pub fn from_backend_at_revision(backend: B, revision: usize, options: ParseOptions) -> Result<File<B>> {
+        let mut revisions = backend.read_revisions()?;
+        if revision >= revisions.len() {
+            bail!(ErrorKind::RevisionOutOfBounds {revision: revision, max: revisions.len()});
+        }
+        revisions.truncate(revision + 1);
+        let (refs, raw_trailer) = build_xref_table(revisions, backend.len() as ObjNr)?;
+        File::from_xref_table(backend, refs, raw_trailer, options, &mut || None)
+    }
+    fn from_xref_table(backend: B, refs: XRefTable, raw_trailer: Dictionary, options: ParseOptions,
+        passwords: &mut FnMut() -> Option<Vec<u8>>) -> Result<File<B>>
+    {
+        let version = match parse_header(&backend.read(.. 1024)?) {
+            Ok(version) => version,
+            Err(e) => {
+                if options.strict {
+                    bail!(e);
+                }
+                // (assumed)
+                PdfVersion::new(1, 7)
+            }
+        };
+        let linearization = match read_linearization(&backend) {
+            Ok(lin) => lin.map(|(lin, _)| lin),
+            // (an invalid linearization dictionary is only a missed optimization)
+            Err(_) => None
+        };
+        // the security handler first - the encryption dictionary and `/ID` aren't encrypted
+        let (security_handler, password_kind) = {
+            let resolve = Resolver::new(|r| backend.resolve_with_options(&refs, r, &options), options);
+            match raw_trailer.get_as::<Dictionary>("Encrypt", &resolve)? {
+                Some(dict) => {
+                    let id: Vec<PdfString> = raw_trailer.get_as("ID", &resolve)?.unwrap_or_default();
+                    match open_security_handler(&dict, &id, &resolve, passwords) {
+                        Ok(handler) => {
+                            let password_kind = handler.password_kind();
+                            (Some(Box::new(handler) as Box<SecurityHandler + Send + Sync>), Some(password_kind))
+                        }
+                        Err(e) => {
+                            let unsupported = match *e.kind() {
+                                ErrorKind::UnsupportedSecurityHandler {..} => true,
+                                _ => false
+                            };
+                            if options.strict || !unsupported {
+                                return Err(e);
+                            }
+                            (None, None)
+                        }
+                    }
+                }
+                None => (None, None)
+            }
+        };
+
+        let mut file = File {
             backend:    backend,
-            trailer:    trailer,
+            trailer:    Trailer::default(),
+            raw_trailer: raw_trailer,
             refs:       refs,
-            changes:    HashMap::new()
-        })
+            changes:    HashMap::new(),
+            decode_limits: DecodeLimits::default(),
+            options:    options,
+            version:    version,
+            object_streams: Mutex::new(ObjectCache::new(OBJECT_STREAM_CACHE_LIMIT)),
+            cache:      Mutex::new(ObjectCache::new(DEFAULT_CACHE_LIMIT)),
+            linearization: linearization,
+            security_handler: security_handler,
+            password_kind: password_kind,
+        };
+        // (read through the file, so that the objects are decrypted)
+        let mut trailer = Trailer::from_primitive(Primitive::Dictionary (file.raw_trailer.clone()), &file.resolver())?;
+        trailer.root.pages.inherit();
+        file.trailer = trailer;
+        Ok(file)
+    }
+
+    /// Sets the limits for `decode_stream()`. The total of the new limits starts at 0.
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.decode_limits = limits;
+    }
+    pub fn get_decode_limits(&self) -> &DecodeLimits {
+        &self.decode_limits
+    }
+    /// Decodes `stream` (see `Stream::decode()`) within the decode limits of this document, so
+    /// the total size of all streams decoded this way is limited too.
+    pub fn decode_stream<T>(&self, stream: &mut Stream<T>) -> Result<()> {
+        stream.decode_limited(&self.decode_limits)
+    }
+
+    /// One more than the highest object number (including added objects).
+    fn num_objects(&self) -> ObjNr {
+        cmp::max(
+            self.refs.len() as ObjNr,
+            self.changes.keys().map(|&id| id + 1).max().unwrap_or(0)
+        )
+    }
+    /// Object numbers of the objects in use (including changed and added objects), with
+    /// generation number.
+    pub fn object_refs(&self) -> Vec<PlainRef> {
+        (1 .. self.num_objects()).filter_map(|id| {
+            if self.changes.contains_key(&id) {
+                return Some(PlainRef {id: id, gen: 0});
+            }
+            match self.refs.get(id) {
+                Ok(XRef::Raw {gen_nr, ..}) => Some(PlainRef {id: id, gen: gen_nr}),
+                Ok(XRef::Stream {..}) => Some(PlainRef {id: id, gen: 0}),
+                _ => None
+            }
+        }).collect()
+    }
+
+    /// The objects that can be reached from the trailer (see `walk`).
+    pub fn reachable_objects(&self) -> Result<Vec<PlainRef>> {
+        struct Reachable (Vec<PlainRef>);
+        impl Visitor for Reachable {
+            fn visit_object(&mut self, r: PlainRef, _: &Primitive) -> Result<bool> {
+                self.0.push(r);
+                Ok(true)
+            }
+        }
+        let mut reachable = Reachable (Vec::new());
+        self.walk(&mut reachable)?;
+        let mut reachable = reachable.0;
+        reachable.sort_by_key(|r| r.id);
+        Ok(reachable)
+    }
+    /// Drops the objects that can't be reached from the trailer, and numbers the others from 1
+    /// without gaps (in the order of their object numbers) - so `save_to` writes only what is
+    /// used, with a compact xref table. Returns the number of dropped objects.
+    ///
+    /// All objects are read into memory (as changes), and the typed trailer is read again.
+    pub fn collect_garbage(&mut self) -> Result<usize> {
+        let reachable = self.reachable_objects()?;
+        let numbers: HashMap<PlainRef, PlainRef> = reachable.iter().enumerate()
+            .map(|(i, &r)| (r, PlainRef {id: i as ObjNr + 1, gen: 0}))
+            .collect();
+
+        let mut changes = HashMap::new();
+        for &r in &reachable {
+            changes.insert(numbers[&r].id, renumber(self.resolve(r)?, &numbers));
+        }
+        let dropped = self.object_refs().len().saturating_sub(reachable.len());
+
+        let mut raw_trailer = match renumber(Primitive::Dictionary (self.raw_trailer.clone()), &numbers) {
+            Primitive::Dictionary (dict) => dict,
+            _ => unreachable!()
+        };
+        // (positions in the original file)
+        raw_trailer.remove("Prev");
+        raw_trailer.remove("XRefStm");
+        raw_trailer.insert("Size", reachable.len() as i32 + 1);
+
+        self.changes = changes;
+        self.refs = XRefTable::new(1);
+        self.raw_trailer = raw_trailer;
+        self.clear_cache();
+
+        let mut trailer = Trailer::from_primitive(
+            Primitive::Dictionary (self.raw_trailer.clone()),
+            &self.resolver()
+        )?;
+        trailer.root.pages.inherit();
+        self.trailer = trailer;
+        Ok(dropped)
+    }
+
+    /// Where each object of the xref table is stored (free ones included), in the order of
+    /// object numbers. Changes are not included.
+    ///
+    /// The length of objects that are not in object streams is found by parsing them.
+    pub fn layout(&self) -> Result<Vec<ObjectLayout>> {
+        let resolve = self.resolver();
+        let mut layout = Vec::new();
+        for id in 0 .. self.refs.len() as ObjNr {
+            let (gen, location) = match self.refs.get(id)? {
+                XRef::Raw {pos, gen_nr} => {
+                    let len = self.backend.parse_at(pos, |lexer| {
+                        let start = lexer.get_pos();
+                        parse_indirect_object(lexer, &resolve)?;
+                        // the last lexeme was `endobj`
+                        Ok(lexer.get_lexeme_end() - start)
+                    })?;
+                    (gen_nr, ObjectLocation::Raw {offset: pos, len: len})
+                }
+                XRef::Stream {stream_id, index} => (0, ObjectLocation::Stream {stream_id: stream_id, index: index}),
+                XRef::Free {gen_nr, ..} => (gen_nr, ObjectLocation::Free),
+                XRef::Promised | XRef::Invalid => continue,
+            };
+            layout.push(ObjectLayout {
+                id:         id,
+                gen:        gen,
+                location:   location,
+            });
+        }
+        Ok(layout)
     }
 
+    /// Parses and decodes (see `Stream::decode()`) all streams of the document, in parallel on
+    /// the rayon thread pool. The decode limits of the document apply.
+    ///
+    /// Streams that can't be decoded don't stop the others - their error is returned instead.
+    pub fn decode_all_streams(&self) -> Vec<(PlainRef, Result<Stream<Dictionary>>)> where B: Sync {
+        self.object_refs().par_iter()
+            .filter_map(|&r| match self.resolve(r) {
+                Ok(p @ Primitive::Stream (_)) => Some((r, self.decode_primitive_stream(p))),
+                Ok(_) => None,
+                Err(e) => Some((r, Err(e))),
+            })
+            .collect()
+    }
+    fn decode_primitive_stream(&self, p: Primitive) -> Result<Stream<Dictionary>> {
+        let mut stream = Stream::<Dictionary>::from_primitive(p, &self.resolver())?;
+        self.decode_stream(&mut stream)?;
+        Ok(stream)
+    }
+
+    /// Writes the whole document (with changes) as a new PDF file. Small objects that are not
+    /// streams are packed into object streams, and the cross-reference table is written as an
+    /// xref stream, so the output needs PDF 1.5 (or the version of the document if it is later).
+    ///
+    /// Object streams and xref streams of the original file are not copied, nor is its
+    /// encryption dictionary: the document is written decrypted.
+    pub fn save_to<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        self.write_document(out, None)
+    }
+    /// `save_to`, encrypting the strings and streams as `options` say - with a new encryption
+    /// dictionary, and a new `/ID` if the document has none. The objects are not put in object
+    /// streams, so each is encrypted with its own key.
+    pub fn save_encrypted_to<W: io::Write>(&self, out: &mut W, options: &EncryptionOptions) -> Result<()> {
+        self.write_document(out, Some(options))
+    }
+    fn write_document<W: io::Write>(&self, out: &mut W, encryption: Option<&EncryptionOptions>) -> Result<()> {
+        // the security handler and the trailer entries for encryption
+        let encryption = match encryption {
+            Some(options) => {
+                let permanent_id = match self.get_id() {
+                    Some((id, _)) => id.to_vec(),
+                    None => random_bytes(16)?,
+                };
+                let (handler, encrypt_dict) = StandardSecurityHandler::create(options, &permanent_id)?;
+                let file_id = vec![
+                    Primitive::String (PdfString::new(permanent_id)),
+                    Primitive::String (PdfString::new(random_bytes(16)?)),
+                ];
+                Some((handler, encrypt_dict, file_id))
+            }
+            None => None
+        };
+        let encrypt_ref = self.get_encrypt_ref();
+
+        let mut buf = Vec::new();
+        // xref and object streams are PDF 1.5
+        let version = cmp::max(self.version(), PdfVersion::new(1, 5));
+        write!(buf, "%PDF-{}\n", version)?;
+        buf.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+
+        let size = self.num_objects();
+        let mut entries = vec![XRef::Free {next_obj_nr: 0, gen_nr: 0}; size as usize];
+        entries[0] = XRef::Free {next_obj_nr: 0, gen_nr: 65535};
+
+        // object streams get the object numbers after the existing objects
+        let mut next_id = size;
+        let mut obj_stream: Option<ObjectStream> = None;
+
+        for PlainRef {id, gen} in self.object_refs() {
+            if encrypt_ref == Some(PlainRef {id: id, gen: gen}) {
+                continue;
+            }
+            let primitive = self.resolve(PlainRef {id: id, gen: gen})?;
+            let compress = match primitive {
+                Primitive::Stream (ref stream) => {
+                    match stream.info.get("Type") {
+                        Some(&Primitive::Name (ref t)) if t == "ObjStm" || t == "XRef" => continue,
+                        _ => false
+                    }
+                }
+                ref p => {
+                    let mut data = Vec::new();
+                    p.serialize(&mut data)?;
+                    gen == 0 && data.len() <= MAX_COMPRESSED_OBJECT_SIZE
+                }
+            };
+            if !compress || encryption.is_some() {
+                let r = PlainRef {id: id, gen: gen};
+                let primitive = match encryption {
+                    Some((ref handler, _, _)) => handler.encrypt_object(r, primitive)?,
+                    None => primitive
+                };
+                entries[id as usize] = XRef::Raw {pos: buf.len(), gen_nr: gen};
+                write_indirect(&mut buf, r, &primitive)?;
+                continue;
+            }
+
+            let full = match obj_stream {
+                Some(ref s) => s.n_objects() >= MAX_OBJECTS_PER_STREAM,
+                None => false
+            };
+            if full {
+                let s = obj_stream.take().unwrap();
+                entries.push(XRef::Raw {pos: buf.len(), gen_nr: 0});
+                write_indirect(&mut buf, PlainRef {id: s.id(), gen: 0}, &s)?;
+            }
+            if obj_stream.is_none() {
+                obj_stream = Some(ObjectStream::empty(next_id));
+                next_id += 1;
+            }
+            let s = obj_stream.as_mut().unwrap();
+            let index = s.add_object(id, &primitive)?;
+            entries[id as usize] = XRef::Stream {stream_id: s.id(), index: index};
+        }
+        if let Some(s) = obj_stream {
+            entries.push(XRef::Raw {pos: buf.len(), gen_nr: 0});
+            write_indirect(&mut buf, PlainRef {id: s.id(), gen: 0}, &s)?;
+        }
+
+        // Link the free entries, keeping their generation numbers
+        let mut next_free = 0;
+        for id in (1 .. size).rev() {
+            if let XRef::Free {..} = entries[id as usize] {
+                let gen_nr = match self.refs.get(id) {
+                    Ok(XRef::Free {gen_nr, ..}) => gen_nr,
+                    _ => 0
+                };
+                entries[id as usize] = XRef::Free {next_obj_nr: next_free, gen_nr: gen_nr};
+                next_free = id;
+            }
+        }
+        entries[0] = XRef::Free {next_obj_nr: next_free, gen_nr: 65535};
+
+        // The xref stream itself
+        let xref_id = next_id;
+        let xref_pos = buf.len();
+        entries.push(XRef::Raw {pos: xref_pos, gen_nr: 0});
+
+        let max_field = entries.iter().map(|e| match *e {
+            XRef::Raw {pos, ..} => pos as u64,
+            XRef::Stream {stream_id, ..} => stream_id,
+            _ => 0,
+        }).max().unwrap_or(0);
+        let mut width = 1;
+        while width < 8 && max_field >> (8 * width) != 0 {
+            width += 1;
+        }
+
+        let mut data = Vec::with_capacity(entries.len() * (width + 3));
+        for entry in &entries {
+            let (t, a, b) = match *entry {
+                XRef::Free {next_obj_nr, gen_nr} => (0, next_obj_nr, gen_nr as u64),
+                XRef::Raw {pos, gen_nr} => (1, pos as u64, gen_nr as u64),
+                XRef::Stream {stream_id, index} => (2, stream_id, index as u64),
+                XRef::Promised | XRef::Invalid => (0, 0, 0),
+            };
+            data.push(t);
+            for i in (0 .. width).rev() {
+                data.push((a >> (8 * i)) as u8);
+            }
+            data.push((b >> 8) as u8);
+            data.push(b as u8);
+        }
+        let data = encode(&data, &StreamFilter::FlateDecode (LZWFlateParams::default()))?;
+
+        let mut info = Dictionary::default();
+        for key in &["Root", "Info", "ID"] {
+            if let Some(p) = self.raw_trailer.get(*key) {
+                info.insert(key.to_string(), p.clone());
+            }
+        }
+        // (the xref stream isn't encrypted)
+        if let Some((_, encrypt_dict, file_id)) = encryption {
+            info.insert("Encrypt", encrypt_dict);
+            info.insert("ID", file_id);
+        }
+        info.insert("Type", Primitive::Name ("XRef".into()));
+        info.insert("Size", Primitive::Integer (entries.len() as i32));
+        info.insert("W", Primitive::Array (vec![
+            Primitive::Integer (1),
+            Primitive::Integer (width as i32),
+            Primitive::Integer (2),
+        ]));
+        info.insert("Filter", Primitive::Name ("FlateDecode".into()));
+        info.insert("Length", Primitive::Integer (data.len() as i32));
+        let xref_stream = Primitive::Stream (PdfStream {info: info, data: data});
+        write_indirect(&mut buf, PlainRef {id: xref_id, gen: 0}, &xref_stream)?;
+
+        write!(buf, "startxref\n{}\n%%EOF\n", xref_pos)?;
+        out.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Writes the file as it is, with the changed and added objects appended as an incremental
+    /// update - so the original bytes, and the signatures over them, stay as they are.
+    ///
+    /// The update has an xref table (also if the file has xref streams). The objects of an
+    /// encrypted document are encrypted like the others - which needs its security handler.
+    pub fn save_incremental<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        if self.raw_trailer.get("Encrypt").is_some() && self.security_handler.is_none() {
+            bail!("The document can't be decrypted, so it can't be updated either");
+        }
+        let encrypt_ref = self.get_encrypt_ref();
+        let mut buf = self.backend.read(..)?.into_owned();
+        if buf.last() != Some(&b'\n') {
+            buf.push(b'\n');
+        }
+        let gens: HashMap<ObjNr, GenNr> = self.object_refs().into_iter().map(|r| (r.id, r.gen)).collect();
+        let mut ids: Vec<ObjNr> = self.changes.keys().cloned().collect();
+        ids.sort();
+
+        let mut offsets = Vec::with_capacity(ids.len());
+        for &id in &ids {
+            let r = PlainRef {id: id, gen: gens.get(&id).cloned().unwrap_or(0)};
+            offsets.push((r, buf.len()));
+            match self.security_handler {
+                // (the encryption dictionary itself isn't encrypted)
+                Some(ref handler) if Some(r) != encrypt_ref => {
+                    let primitive = handler.encrypt_object(r, self.changes[&id].clone())?;
+                    write_indirect(&mut buf, r, &primitive)?;
+                }
+                _ => write_indirect(&mut buf, r, &self.changes[&id])?,
+            }
+        }
+
+        let xref_pos = buf.len();
+        write!(buf, "xref\n")?;
+        for (r, pos) in offsets {
+            write!(buf, "{} 1\n{:010} {:05} n \n", r.id, pos, r.gen)?;
+        }
+        let mut trailer = Dictionary::new();
+        for key in &["Root", "Info", "ID", "Encrypt"] {
+            if let Some(p) = self.raw_trailer.get(*key) {
+                trailer.insert(key.to_string(), p.clone());
+            }
+        }
+        trailer.insert("Size", Primitive::Integer (self.num_objects() as i32));
+        trailer.insert("Prev", Primitive::Integer (self.backend.locate_xref_offset()? as i32));
+        write!(buf, "trailer\n")?;
+        trailer.serialize(&mut buf)?;
+        write!(buf, "\nstartxref\n{}\n%%EOF\n", xref_pos)?;
+        out.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Signs the document: adds an (invisible) signature field on the first page, and returns
+    /// the file with it as incremental update.
+    ///
+    /// `signer` gets the bytes to sign - the file without the `/Contents` of the signature - and
+    /// returns the CMS blob (a detached signature of them), which has to fit in
+    /// `options.contents_size`. The new objects stay in `self` as changes.
+    pub fn sign<F>(&mut self, options: &SigningOptions, signer: F) -> Result<Vec<u8>>
+        where F: FnOnce(&[u8]) -> Result<Vec<u8>>
+    {
+        let sig_dict = options.signature_dict();
+        let sig_ref = self.add_primitive(Primitive::Dictionary (sig_dict));
+        let page_ref = self.first_page_ref()?;
+        let field = Dictionary::new()
+            .with("FT", Primitive::Name ("Sig".into()))
+            .with("T", PdfString::from_text(&options.field_name))
+            .with("V", sig_ref)
+            .with("Type", Primitive::Name ("Annot".into()))
+            .with("Subtype", Primitive::Name ("Widget".into()))
+            .with("Rect", Rect::new(0., 0., 0., 0.).to_primitive()?)
+            // printed and locked
+            .with("F", 132)
+            .with("P", page_ref);
+        let field_ref = self.add_primitive(Primitive::Dictionary (field));
+
+        let (mut page, mut form, form_ref) = {
+            let resolve = self.resolver();
+            let page = Dictionary::from_primitive(Primitive::Reference (page_ref), &resolve)?;
+            let form_ref = self.trailer.root.acro_form.as_ref().and_then(|acro_form| acro_form.get_ref());
+            let form = match self.trailer.root.acro_form {
+                Some(ref acro_form) => self.get(acro_form)?,
+                None => Dictionary::new(),
+            };
+            (page, form, form_ref)
+        };
+        let mut annots = match page.remove("Annots") {
+            Some(annots) => Vec::<Primitive>::from_primitive(annots, &self.resolver())?,
+            None => Vec::new()
+        };
+        annots.push(Primitive::Reference (field_ref));
+        page.insert("Annots", annots);
+        self.update(page_ref.id, Primitive::Dictionary (page));
+
+        let mut fields = match form.remove("Fields") {
+            Some(fields) => Vec::<Primitive>::from_primitive(fields, &self.resolver())?,
+            None => Vec::new()
+        };
+        fields.push(Primitive::Reference (field_ref));
+        form.insert("Fields", fields);
+        // SignaturesExist | AppendOnly
+        form.insert("SigFlags", 3);
+        match form_ref {
+            Some(r) => self.update(r.get_inner().id, Primitive::Dictionary (form)),
+            None => {
+                let r = self.add_primitive(Primitive::Dictionary (form));
+                self.update_catalog("AcroForm", Primitive::Reference (r))?;
+                self.trailer.root.acro_form = Some(Lazy::new(Primitive::Reference (r)));
+            }
+        }
+
+        let mut data = Vec::new();
+        self.save_incremental(&mut data)?;
+        options.sign_placeholders(&mut data, self.backend.len(), signer)?;
+        Ok(data)
+    }
+    fn first_page_ref(&self) -> Result<PlainRef> {
+        match self.page_refs()?.first() {
+            Some(&r) => Ok(r),
+            None => bail!("The document has no pages"),
+        }
+    }
+    /// The page objects, in order - found by following `/Kids` from `/Pages` of the catalog
+    /// (so that they can be matched with references to pages, like `/Pg` or `/P`).
+    pub fn page_refs(&self) -> Result<Vec<PlainRef>> {
+        let resolve = self.resolver();
+        let root = match self.get_trailer_ref("Root") {
+            Some(r) => Dictionary::from_primitive(Primitive::Reference (r), &resolve)?,
+            None => bail!("The catalog is not an indirect object"),
+        };
+        let mut pages = Vec::new();
+        let mut visited = Vec::new();
+        // the nodes still to visit, the next one last
+        let mut stack: Vec<Primitive> = root.get("Pages").cloned().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let r = match node {
+                Primitive::Reference (r) => r,
+                _ => bail!("A page tree node is not an indirect object"),
+            };
+            if visited.contains(&r) {
+                bail!(ErrorKind::CircularReference {obj_nr: r.id});
+            }
+            visited.push(r);
+            let dict = Dictionary::from_primitive(Primitive::Reference (r), &resolve)?;
+            match dict.get("Kids") {
+                Some(kids) => {
+                    let kids = Vec::<Primitive>::from_primitive(kids.clone(), &resolve)?;
+                    stack.extend(kids.into_iter().rev());
+                }
+                None => pages.push(r),
+            }
+        }
+        Ok(pages)
+    }
+
+    /// The version of the PDF syntax the document uses: that in the header, or the `/Version`
+    /// of the catalog if that is later.
+    pub fn version(&self) -> PdfVersion {
+        let catalog_version = self.trailer.root.version.as_ref()
+            .and_then(|v| PdfVersion::parse(v).ok());
+        match catalog_version {
+            Some(v) if v > self.version => v,
+            _ => self.version
+        }
+    }
+
+    /// Whether the file is linearized (and not changed by incremental updates since).
+    pub fn is_linearized(&self) -> bool {
+        match self.linearization {
+            Some(ref lin) => lin.file_len as usize == self.backend.len(),
+            None => false
+        }
+    }
+    /// The linearization dictionary, if the file has one. See also `is_linearized()`.
+    pub fn get_linearization(&self) -> Option<&LinearizationDict> {
+        self.linearization.as_ref()
+    }
+    /// The page offset hint table of a linearized file.
+    pub fn page_offset_hints(&self) -> Result<PageOffsetHints> {
+        match self.linearization {
+            Some(ref lin) => read_page_offset_hints(&self.backend, lin, &self.resolver()),
+            None => bail!("Not a linearized file"),
+        }
+    }
+    /// Reads only the first page of a linearized file, which is much faster than `open()`
+    /// (which reads the whole page tree) for large files, especially remote ones.
+    pub fn open_first_page(path: &str, options: ParseOptions) -> Result<Page> {
+        read_first_page(&B::open(path)?, options)
+    }
+
+    /// Number of revisions of the document: 1 + the number of incremental updates.
+    pub fn num_revisions(&self) -> usize {
+        self.refs.num_revisions()
+    }
+    /// The revisions of the document, the original one first: their trailers, and where each
+    /// ends. To look at the document as of a revision, open it with `from_backend_at_revision`.
+    pub fn revisions(&self) -> Result<Vec<Revision>> {
+        self.backend.read_revisions()
+    }
 
     pub fn get_root(&self) -> &Catalog {
         &self.trailer.root
     }
+    /// The trailer of the latest revision.
+    pub fn get_trailer(&self) -> &Trailer {
+        &self.trailer
+    }
+    /// The file identifier (`/ID`): the permanent identifier, which stays the same when the
+    /// document is updated, and the identifier of this revision.
+    pub fn get_id(&self) -> Option<(&[u8], &[u8])> {
+        let id = &self.trailer.id;
+        if id.len() == 2 {
+            Some((id[0].as_bytes(), id[1].as_bytes()))
+        } else {
+            None
+        }
+    }
+    /// Whether both files are (revisions of) the same document, according to their permanent
+    /// identifiers. Files without `/ID` never are.
+    pub fn same_document<C: Backend>(&self, other: &File<C>) -> bool {
+        match (self.get_id(), other.get_id()) {
+            (Some((a, _)), Some((b, _))) => a == b,
+            _ => false
+        }
+    }
+    /// Reference to the document information dictionary (`/Info`).
+    pub fn get_info_ref(&self) -> Option<PlainRef> {
+        self.get_trailer_ref("Info")
+    }
+    /// The encryption dictionary - `None` if the document isn't encrypted.
+    pub fn encryption(&self) -> Result<Option<CryptDict>> {
+        match self.trailer.encrypt_dict {
+            Some(ref dict) => Ok(Some(CryptDict::from_primitive(Primitive::Dictionary (dict.clone()), &self.resolver())?)),
+            None => Ok(None)
+        }
+    }
+    /// What the document allows (see `Permissions`): those of the `/P` flags if it is
+    /// encrypted and was opened with the user password, all otherwise. It is up to the caller
+    /// whether to honor them.
+    pub fn permissions(&self) -> Result<Permissions> {
+        if self.password_kind == Some(PasswordKind::Owner) {
+            return Ok(Permissions::all());
+        }
+        match self.encryption()? {
+            Some(ref dict) => Ok(dict.permissions()),
+            None => Ok(Permissions::all())
+        }
+    }
+    /// Which password the document was opened with - `None` if it isn't encrypted (or wasn't
+    /// decrypted, see `from_backend_with_passwords`).
+    pub fn password_kind(&self) -> Option<PasswordKind> {
+        self.password_kind
+    }
+    /// The security handler of an encrypted document - `None` if the document isn't encrypted,
+    /// or its security handler isn't supported. Objects are decrypted with it as they are read,
+    /// so this is only needed for data from elsewhere.
+    pub fn security_handler(&self) -> Option<&SecurityHandler> {
+        self.security_handler.as_ref().map(|handler| &**handler as &SecurityHandler)
+    }
+    /// Reference to the encryption dictionary (`/Encrypt`), if it is indirect.
+    pub fn get_encrypt_ref(&self) -> Option<PlainRef> {
+        self.get_trailer_ref("Encrypt")
+    }
+    fn get_trailer_ref(&self, key: &str) -> Option<PlainRef> {
+        match self.raw_trailer.get(key) {
+            Some(&Primitive::Reference (r)) => Some(r),
+            _ => None
+        }
+    }
+    /// `/Size` of the trailer: 1 + the highest object number.
+    pub fn get_size(&self) -> ObjNr {
+        self.trailer.highest_id as ObjNr
+    }
 
     fn resolve(&self, r: PlainRef) -> Result<Primitive> {
-        match self.changes.get(&r.id) {
-            Some(ref p) => Ok((*p).clone()),
-            None => self.backend.resolve(&self.refs, r)
+        if let Some(p) = self.changes.get(&r.id) {
+            return Ok(p.clone());
+        }
+        if let Some(p) = self.cache.lock().unwrap().get(r) {
+            return Ok(p.clone());
+        }
+        let p = guard_cycle(r, || match self.load(r)? {
+            // an object that is just a reference to another one
+            Primitive::Reference (next) => self.resolve(next),
+            p => Ok(p)
+        })?;
+        self.cache.lock().unwrap().insert(r, p.clone());
+        Ok(p)
+    }
+    fn load(&self, r: PlainRef) -> Result<Primitive> {
+        match self.refs.get(r.id)? {
+            XRef::Stream {stream_id, index} => {
+                check_generation(r, 0, &self.options)?;
+                let obj_stream = self.get_object_stream(stream_id)?;
+                obj_stream.get_object_at(r.id, index, &self.resolver())
+            }
+            // (`resolve` checks for cycles already)
+            _ => {
+                let p = self.backend.resolve_unguarded(&self.refs, r, &self.options)?;
+                match self.security_handler {
+                    // (the encryption dictionary itself isn't encrypted)
+                    Some(ref handler) if Some(r) != self.get_encrypt_ref() => decrypt_object(&**handler, r, p),
+                    _ => Ok(p)
+                }
+            }
+        }
+    }
+    /// Sets the maximum number of objects that are kept parsed in memory, so resolving them
+    /// again is fast. 0 disables the cache.
+    pub fn set_cache_limit(&self, limit: usize) {
+        self.cache.lock().unwrap().set_limit(limit);
+    }
+    /// Forgets all parsed objects and decoded object streams.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+        self.object_streams.lock().unwrap().clear();
+    }
+
+    /// The object stream with number `id` - decoded only if it isn't cached (within the decode
+    /// limits of the document).
+    fn get_object_stream(&self, id: ObjNr) -> Result<Arc<ObjectStream>> {
+        if let Some(s) = self.object_streams.lock().unwrap().get(id) {
+            return Ok(s.clone());
         }
+        // (not cached as primitive too)
+        let r = PlainRef {id: id, gen: 0};
+        let p = guard_cycle(r, || self.load(r))?;
+        let s = Arc::new(ObjectStream::from_primitive(p, &self.resolver())?);
+        self.object_streams.lock().unwrap().insert(id, s.clone());
+        Ok(s)
+    }
+    pub fn get_parse_options(&self) -> &ParseOptions {
+        &self.options
     }
 
     pub fn deref<T: Object>(&self, r: Ref<T>) -> Result<T> {
         let primitive = self.resolve(r.get_inner())?;
-        T::from_primitive(primitive, &|id| self.resolve(id))
+        T::from_primitive(primitive, &self.resolver())
+    }
+    /// Visits the trailer and all objects reachable from it (see `visit::walk`) - with the
+    /// changes made to the document.
+    pub fn walk<V: Visitor>(&self, visitor: &mut V) -> Result<()> {
+        let trailer = Primitive::Dictionary (self.raw_trailer.clone());
+        walk(&trailer, &self.resolver(), visitor)
+    }
+    /// The file as `Resolve` - for reading objects with the functions that take one.
+    pub fn resolver(&self) -> FileResolver<B> {
+        FileResolver {
+            file:   self,
+        }
+    }
+    /// Reads a `Lazy` value - like the entries of the catalog.
+    pub fn get<T: Object>(&self, lazy: &Lazy<T>) -> Result<T> {
+        lazy.get(&self.resolver())
+    }
+    /// The items of the document outline (the bookmarks) - none if there is no outline.
+    pub fn outline(&self) -> Result<Vec<OutlineItem>> {
+        let outlines = match self.trailer.root.outlines {
+            Some(ref outlines) => self.get(outlines)?,
+            None => return Ok(Vec::new()),
+        };
+        read_outline(&outlines, &self.resolver())
+    }
+    /// The destination called `name` - from the `Dests` name tree of the name dictionary, or
+    /// else from the `/Dests` dictionary of the catalog (PDF 1.1). `None` if there is none.
+    pub fn resolve_destination(&self, name: &[u8]) -> Result<Option<Destination>> {
+        let resolve = self.resolver();
+        let root = &self.trailer.root;
+        let mut dest = None;
+        if let Some(ref tree) = root.names.as_ref().and_then(|names| names.dests.as_ref()) {
+            dest = tree.get(name, &resolve)?;
+        }
+        if dest.is_none() {
+            if let (Some(ref dests), Ok(name)) = (root.dests.as_ref(), str::from_utf8(name)) {
+                dest = self.get(dests)?.remove(name);
+            }
+        }
+        match dest {
+            Some(dest) => Ok(Some(Destination::from_primitive(dest, &resolve)
+                .chain_err(|| format!("Destination {:?}", String::from_utf8_lossy(name)))?)),
+            None => Ok(None)
+        }
+    }
+    /// The viewer preferences of the document - all unset if there are none.
+    pub fn viewer_preferences(&self) -> Result<ViewerPreferences> {
+        match self.trailer.root.viewer_preferences {
+            Some(ref preferences) => self.get(preferences),
+            None => Ok(ViewerPreferences::default())
+        }
+    }
+    /// What to show or do when the document is opened - `None` for the first page.
+    pub fn open_action(&self) -> Result<Option<OpenAction>> {
+        match self.trailer.root.open_action {
+            Some(ref action) => Ok(Some(OpenAction::from_primitive(action.get_primitive().clone(), &self.resolver())?)),
+            None => Ok(None)
+        }
+    }
+    /// All actions of the document, with where they are - to find the links or scripts in it,
+    /// for example. (The actions performed after an action are in its `next` - see
+    /// `Action::all`.)
+    pub fn actions(&self) -> Result<Vec<(ActionSource, Action)>> {
+        let resolve = self.resolver();
+        let root = &self.trailer.root;
+        let mut actions = Vec::new();
+        if let Some(OpenAction::Action (action)) = self.open_action()? {
+            actions.push((ActionSource::OpenAction, action));
+        }
+        if let Some(ref aa) = root.additional_actions {
+            for (trigger, action) in read_additional_actions(aa, DOCUMENT_TRIGGERS, &resolve)? {
+                actions.push((ActionSource::Document (trigger), action));
+            }
+        }
+        if let Some(tree) = root.names.as_ref().and_then(|names| names.javascript.as_ref()) {
+            for (name, action) in tree.entries(&resolve)? {
+                let action = Action::from_primitive(Primitive::Dictionary (action), &resolve)?;
+                actions.push((ActionSource::NamedJavaScript (name.to_string_lossy()), action));
+            }
+        }
+
+        let outline = self.outline()?;
+        let mut items: Vec<&OutlineItem> = outline.iter().rev().collect();
+        while let Some(item) = items.pop() {
+            if let Some(ref action) = item.action {
+                actions.push((ActionSource::Outline (item.title.clone()), action.clone()));
+            }
+            items.extend(item.children.iter().rev());
+        }
+
+        for (i, page) in self.pages()?.enumerate() {
+            if let Some(ref aa) = page.additional_actions {
+                for (trigger, action) in read_additional_actions(aa, PAGE_TRIGGERS, &resolve)? {
+                    actions.push((ActionSource::Page (i, trigger), action));
+                }
+            }
+            for (j, annotation) in self.annotations(page)?.into_iter().enumerate() {
+                let action = match annotation.kind {
+                    AnnotationKind::Link {ref action, ..} => action.clone(),
+                    // (like the actions of push buttons)
+                    _ => annotation.dict.get_as("A", &resolve)?,
+                };
+                if let Some(action) = action {
+                    actions.push((ActionSource::Annotation {page: i, annotation: j, trigger: None}, action));
+                }
+                for (trigger, action) in annotation.additional_actions {
+                    actions.push((ActionSource::Annotation {page: i, annotation: j, trigger: Some(trigger)}, action));
+                }
+            }
+        }
+
+        if let Some(form) = self.acro_form()? {
+            for field in form.fields.iter().flat_map(|field| field.all()) {
+                for (trigger, action) in &field.additional_actions {
+                    actions.push((ActionSource::Field (field.full_name.clone(), trigger.clone()), action.clone()));
+                }
+            }
+        }
+        Ok(actions)
+    }
+    /// The destination of `target` - `None` if it is a name without destination.
+    pub fn resolve_target(&self, target: &Target) -> Result<Option<Destination>> {
+        match *target {
+            Target::Explicit (ref dest) => Ok(Some(dest.clone())),
+            Target::Named (ref name) => self.resolve_destination(name),
+        }
+    }
+    /// The index of the page of `dest` - `None` if it isn't a page of the document.
+    pub fn destination_page(&self, dest: &Destination) -> Result<Option<usize>> {
+        Ok(dest.page_index(&self.page_refs()?))
+    }
+    /// The index of the page that `target` - of an outline item, a link or a go-to action - goes
+    /// to; `None` if it doesn't go to a page of the document.
+    pub fn target_page(&self, target: &Target) -> Result<Option<usize>> {
+        match self.resolve_target(target)? {
+            Some(dest) => self.destination_page(&dest),
+            None => Ok(None)
+        }
+    }
+    /// The label of the page `index` (from 0) - like `iv` or `A-7` - as given by `/PageLabels`
+    /// of the catalog. Without page labels, it is the page number (from 1).
+    pub fn page_label(&self, index: i32) -> Result<String> {
+        let labels = match self.trailer.root.page_labels {
+            Some(ref labels) => self.get(labels)?,
+            None => return Ok((index + 1).to_string()),
+        };
+        match labels.get_range(index, &self.resolver())? {
+            Some((first, label)) => Ok(label.label((index - first) as usize)),
+            None => Ok((index + 1).to_string()),
+        }
     }
     pub fn get_num_pages(&self) -> Result<i32> {
         Ok(self.trailer.root.pages.count)
     }
+    /// All pages, in order. Fails (if strict) if a `/Count` in the page tree is wrong.
+    pub fn pages(&self) -> Result<Pages> {
+        let pages = &self.trailer.root.pages;
+        if let Err(e) = pages.check_count() {
+            if self.options.strict {
+                return Err(e);
+            }
+        }
+        Ok(pages.pages())
+    }
+    /// The annotations of `page` (see `Page::annotations`).
+    pub fn annotations(&self, page: &Page) -> Result<Vec<Annotation>> {
+        page.annotations(&self.resolver())
+    }
+    /// The attachments of the document: the files in `/EmbeddedFiles`, then those of the
+    /// `FileAttachment` annotations of the pages.
+    pub fn attachments(&self) -> Result<Vec<Attachment>> {
+        let resolve = self.resolver();
+        let tree = self.trailer.root.names.as_ref().and_then(|names| names.embedded_files.as_ref());
+        let mut attachments = match tree {
+            Some(tree) => read_embedded_files(tree, &resolve)?,
+            None => Vec::new()
+        };
+        for (index, page) in self.pages()?.enumerate() {
+            for annotation in page.annotations(&resolve)? {
+                let spec = match annotation.kind {
+                    AnnotationKind::FileAttachment {file: Some(spec), ..} => spec,
+                    _ => continue,
+                };
+                if let Some(mut attachment) = Attachment::from_file_spec(spec, &resolve)? {
+                    attachment.page = Some(index as i32);
+                    attachments.push(attachment);
+                }
+            }
+        }
+        Ok(attachments)
+    }
+    /// The attachment `name` in `/EmbeddedFiles`.
+    pub fn attachment(&self, name: &str) -> Result<Option<Attachment>> {
+        let resolve = self.resolver();
+        let tree = self.trailer.root.names.as_ref().and_then(|names| names.embedded_files.as_ref());
+        let spec = match tree {
+            Some(tree) => tree.get(PdfString::from_text(name).as_bytes(), &resolve)?,
+            None => None
+        };
+        match spec {
+            Some(spec) => Attachment::from_file_spec(spec, &resolve),
+            None => Ok(None)
+        }
+    }
+    /// The contents of the file `attachment`, decoded. If it doesn't have the size or checksum
+    /// of its parameters, that is an error (if strict).
+    pub fn attachment_data(&self, attachment: &Attachment) -> Result<Vec<u8>> {
+        let mut stream = attachment.stream.clone();
+        self.decode_stream(&mut stream)?;
+        let data = stream.get_data_raw().to_vec();
+        if attachment.check(&data) == Some(false) {
+            let name = attachment.file_name.clone().unwrap_or_default();
+            if self.options.strict {
+                bail!("The size or checksum of the embedded file {} is wrong", name);
+            }
+        }
+        Ok(data)
+    }
+    /// Attaches `data` as the embedded file `file_name`: adds the embedded file stream (with
+    /// the size and checksum) and its file specification, and the file specification to
+    /// `/EmbeddedFiles` - replacing a file with the same name. Returns the file specification,
+    /// for referring to it (like in `/AF` of a page).
+    ///
+    /// The name tree is written anew, as a single node.
+    pub fn add_attachment(&mut self, file_name: &str, data: &[u8], options: &AttachmentOptions) -> Result<PlainRef> {
+        let stream = options.embedded_file(data)?;
+        let stream_ref = self.add_primitive(Primitive::Stream (stream));
+        let spec_ref = self.add_primitive(Primitive::Dictionary (options.file_spec(file_name, stream_ref)));
+
+        let (names_ref, mut names, mut entries, mut associated) = {
+            let resolve = self.resolver();
+            let catalog = match self.get_trailer_ref("Root") {
+                Some(r) => Dictionary::from_primitive(Primitive::Reference (r), &resolve)?,
+                None => bail!("The catalog is not an indirect object"),
+            };
+            let names_ref = match catalog.get("Names") {
+                Some(&Primitive::Reference (r)) => Some(r),
+                _ => None
+            };
+            let names = match catalog.get("Names") {
+                Some(names) => Dictionary::from_primitive(names.clone(), &resolve)?,
+                None => Dictionary::new()
+            };
+            let entries = match names.get("EmbeddedFiles") {
+                Some(tree) => {
+                    let tree = match *tree {
+                        Primitive::Reference (r) => resolve.resolve(r)?,
+                        ref tree => tree.clone()
+                    };
+                    NameTree::<Primitive>::from_primitive(tree, &resolve)?.entries(&resolve)?
+                }
+                None => Vec::new()
+            };
+            let associated = match catalog.get("AF") {
+                Some(files) => Vec::<Primitive>::from_primitive(files.clone(), &resolve)?,
+                None => Vec::new()
+            };
+            (names_ref, names, entries, associated)
+        };
+
+        let key = PdfString::from_text(file_name);
+        entries.retain(|&(ref name, _)| name.as_bytes() != key.as_bytes());
+        entries.push((key, Primitive::Reference (spec_ref)));
+        entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+        let mut leaf = Vec::with_capacity(2 * entries.len());
+        for (name, spec) in entries {
+            leaf.push(Primitive::String (name));
+            leaf.push(spec);
+        }
+        let tree_ref = self.add_primitive(Primitive::Dictionary (Dictionary::new().with("Names", leaf)));
+        names.insert("EmbeddedFiles", tree_ref);
+        match names_ref {
+            Some(r) => self.update(r.id, Primitive::Dictionary (names.clone())),
+            None => self.update_catalog("Names", Primitive::Dictionary (names.clone()))?,
+        }
+        if options.relationship.is_some() {
+            associated.push(Primitive::Reference (spec_ref));
+            self.update_catalog("AF", Primitive::Array (associated))?;
+        }
+        let names = NameDictionary::from_primitive(Primitive::Dictionary (names), &self.resolver())?;
+        self.trailer.root.names = Some(names);
+        Ok(spec_ref)
+    }
+    /// The content of `page`: its content streams, decoded and parsed as one.
+    pub fn page_content(&self, page: &Page) -> Result<Content> {
+        let contents = match page.contents {
+            Some(ref contents) => self.get(contents)?,
+            None => return Ok(Content {operations: Vec::new()}),
+        };
+        let streams = match contents {
+            Primitive::Array (streams) => streams,
+            stream => vec![stream],
+        };
+        let resolve = self.resolver();
+        let mut data = Vec::new();
+        for stream in streams {
+            let mut stream = Stream::<Dictionary>::from_primitive(stream, &resolve)?;
+            self.decode_stream(&mut stream)?;
+            data.extend_from_slice(stream.get_data_raw());
+            // (the streams are split between tokens)
+            data.push(b'\n');
+        }
+        Content::parse_from(&data).chain_err(|| "Page content")
+    }
+    /// The structure tree of the document, if it is a Tagged PDF.
+    pub fn struct_tree(&self) -> Result<Option<StructTreeRoot>> {
+        match self.trailer.root.struct_tree_root {
+            Some(ref root) => Ok(Some(StructTreeRoot::from_dict(&self.get(root)?, &self.resolver())?)),
+            None => Ok(None)
+        }
+    }
+    /// The optional content (layers) of the document, if it has any.
+    pub fn optional_content(&self) -> Result<Option<OptionalContent>> {
+        match self.trailer.root.oc_properties {
+            Some(ref properties) => Ok(Some(OptionalContent::from_dict(&self.get(properties)?, &self.resolver())?)),
+            None => Ok(None)
+        }
+    }
+    /// Which content of `page` belongs to optional content (see `OptionalContent::page_layers`).
+    /// Empty if the document has no optional content.
+    pub fn page_layers(&self, page: &Page) -> Result<PageLayers> {
+        let oc = match self.optional_content()? {
+            Some(oc) => oc,
+            None => return Ok(PageLayers {xobjects: Vec::new(), annotations: Vec::new(), sections: Vec::new()}),
+        };
+        oc.page_layers(page, &self.page_content(page)?, &self.resolver())
+    }
+    /// The interactive form of the document, if it has one.
+    pub fn acro_form(&self) -> Result<Option<AcroForm>> {
+        match self.trailer.root.acro_form {
+            Some(ref acro_form) => Ok(Some(read_acro_form(self.get(acro_form)?, &self.resolver())?)),
+            None => Ok(None)
+        }
+    }
+    /// Sets the value of the form field `name` (fully qualified), and the appearance of its
+    /// widgets to match: new appearance streams for text fields (see
+    /// `AcroForm::text_appearance`), and the appearance states for check boxes and radio
+    /// buttons. If an appearance can't be made - and for choice fields - the old appearance of
+    /// the widget is removed and `/NeedAppearances` of the form is set, so that viewers make a
+    /// new one.
+    pub fn set_field_value(&mut self, name: &str, value: &FieldValue) -> Result<()> {
+        let form = match self.acro_form()? {
+            Some(form) => form,
+            None => bail!("The document has no form"),
+        };
+        let field = match form.field(name) {
+            Some(field) => field.clone(),
+            None => bail!("No form field {}", name),
+        };
+        let field_id = match field.id {
+            Some(id) => id,
+            None => bail!("Form field {} is not an indirect object", name),
+        };
+
+        // the dictionaries of the field and its widgets, by object (a field can be a widget too)
+        let mut changed: HashMap<PlainRef, Dictionary> = HashMap::new();
+        changed.insert(field_id, field.dict.clone());
+        for widget in &field.widgets {
+            if let Some(id) = widget.id {
+                changed.entry(id).or_insert_with(|| widget.annotation.dict.clone());
+            }
+        }
+        let mut appearances = Vec::new();
+        let mut need_appearances = false;
+        {
+            let resolve = self.resolver();
+            match (field.field_type, value) {
+                (Some(FieldType::Text), &FieldValue::Text (ref text)) => {
+                    let text = text.as_ref().map(|text| text.as_str()).unwrap_or("");
+                    changed.get_mut(&field_id).unwrap().insert("V", Primitive::String (PdfString::from_text(text)));
+                    for widget in &field.widgets {
+                        match (widget.id, form.text_appearance(&field, widget, text, &resolve)?) {
+                            (Some(id), Some(appearance)) => appearances.push((id, appearance)),
+                            (Some(id), None) => {
+                                changed.get_mut(&id).unwrap().remove("AP");
+                                need_appearances = true;
+                            }
+                            (None, _) => need_appearances = true,
+                        }
+                    }
+                }
+                (Some(FieldType::Button), _) if field.is_check_box() || field.is_radio() => {
+                    let state = match *value {
+                        FieldValue::CheckBox (false) | FieldValue::Radio (None) => "Off".to_string(),
+                        // the "on" state of check boxes is the one that isn't Off
+                        FieldValue::CheckBox (true) if field.is_check_box() => {
+                            let mut on = None;
+                            for widget in &field.widgets {
+                                if on.is_none() {
+                                    on = widget.states(&resolve)?.into_iter().find(|state| state != "Off");
+                                }
+                            }
+                            on.unwrap_or_else(|| "Yes".to_string())
+                        }
+                        FieldValue::Radio (Some(ref button)) if field.is_radio() => button.clone(),
+                        _ => bail!("Can't set form field {} to {:?}", name, value),
+                    };
+                    let mut found = state == "Off";
+                    for widget in &field.widgets {
+                        let id = match widget.id {
+                            Some(id) => id,
+                            None => continue,
+                        };
+                        let has_state = widget.states(&resolve)?.contains(&state);
+                        found |= has_state;
+                        let widget_state = if has_state { state.clone() } else { "Off".to_string() };
+                        changed.get_mut(&id).unwrap().insert("AS", Primitive::Name (widget_state));
+                    }
+                    if !found {
+                        bail!("Form field {} has no state {}", name, state);
+                    }
+                    changed.get_mut(&field_id).unwrap().insert("V", Primitive::Name (state));
+                }
+                (Some(FieldType::Choice), &FieldValue::Choice (ref selected)) => {
+                    let mut options: Vec<Primitive> = selected.iter()
+                        .map(|option| Primitive::String (PdfString::from_text(option)))
+                        .collect();
+                    {
+                        let field_dict = changed.get_mut(&field_id).unwrap();
+                        match options.len() {
+                            0 => { field_dict.remove("V"); }
+                            1 => { field_dict.insert("V", options.remove(0)); }
+                            _ => { field_dict.insert("V", Primitive::Array (options)); }
+                        }
+                    }
+                    for id in field.widgets.iter().filter_map(|widget| widget.id) {
+                        changed.get_mut(&id).unwrap().remove("AP");
+                    }
+                    need_appearances = true;
+                }
+                _ => bail!("Can't set form field {} to {:?}", name, value),
+            }
+        }
+
+        for (id, appearance) in appearances {
+            let r = self.add_primitive(Primitive::Stream (appearance));
+            changed.get_mut(&id).unwrap().insert("AP", Dictionary::new().with("N", Primitive::Reference (r)));
+        }
+        for (id, dict) in changed {
+            self.update(id.id, Primitive::Dictionary (dict));
+        }
+        if need_appearances && !form.need_appearances {
+            let dict = form.dict.clone().with("NeedAppearances", true);
+            let form_ref = self.trailer.root.acro_form.as_ref().and_then(|acro_form| acro_form.get_ref());
+            match form_ref {
+                Some(r) => self.update(r.get_inner().id, Primitive::Dictionary (dict)),
+                None => {
+                    self.update_catalog("AcroForm", Primitive::Dictionary (dict.clone()))?;
+                    self.trailer.root.acro_form = Some(Lazy::new(Primitive::Dictionary (dict)));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// The signatures of the signed signature fields.
+    pub fn signatures(&self) -> Result<Vec<Signature>> {
+        match self.acro_form()? {
+            Some(form) => read_signatures(&form, &self.resolver()),
+            None => Ok(Vec::new())
+        }
+    }
+    /// Checks the byte range and digest of `signature` against the file (see
+    /// `Signature::verify`).
+    pub fn verify_signature(&self, signature: &Signature) -> Result<Verification> {
+        let data = self.backend.read(..)?;
+        signature.verify(&data, &self.revisions()?)
+    }
+
     pub fn get_page(&self, n: i32) -> Result<&Page> {
         if n >= self.get_num_pages()? {
             return Err(ErrorKind::PageOutOfBounds {page_nr: n, max: self.get_num_pages()?}.into());
         }
         find_page(&self.trailer.root.pages, 0, n)
     }
-    
+
+    /// The document information dictionary - empty if there is none.
+    pub fn info(&self) -> Result<InfoDict> {
+        match self.raw_trailer.get("Info") {
+            Some(info) => InfoDict::from_primitive(info.clone(), &self.resolver()),
+            None => Ok(InfoDict::new())
+        }
+    }
+    /// Replaces the document information dictionary (or adds one) - for reading it and for
+    /// `save_to`.
+    pub fn set_info(&mut self, info: &InfoDict) {
+        let dict = info.as_dictionary().clone();
+        let r = match self.get_info_ref() {
+            Some(r) => r,
+            None => {
+                let r = self.add_primitive(Primitive::Null);
+                self.raw_trailer.insert("Info", Primitive::Reference (r));
+                r
+            }
+        };
+        self.update(r.id, Primitive::Dictionary (dict.clone()));
+        self.trailer.info_dict = Some(dict);
+    }
+
+    /// The XMP metadata of the document (the decoded `/Metadata` stream of the catalog) - see
+    /// `xmp::properties`.
+    pub fn metadata(&self) -> Result<Option<Vec<u8>>> {
+        let mut stream = match self.trailer.root.metadata {
+            Some(ref metadata) => self.get(metadata)?,
+            None => return Ok(None),
+        };
+        self.decode_stream(&mut stream)?;
+        Ok(Some(stream.get_data_raw().to_vec()))
+    }
+    /// Replaces the XMP metadata of the document (or adds it). The stream isn't compressed, so
+    /// that applications that don't know PDF can find it.
+    pub fn set_metadata(&mut self, xml: &[u8]) -> Result<()> {
+        let info = Dictionary::new()
+            .with("Type", Primitive::Name ("Metadata".into()))
+            .with("Subtype", Primitive::Name ("XML".into()))
+            .with("Length", xml.len() as i32);
+        let stream = Primitive::Stream (PdfStream {info: info, data: xml.to_vec()});
+        let existing = self.trailer.root.metadata.as_ref().and_then(|m| m.get_ref());
+        let r = match existing {
+            Some(r) => {
+                self.update(r.get_inner().id, stream);
+                r.get_inner()
+            }
+            None => {
+                let r = self.add_primitive(stream);
+                self.update_catalog("Metadata", Primitive::Reference (r))?;
+                r
+            }
+        };
+        self.trailer.root.metadata = Some(Lazy::new(Primitive::Reference (r)));
+        Ok(())
+    }
+    /// Sets the entry `key` of the catalog (in `changes`, so `save_to` writes it).
+    fn update_catalog(&mut self, key: &str, value: Primitive) -> Result<()> {
+        let r = match self.get_trailer_ref("Root") {
+            Some(r) => r,
+            None => bail!("The catalog is not an indirect object"),
+        };
+        let mut catalog = self.resolve(r)?.to_dictionary(&self.resolver())?;
+        catalog.insert(key, value);
+        self.update(r.id, Primitive::Dictionary (catalog));
+        Ok(())
+    }
+
+    /// Replaces object `id` - or adds it, if `id` is not in use - for reading the document and
+    /// for `save_to`. (Objects read already, like the pages, stay as they are.)
+    pub fn update(&mut self, id: ObjNr, primitive: Primitive) {
+        self.changes.insert(id, primitive);
+    }
+    /// Adds `primitive` as a new object, with the next unused object number.
+    pub fn add_primitive(&mut self, primitive: Primitive) -> PlainRef {
+        let id = self.num_objects();
+        self.update(id, primitive);
+        PlainRef {id: id, gen: 0}
+    }
+    /// Replaces the object that `r` refers to with `obj` (e.g. a modified `Page`).
+    ///
+    /// Types read only some of the entries of a dictionary. The entries of the current object
+    /// that `T` doesn't read (like `/Tabs` or `/Group` of a page, for `Page`) are kept.
+    pub fn update_object<T: Object>(&mut self, r: Ref<T>, obj: &T) -> Result<()> {
+        let mut p = obj.to_primitive()?;
+        if let Primitive::Dictionary (ref mut dict) = p {
+            for (key, value) in self.unread_entries::<T>(r.get_inner()) {
+                if dict.get(&key).is_none() {
+                    dict.insert(key, value);
+                }
+            }
+        }
+        self.update(r.get_inner().id, p);
+        Ok(())
+    }
+    /// The entries of the dictionary `r` that are lost when it is read as `T` and converted
+    /// back. None if `r` is not a dictionary (or not in use).
+    fn unread_entries<T: Object>(&self, r: PlainRef) -> Vec<(String, Primitive)> {
+        let old = match self.resolve(r) {
+            Ok(Primitive::Dictionary (old)) => old,
+            _ => return Vec::new()
+        };
+        let read = T::from_primitive(Primitive::Dictionary (old.clone()), &self.resolver())
+            .and_then(|obj| obj.to_primitive());
+        let read = match read {
+            Ok(Primitive::Dictionary (read)) => read,
+            _ => return Vec::new()
+        };
+        old.iter()
+            .filter(|&(key, _)| read.get(key).is_none())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
     // From earlier attempts
     /*
     pub fn update_page(&mut self, page_nr: i32, page: Page) -> Result<()> {
         update_pages(&mut self.trailer.root.pages, 0, page_nr, page)
     }
     
-    pub fn update(&mut self, id: ObjNr, primitive: Primitive) {
-        self.changes.insert(id, primitive);
-    }
-    
     pub fn promise<T: Object>(&mut self) -> PromisedRef<T> {
         let id = self.refs.len() as u64;
         
@@ -176,6 +1653,17 @@ impl<B: Backend> File<B> {
 }
 
 
+impl File<Vec<u8>> {
+    /// Reads the whole of `reader` into memory, because `File` needs random access. To read
+    /// the objects of a file one after another without that, see `parser::ObjectReader`.
+    pub fn from_reader<R: io::Read>(mut reader: R, options: ParseOptions) -> Result<File<Vec<u8>>> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        File::from_backend(data, options)
+    }
+}
+
+
 #[derive(Object, Default)]
 #[pdf(Type=false)]
 pub struct Trailer {
@@ -218,24 +1706,3 @@ pub struct XRefInfo {
     pub w: Vec<i32>
 }
 
-/*
-pub struct XRefStream {
-    pub data: Vec<u8>,
-    pub info: XRefInfo,
-}
-
-impl Object for XRefStream {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        unimplemented!();
-    }
-    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
-        let stream = p.to_stream(resolve)?;
-        let info = XRefInfo::from_primitive(Primitive::Dictionary (stream.info), resolve)?;
-        let data = stream.data.clone();
-        Ok(XRefStream {
-            data: data,
-            info: info,
-        })
-    }
-}
-*/
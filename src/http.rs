@@ -0,0 +1,120 @@
+//! A `Backend` that loads a remote file over HTTP(S) on demand, using range requests.
+
+use std::cell::RefCell;
+use std::io::Read;
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
+use reqwest::{Client, StatusCode};
+use reqwest::header::{ContentLength, Range};
+use err::*;
+use backend::{Backend, read_range};
+
+/// Data is requested in multiples of this.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Reads a file from a URL, fetching only the blocks of it that are read. (Together with
+/// `Backend::parse_at`, which reads only the data around the objects that are parsed.)
+///
+/// The server has to support range requests.
+///
+/// ```ignore
+/// let file = File::<HttpBackend>::open("https://example.com/document.pdf")?;
+/// ```
+pub struct HttpBackend {
+    client: Client,
+    url:    String,
+    /// Length of the file, as the server claims it
+    len:    usize,
+    /// The loaded blocks, by number (only the last one can be shorter than `BLOCK_SIZE`)
+    blocks: RefCell<HashMap<usize, Vec<u8>>>,
+}
+
+impl HttpBackend {
+    /// Loads the blocks in `range` that aren't loaded yet - each run of missing blocks with one
+    /// request.
+    fn load(&self, range: ::std::ops::Range<usize>) -> Result<()> {
+        if range.start >= range.end {
+            return Ok(());
+        }
+        let mut blocks = self.blocks.borrow_mut();
+        let last = (range.end - 1) / BLOCK_SIZE;
+        let mut block = range.start / BLOCK_SIZE;
+        while block <= last {
+            if blocks.contains_key(&block) {
+                block += 1;
+                continue;
+            }
+            let first = block;
+            while block <= last && !blocks.contains_key(&block) {
+                block += 1;
+            }
+            let start = first * BLOCK_SIZE;
+            let end = cmp::min(block * BLOCK_SIZE, self.len);
+            let data = self.fetch(start, end)?;
+
+            for (i, chunk) in data.chunks(BLOCK_SIZE).enumerate() {
+                blocks.insert(first + i, chunk.to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    fn fetch(&self, start: usize, end: usize) -> Result<Vec<u8>> {
+        let mut response = self.client.get(&self.url)
+            .header(Range::bytes(start as u64, end as u64 - 1))
+            .send()
+            .chain_err(|| format!("Requesting bytes {}..{} of {}", start, end, self.url))?;
+        if response.status() != StatusCode::PartialContent {
+            bail!("Requesting bytes {}..{} of {}: status {}", start, end, self.url, response.status());
+        }
+        // (the buffer grows with what is received - not with what the length promises)
+        let mut data = Vec::new();
+        (&mut response).take((end - start) as u64 + 1).read_to_end(&mut data)?;
+        if data.len() != end - start {
+            bail!("Requested {} bytes of {}, got {}", end - start, self.url, data.len());
+        }
+        Ok(data)
+    }
+}
+
+impl Backend for HttpBackend {
+    /// `path` is the URL.
+    fn open(path: &str) -> Result<HttpBackend> {
+        let client = Client::new();
+        let response = client.head(path).send()
+            .chain_err(|| format!("Requesting {}", path))?;
+        if !response.status().is_success() {
+            bail!("Requesting {}: status {}", path, response.status());
+        }
+        let len = match response.headers().get::<ContentLength>() {
+            Some(&ContentLength (len)) => len as usize,
+            None => bail!("{}: unknown length", path),
+        };
+        Ok(HttpBackend {
+            client: client,
+            url:    path.into(),
+            len:    len,
+            blocks: RefCell::new(HashMap::new()),
+        })
+    }
+    fn read_at(&self, offset: usize, len: usize) -> Result<Cow<[u8]>> {
+        let r = read_range(offset, len, self.len)?;
+        self.load(r.clone())?;
+        // (all of `r` is loaded now)
+        let blocks = self.blocks.borrow();
+        let mut data = Vec::with_capacity(r.len());
+        let mut pos = r.start;
+        while pos < r.end {
+            let block = &blocks[&(pos / BLOCK_SIZE)];
+            let start = pos % BLOCK_SIZE;
+            let end = cmp::min(block.len(), start + (r.end - pos));
+            data.extend_from_slice(&block[start .. end]);
+            pos += end - start;
+        }
+        Ok(Cow::Owned(data))
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
@@ -0,0 +1,183 @@
+//! Conversion of primitives to and from JSON, to inspect object trees or to exchange them with
+//! other tools.
+//!
+//! The mapping is reversible:
+//!
+//! | PDF                       | JSON                                          |
+//! |---------------------------|-----------------------------------------------|
+//! | `null`, booleans, numbers | the same                                      |
+//! | name `/Type`              | `"/Type"`                                     |
+//! | string `(text)`           | `{"string": "text"}`, or `{"hex": "..."}` if it isn't UTF-8 |
+//! | array                     | array                                         |
+//! | dictionary `<< /A 1 >>`   | `{"/A": 1}` (keys with slash)                 |
+//! | stream                    | `{"stream": {dictionary}, "data": "hex"}` (data not decoded) |
+//! | reference `1 0 R`         | `{"ref": [1, 0]}`                             |
+
+use std::str;
+use std::fmt::Write;
+use serde_json::{Value, Map, Number};
+use err::*;
+use object::{PlainRef, Resolve};
+use primitive::{Primitive, Dictionary, PdfString, PdfStream};
+
+/// Converts `p` to JSON. References are resolved with `resolve` and replaced by the object -
+/// except references to an object that contains them (like `/Parent` of a page), and all
+/// references if `resolve` is `NO_RESOLVE`.
+pub fn to_json(p: &Primitive, resolve: &Resolve) -> Result<Value> {
+    to_json_inner(p, resolve, &mut Vec::new())
+}
+
+/// `path`: the objects that contain `p`
+fn to_json_inner(p: &Primitive, resolve: &Resolve, path: &mut Vec<PlainRef>) -> Result<Value> {
+    Ok(match *p {
+        Primitive::Null => Value::Null,
+        Primitive::Boolean (b) => Value::Bool (b),
+        Primitive::Integer (n) => Value::Number (n.into()),
+        Primitive::Number (n) => match Number::from_f64(n as f64) {
+            Some(n) => Value::Number (n),
+            None => bail!("{} can't be written in JSON", n),
+        },
+        Primitive::Name (ref name) => Value::String (format!("/{}", name)),
+        Primitive::String (ref s) => string_to_json(s),
+        Primitive::Array (ref array) => {
+            let mut values = Vec::with_capacity(array.len());
+            for p in array {
+                values.push(to_json_inner(p, resolve, path)?);
+            }
+            Value::Array (values)
+        }
+        Primitive::Dictionary (ref dict) => dict_to_json(dict, resolve, path)?,
+        Primitive::Stream (ref stream) => {
+            let mut object = Map::new();
+            object.insert("stream".into(), dict_to_json(&stream.info, resolve, path)?);
+            object.insert("data".into(), Value::String (hex(&stream.data)));
+            Value::Object (object)
+        }
+        Primitive::Reference (r) => {
+            if path.contains(&r) {
+                return Ok(ref_to_json(r));
+            }
+            let p = match resolve.resolve(r) {
+                Ok(p) => p,
+                Err(Error(ErrorKind::FollowReference, _)) => return Ok(ref_to_json(r)),
+                Err(e) => return Err(e),
+            };
+            path.push(r);
+            let value = to_json_inner(&p, resolve, path);
+            path.pop();
+            value?
+        }
+    })
+}
+fn dict_to_json(dict: &Dictionary, resolve: &Resolve, path: &mut Vec<PlainRef>) -> Result<Value> {
+    let mut object = Map::new();
+    for (key, p) in dict {
+        object.insert(format!("/{}", key), to_json_inner(p, resolve, path)?);
+    }
+    Ok(Value::Object (object))
+}
+fn string_to_json(s: &PdfString) -> Value {
+    let mut object = Map::new();
+    match str::from_utf8(s.as_bytes()) {
+        Ok(text) => object.insert("string".into(), Value::String (text.into())),
+        Err(_) => object.insert("hex".into(), Value::String (hex(s.as_bytes()))),
+    };
+    Value::Object (object)
+}
+fn ref_to_json(r: PlainRef) -> Value {
+    let mut object = Map::new();
+    object.insert("ref".into(), Value::Array (vec![Value::Number (r.id.into()), Value::Number (r.gen.into())]));
+    Value::Object (object)
+}
+fn hex(data: &[u8]) -> String {
+    let mut s = String::with_capacity(2 * data.len());
+    for b in data {
+        write!(s, "{:02X}", b).unwrap();
+    }
+    s
+}
+
+/// Converts JSON in the form written by `to_json` back to a primitive. (Plain JSON strings
+/// without slash are taken as text strings.)
+pub fn from_json(value: &Value) -> Result<Primitive> {
+    Ok(match *value {
+        Value::Null => Primitive::Null,
+        Value::Bool (b) => Primitive::Boolean (b),
+        Value::Number (ref n) => match n.as_i64() {
+            Some(i) if i >= i32::min_value() as i64 && i <= i32::max_value() as i64 => Primitive::Integer (i as i32),
+            _ => match n.as_f64() {
+                Some(f) => Primitive::Number (f as f32),
+                None => bail!("JSON number {} out of range", n),
+            }
+        },
+        Value::String (ref s) => {
+            if s.starts_with('/') {
+                Primitive::Name (s[1 ..].into())
+            } else {
+                Primitive::String (PdfString::new(s.clone().into_bytes()))
+            }
+        }
+        Value::Array (ref values) => {
+            Primitive::Array (values.iter().map(from_json).collect::<Result<Vec<_>>>()?)
+        }
+        Value::Object (ref object) => object_from_json(object)?,
+    })
+}
+fn object_from_json(object: &Map<String, Value>) -> Result<Primitive> {
+    if let Some(value) = object.get("string") {
+        return match *value {
+            Value::String (ref s) => Ok(Primitive::String (PdfString::new(s.clone().into_bytes()))),
+            _ => bail!("JSON: \"string\" is not a string"),
+        };
+    }
+    if let Some(value) = object.get("hex") {
+        return Ok(Primitive::String (PdfString::new(unhex(value)?)));
+    }
+    if let Some(value) = object.get("ref") {
+        let r = match *value {
+            Value::Array (ref a) if a.len() == 2 => match (a[0].as_u64(), a[1].as_u64()) {
+                (Some(id), Some(gen)) if gen <= 65535 => Some(PlainRef {id: id, gen: gen as u16}),
+                _ => None
+            },
+            _ => None
+        };
+        return match r {
+            Some(r) => Ok(Primitive::Reference (r)),
+            None => bail!("JSON: \"ref\" is not [object number, generation number]"),
+        };
+    }
+    if let Some(value) = object.get("stream") {
+        let info = match from_json(value)? {
+            Primitive::Dictionary (dict) => dict,
+            p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Dictionary", found: p.get_debug_name()}),
+        };
+        let data = match object.get("data") {
+            Some(data) => unhex(data)?,
+            None => Vec::new(),
+        };
+        return Ok(Primitive::Stream (PdfStream {info: info, data: data}));
+    }
+    let mut dict = Dictionary::new();
+    for (key, value) in object {
+        if !key.starts_with('/') {
+            bail!("JSON: dictionary key {:?} without slash", key);
+        }
+        dict.insert(&key[1 ..], from_json(value)?);
+    }
+    Ok(Primitive::Dictionary (dict))
+}
+fn unhex(value: &Value) -> Result<Vec<u8>> {
+    let s = match *value {
+        Value::String (ref s) => s.as_bytes(),
+        _ => bail!("JSON: hex data is not a string"),
+    };
+    if s.len() % 2 != 0 {
+        bail!("JSON: hex data of odd length");
+    }
+    s.chunks(2).map(|pair| {
+        match str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()) {
+            Some(b) => Ok(b),
+            None => bail!("JSON: invalid hex data"),
+        }
+    }).collect()
+}
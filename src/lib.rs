@@ -11,10 +11,24 @@ extern crate pdf_derive;
 extern crate error_chain;
 extern crate num_traits;
 extern crate inflate;
+extern crate deflate;
+extern crate byteorder;
 extern crate itertools;
 extern crate memmap;
 extern crate tuple;
 extern crate chrono;
+extern crate rayon;
+extern crate memchr;
+extern crate crypto;
+extern crate rand;
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "jpeg")]
+extern crate jpeg_decoder;
+#[cfg(feature = "http")]
+extern crate reqwest;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 //#[macro_use]
 //mod macros;
@@ -24,10 +38,22 @@ pub mod xref;
 pub mod primitive;
 pub mod file;
 pub mod backend;
+pub mod linearization;
+pub mod visit;
+pub mod copy;
+pub mod diff;
+pub mod xmp;
+pub mod signature;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod crypt;
+#[cfg(feature = "serde")]
+pub mod json;
 
 mod err;
-// mod content;
-mod enc;
+pub mod content;
+pub mod text;
+pub mod enc;
 
 // pub use content::*;
 pub use err::*;
@@ -0,0 +1,226 @@
+//! Linearized files (PDF reference, appendix F): the objects of the first page come first,
+//! with their own xref section, so a viewer can show it before the rest of the file is loaded.
+
+use err::*;
+use object::*;
+use backend::Backend;
+use parser::{ParseOptions, parse_indirect_object, read_xref_and_trailer_at};
+use primitive::{Primitive, Dictionary};
+use xref::XRefTable;
+
+/// The linearization parameter dictionary - the first object of a linearized file.
+#[derive(Object, Debug, Clone)]
+#[pdf(Type=false)]
+pub struct LinearizationDict {
+    #[pdf(key="Linearized")]
+    pub version: f32,
+
+    /// Length of the file. If the actual length differs, the file has been updated and is no
+    /// longer linearized.
+    #[pdf(key="L")]
+    pub file_len: i32,
+
+    /// Offset and length of the primary hint stream (and optionally of the overflow hint stream)
+    #[pdf(key="H")]
+    pub hint_stream: Vec<i32>,
+
+    /// Object number of the first page
+    #[pdf(key="O")]
+    pub first_page: i32,
+
+    /// Offset of the end of the first page
+    #[pdf(key="E")]
+    pub first_page_end: i32,
+
+    #[pdf(key="N")]
+    pub num_pages: i32,
+
+    /// Offset of the first entry of the main xref table
+    #[pdf(key="T")]
+    pub main_xref_entry: i32,
+
+    /// Page number of the first page, if not 0 (PDF 1.2)
+    #[pdf(key="P")]
+    pub first_page_nr: Option<i32>,
+}
+
+/// The linearization dictionary of the file in `backend`, if it has one, and the offset of the
+/// data after it (which is the first-page xref section).
+pub fn read_linearization<B: Backend>(backend: &B) -> Result<Option<(LinearizationDict, usize)>> {
+    // The first object follows the header (which the lexer skips as comment)
+    let first = backend.parse_at(0, |lexer| {
+        let (_, obj) = parse_indirect_object(lexer, NO_RESOLVE)?;
+        Ok((obj, lexer.get_pos()))
+    });
+    let (dict, end) = match first {
+        Ok((Primitive::Dictionary (dict), end)) => (dict, end),
+        _ => return Ok(None),
+    };
+    if dict.get("Linearized").is_none() {
+        return Ok(None);
+    }
+    let lin = LinearizationDict::from_primitive(Primitive::Dictionary (dict), NO_RESOLVE)?;
+    Ok(Some((lin, end)))
+}
+
+/// Reads the first page of a linearized file, using only the first-page xref section - so only
+/// the objects of the first page are read.
+///
+/// The inherited attributes of the page (e.g. `/Resources` of the page tree) are read from its
+/// ancestors if they are in the first-page section too.
+pub fn read_first_page<B: Backend>(backend: &B, options: ParseOptions) -> Result<Page> {
+    let (lin, xref_pos) = match read_linearization(backend)? {
+        Some(lin) => lin,
+        None => bail!("Not a linearized file"),
+    };
+    // Load the whole first page at once (`HttpBackend` fetches it with one request)
+    backend.read(.. lin.first_page_end as usize)?;
+
+    let (sections, trailer) = backend.parse_at(xref_pos, |lexer| read_xref_and_trailer_at(lexer, NO_RESOLVE))?;
+    let size = trailer.get_int("Size")?;
+    check_min("Size", size, 0)?;
+    let mut refs = XRefTable::with_max_id(size as ObjNr, backend.len() as ObjNr);
+    refs.add_revision(sections);
+
+    let resolve = Resolver::new(|r| backend.resolve_with_options(&refs, r, &options), options);
+    let mut page = Dictionary::from_primitive(resolve.resolve(PlainRef {id: lin.first_page as ObjNr, gen: 0})?, &resolve)?;
+    // (if not strict, the page is read without them)
+    if let Err(e) = inherit_page_attributes(&mut page, &resolve) {
+        if options.strict {
+            return Err(e);
+        }
+    }
+    Page::from_primitive(Primitive::Dictionary (page), &resolve)
+}
+
+/// Reads the bit fields of hint tables, most significant bit first.
+struct BitReader<'a> {
+    data:   &'a [u8],
+    /// in bits
+    pos:    usize,
+}
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data:   data,
+            pos:    0,
+        }
+    }
+    fn read(&mut self, bits: usize) -> Result<u32> {
+        if bits > 32 {
+            bail!("Hint table: field of {} bits", bits);
+        }
+        let mut value: u64 = 0;
+        for _ in 0 .. bits {
+            let byte = match self.data.get(self.pos / 8) {
+                Some(&b) => b,
+                None => bail!(ErrorKind::EOF),
+            };
+            value = value << 1 | ((byte >> (7 - self.pos % 8)) & 1) as u64;
+            self.pos += 1;
+        }
+        Ok(value as u32)
+    }
+    /// Each item of the per-page entries starts at a byte boundary.
+    fn align(&mut self) {
+        self.pos = (self.pos + 7) / 8 * 8;
+    }
+}
+
+/// A page of the page offset hint table
+#[derive(Debug, Clone)]
+pub struct PageHint {
+    pub num_objects:    u32,
+    /// Length of the page in bytes
+    pub len:            u32,
+    /// Number of objects shared with other pages that the page uses
+    pub num_shared:     u32,
+}
+
+/// The page offset hint table (table F.3 and F.4 of the PDF reference), without the
+/// identifiers of shared objects and the content stream positions.
+#[derive(Debug, Clone)]
+pub struct PageOffsetHints {
+    /// Offset of the first page object
+    pub first_page_offset:  u32,
+    pub pages:              Vec<PageHint>,
+}
+impl PageOffsetHints {
+    /// Offset of the objects of each page (the first page is at `first_page_offset`, each
+    /// following page after the previous one).
+    pub fn page_offsets(&self) -> Result<Vec<u32>> {
+        let mut offsets = Vec::with_capacity(self.pages.len());
+        let mut offset = self.first_page_offset;
+        for page in &self.pages {
+            offsets.push(offset);
+            offset = match offset.checked_add(page.len) {
+                Some(offset) => offset,
+                None => bail!("Hint table: page offsets beyond 4 GiB"),
+            };
+        }
+        Ok(offsets)
+    }
+}
+
+/// Parses the page offset hint table at the start of the (decoded) primary hint stream, of a
+/// file of `file_len` bytes: the pages, and the objects and bytes of each, have to fit in it.
+pub fn parse_page_offset_hints(data: &[u8], num_pages: usize, file_len: usize) -> Result<PageOffsetHints> {
+    // (every page has at least its page object)
+    if num_pages > file_len {
+        bail!("Hint table: {} pages in a file of {} bytes", num_pages, file_len);
+    }
+    let in_file = |n: Option<u32>, what: &str| -> Result<u32> {
+        match n {
+            Some(n) if (n as u64) <= file_len as u64 => Ok(n),
+            _ => bail!("Hint table: {} beyond the end of the file", what),
+        }
+    };
+    let mut r = BitReader::new(data);
+    let least_objects = r.read(32)?;
+    let first_page_offset = r.read(32)?;
+    let objects_bits = r.read(16)? as usize;
+    let least_len = r.read(32)?;
+    let len_bits = r.read(16)? as usize;
+    let _least_content_offset = r.read(32)?;
+    let _content_offset_bits = r.read(16)?;
+    let _least_content_len = r.read(32)?;
+    let _content_len_bits = r.read(16)?;
+    let shared_bits = r.read(16)? as usize;
+    let _shared_id_bits = r.read(16)?;
+    let _numerator_bits = r.read(16)?;
+    let _denominator = r.read(16)?;
+
+    in_file(Some(first_page_offset), "first page offset")?;
+
+    let mut pages = vec![PageHint {num_objects: 0, len: 0, num_shared: 0}; num_pages];
+    for page in &mut pages {
+        page.num_objects = in_file(least_objects.checked_add(r.read(objects_bits)?), "number of objects")?;
+    }
+    r.align();
+    let mut end = first_page_offset;
+    for page in &mut pages {
+        page.len = in_file(least_len.checked_add(r.read(len_bits)?), "page length")?;
+        end = in_file(end.checked_add(page.len), "page")?;
+    }
+    r.align();
+    for page in &mut pages {
+        page.num_shared = in_file(Some(r.read(shared_bits)?), "number of shared objects")?;
+    }
+    Ok(PageOffsetHints {
+        first_page_offset:  first_page_offset,
+        pages:              pages,
+    })
+}
+
+/// Reads and parses the page offset hint table of a linearized file.
+pub fn read_page_offset_hints<B: Backend>(backend: &B, lin: &LinearizationDict, resolve: &Resolve) -> Result<PageOffsetHints> {
+    let pos = match lin.hint_stream.first() {
+        Some(&pos) => pos as usize,
+        None => bail!("Linearization dictionary without hint stream"),
+    };
+    let (_, p) = backend.parse_at(pos, |lexer| parse_indirect_object(lexer, resolve))?;
+    let mut stream = Stream::<Dictionary>::from_primitive(p, resolve)?;
+    stream.decode()?;
+    check_min("N", lin.num_pages, 0)?;
+    parse_page_offset_hints(stream.get_data_raw(), lin.num_pages as usize, backend.len())
+}
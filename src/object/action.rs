@@ -0,0 +1,265 @@
+//! Actions (PDF reference, section 8.5): what to do when a link is clicked, an outline item is
+//! activated, the document is opened - or, as additional actions (`/AA`), when a page is
+//! opened, a form field changes and the like.
+
+use std::io;
+use std::collections::{BTreeMap, HashSet};
+use object::*;
+use err::*;
+
+/// The triggers of the additional actions of the catalog: before closing, saving and printing
+/// the document (`WC`, `WS`, `WP`), and after saving and printing it (`DS`, `DP`).
+pub const DOCUMENT_TRIGGERS: &'static [&'static str] = &["WC", "WS", "DS", "WP", "DP"];
+/// The triggers of the additional actions of a page: when it is opened (`O`) and closed (`C`).
+pub const PAGE_TRIGGERS: &'static [&'static str] = &["O", "C"];
+/// The triggers of the additional actions of an annotation: the mouse entering (`E`) and
+/// leaving (`X`) it, the button pressed (`D`) and released (`U`), the focus received (`Fo`) and
+/// lost (`Bl`), and its page opened (`PO`), closed (`PC`), made visible (`PV`) and made
+/// invisible (`PI`).
+pub const ANNOTATION_TRIGGERS: &'static [&'static str] = &["E", "X", "D", "U", "Fo", "Bl", "PO", "PC", "PV", "PI"];
+/// The triggers of the additional actions of a form field: a keystroke (`K`), the value to be
+/// formatted (`F`) or validated (`V`), and another field that it is calculated from changed (`C`).
+/// These are JavaScript actions.
+pub const FIELD_TRIGGERS: &'static [&'static str] = &["K", "F", "V", "C"];
+
+/// An action, with the actions to perform after it.
+#[derive(Debug, Clone)]
+pub struct Action {
+    /// `/S`, like `GoTo` or `URI`
+    pub action_type:    String,
+    pub kind:           ActionKind,
+    /// `/Next`: the actions to perform after this one, in order
+    pub next:           Vec<Action>,
+    /// The action dictionary
+    pub dict:           Dictionary,
+}
+
+/// The entries of the action types that are read; `Other` for the other types.
+#[derive(Debug, Clone)]
+pub enum ActionKind {
+    /// Go to a destination in the document
+    GoTo {
+        /// `/D`
+        dest:       Target,
+    },
+    /// Go to a destination in another document
+    GoToR {
+        /// `/F`: the file name of the document
+        file:       Option<String>,
+        /// `/D`: a destination (with a page number, from 0), or the name of a destination
+        dest:       Target,
+        /// `/NewWindow`: whether to open the document in a new window
+        new_window: Option<bool>,
+    },
+    /// Launch an application, or open or print a document
+    Launch {
+        /// `/F` - or the file name `/F` of the Windows-specific `/Win`
+        file:       Option<String>,
+        /// `/P` of `/Win`: the parameters for the application
+        parameters: Option<String>,
+        new_window: Option<bool>,
+    },
+    /// Go to a URI
+    URI {
+        uri:        String,
+        /// `/IsMap`: whether to add the position of the mouse to the URI
+        is_map:     bool,
+    },
+    /// A predefined action of the viewer, like `NextPage` or `Print`
+    Named {
+        name:       String,
+    },
+    /// Run a JavaScript script (PDF 1.3)
+    JavaScript {
+        /// `/JS`, a text string or stream, decoded
+        script:     String,
+    },
+    Other,
+}
+
+impl Action {
+    /// This action and the actions it is followed by (see `next`), in the order they are
+    /// performed.
+    pub fn all(&self) -> Vec<&Action> {
+        let mut all = vec![self];
+        for next in &self.next {
+            all.extend(next.all());
+        }
+        all
+    }
+
+    fn read(p: Primitive, resolve: &Resolve, visited: &mut HashSet<PlainRef>) -> Result<Action> {
+        let dict = match p {
+            Primitive::Reference (r) => {
+                if !visited.insert(r) {
+                    bail!(ErrorKind::CircularReference {obj_nr: r.id});
+                }
+                Dictionary::from_primitive(Primitive::Reference (r), resolve)
+                    .chain_err(|| format!("Action {} {} R", r.id, r.gen))?
+            }
+            p => Dictionary::from_primitive(p, resolve)?,
+        };
+        let action_type: String = dict.expect("S", resolve)?;
+        let text = |dict: &Dictionary, key| -> Result<Option<String>> {
+            Ok(dict.get_as::<PdfString>(key, resolve)?.map(|s| s.to_string_lossy()))
+        };
+        let kind = match action_type.as_str() {
+            "GoTo" => ActionKind::GoTo {
+                dest:       destination(&dict, resolve)?,
+            },
+            "GoToR" => ActionKind::GoToR {
+                file:       file_name(&dict, resolve)?,
+                dest:       destination(&dict, resolve)?,
+                new_window: dict.get_as("NewWindow", resolve)?,
+            },
+            "Launch" => {
+                let win: Option<Dictionary> = dict.get_as("Win", resolve)?;
+                let (file, parameters) = match (file_name(&dict, resolve)?, win) {
+                    (Some(file), _) => (Some(file), None),
+                    (None, Some(win)) => (text(&win, "F")?, text(&win, "P")?),
+                    (None, None) => (None, None),
+                };
+                ActionKind::Launch {
+                    file:       file,
+                    parameters: parameters,
+                    new_window: dict.get_as("NewWindow", resolve)?,
+                }
+            }
+            "URI" => {
+                // (an ASCII string - but read leniently)
+                let uri: PdfString = dict.expect("URI", resolve)?;
+                ActionKind::URI {
+                    uri:        String::from_utf8_lossy(uri.as_bytes()).into_owned(),
+                    is_map:     dict.get_as("IsMap", resolve)?.unwrap_or(false),
+                }
+            }
+            "Named" => ActionKind::Named {
+                name:       dict.expect("N", resolve)?,
+            },
+            "JavaScript" => ActionKind::JavaScript {
+                script:     match dict.get("JS") {
+                    Some(js) => script(js.clone(), resolve)?,
+                    None => bail!(ErrorKind::EntryNotFound {key: "JS"}),
+                },
+            },
+            _ => ActionKind::Other,
+        };
+
+        // `/Next`: an action, or an array of them
+        let next = match dict.get("Next") {
+            Some(&Primitive::Array (ref actions)) => actions.clone(),
+            Some(action) => vec![action.clone()],
+            None => Vec::new(),
+        };
+        let mut next_actions = Vec::with_capacity(next.len());
+        for action in next {
+            match Action::read(action, resolve, visited) {
+                Ok(action) => next_actions.push(action),
+                Err(e) => {
+                    // (left out, if not strict)
+                    if resolve.options().strict {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(Action {
+            action_type:    action_type,
+            kind:           kind,
+            next:           next_actions,
+            dict:           dict,
+        })
+    }
+}
+
+impl Object for Action {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.dict.serialize(out)
+    }
+    /// An action that is reached again through `/Next` is an error - or, if not strict, left
+    /// out.
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        Action::read(p, resolve, &mut HashSet::new())
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Dictionary (self.dict.clone()))
+    }
+}
+
+/// `/D` of a go-to action.
+fn destination(dict: &Dictionary, resolve: &Resolve) -> Result<Target> {
+    dict.expect("D", resolve)
+}
+
+/// `/F` of an action: a file name, or a file specification with one.
+fn file_name(dict: &Dictionary, resolve: &Resolve) -> Result<Option<String>> {
+    let spec = match dict.get("F") {
+        Some(&Primitive::Reference (r)) => resolve.resolve(r)?,
+        Some(spec) => spec.clone(),
+        None => return Ok(None),
+    };
+    match spec {
+        Primitive::String (name) => Ok(Some(name.to_string_lossy())),
+        Primitive::Dictionary (spec) => {
+            let name = match spec.get("UF") {
+                Some(name) => name.clone(),
+                None => match spec.get("F") {
+                    Some(name) => name.clone(),
+                    None => return Ok(None),
+                },
+            };
+            Ok(Some(PdfString::from_primitive(name, resolve)?.to_string_lossy()))
+        }
+        p => bail!(ErrorKind::UnexpectedPrimitive {expected: "String or Dictionary", found: p.get_debug_name()}),
+    }
+}
+
+/// The script of `/JS`: a text string, or a stream with the text.
+fn script(js: Primitive, resolve: &Resolve) -> Result<String> {
+    let js = match js {
+        Primitive::Reference (r) => resolve.resolve(r)?,
+        js => js
+    };
+    match js {
+        Primitive::Stream (stream) => {
+            let stream = Stream::<Dictionary>::from_primitive(Primitive::Stream (stream), resolve)?;
+            Ok(PdfString::new(stream.get_data_for(resolve)?.into_owned()).to_string_lossy())
+        }
+        js => Ok(PdfString::from_primitive(js, resolve)?.to_string_lossy()),
+    }
+}
+
+/// The additional actions of the `/AA` dictionary `aa` for the triggers `triggers` (like
+/// `PAGE_TRIGGERS`), by trigger.
+pub fn read_additional_actions(aa: &Dictionary, triggers: &[&str], resolve: &Resolve) -> Result<BTreeMap<String, Action>> {
+    let mut actions = BTreeMap::new();
+    for (trigger, action) in aa.iter() {
+        if !triggers.contains(&trigger.as_str()) {
+            continue;
+        }
+        let action = Action::from_primitive(action.clone(), resolve)
+            .chain_err(|| format!("Additional action {}", trigger))?;
+        actions.insert(trigger.clone(), action);
+    }
+    Ok(actions)
+}
+
+/// Where `File::actions` found an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionSource {
+    /// `/OpenAction` of the catalog
+    OpenAction,
+    /// `/AA` of the catalog, with the trigger (see `DOCUMENT_TRIGGERS`)
+    Document (String),
+    /// A document-level script of the `JavaScript` name tree, with its name - run when the
+    /// document is opened
+    NamedJavaScript (String),
+    /// An outline item, with its title
+    Outline (String),
+    /// `/AA` of the page (an index), with the trigger (see `PAGE_TRIGGERS`)
+    Page (usize, String),
+    /// An annotation of a page: `/A`, or - with the trigger (see `ANNOTATION_TRIGGERS`) - `/AA`
+    Annotation {page: usize, annotation: usize, trigger: Option<String>},
+    /// `/AA` of the form field (a full name), with the trigger (see `FIELD_TRIGGERS`)
+    Field (String, String),
+}
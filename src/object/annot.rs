@@ -0,0 +1,180 @@
+//! Annotations (PDF reference, section 8.4): the `/Annots` of a page.
+
+use std::io;
+use std::collections::BTreeMap;
+use object::*;
+use err::*;
+
+const FLAG_INVISIBLE: u32 = 1;
+const FLAG_HIDDEN: u32 = 1 << 1;
+const FLAG_PRINT: u32 = 1 << 2;
+const FLAG_NO_VIEW: u32 = 1 << 5;
+const FLAG_READ_ONLY: u32 = 1 << 6;
+
+/// An annotation: the entries that all annotations have, and those of its subtype in `kind`.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    /// `/Subtype`, like `Link` or `Widget`
+    pub subtype:    String,
+    pub rect:       Rect,
+    /// `/Contents`, decoded: the text of the annotation, or a description of it
+    pub contents:   Option<String>,
+    /// `/NM`: the name of the annotation, unique on its page
+    pub name:       Option<String>,
+    /// `/F`: see `is_hidden` etc.
+    pub flags:      u32,
+    /// `/AP`: the appearance streams - see `normal_appearance`
+    pub appearance: Option<Dictionary>,
+    /// `/AS`: the appearance state, for appearances with several states (like check boxes)
+    pub appearance_state:   Option<String>,
+    /// `/AA`: the actions for `ANNOTATION_TRIGGERS`, by trigger
+    pub additional_actions: BTreeMap<String, Action>,
+    pub kind:       AnnotationKind,
+    /// The whole annotation dictionary, for the entries not read in the fields
+    pub dict:       Dictionary,
+}
+
+/// The entries of the annotation subtypes that are read; `Other` for the other subtypes.
+#[derive(Debug, Clone)]
+pub enum AnnotationKind {
+    Link {
+        /// `/Dest`: where to go when the link is clicked
+        dest:       Option<Target>,
+        /// `/A`: the action to perform, instead of going to `dest`
+        action:     Option<Action>,
+    },
+    /// A note
+    Text {
+        /// Whether the note is shown open
+        open:       bool,
+        /// `/Name`, like `Comment` or `Help`
+        icon:       Option<String>,
+    },
+    Highlight {
+        /// `/QuadPoints`: the corners of the highlighted areas, 8 numbers each
+        quad_points:    Vec<f32>,
+    },
+    /// The appearance of a form field (whose entries are in the same dictionary, if it has only
+    /// this widget)
+    Widget {
+        /// `/H`: how the widget looks when it is clicked
+        highlighting:   Option<String>,
+        /// `/MK`: the colors, captions and icons of the widget
+        characteristics:    Option<Dictionary>,
+    },
+    FreeText {
+        /// `/DA`: the default appearance - content stream operators that set the font and color
+        default_appearance: Option<PdfString>,
+        /// `/Q`: 0 for left-aligned text, 1 for centered, 2 for right-aligned
+        justification:  i32,
+    },
+    /// An attached file, shown as an icon
+    FileAttachment {
+        /// `/FS`: the file specification (see `Attachment::from_file_spec`)
+        file:       Option<Dictionary>,
+        /// `/Name`, like `PushPin` or `Paperclip`
+        icon:       Option<String>,
+    },
+    Other,
+}
+
+impl Annotation {
+    pub fn is_invisible(&self) -> bool {
+        self.flags & FLAG_INVISIBLE != 0
+    }
+    pub fn is_hidden(&self) -> bool {
+        self.flags & FLAG_HIDDEN != 0
+    }
+    pub fn is_printed(&self) -> bool {
+        self.flags & FLAG_PRINT != 0
+    }
+    /// Neither shown nor interactive on the screen (but possibly printed)
+    pub fn is_no_view(&self) -> bool {
+        self.flags & FLAG_NO_VIEW != 0
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.flags & FLAG_READ_ONLY != 0
+    }
+
+    /// The normal appearance stream (`/N` of `/AP`) - for appearances with several states, that
+    /// of the state `/AS`.
+    pub fn normal_appearance(&self, resolve: &Resolve) -> Result<Option<Stream<Dictionary>>> {
+        let normal = match self.appearance.as_ref().and_then(|ap| ap.get("N")) {
+            Some(normal) => normal.clone(),
+            None => return Ok(None),
+        };
+        let normal = match normal {
+            Primitive::Reference (r) => resolve.resolve(r)?,
+            p => p
+        };
+        match normal {
+            Primitive::Dictionary (states) => {
+                let state = match self.appearance_state {
+                    Some(ref state) => states.get(state),
+                    None => None
+                };
+                match state {
+                    Some(stream) => Ok(Some(Stream::from_primitive(stream.clone(), resolve)?)),
+                    None => Ok(None)
+                }
+            }
+            p => Ok(Some(Stream::from_primitive(p, resolve)?)),
+        }
+    }
+}
+
+/// The text string `key` of `dict`, decoded.
+fn text_entry(dict: &Dictionary, key: &'static str, resolve: &Resolve) -> Result<Option<String>> {
+    Ok(dict.get_as::<PdfString>(key, resolve)?.map(|s| s.to_string_lossy()))
+}
+
+impl Object for Annotation {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.dict.serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        let dict = Dictionary::from_primitive(p, resolve)?;
+        let subtype: String = dict.expect("Subtype", resolve)?;
+        let kind = match subtype.as_str() {
+            "Link" => AnnotationKind::Link {
+                dest:       dict.get_as("Dest", resolve)?,
+                action:     dict.get_as("A", resolve)?,
+            },
+            "Text" => AnnotationKind::Text {
+                open:       dict.get_as("Open", resolve)?.unwrap_or(false),
+                icon:       dict.get_as("Name", resolve)?,
+            },
+            "Highlight" => AnnotationKind::Highlight {
+                quad_points:    dict.get_as("QuadPoints", resolve)?.unwrap_or_default(),
+            },
+            "Widget" => AnnotationKind::Widget {
+                highlighting:   dict.get_as("H", resolve)?,
+                characteristics:    dict.get_as("MK", resolve)?,
+            },
+            "FreeText" => AnnotationKind::FreeText {
+                default_appearance: dict.get_as("DA", resolve)?,
+                justification:  dict.get_as("Q", resolve)?.unwrap_or(0),
+            },
+            "FileAttachment" => AnnotationKind::FileAttachment {
+                file:       dict.get_as("FS", resolve)?,
+                icon:       dict.get_as("Name", resolve)?,
+            },
+            _ => AnnotationKind::Other,
+        };
+        Ok(Annotation {
+            rect:       dict.expect("Rect", resolve)?,
+            contents:   text_entry(&dict, "Contents", resolve)?,
+            name:       text_entry(&dict, "NM", resolve)?,
+            flags:      dict.get_as::<i32>("F", resolve)?.unwrap_or(0) as u32,
+            appearance: dict.get_as("AP", resolve)?,
+            appearance_state:   dict.get_as("AS", resolve)?,
+            additional_actions: read_additional_actions(&dict.get_as("AA", resolve)?.unwrap_or_default(), ANNOTATION_TRIGGERS, resolve)?,
+            subtype:    subtype,
+            kind:       kind,
+            dict:       dict,
+        })
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Dictionary (self.dict.clone()))
+    }
+}
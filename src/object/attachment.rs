@@ -0,0 +1,172 @@
+//! Embedded files (PDF reference, section 3.10.3): the attachments of the document, in
+//! `/EmbeddedFiles` of the name dictionary, and those of `FileAttachment` annotations.
+
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+use object::*;
+use err::*;
+use enc::{encode, StreamFilter, LZWFlateParams};
+
+/// An embedded file, read from its file specification dictionary.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    /// The key in `/EmbeddedFiles` - `None` for the files of annotations
+    pub name:           Option<String>,
+    /// `/UF` (or else `/F`) of the file specification
+    pub file_name:      Option<String>,
+    /// `/Desc` of the file specification
+    pub description:    Option<String>,
+    /// `/Subtype` of the embedded file stream, like `text/xml`
+    pub mime_type:      Option<String>,
+    /// `/AFRelationship` (PDF 2.0, and PDF/A-3 - as in ZUGFeRD and Factur-X invoices): how
+    /// the file relates to the document, like `Data`, `Source` or `Alternative`
+    pub relationship:   Option<String>,
+    /// `/Size` of the parameters: the size of the (decoded) file
+    pub size:           Option<usize>,
+    /// `/CheckSum` of the parameters: the MD5 digest of the (decoded) file
+    pub checksum:       Option<Vec<u8>>,
+    pub creation_date:  Option<Date>,
+    pub mod_date:       Option<Date>,
+    /// The index of the page, for the file of an annotation
+    pub page:           Option<i32>,
+    /// The embedded file stream (encoded - see `File::attachment_data`)
+    pub stream:         Stream<Dictionary>,
+    /// The file specification dictionary
+    pub spec:           Dictionary,
+}
+
+impl Attachment {
+    /// Reads the file specification `spec` - `None` if it is not of an embedded file (but of
+    /// an external one).
+    pub fn from_file_spec(spec: Dictionary, resolve: &Resolve) -> Result<Option<Attachment>> {
+        let files: Dictionary = match spec.get_as("EF", resolve)? {
+            Some(files) => files,
+            None => return Ok(None),
+        };
+        let stream = match files.get("UF").or(files.get("F")) {
+            Some(stream) => Stream::<Dictionary>::from_primitive(stream.clone(), resolve)?,
+            None => bail!(ErrorKind::EntryNotFound {key: "F"}),
+        };
+        let params: Dictionary = stream.info.get_as("Params", resolve)?.unwrap_or_default();
+        let text = |key| -> Result<Option<String>> {
+            Ok(spec.get_as::<PdfString>(key, resolve)?.map(|s| s.to_string_lossy()))
+        };
+        let file_name = match text("UF")? {
+            Some(name) => Some(name),
+            None => text("F")?,
+        };
+        Ok(Some(Attachment {
+            name:           None,
+            file_name:      file_name,
+            description:    text("Desc")?,
+            mime_type:      stream.info.get_as("Subtype", resolve)?,
+            relationship:   spec.get_as("AFRelationship", resolve)?,
+            size:           params.get_as("Size", resolve)?,
+            checksum:       params.get_as::<PdfString>("CheckSum", resolve)?.map(|s| s.into_bytes()),
+            creation_date:  params.get_as("CreationDate", resolve)?,
+            mod_date:       params.get_as("ModDate", resolve)?,
+            page:           None,
+            stream:         stream,
+            spec:           spec,
+        }))
+    }
+
+    /// Whether `data` - the decoded file - has the size and checksum of the parameters (`None`
+    /// if there are neither).
+    pub fn check(&self, data: &[u8]) -> Option<bool> {
+        if self.size.is_none() && self.checksum.is_none() {
+            return None;
+        }
+        let size_matches = self.size.map_or(true, |size| size == data.len());
+        let checksum_matches = self.checksum.as_ref().map_or(true, |checksum| md5(data) == *checksum);
+        Some(size_matches && checksum_matches)
+    }
+}
+
+/// The entries of an attachment that `File::add_attachment` adds.
+#[derive(Debug, Clone, Default)]
+pub struct AttachmentOptions {
+    /// `/Subtype` of the embedded file stream, like `text/xml`
+    pub mime_type:      Option<String>,
+    /// `/Desc` of the file specification
+    pub description:    Option<String>,
+    /// `/AFRelationship`, like `Data` or `Alternative` - with it, the file is also an
+    /// associated file of the document (in `/AF` of the catalog), as PDF/A-3 invoices need
+    pub relationship:   Option<String>,
+    pub creation_date:  Option<Date>,
+    pub mod_date:       Option<Date>,
+}
+
+impl AttachmentOptions {
+    /// The embedded file stream for `data` (compressed), with the size and checksum.
+    pub fn embedded_file(&self, data: &[u8]) -> Result<PdfStream> {
+        let mut params = Dictionary::new()
+            .with("Size", data.len() as i32)
+            .with("CheckSum", PdfString::new(md5(data)));
+        if let Some(ref date) = self.creation_date {
+            params.insert("CreationDate", date.to_primitive()?);
+        }
+        if let Some(ref date) = self.mod_date {
+            params.insert("ModDate", date.to_primitive()?);
+        }
+        let mut info = Dictionary::new()
+            .with("Type", Primitive::Name ("EmbeddedFile".into()))
+            .with("Filter", Primitive::Name ("FlateDecode".into()))
+            .with("Params", params);
+        if let Some(ref mime_type) = self.mime_type {
+            info.insert("Subtype", Primitive::Name (mime_type.clone()));
+        }
+        let data = encode(data, &StreamFilter::FlateDecode (LZWFlateParams::default()))?;
+        Ok(PdfStream {info: info, data: data})
+    }
+    /// The file specification of the file `file_name`, whose embedded file stream is `stream`.
+    pub fn file_spec(&self, file_name: &str, stream: PlainRef) -> Dictionary {
+        let name = PdfString::from_text(file_name);
+        let mut spec = Dictionary::new()
+            .with("Type", Primitive::Name ("Filespec".into()))
+            .with("F", name.clone())
+            .with("UF", name)
+            .with("EF", Dictionary::new().with("F", stream).with("UF", stream));
+        if let Some(ref description) = self.description {
+            spec.insert("Desc", PdfString::from_text(description));
+        }
+        if let Some(ref relationship) = self.relationship {
+            spec.insert("AFRelationship", Primitive::Name (relationship.clone()));
+        }
+        spec
+    }
+}
+
+/// The MD5 digest of `data`, as in `/CheckSum`.
+fn md5(data: &[u8]) -> Vec<u8> {
+    let mut md5 = Md5::new();
+    md5.input(data);
+    let mut digest = vec![0; 16];
+    md5.result(&mut digest);
+    digest
+}
+
+/// The attachments in the `/EmbeddedFiles` name tree `tree`, in the order of their names.
+/// File specifications without an embedded file are left out.
+pub fn read_embedded_files(tree: &NameTree<Dictionary>, resolve: &Resolve) -> Result<Vec<Attachment>> {
+    let mut attachments = Vec::new();
+    for (name, spec) in tree.entries(resolve)? {
+        let name = name.to_string_lossy();
+        let attachment = Attachment::from_file_spec(spec, resolve)
+            .chain_err(|| format!("Embedded file {}", name));
+        match attachment {
+            Ok(Some(mut attachment)) => {
+                attachment.name = Some(name);
+                attachments.push(attachment);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                // (left out, if not strict)
+                if resolve.options().strict {
+                    return Err(e);
+                }
+            }
+        }
+    }
+    Ok(attachments)
+}
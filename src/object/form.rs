@@ -0,0 +1,444 @@
+//! Interactive forms (PDF reference, section 8.6): the fields of `/AcroForm` in the catalog.
+
+use std::io::Write;
+use std::collections::{BTreeMap, HashSet};
+use object::*;
+use err::*;
+
+/// `/FT` of a field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    /// Push buttons, check boxes and radio buttons
+    Button,
+    Text,
+    /// List boxes and combo boxes
+    Choice,
+    Signature,
+}
+impl FieldType {
+    fn from_name(name: &str) -> Result<FieldType> {
+        Ok(match name {
+            "Btn" => FieldType::Button,
+            "Tx" => FieldType::Text,
+            "Ch" => FieldType::Choice,
+            "Sig" => FieldType::Signature,
+            _ => bail!(ErrorKind::InvalidEntry {key: "FT", reason: format!("unknown field type {}", name)}),
+        })
+    }
+}
+
+const FLAG_READ_ONLY: u32 = 1;
+const FLAG_REQUIRED: u32 = 1 << 1;
+const FLAG_MULTILINE: u32 = 1 << 12;
+const FLAG_PASSWORD: u32 = 1 << 13;
+const FLAG_RADIO: u32 = 1 << 15;
+const FLAG_PUSH_BUTTON: u32 = 1 << 16;
+const FLAG_COMBO: u32 = 1 << 17;
+const FLAG_MULTI_SELECT: u32 = 1 << 21;
+
+/// The value of a field, by the kind of field (`FormField::value`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// A field without type - one that only groups its children
+    None,
+    Text (Option<String>),
+    CheckBox (bool),
+    /// The name of the selected button - `None` if no button is selected
+    Radio (Option<String>),
+    /// The selected options (of a multiple-selection list, or the one selected or entered)
+    Choice (Vec<String>),
+    PushButton,
+    /// Whether the field is signed (the signature dictionary is the `value` of the field)
+    Signature (bool),
+}
+
+/// A widget annotation of a field - one of the places where the field is shown.
+#[derive(Debug, Clone)]
+pub struct Widget {
+    /// The annotation object (`None` if it is a direct object)
+    pub id:         Option<PlainRef>,
+    pub annotation: Annotation,
+}
+
+impl Widget {
+    /// The states of the normal appearance (`/N` of `/AP`), if it has states - like `Off` and
+    /// `Yes` for a check box.
+    pub fn states(&self, resolve: &Resolve) -> Result<Vec<String>> {
+        let normal = match self.annotation.appearance.as_ref().and_then(|ap| ap.get("N")) {
+            Some(normal) => normal.clone(),
+            None => return Ok(Vec::new()),
+        };
+        let normal = match normal {
+            Primitive::Reference (r) => resolve.resolve(r)?,
+            p => p
+        };
+        match normal {
+            Primitive::Dictionary (states) => Ok(states.keys().cloned().collect()),
+            _ => Ok(Vec::new())
+        }
+    }
+}
+
+/// A form field, with the inheritable entries (`/FT`, `/V`, `/DV`, `/Ff`, `/DA` and `/Q`) taken
+/// from its ancestors - or the `/AcroForm` dictionary - if it doesn't have them.
+#[derive(Debug, Clone)]
+pub struct FormField {
+    /// The field object (`None` if it is a direct object)
+    pub id:             Option<PlainRef>,
+    /// `/T`, decoded
+    pub partial_name:   Option<String>,
+    /// The partial names of the field and its ancestors, joined with `.`
+    pub full_name:      String,
+    pub field_type:     Option<FieldType>,
+    /// `/V` (resolved): see `value()`
+    pub value:          Option<Primitive>,
+    /// `/DV` (resolved): the value that the field is reset to
+    pub default_value:  Option<Primitive>,
+    /// `/Ff`
+    pub flags:          u32,
+    /// `/DA`: content stream operators that set the font and color of variable text
+    pub default_appearance: Option<PdfString>,
+    /// `/Q`: 0 for left-aligned text, 1 for centered, 2 for right-aligned
+    pub justification:  i32,
+    pub children:       Vec<FormField>,
+    /// The widgets of the field: its kids that are only widgets, and the field itself if its
+    /// dictionary is a widget annotation too.
+    pub widgets:        Vec<Widget>,
+    /// `/AA`: the JavaScript actions for `FIELD_TRIGGERS`, by trigger
+    pub additional_actions: BTreeMap<String, Action>,
+    /// The field dictionary
+    pub dict:           Dictionary,
+}
+impl FormField {
+    /// The field called `name` (fully qualified, like `address.city`) - this one or one below it.
+    pub fn find(&self, name: &str) -> Option<&FormField> {
+        if self.full_name == name {
+            return Some(self);
+        }
+        self.children.iter().filter_map(|child| child.find(name)).next()
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.flags & FLAG_READ_ONLY != 0
+    }
+    pub fn is_required(&self) -> bool {
+        self.flags & FLAG_REQUIRED != 0
+    }
+    pub fn is_multiline(&self) -> bool {
+        self.field_type == Some(FieldType::Text) && self.flags & FLAG_MULTILINE != 0
+    }
+    pub fn is_password(&self) -> bool {
+        self.field_type == Some(FieldType::Text) && self.flags & FLAG_PASSWORD != 0
+    }
+    pub fn is_push_button(&self) -> bool {
+        self.field_type == Some(FieldType::Button) && self.flags & FLAG_PUSH_BUTTON != 0
+    }
+    pub fn is_radio(&self) -> bool {
+        self.field_type == Some(FieldType::Button) && self.flags & (FLAG_RADIO | FLAG_PUSH_BUTTON) == FLAG_RADIO
+    }
+    pub fn is_check_box(&self) -> bool {
+        self.field_type == Some(FieldType::Button) && self.flags & (FLAG_RADIO | FLAG_PUSH_BUTTON) == 0
+    }
+    pub fn is_combo_box(&self) -> bool {
+        self.field_type == Some(FieldType::Choice) && self.flags & FLAG_COMBO != 0
+    }
+    pub fn is_multi_select(&self) -> bool {
+        self.field_type == Some(FieldType::Choice) && self.flags & FLAG_MULTI_SELECT != 0
+    }
+
+    /// The value of the field (`/V`), by its kind.
+    ///
+    /// Check boxes and radio buttons without `/V` have the state (`/AS`) of their widgets as
+    /// value; the state `Off` is not checked (not selected). A text stream is decoded within the
+    /// decode limits of `resolve`.
+    pub fn value(&self, resolve: &Resolve) -> Result<FieldValue> {
+        let field_type = match self.field_type {
+            Some(field_type) => field_type,
+            None => return Ok(FieldValue::None),
+        };
+        Ok(match field_type {
+            FieldType::Text => FieldValue::Text (match self.value {
+                Some(ref value) => text_value(value, resolve)?,
+                None => None
+            }),
+            FieldType::Button if self.is_push_button() => FieldValue::PushButton,
+            FieldType::Button if self.is_radio() => FieldValue::Radio (self.button_state()),
+            FieldType::Button => FieldValue::CheckBox (self.button_state().is_some()),
+            FieldType::Choice => FieldValue::Choice (match self.value {
+                Some(Primitive::Array (ref options)) => {
+                    let mut selected = Vec::with_capacity(options.len());
+                    for option in options {
+                        selected.extend(text_value(option, resolve)?);
+                    }
+                    selected
+                }
+                Some(ref value) => text_value(value, resolve)?.into_iter().collect(),
+                None => Vec::new()
+            }),
+            FieldType::Signature => FieldValue::Signature (match self.value {
+                Some(Primitive::Dictionary (_)) => true,
+                Some(Primitive::Null) | None => false,
+                Some(ref p) => bail!(ErrorKind::UnexpectedPrimitive {expected: "Dictionary", found: p.get_debug_name()}),
+            }),
+        })
+    }
+    /// The name of the state of a button field that isn't `Off`, from `/V` or else from the
+    /// states of the widgets.
+    fn button_state(&self) -> Option<String> {
+        let state = match self.value {
+            Some(Primitive::Name (ref name)) => Some(name.clone()),
+            _ => self.widgets.iter()
+                .filter_map(|widget| widget.annotation.appearance_state.clone())
+                .find(|state| state != "Off"),
+        };
+        state.and_then(|state| if state == "Off" { None } else { Some(state) })
+    }
+    /// This field and all fields below it, parents before children.
+    pub fn all(&self) -> Vec<&FormField> {
+        let mut fields = vec![self];
+        for child in &self.children {
+            fields.extend(child.all());
+        }
+        fields
+    }
+}
+
+/// The interactive form of a document
+#[derive(Debug, Clone)]
+pub struct AcroForm {
+    /// The top-level fields (`/Fields`)
+    pub fields:         Vec<FormField>,
+    /// `/NeedAppearances`: the viewer has to make the appearance streams of the widgets
+    pub need_appearances:   bool,
+    /// The `/AcroForm` dictionary
+    pub dict:           Dictionary,
+}
+impl AcroForm {
+    /// The field called `name`, fully qualified - like `address.city`.
+    pub fn field(&self, name: &str) -> Option<&FormField> {
+        self.fields.iter().filter_map(|field| field.find(name)).next()
+    }
+    /// All fields, depth-first.
+    pub fn all_fields(&self) -> Vec<&FormField> {
+        self.fields.iter().flat_map(|field| field.all()).collect()
+    }
+
+    /// A normal appearance stream for the widget `widget` of the text field `field`, that shows
+    /// `text` on one line at the left.
+    ///
+    /// `None` if it can't be made here: if `text` is not printable ASCII, or if the default
+    /// appearance (`/DA`) doesn't set a font of the default resources (`/DR`) of the form.
+    pub fn text_appearance(&self, field: &FormField, widget: &Widget, text: &str, resolve: &Resolve) -> Result<Option<PdfStream>> {
+        if !text.bytes().all(|b| b >= b' ' && b <= b'~') {
+            return Ok(None);
+        }
+        let da = match field.default_appearance.as_ref().and_then(|da| da.as_str().ok()) {
+            Some(da) => da.to_string(),
+            None => return Ok(None),
+        };
+        let mut operands: Vec<String> = da.split_whitespace().map(|s| s.to_string()).collect();
+        let tf = match operands.iter().position(|op| op == "Tf") {
+            Some(tf) if tf >= 2 && operands[tf - 2].starts_with('/') => tf,
+            _ => return Ok(None),
+        };
+        let font_name = operands[tf - 2][1 ..].to_string();
+        let fonts = match resolved_entry(&self.dict, "DR", resolve)? {
+            Some(dr) => match resolved_entry(&Dictionary::from_primitive(dr, resolve)?, "Font", resolve)? {
+                Some(fonts) => Dictionary::from_primitive(fonts, resolve)?,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let font = match fonts.get(&font_name) {
+            Some(font) => font.clone(),
+            None => return Ok(None),
+        };
+
+        let rect = widget.annotation.rect;
+        let (width, height) = (rect.width(), rect.height());
+        // a size of 0 is "fit the widget"
+        let size = match operands[tf - 1].parse::<f32>() {
+            Ok(size) if size > 0. => size,
+            _ => (height * 0.7).max(1.),
+        };
+        operands[tf - 1] = size.to_string();
+
+        let mut content = Vec::new();
+        write!(content, "/Tx BMC\nq\nBT\n{}\n2 {} Td\n", operands.join(" "), (height - size) / 2. + 0.2 * size)?;
+        PdfString::new(text.as_bytes().to_vec()).serialize(&mut content)?;
+        write!(content, " Tj\nET\nQ\nEMC\n")?;
+
+        let info = Dictionary::new()
+            .with("Type", Primitive::Name ("XObject".into()))
+            .with("Subtype", Primitive::Name ("Form".into()))
+            .with("BBox", Rect::new(0., 0., width, height).to_primitive()?)
+            .with("Resources", Dictionary::new().with("Font", Dictionary::new().with(font_name, font)))
+            .with("Length", content.len() as i32);
+        Ok(Some(PdfStream {info: info, data: content}))
+    }
+}
+
+/// A text value: a text string, a name - or a text stream, for long texts.
+fn text_value(p: &Primitive, resolve: &Resolve) -> Result<Option<String>> {
+    match *p {
+        Primitive::String (ref s) => Ok(Some(s.to_string_lossy())),
+        Primitive::Name (ref name) => Ok(Some(name.clone())),
+        Primitive::Stream (_) => {
+            let stream = Stream::<Dictionary>::from_primitive(p.clone(), resolve)?;
+            Ok(Some(PdfString::new(stream.get_data_for(resolve)?.into_owned()).to_string_lossy()))
+        }
+        Primitive::Null => Ok(None),
+        ref p => bail!(ErrorKind::UnexpectedPrimitive {expected: "String", found: p.get_debug_name()}),
+    }
+}
+
+/// The inheritable entries of the parent
+#[derive(Clone)]
+struct Inherited {
+    full_name:      String,
+    field_type:     Option<FieldType>,
+    value:          Option<Primitive>,
+    default_value:  Option<Primitive>,
+    flags:          u32,
+    default_appearance: Option<PdfString>,
+    justification:  i32,
+}
+
+/// Reads the form `acro_form` (`/AcroForm` of the catalog) with its field tree.
+///
+/// Every field is read once: a field that is reached again - through a cycle of `/Kids` - is an
+/// error. In lenient mode, it is left out there.
+pub fn read_acro_form(acro_form: Dictionary, resolve: &Resolve) -> Result<AcroForm> {
+    let inherited = Inherited {
+        full_name:      String::new(),
+        field_type:     None,
+        value:          None,
+        default_value:  None,
+        flags:          0,
+        default_appearance: acro_form.get_as("DA", resolve)?,
+        justification:  acro_form.get_as("Q", resolve)?.unwrap_or(0),
+    };
+    let fields = array_entry(&acro_form, "Fields", resolve)?;
+    let mut visited = HashSet::new();
+    let mut top = Vec::with_capacity(fields.len());
+    for field in fields {
+        if let Some(field) = read_field(field, &inherited, resolve, &mut visited)? {
+            top.push(field);
+        }
+    }
+    Ok(AcroForm {
+        fields:             top,
+        need_appearances:   acro_form.get_as("NeedAppearances", resolve)?.unwrap_or(false),
+        dict:               acro_form,
+    })
+}
+
+/// Reads the field `p`, or returns `None` if it has been read already (in lenient mode).
+fn read_field(p: Primitive, parent: &Inherited, resolve: &Resolve, visited: &mut HashSet<PlainRef>) -> Result<Option<FormField>> {
+    let id = match p {
+        Primitive::Reference (r) => Some(r),
+        _ => None
+    };
+    if let Some(r) = id {
+        if !visited.insert(r) {
+            if resolve.options().strict {
+                bail!(ErrorKind::CircularReference {obj_nr: r.id});
+            }
+            return Ok(None);
+        }
+    }
+    let dict = Dictionary::from_primitive(p, resolve).chain_err(|| match id {
+        Some(r) => format!("Form field {} {} R", r.id, r.gen),
+        None => "Form field".to_string(),
+    })?;
+
+    let partial_name = dict.get_as::<PdfString>("T", resolve)?.map(|t| t.to_string_lossy());
+    let field_type = match dict.get_as::<String>("FT", resolve)? {
+        Some(ft) => Some(FieldType::from_name(&ft)?),
+        None => parent.field_type,
+    };
+    let inherited = Inherited {
+        full_name: match partial_name {
+            Some(ref name) if parent.full_name.is_empty() => name.clone(),
+            Some(ref name) => format!("{}.{}", parent.full_name, name),
+            None => parent.full_name.clone(),
+        },
+        field_type:     field_type,
+        value:          match resolved_entry(&dict, "V", resolve)? {
+            Some(value) => Some(value),
+            None => parent.value.clone(),
+        },
+        default_value:  match resolved_entry(&dict, "DV", resolve)? {
+            Some(value) => Some(value),
+            None => parent.default_value.clone(),
+        },
+        flags:          dict.get_as::<i32>("Ff", resolve)?.map(|ff| ff as u32).unwrap_or(parent.flags),
+        default_appearance: dict.get_as("DA", resolve)?.or_else(|| parent.default_appearance.clone()),
+        justification:  dict.get_as("Q", resolve)?.unwrap_or(parent.justification),
+    };
+
+    let mut children = Vec::new();
+    let mut widgets = Vec::new();
+    if is_widget(&dict) {
+        widgets.push(Widget {
+            id:         id,
+            annotation: Annotation::from_primitive(Primitive::Dictionary (dict.clone()), resolve)?,
+        });
+    }
+    let kids = array_entry(&dict, "Kids", resolve)?;
+    for kid in kids {
+        let kid_id = match kid {
+            Primitive::Reference (r) => Some(r),
+            _ => None
+        };
+        let kid_dict = Dictionary::from_primitive(kid.clone(), resolve)?;
+        // A kid without name that is a widget is a widget of this field - else it is a field.
+        if kid_dict.get("T").is_none() && is_widget(&kid_dict) {
+            widgets.push(Widget {
+                id:         kid_id,
+                annotation: Annotation::from_primitive(Primitive::Dictionary (kid_dict), resolve)?,
+            });
+        } else if let Some(field) = read_field(kid, &inherited, resolve, visited)? {
+            children.push(field);
+        }
+    }
+
+    Ok(Some(FormField {
+        id:             id,
+        partial_name:   partial_name,
+        full_name:      inherited.full_name,
+        field_type:     inherited.field_type,
+        value:          inherited.value,
+        default_value:  inherited.default_value,
+        flags:          inherited.flags,
+        default_appearance: inherited.default_appearance,
+        justification:  inherited.justification,
+        children:       children,
+        widgets:        widgets,
+        additional_actions: read_additional_actions(&dict.get_as("AA", resolve)?.unwrap_or_default(), FIELD_TRIGGERS, resolve)?,
+        dict:           dict,
+    }))
+}
+
+/// The entry `key` of `dict` - the object it refers to, if it is a reference.
+fn resolved_entry(dict: &Dictionary, key: &str, resolve: &Resolve) -> Result<Option<Primitive>> {
+    match dict.get(key) {
+        Some(&Primitive::Reference (r)) => Ok(Some(resolve.resolve(r)?)),
+        Some(p) => Ok(Some(p.clone())),
+        None => Ok(None)
+    }
+}
+
+/// The array `key` of `dict` (which may be an indirect object) - empty if it is missing.
+fn array_entry(dict: &Dictionary, key: &str, resolve: &Resolve) -> Result<Vec<Primitive>> {
+    match resolved_entry(dict, key, resolve)? {
+        Some(array) => array.to_array(resolve),
+        None => Ok(Vec::new())
+    }
+}
+
+fn is_widget(dict: &Dictionary) -> bool {
+    match dict.get("Subtype") {
+        Some(&Primitive::Name (ref subtype)) => subtype == "Widget",
+        _ => false
+    }
+}
@@ -2,32 +2,85 @@
 //!
 //! Some of the structs are incomplete (missing fields that are in the PDF references).
 
+#[macro_use]
+mod name_enum;
 mod types;
 mod stream;
+mod validate;
+mod annot;
+mod form;
+mod attachment;
+mod optional_content;
+mod structure;
+mod viewer;
+mod action;
 
 pub use self::types::*;
 pub use self::stream::*;
+pub use self::validate::*;
+pub use self::annot::*;
+pub use self::form::*;
+pub use self::attachment::*;
+pub use self::optional_content::*;
+pub use self::structure::*;
+pub use self::viewer::*;
+pub use self::action::*;
 
 use primitive::*;
 use err::*;
 use enc::*;
+use parser::{ParseOptions, TypeCheck, parse};
 
 use std::io;
 use std::fmt;
 use std::marker::PhantomData;
+use std::cell::RefCell;
 use std::collections::BTreeMap;
 
 pub type ObjNr = u64;
 pub type GenNr = u16;
 pub trait Resolve: {
     fn resolve(&self, r: PlainRef) -> Result<Primitive>;
+    /// The options that objects are parsed and converted with. Strict by default.
+    fn options(&self) -> &ParseOptions {
+        &STRICT
+    }
+    /// The limits for stream data decoded while converting objects (see `Stream::get_data_for`).
+    /// `None` by default: each stream is limited to the default `DecodeLimits` then.
+    fn decode_limits(&self) -> Option<&DecodeLimits> {
+        None
+    }
 }
+static STRICT: ParseOptions = ParseOptions {strict: true, type_check: TypeCheck::Error};
+
 impl<F> Resolve for F where F: Fn(PlainRef) -> Result<Primitive> {
     fn resolve(&self, r: PlainRef) -> Result<Primitive> {
         self(r)
     }
 }
 
+/// A resolve function with `ParseOptions`.
+pub struct Resolver<F> {
+    f:          F,
+    options:    ParseOptions,
+}
+impl<F> Resolver<F> where F: Fn(PlainRef) -> Result<Primitive> {
+    pub fn new(f: F, options: ParseOptions) -> Resolver<F> {
+        Resolver {
+            f:          f,
+            options:    options,
+        }
+    }
+}
+impl<F> Resolve for Resolver<F> where F: Fn(PlainRef) -> Result<Primitive> {
+    fn resolve(&self, r: PlainRef) -> Result<Primitive> {
+        (self.f)(r)
+    }
+    fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+}
+
 
 pub struct NoResolve {}
 impl Resolve for NoResolve {
@@ -38,12 +91,114 @@ impl Resolve for NoResolve {
 /// Resolve function that just throws an error
 pub const NO_RESOLVE: &'static Resolve = &NoResolve {} as &Resolve;
 
+thread_local! {
+    /// The objects being resolved on this thread, innermost last.
+    static RESOLVING: RefCell<Vec<PlainRef>> = RefCell::new(Vec::new());
+}
+/// Removes its reference from `RESOLVING` again.
+struct ResolvingGuard;
+impl Drop for ResolvingGuard {
+    fn drop(&mut self) {
+        RESOLVING.with(|stack| stack.borrow_mut().pop());
+    }
+}
+/// Runs `f` - which resolves or converts the object `r` - with `r` on the resolution stack of
+/// this thread. If `r` is on it already, `r` refers to itself and `f` would not terminate:
+/// then fails with `CircularReference` instead.
+pub fn guard_cycle<T, F>(r: PlainRef, f: F) -> Result<T> where F: FnOnce() -> Result<T> {
+    let cycle = RESOLVING.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        if stack.contains(&r) {
+            true
+        } else {
+            stack.push(r);
+            false
+        }
+    });
+    if cycle {
+        bail!(ErrorKind::CircularReference {obj_nr: r.id});
+    }
+    let _guard = ResolvingGuard;
+    f()
+}
+
+/// Checks that `key` (`"Type"` or `"Subtype"`) of `dict` is the name `expected` - or, depending
+/// on `options.type_check`, doesn't fail if it isn't (also if the entry is missing).
+///
+/// `from_primitive` implementations that check the type of a dictionary should use it, so the
+/// policy applies to them - as the ones of `#[pdf(Type="...")]` do.
+pub fn check_type(dict: &Dictionary, key: &'static str, expected: &str, options: &ParseOptions) -> Result<()> {
+    if options.type_check == TypeCheck::Skip {
+        return Ok(());
+    }
+    let result = match dict.get(key) {
+        Some(&Primitive::Name (ref name)) if name == expected => return Ok(()),
+        Some(p) => {
+            let found = match *p {
+                Primitive::Name (ref name) => name.clone(),
+                ref p => p.get_debug_name().into(),
+            };
+            Err(Error::from(ErrorKind::WrongDictionaryType {expected: expected.into(), found: found}))
+        }
+        None => Err(Error::from(ErrorKind::EntryNotFound {key: key})),
+    };
+    match options.type_check {
+        TypeCheck::Tolerate => Ok(()),
+        _ => result
+    }
+}
+
 /// A PDF Object
+///
+/// For dictionaries, `#[derive(Object)]` (from `pdf_derive`) maps each field to the key given
+/// with `#[pdf(key="...")]`, both for reading and for writing (`to_primitive` and `serialize`,
+/// which leave out the fields that are `null`, like `None`):
+///
+/// - A missing key is converted with `Object::from_missing`, which is the same as from
+///   `Primitive::Null` for most types. So `Option<T>` fields are optional keys (`None` if missing
+///   or `null`), as are `Vec<T>` fields (empty). `Entry<T>` tells the two apart.
+/// - `#[pdf(key="...", default="expr")]` uses `expr` if the key is missing - other fields can be
+///   used in it, e.g. `default="vec![0, size]"`.
+/// - `#[pdf(other)]` on a `Dictionary` field collects the entries that aren't mapped to another
+///   field (so producer-specific keys are kept, and written again).
+/// - `#[pdf(Type="...")]` on the struct checks `/Type` (and `Subtype="..."` `/Subtype`);
+///   `#[pdf(Type=false)]` is for dictionaries without `/Type`. The names are checked with
+///   `check_type` - so as `ParseOptions::type_check` says - and written, too.
+///
+/// ```ignore
+/// #[derive(Object)]
+/// #[pdf(Type="Example")]
+/// struct Example {
+///     #[pdf(key="Count")]
+///     count: i32,
+///     #[pdf(key="Name")]
+///     name: Option<String>,
+///     #[pdf(key="Flag", default="false")]
+///     flag: bool,
+///     #[pdf(other)]
+///     other: Dictionary,
+/// }
+/// ```
 pub trait Object: Sized {
     /// Write object as a byte stream
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()>;
     /// Convert primitive to Self
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self>;
+    /// Self for a missing dictionary entry. By default the same as for `null` - which the PDF
+    /// reference says is equivalent - but types can tell them apart, like `Entry<T>`.
+    fn from_missing(resolve: &Resolve) -> Result<Self> {
+        Self::from_primitive(Primitive::Null, resolve)
+    }
+    /// Convert Self to a primitive - so a modified object can be put into the document again
+    /// (see `File::update_object`).
+    ///
+    /// By default `serialize`s and parses the result, which works for everything that is written
+    /// as valid PDF syntax without indirect objects.
+    fn to_primitive(&self) -> Result<Primitive> {
+        let mut data = Vec::new();
+        self.serialize(&mut data)?;
+        parse(&data, NO_RESOLVE)
+    }
 }
 
 ///////
@@ -63,15 +218,31 @@ impl Object for PlainRef {
     fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
         p.to_reference()
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Reference (*self))
+    }
 }
 
 
+/// A reference to an object of type `T`. The object is only read when it is resolved, so
+/// structs can refer to large parts of the document (like the parent of a page) cheaply.
 // NOTE: Copy & Clone implemented manually ( https://github.com/rust-lang/rust/issues/26925 )
-#[derive(Copy,Clone)]
 pub struct Ref<T> {
     inner:      PlainRef,
     _marker:    PhantomData<T>
 }
+impl<T> Clone for Ref<T> {
+    fn clone(&self) -> Ref<T> {
+        *self
+    }
+}
+impl<T> Copy for Ref<T> {}
+impl<T> PartialEq for Ref<T> {
+    fn eq(&self, other: &Ref<T>) -> bool {
+        self.inner == other.inner
+    }
+}
+impl<T> Eq for Ref<T> {}
 impl<T> Ref<T> {
     pub fn new(inner: PlainRef) -> Ref<T> {
         Ref {
@@ -89,6 +260,13 @@ impl<T> Ref<T> {
         self.inner
     }
 }
+impl<T: Object> Ref<T> {
+    /// Reads the object. (Converting it fails with `CircularReference` if it contains itself.)
+    pub fn resolve(&self, resolve: &Resolve) -> Result<T> {
+        let p = resolve.resolve(self.inner)?;
+        guard_cycle(self.inner, || T::from_primitive(p, resolve))
+    }
+}
 impl<T: Object> Object for Ref<T> {
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()>  {
         self.inner.serialize(out)
@@ -96,6 +274,9 @@ impl<T: Object> Object for Ref<T> {
     fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
         Ok(Ref::new(p.to_reference()?))
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Reference (self.inner))
+    }
 }
 
 impl<T> fmt::Debug for Ref<T> {
@@ -104,6 +285,174 @@ impl<T> fmt::Debug for Ref<T> {
     }
 }
 
+/// A value that may be written directly or as a reference to an indirect object (like
+/// `/Resources`). Unlike `T`, it remembers which, so it is written the same way again.
+#[derive(Debug, Clone)]
+pub enum MaybeRef<T> {
+    Direct (T),
+    Indirect (Ref<T>, T),
+}
+impl<T> MaybeRef<T> {
+    pub fn get(&self) -> &T {
+        match *self {
+            MaybeRef::Direct (ref t) | MaybeRef::Indirect (_, ref t) => t
+        }
+    }
+    /// The reference, if the value is an indirect object.
+    pub fn get_ref(&self) -> Option<Ref<T>> {
+        match *self {
+            MaybeRef::Direct (_) => None,
+            MaybeRef::Indirect (r, _) => Some(r),
+        }
+    }
+    pub fn into_inner(self) -> T {
+        match self {
+            MaybeRef::Direct (t) | MaybeRef::Indirect (_, t) => t
+        }
+    }
+}
+impl<T> ::std::ops::Deref for MaybeRef<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+impl<T: Object> Object for MaybeRef<T> {
+    /// Writes the value, or the reference to it if it is indirect.
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            MaybeRef::Direct (ref t) => t.serialize(out),
+            MaybeRef::Indirect (r, _) => r.serialize(out),
+        }
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        match p {
+            Primitive::Reference (r) => {
+                let r = Ref::new(r);
+                Ok(MaybeRef::Indirect (r, r.resolve(resolve)?))
+            }
+            p => Ok(MaybeRef::Direct (T::from_primitive(p, resolve)?))
+        }
+    }
+    /// The reference if the value is indirect (the object itself has to be updated separately).
+    fn to_primitive(&self) -> Result<Primitive> {
+        match *self {
+            MaybeRef::Direct (ref t) => t.to_primitive(),
+            MaybeRef::Indirect (r, _) => r.to_primitive(),
+        }
+    }
+}
+
+/// A value of type `T` that is converted (and resolved, if it is a reference) only when it is
+/// needed - for parts of the document that are large or rarely used, like the outlines.
+pub struct Lazy<T> {
+    primitive:  Primitive,
+    _marker:    PhantomData<T>,
+}
+impl<T: Object> Lazy<T> {
+    pub fn new(primitive: Primitive) -> Lazy<T> {
+        Lazy {
+            primitive:  primitive,
+            _marker:    PhantomData,
+        }
+    }
+    /// Resolves and converts the value.
+    pub fn get(&self, resolve: &Resolve) -> Result<T> {
+        match self.primitive {
+            Primitive::Reference (r) => Ref::<T>::new(r).resolve(resolve),
+            ref p => T::from_primitive(p.clone(), resolve),
+        }
+    }
+    /// The reference, if the value is an indirect object.
+    pub fn get_ref(&self) -> Option<Ref<T>> {
+        match self.primitive {
+            Primitive::Reference (r) => Some(Ref::new(r)),
+            _ => None
+        }
+    }
+    /// The value as it is written in the document.
+    pub fn get_primitive(&self) -> &Primitive {
+        &self.primitive
+    }
+}
+impl<T> Clone for Lazy<T> {
+    fn clone(&self) -> Lazy<T> {
+        Lazy {
+            primitive:  self.primitive.clone(),
+            _marker:    PhantomData,
+        }
+    }
+}
+impl<T> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lazy({:?})", self.primitive)
+    }
+}
+impl<T: Object> Object for Lazy<T> {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.primitive.serialize(out)
+    }
+    fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
+        Ok(Lazy::new(p))
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(self.primitive.clone())
+    }
+}
+
+/// A dictionary entry that may be missing, `null` or a value - for the cases where a missing
+/// entry and `null` mean different things (unlike for `Option<T>`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<T> {
+    Missing,
+    Null,
+    Value (T),
+}
+impl<T> Entry<T> {
+    pub fn value(&self) -> Option<&T> {
+        match *self {
+            Entry::Value (ref t) => Some(t),
+            _ => None
+        }
+    }
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            Entry::Value (t) => Some(t),
+            _ => None
+        }
+    }
+    pub fn is_missing(&self) -> bool {
+        match *self {
+            Entry::Missing => true,
+            _ => false
+        }
+    }
+}
+impl<T: Object> Object for Entry<T> {
+    /// A missing entry is written as `null` - `Dictionary` can't leave it out.
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            Entry::Value (ref t) => t.serialize(out),
+            Entry::Missing | Entry::Null => write!(out, "null"),
+        }
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        match p {
+            Primitive::Null => Ok(Entry::Null),
+            p => Ok(Entry::Value (T::from_primitive(p, resolve)?)),
+        }
+    }
+    fn from_missing(_: &Resolve) -> Result<Self> {
+        Ok(Entry::Missing)
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        match *self {
+            Entry::Value (ref t) => t.to_primitive(),
+            Entry::Missing | Entry::Null => Ok(Primitive::Null),
+        }
+    }
+}
+
 //////////////////////////////////////
 // Object for Primitives & other types
 //////////////////////////////////////
@@ -112,30 +461,113 @@ impl Object for i32 {
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
         write!(out, "{}", self)
     }
+    /// Also accepts a real without fractional part (like `3.0`) - or, if not strict, any real
+    /// in range, rounded.
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
         Ok(
         match p {
             Primitive::Integer (n) => n,
+            Primitive::Number (f) => real_to_integer(f, resolve.options())?,
             Primitive::Reference (r) => i32::from_primitive(resolve.resolve(r)?, resolve)?,
             p => bail!(Error::from(ErrorKind::UnexpectedPrimitive {expected: "Integer", found: p.get_debug_name()}))
         }
         )
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Integer (*self))
+    }
 }
 impl Object for usize {
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
         write!(out, "{}", self)
     }
     fn from_primitive(p: Primitive, r: &Resolve) -> Result<Self> {
-        Ok(i32::from_primitive(p, r)? as usize)
+        let n = i32::from_primitive(p, r)?;
+        if n < 0 {
+            bail!(ErrorKind::NumberOutOfRange {number: n as f64, expected: "non-negative integer"});
+        }
+        Ok(n as usize)
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Integer (*self as i32))
     }
 }
 impl Object for f32 {
+    /// Writes the number in decimal notation (`Display` of floats never uses an exponent, which
+    /// PDF doesn't allow). There is no infinity or NaN in PDF - they are written as 0.
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
-        write!(out, "{}", self)
+        if self.is_finite() {
+            write!(out, "{}", self)
+        } else {
+            write!(out, "0")
+        }
     }
-    fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
-        p.as_number()
+    /// Accepts integers too.
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        match p {
+            Primitive::Reference (r) => f32::from_primitive(resolve.resolve(r)?, resolve),
+            p => p.as_number()
+        }
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Number (if self.is_finite() { *self } else { 0. }))
+    }
+}
+impl Object for f64 {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        (*self as f32).serialize(out)
+    }
+    /// Accepts integers too. (The value has the precision of `Primitive::Number` - `f32`.)
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        Ok(f32::from_primitive(p, resolve)? as f64)
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        (*self as f32).to_primitive()
+    }
+}
+/// An integer written as a real: an error if it has a fractional part (when strict) or doesn't
+/// fit into `i32`.
+fn real_to_integer(f: f32, options: &ParseOptions) -> Result<i32> {
+    // (also false for NaN)
+    if !(f >= -2147483648. && f < 2147483648.) {
+        bail!(ErrorKind::NumberOutOfRange {number: f as f64, expected: "integer"});
+    }
+    if f.fract() != 0. {
+        if options.strict {
+            bail!(ErrorKind::NumberOutOfRange {number: f as f64, expected: "integer"});
+        }
+    }
+    Ok(f.round() as i32)
+}
+
+/// A number that may be written as an integer or as a real - for fields that the PDF reference
+/// says are numbers. It is written as an integer if it is one.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Number (pub f32);
+impl Object for Number {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        // (`Display` of floats writes `3` for `3.0`)
+        self.0.serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        f32::from_primitive(p, resolve).map(Number)
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        if self.0.fract() == 0. && self.0 >= -2147483648. && self.0 < 2147483648. {
+            Ok(Primitive::Integer (self.0 as i32))
+        } else {
+            self.0.to_primitive()
+        }
+    }
+}
+impl From<f32> for Number {
+    fn from(f: f32) -> Number {
+        Number (f)
+    }
+}
+impl From<i32> for Number {
+    fn from(n: i32) -> Number {
+        Number (n as f32)
     }
 }
 impl Object for bool {
@@ -145,15 +577,20 @@ impl Object for bool {
     fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
         p.as_bool()
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Boolean (*self))
+    }
 }
 impl Object for Dictionary {
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
         write!(out, "<<")?;
         for (key, val) in self.iter() {
-            write!(out, "/{} ", key)?;
+            write!(out, " ")?;
+            write_name(out, key)?;
+            write!(out, " ")?;
             val.serialize(out)?;
         }
-        write!(out, ">>")
+        write!(out, " >>")
     }
     fn from_primitive(p: Primitive, r: &Resolve) -> Result<Self> {
         match p {
@@ -162,23 +599,22 @@ impl Object for Dictionary {
             _ => bail!(ErrorKind::UnexpectedPrimitive {expected: "Dictionary", found: p.get_debug_name()}),
         }
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Dictionary (self.clone()))
+    }
 }
 
 impl Object for String {
+    /// `String` is a name (as in `Primitive::Name`).
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
-        for b in self.as_str().chars() {
-            match b {
-                '\\' | '(' | ')' => write!(out, r"\")?,
-                c if c > '~' => panic!("only ASCII"),
-                _ => ()
-            }
-            write!(out, "{}", b)?;
-        }
-        Ok(())
+        write_name(out, self)
     }
     fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
         Ok(p.to_name()?)
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Name (self.clone()))
+    }
 }
 
 impl<T: Object> Object for Vec<T> {
@@ -202,6 +638,9 @@ impl<T: Object> Object for Vec<T> {
         }
         )
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Array (self.iter().map(|t| t.to_primitive()).collect::<Result<Vec<_>>>()?))
+    }
 }
 
 impl Object for Primitive {
@@ -222,11 +661,21 @@ impl Object for Primitive {
     fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
         Ok(p)
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(self.clone())
+    }
 }
 
 impl<V: Object> Object for BTreeMap<String, V> {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        unimplemented!();
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "<<")?;
+        for (key, val) in self.iter() {
+            write!(out, " ")?;
+            write_name(out, key)?;
+            write!(out, " ")?;
+            val.serialize(out)?;
+        }
+        write!(out, " >>")
     }
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
         match p {
@@ -240,6 +689,13 @@ impl<V: Object> Object for BTreeMap<String, V> {
             p =>  Err(ErrorKind::UnexpectedPrimitive {expected: "Dictionary", found: p.get_debug_name()}.into())
         }
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        for (key, val) in self {
+            dict.insert(key.clone(), val.to_primitive()?);
+        }
+        Ok(Primitive::Dictionary (dict))
+    }
 }
 
 impl Object for () {
@@ -250,4 +706,7 @@ impl Object for () {
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
         Ok(())
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Null)
+    }
 }
@@ -0,0 +1,54 @@
+//! `pdf_name_enum!`, for entries whose values are a set of names.
+
+/// Defines an enum of the values of a name entry (like `/Intent`), with a variant for each name
+/// (named like it) and `Other` for the names that aren't known, so that these can be read and
+/// written again. The enum is an `Object` - written as `Primitive::Name`.
+///
+/// ```ignore
+/// pdf_name_enum! {
+///     /// `/BM` of a graphics state
+///     pub enum BlendMode {
+///         Normal,
+///         Multiply,
+///     }
+/// }
+/// assert_eq!(BlendMode::from_name("Screen"), BlendMode::Other ("Screen".into()));
+/// ```
+#[macro_export]
+macro_rules! pdf_name_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($(#[$variant_meta:meta])* $variant:ident),* $(,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A name that isn't one of the others
+            Other (String),
+        }
+        impl $name {
+            pub fn from_name(name: &str) -> $name {
+                match name {
+                    $(stringify!($variant) => $name::$variant,)*
+                    _ => $name::Other (name.into())
+                }
+            }
+            pub fn name(&self) -> &str {
+                match *self {
+                    $($name::$variant => stringify!($variant),)*
+                    $name::Other (ref name) => name,
+                }
+            }
+        }
+        impl $crate::object::Object for $name {
+            fn serialize<W: ::std::io::Write>(&self, out: &mut W) -> ::std::io::Result<()> {
+                $crate::primitive::write_name(out, self.name())
+            }
+            fn from_primitive(p: $crate::primitive::Primitive, resolve: &$crate::object::Resolve) -> $crate::Result<Self> {
+                let name = <String as $crate::object::Object>::from_primitive(p, resolve)?;
+                Ok($name::from_name(&name))
+            }
+            fn to_primitive(&self) -> $crate::Result<$crate::primitive::Primitive> {
+                Ok($crate::primitive::Primitive::Name (self.name().into()))
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Optional content (PDF reference, section 4.10): the layers of `/OCProperties` in the
+//! catalog, and which content belongs to them.
+//!
+//! Content belongs to optional content through an `/OC` entry - of an XObject or an
+//! annotation - or through marked content `/OC /Name BDC ... EMC` (with `/Name` in
+//! `/Properties` of the resources). `/OC` is an optional content group (a layer) or a
+//! membership dictionary, which makes the content visible depending on several groups.
+
+use std::collections::HashSet;
+use object::*;
+use err::*;
+use content::{Content, Operation};
+
+/// An optional content group: a layer.
+#[derive(Debug, Clone)]
+pub struct OptionalContentGroup {
+    /// The object of the group dictionary - groups are referred to by it
+    pub id:         PlainRef,
+    /// `/Name`, as shown in viewers
+    pub name:       String,
+    /// `/Intent`: `View` (the default) or `Design`, or several
+    pub intent:     Vec<String>,
+    /// The group dictionary, for `/Usage`
+    pub dict:       Dictionary,
+}
+
+/// `/P` of a membership dictionary: when the content is visible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VisibilityPolicy {
+    AllOn,
+    AnyOn,
+    AnyOff,
+    AllOff,
+}
+
+/// `/VE` of a membership dictionary (PDF 1.6): a boolean expression of groups.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VisibilityExpression {
+    Group (PlainRef),
+    And (Vec<VisibilityExpression>),
+    Or (Vec<VisibilityExpression>),
+    Not (Box<VisibilityExpression>),
+}
+impl VisibilityExpression {
+    fn from_primitive(p: &Primitive, resolve: &Resolve) -> Result<VisibilityExpression> {
+        match *p {
+            Primitive::Reference (r) => match resolve.resolve(r)? {
+                // (arrays can be indirect objects too - and contain themselves)
+                Primitive::Array (array) => guard_cycle(r, || VisibilityExpression::from_array(&array, resolve)),
+                _ => Ok(VisibilityExpression::Group (r)),
+            },
+            Primitive::Array (ref array) => VisibilityExpression::from_array(array, resolve),
+            ref p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Array", found: p.get_debug_name()}),
+        }
+    }
+    fn from_array(array: &[Primitive], resolve: &Resolve) -> Result<VisibilityExpression> {
+        let operator = match array.first() {
+            Some(&Primitive::Name (ref name)) => name.clone(),
+            _ => bail!(ErrorKind::InvalidEntry {key: "VE", reason: "no /And, /Or or /Not".into()}),
+        };
+        let operands = array[1 ..].iter()
+            .map(|operand| VisibilityExpression::from_primitive(operand, resolve))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(match operator.as_str() {
+            "And" => VisibilityExpression::And (operands),
+            "Or" => VisibilityExpression::Or (operands),
+            "Not" if operands.len() == 1 => VisibilityExpression::Not (Box::new(operands.into_iter().next().unwrap())),
+            _ => bail!(ErrorKind::InvalidEntry {key: "VE", reason: format!("invalid operator /{} with {} operands", operator, operands.len())}),
+        })
+    }
+    /// Whether the expression is true if the groups `active` are on.
+    pub fn eval(&self, active: &HashSet<PlainRef>) -> bool {
+        match *self {
+            VisibilityExpression::Group (r) => active.contains(&r),
+            VisibilityExpression::And (ref operands) => operands.iter().all(|e| e.eval(active)),
+            VisibilityExpression::Or (ref operands) => operands.iter().any(|e| e.eval(active)),
+            VisibilityExpression::Not (ref operand) => !operand.eval(active),
+        }
+    }
+    fn groups(&self, out: &mut Vec<PlainRef>) {
+        match *self {
+            VisibilityExpression::Group (r) => out.push(r),
+            VisibilityExpression::And (ref operands) | VisibilityExpression::Or (ref operands) => {
+                for operand in operands {
+                    operand.groups(out);
+                }
+            }
+            VisibilityExpression::Not (ref operand) => operand.groups(out),
+        }
+    }
+}
+
+/// The value of an `/OC` entry (or of a property list in `BDC /OC`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionalContentMembership {
+    /// Visible if the group is on
+    Group (PlainRef),
+    /// A membership dictionary: visible if `expression` is true - or, without one, according to
+    /// `policy` for `groups`
+    Membership {
+        groups:     Vec<PlainRef>,
+        policy:     VisibilityPolicy,
+        expression: Option<VisibilityExpression>,
+    },
+}
+impl OptionalContentMembership {
+    /// Reads an `/OC` entry, which has to be a reference to the group or membership dictionary.
+    pub fn from_primitive(p: &Primitive, resolve: &Resolve) -> Result<OptionalContentMembership> {
+        let r = match *p {
+            Primitive::Reference (r) => r,
+            ref p => bail!(ErrorKind::InvalidEntry {key: "OC", reason: format!("{} instead of a reference", p.get_debug_name())}),
+        };
+        let dict = Dictionary::from_primitive(Primitive::Reference (r), resolve)?;
+        match dict.get("Type") {
+            Some(&Primitive::Name (ref t)) if t == "OCMD" => {}
+            _ => return Ok(OptionalContentMembership::Group (r)),
+        }
+        let groups = match dict.get("OCGs") {
+            Some(&Primitive::Reference (r)) => match resolve.resolve(r)? {
+                Primitive::Array (array) => array,
+                _ => vec![Primitive::Reference (r)],
+            },
+            Some(&Primitive::Array (ref array)) => array.clone(),
+            _ => Vec::new()
+        };
+        let policy = match dict.get_as::<String>("P", resolve)? {
+            Some(ref p) if p == "AllOn" => VisibilityPolicy::AllOn,
+            Some(ref p) if p == "AnyOff" => VisibilityPolicy::AnyOff,
+            Some(ref p) if p == "AllOff" => VisibilityPolicy::AllOff,
+            Some(ref p) if p != "AnyOn" => bail!(ErrorKind::InvalidEntry {key: "P", reason: format!("unknown policy /{}", p)}),
+            _ => VisibilityPolicy::AnyOn,
+        };
+        let expression = match dict.get("VE") {
+            Some(ve) => Some(VisibilityExpression::from_primitive(ve, resolve)?),
+            None => None
+        };
+        Ok(OptionalContentMembership::Membership {
+            // (null entries are ignored)
+            groups:     groups.iter().filter_map(|g| match *g {
+                Primitive::Reference (r) => Some(r),
+                _ => None
+            }).collect(),
+            policy:     policy,
+            expression: expression,
+        })
+    }
+    /// Whether the content is visible if the groups `active` are on.
+    pub fn is_visible(&self, active: &HashSet<PlainRef>) -> bool {
+        match *self {
+            OptionalContentMembership::Group (r) => active.contains(&r),
+            OptionalContentMembership::Membership {ref expression, ..} if expression.is_some() => {
+                expression.as_ref().unwrap().eval(active)
+            }
+            OptionalContentMembership::Membership {ref groups, policy, ..} => {
+                if groups.is_empty() {
+                    return true;
+                }
+                let on = |r: &PlainRef| active.contains(r);
+                match policy {
+                    VisibilityPolicy::AllOn => groups.iter().all(on),
+                    VisibilityPolicy::AnyOn => groups.iter().any(on),
+                    VisibilityPolicy::AnyOff => !groups.iter().all(on),
+                    VisibilityPolicy::AllOff => !groups.iter().any(on),
+                }
+            }
+        }
+    }
+    /// The groups that the visibility depends on.
+    pub fn groups(&self) -> Vec<PlainRef> {
+        match *self {
+            OptionalContentMembership::Group (r) => vec![r],
+            OptionalContentMembership::Membership {ref groups, ref expression, ..} => {
+                let mut all = groups.clone();
+                if let Some(ref expression) = *expression {
+                    expression.groups(&mut all);
+                }
+                let mut seen = HashSet::new();
+                all.retain(|r| seen.insert(*r));
+                all
+            }
+        }
+    }
+}
+
+/// An optional content configuration: `/D` (the default) or an entry of `/Configs`.
+#[derive(Debug, Clone)]
+pub struct OptionalContentConfig {
+    pub name:       Option<String>,
+    /// `/BaseState`: whether the groups are on if they're not in `on` or `off` - `None` for
+    /// `/Unchanged`
+    pub base_state: Option<bool>,
+    pub on:         Vec<PlainRef>,
+    pub off:        Vec<PlainRef>,
+    /// `/Locked`: the groups that users can't switch
+    pub locked:     Vec<PlainRef>,
+    /// `/RBGroups`: groups of which at most one can be on, like radio buttons
+    pub radio_button_groups:    Vec<Vec<PlainRef>>,
+    /// `/Order`: how viewers show the groups - a nested array of groups and labels
+    pub order:      Option<Primitive>,
+}
+impl OptionalContentConfig {
+    fn from_dict(dict: &Dictionary, resolve: &Resolve) -> Result<OptionalContentConfig> {
+        let base_state = match dict.get_as::<String>("BaseState", resolve)? {
+            Some(ref state) if state == "OFF" => Some(false),
+            Some(ref state) if state == "Unchanged" => None,
+            _ => Some(true),
+        };
+        let groups = |key| -> Result<Vec<PlainRef>> {
+            Ok(references(dict.get_as::<Vec<Primitive>>(key, resolve)?.unwrap_or_default()))
+        };
+        let radio_button_groups = dict.get_as::<Vec<Vec<Primitive>>>("RBGroups", resolve)?.unwrap_or_default();
+        Ok(OptionalContentConfig {
+            name:       dict.get_as::<PdfString>("Name", resolve)?.map(|s| s.to_string_lossy()),
+            base_state: base_state,
+            on:         groups("ON")?,
+            off:        groups("OFF")?,
+            locked:     groups("Locked")?,
+            radio_button_groups:    radio_button_groups.into_iter().map(references).collect(),
+            order:      dict.get("Order").cloned(),
+        })
+    }
+}
+
+/// The references in `array` (Vec<Ref<T>> would resolve them).
+fn references(array: Vec<Primitive>) -> Vec<PlainRef> {
+    array.into_iter().filter_map(|p| match p {
+        Primitive::Reference (r) => Some(r),
+        _ => None
+    }).collect()
+}
+
+/// `/OCProperties` of the catalog.
+#[derive(Debug, Clone)]
+pub struct OptionalContent {
+    /// `/OCGs`: all groups of the document
+    pub groups:         Vec<OptionalContentGroup>,
+    /// `/D`: the configuration viewers start with
+    pub default_config: OptionalContentConfig,
+    /// `/Configs`: other configurations
+    pub configs:        Vec<OptionalContentConfig>,
+}
+
+/// Which content of a page belongs to optional content - see `File::page_layers`.
+#[derive(Debug, Clone)]
+pub struct PageLayers {
+    /// The XObjects of the resources, by name
+    pub xobjects:       Vec<(String, OptionalContentMembership)>,
+    /// The annotations, by index (in `Page::annotations`)
+    pub annotations:    Vec<(usize, OptionalContentMembership)>,
+    /// The marked content sections `BDC /OC`: the index of the `BDC` operation in the page
+    /// content, and of its `EMC`
+    pub sections:       Vec<(usize, usize, OptionalContentMembership)>,
+}
+
+impl OptionalContent {
+    pub fn from_dict(dict: &Dictionary, resolve: &Resolve) -> Result<OptionalContent> {
+        let mut groups = Vec::new();
+        for r in references(dict.get_as::<Vec<Primitive>>("OCGs", resolve)?.unwrap_or_default()) {
+            let group = Dictionary::from_primitive(Primitive::Reference (r), resolve)?;
+            let intent = match group.get_as::<Primitive>("Intent", resolve)? {
+                Some(Primitive::Name (intent)) => vec![intent],
+                Some(intent) => Vec::<String>::from_primitive(intent, resolve)?,
+                None => vec!["View".into()],
+            };
+            groups.push(OptionalContentGroup {
+                id:     r,
+                name:   group.expect::<PdfString>("Name", resolve)?.to_string_lossy(),
+                intent: intent,
+                dict:   group,
+            });
+        }
+        let default_config = match dict.get_as::<Dictionary>("D", resolve)? {
+            Some(d) => OptionalContentConfig::from_dict(&d, resolve)?,
+            None if !resolve.options().strict => OptionalContentConfig::from_dict(&Dictionary::new(), resolve)?,
+            None => bail!(ErrorKind::EntryNotFound {key: "D"}),
+        };
+        let mut configs = Vec::new();
+        for config in dict.get_as::<Vec<Dictionary>>("Configs", resolve)?.unwrap_or_default() {
+            configs.push(OptionalContentConfig::from_dict(&config, resolve)?);
+        }
+        Ok(OptionalContent {
+            groups:         groups,
+            default_config: default_config,
+            configs:        configs,
+        })
+    }
+
+    pub fn group(&self, id: PlainRef) -> Option<&OptionalContentGroup> {
+        self.groups.iter().find(|group| group.id == id)
+    }
+    /// The (first) group named `name`.
+    pub fn group_by_name(&self, name: &str) -> Option<&OptionalContentGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// The groups that are on in `config` - for `/BaseState /Unchanged`, starting from the
+    /// default configuration.
+    pub fn active_groups(&self, config: &OptionalContentConfig) -> HashSet<PlainRef> {
+        let mut active: HashSet<PlainRef> = match config.base_state {
+            Some(true) => self.groups.iter().map(|group| group.id).collect(),
+            Some(false) => HashSet::new(),
+            None => self.active_groups(&self.default_config),
+        };
+        for r in &config.off {
+            active.remove(r);
+        }
+        active.extend(config.on.iter().cloned());
+        active
+    }
+    /// The groups that are on by default.
+    pub fn default_active_groups(&self) -> HashSet<PlainRef> {
+        self.active_groups(&self.default_config)
+    }
+
+    /// Whether the content with the `/OC` entry `oc` is visible if the groups `active` are on.
+    pub fn is_visible(&self, oc: &Primitive, active: &HashSet<PlainRef>, resolve: &Resolve) -> Result<bool> {
+        Ok(OptionalContentMembership::from_primitive(oc, resolve)?.is_visible(active))
+    }
+
+    /// `content` without what is not visible if the groups `active` are on: the marked content
+    /// sections `BDC /OC` and the XObjects (`Do`) with an `/OC` that is not visible.
+    /// `resources` are those of the content.
+    pub fn filter_content(&self, content: &Content, resources: Option<&Resources>, active: &HashSet<PlainRef>, resolve: &Resolve) -> Result<Content> {
+        let mut operations = Vec::with_capacity(content.operations.len());
+        // for the marked content sections we're in: whether they are hidden
+        let mut sections: Vec<bool> = Vec::new();
+        for operation in &content.operations {
+            let hidden = sections.iter().any(|&hidden| hidden);
+            match operation.operator.as_str() {
+                "BMC" => sections.push(false),
+                "BDC" => {
+                    let visible = match section_membership(operation, resources, resolve)? {
+                        Some(membership) => membership.is_visible(active),
+                        None => true
+                    };
+                    sections.push(!visible);
+                    if !visible {
+                        continue;
+                    }
+                }
+                "EMC" => {
+                    if sections.pop() == Some(true) {
+                        continue;
+                    }
+                }
+                "Do" if !hidden => {
+                    let oc = match (operation.operands.first(), resources) {
+                        (Some(&Primitive::Name (ref name)), Some(resources)) => {
+                            resources.xobject(name).and_then(|xobject| xobject.oc())
+                        }
+                        _ => None
+                    };
+                    if let Some(oc) = oc {
+                        if !self.is_visible(oc, active, resolve)? {
+                            continue;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if !hidden {
+                operations.push(operation.clone());
+            }
+        }
+        Ok(Content {operations: operations})
+    }
+
+    /// Which XObjects, annotations and marked content sections of `page` - with `content` -
+    /// belong to optional content.
+    pub fn page_layers(&self, page: &Page, content: &Content, resolve: &Resolve) -> Result<PageLayers> {
+        let resources = page.resources();
+        let mut xobjects = Vec::new();
+        if let Some(resources) = resources {
+            if let Some(names) = resources.xobjects() {
+                for (name, xobject) in names {
+                    if let Some(oc) = xobject.oc() {
+                        xobjects.push((name.clone(), OptionalContentMembership::from_primitive(oc, resolve)?));
+                    }
+                }
+            }
+        }
+        let mut annotations = Vec::new();
+        for (i, annotation) in page.annotations(resolve)?.iter().enumerate() {
+            if let Some(oc) = annotation.dict.get("OC") {
+                annotations.push((i, OptionalContentMembership::from_primitive(oc, resolve)?));
+            }
+        }
+        let mut sections = Vec::new();
+        // the BDC operations we're in, with their membership
+        let mut open = Vec::new();
+        for (i, operation) in content.operations.iter().enumerate() {
+            match operation.operator.as_str() {
+                "BMC" => open.push((i, None)),
+                "BDC" => open.push((i, section_membership(operation, resources, resolve)?)),
+                "EMC" => {
+                    if let Some((start, Some(membership))) = open.pop() {
+                        sections.push((start, i, membership));
+                    }
+                }
+                _ => {}
+            }
+        }
+        sections.sort_by_key(|&(start, _, _)| start);
+        Ok(PageLayers {
+            xobjects:       xobjects,
+            annotations:    annotations,
+            sections:       sections,
+        })
+    }
+}
+
+/// The optional content of the marked content operation `BDC /OC /Name` (`None` for other tags).
+fn section_membership(operation: &Operation, resources: Option<&Resources>, resolve: &Resolve) -> Result<Option<OptionalContentMembership>> {
+    match operation.operands.first() {
+        Some(&Primitive::Name (ref tag)) if tag == "OC" => {}
+        _ => return Ok(None),
+    }
+    let oc = match operation.operands.get(1) {
+        Some(&Primitive::Name (ref name)) => {
+            let properties = resources.and_then(|resources| resources.properties.as_ref());
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(oc) => oc.clone(),
+                None => bail!("Marked content with the undefined property list /{}", name),
+            }
+        }
+        Some(p) => p.clone(),
+        None => bail!("BDC without a property list"),
+    };
+    Ok(Some(OptionalContentMembership::from_primitive(&oc, resolve)?))
+}
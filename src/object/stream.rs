@@ -1,13 +1,17 @@
 use object::*;
 use primitive::*;
 use err::*;
-use parser::Lexer;
+use parser::{Lexer, parse};
 use backend::Backend;
 use file::File;
 
 
-use std::io;
+use std::io::{self, Read, Write};
 use std::ops::Deref;
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::path::Path;
+use std::{fs, mem};
 
 /// General stream type. `T` is the info dictionary.
 #[derive(Debug, Clone)]
@@ -19,23 +23,16 @@ pub struct Stream<T> {
 
     /// Eventual file containing the stream contentst
     file: Option<FileSpec>,
-    /// Filters to apply to external file specified in `file`.
+    /// Filters to apply to external file specified in `file` (corresponds to both `/FFilter` and
+    /// `/FDecodeParms`).
     file_filters: Vec<StreamFilter>,
-
-    // TODO:
-    /*
-    /// Filters to apply to external file specified in `file`.
-    #[pdf(key="FFilter")]
-    file_filters: Vec<StreamFilter>,
-    #[pdf(key="FDecodeParms")]
-    file_decode_parms: Vec<DecodeParms>,
-    /// Number of bytes in the decoded stream
-    #[pdf(key="DL")]
-    dl: Option<usize>,
-    */
+    /// Number of bytes in the decoded stream (`/DL`) - a hint, which may be wrong.
+    decoded_length: Option<usize>,
     // Specialized dictionary entries
     pub info: T,
-    data: Vec<u8>,
+    /// Shared, so that cloning a stream (or the `ObjectStream` it belongs to) doesn't copy the
+    /// data. `Arc::make_mut` copies it only if it's actually shared.
+    data: Arc<Vec<u8>>,
 }
 
 impl<T: Default> Default for Stream<T> {
@@ -44,26 +41,106 @@ impl<T: Default> Default for Stream<T> {
             filters: Vec::new(),
             file: None,
             file_filters: Vec::new(),
+            decoded_length: None,
             info: T::default(),
-            data: Vec::new(),
+            data: Arc::new(Vec::new()),
         }
     }
 }
 impl<T> Stream<T> {
     /// If the stream is not encoded, this is a no-op. `decode()` should be called whenever it's uncertain
     /// whether the stream is encoded.
+    ///
+    /// Image codecs (see `StreamFilter::is_image_codec`) are left in place - use `image_data()`
+    /// to hand the payload to an image decoder, or `decode_image()` to decode it anyway.
+    ///
+    /// The decoded data is limited to the default `DecodeLimits` (256 MiB) - use
+    /// `decode_limited()` for other limits.
     pub fn decode(&mut self) -> Result<()> {
+        self.decode_limited(&DecodeLimits::default())
+    }
+    /// `decode()` within `limits`. If the data is larger, `ErrorKind::DecodeLimit` is returned
+    /// and the stream is left as it was.
+    pub fn decode_limited(&mut self, limits: &DecodeLimits) -> Result<()> {
+        let n = self.filters.iter().take_while(|f| !f.is_image_codec()).count();
+        if n == 0 {
+            return Ok(());
+        }
+        let max_size = limits.stream_limit();
+        let mut data = decode_limited(&self.data, &self.filters[0], max_size)?;
+        for filter in &self.filters[1 .. n] {
+            data = decode_limited(&data, filter, max_size)?;
+        }
+        limits.add(data.len())?;
+        self.data = Arc::new(data);
+        self.filters.drain(.. n);
+        Ok(())
+    }
+    /// Decodes all filters, including image codecs (which may require a feature, like `jpeg`
+    /// for `DCTDecode`), within the default `DecodeLimits`.
+    pub fn decode_image(&mut self) -> Result<()> {
+        self.decode()?;
+        let max_size = DecodeLimits::default().stream_limit();
         for filter in &self.filters {
-            eprintln!("Decode filter: {:?}", filter);
-            self.data = decode(&self.data, filter)?;
+            self.data = Arc::new(decode_limited(&self.data, filter, max_size)?);
         }
         self.filters.clear();
         Ok(())
     }
-    pub fn encode(&mut self, _filter: StreamFilter) {
-        // TODO this should add the filter to `self.filters` and encode the data with the given
-        // filter
-        unimplemented!();
+    /// If the stream is encoded with an image codec, decodes the other filters and returns the
+    /// codec together with its payload.
+    pub fn image_data(&mut self) -> Result<Option<(&StreamFilter, &[u8])>> {
+        self.decode()?;
+        Ok(match self.filters.first() {
+            Some(filter) => Some((filter, &self.data[..])),
+            None => None
+        })
+    }
+    /// Like `decode()`, but lazily: the returned reader decodes while it is read, so the decoded
+    /// data doesn't have to be held in memory. Image codecs are left in place, like in `decode()`.
+    pub fn decoded_reader<'a>(&'a self) -> Box<io::Read + 'a> {
+        let mut reader: Box<io::Read + 'a> = Box::new(&self.data[..]);
+        for filter in self.filters.iter().take_while(|f| !f.is_image_codec()) {
+            reader = decode_reader(reader, filter);
+        }
+        reader
+    }
+    /// Encodes the data with `filter`. The filter becomes the first one to be undone by
+    /// `decode()`, so that `/Filter` and `/DecodeParms` stay in the right order when written.
+    pub fn encode(&mut self, filter: StreamFilter) -> Result<()> {
+        self.data = Arc::new(encode(&self.data, &filter)?);
+        self.filters.insert(0, filter);
+        Ok(())
+    }
+    /// The file containing the stream data, if it's external (`/F`).
+    pub fn get_file(&self) -> Option<&FileSpec> {
+        self.file.as_ref()
+    }
+    /// Loads the data of an external stream: `load` is called with the file name of `/F` and
+    /// returns the contents of the file. These replace the (ignored) embedded data, the
+    /// `/FFilter`s become the filters of the stream and are decoded like with `decode()`.
+    /// Afterwards, the stream behaves like an embedded one.
+    pub fn load_external<F>(&mut self, load: F) -> Result<()>
+        where F: FnOnce(&str) -> io::Result<Vec<u8>>
+    {
+        let name = match self.file.as_ref().map(|f| f.file_name()) {
+            Some(Some(name)) => name,
+            Some(None) => bail!("File specification of the stream has no file name"),
+            None => bail!("Stream is not external (no /F entry)"),
+        };
+        self.data = Arc::new(load(&name)?);
+        self.filters = mem::replace(&mut self.file_filters, Vec::new());
+        self.file = None;
+        self.decode()
+    }
+    /// `load_external()` from the file system, with relative file names resolved against
+    /// `base` (usually the directory of the PDF file).
+    pub fn load_external_from(&mut self, base: &Path) -> Result<()> {
+        self.load_external(|name| {
+            let mut data = Vec::new();
+            fs::File::open(base.join(name))?.read_to_end(&mut data)?;
+            Ok(data)
+        })
     }
     pub fn get_length(&self) -> usize {
         self.data.len()
@@ -71,23 +148,157 @@ impl<T> Stream<T> {
     pub fn get_filters(&self) -> &[StreamFilter] {
         &self.filters
     }
-    /// Get data - panics if it's not decoded in advance.
-    /// Ideally I would have it take &mut self and do it itself,
-    /// but that leads to problems in the code (aliasing)...
-    pub fn get_data(&self) -> &[u8] {
-        if self.get_filters().len() > 0 {
-            panic!("Data not decoded! Consider using `get_data_raw`");
+    /// Filters of the external file (`/FFilter` and `/FDecodeParms`), see `load_external()`.
+    pub fn get_file_filters(&self) -> &[StreamFilter] {
+        &self.file_filters
+    }
+    /// The length of the decoded stream according to `/DL`.
+    pub fn get_decoded_length(&self) -> Option<usize> {
+        self.decoded_length
+    }
+    pub fn set_decoded_length(&mut self, decoded_length: Option<usize>) {
+        self.decoded_length = decoded_length;
+    }
+    /// Get the decoded data. If the stream is already decoded (see `decode()`), the data is
+    /// borrowed, otherwise a decoded copy is returned. As with `decode()`, image codecs are not
+    /// undone, and the decoded data is limited to the default `DecodeLimits`.
+    pub fn get_data(&self) -> Result<Cow<[u8]>> {
+        self.get_data_limited(&DecodeLimits::default())
+    }
+    /// `get_data()` within `limits` (the copy counts towards their total).
+    pub fn get_data_limited(&self, limits: &DecodeLimits) -> Result<Cow<[u8]>> {
+        let mut data = Cow::Borrowed(&self.data[..]);
+        let max_size = limits.stream_limit();
+        for filter in self.filters.iter().take_while(|f| !f.is_image_codec()) {
+            data = Cow::Owned(decode_limited(&data, filter, max_size)?);
+        }
+        if let Cow::Owned(ref data) = data {
+            limits.add(data.len())?;
+        }
+        Ok(data)
+    }
+    /// `get_data()` within the decode limits of `resolve` - for a `File::resolver`, those of the
+    /// document.
+    pub fn get_data_for(&self, resolve: &Resolve) -> Result<Cow<[u8]>> {
+        match resolve.decode_limits() {
+            Some(limits) => self.get_data_limited(limits),
+            None => self.get_data()
         }
-        &self.data
     }
     /// Doesn't decode/unfilter the data.
     pub fn get_data_raw(&self) -> &[u8] {
         &self.data
     }
+    /// The (not decoded) data, shared with this stream.
+    pub fn get_data_shared(&self) -> Arc<Vec<u8>> {
+        self.data.clone()
+    }
+}
+/// Writes `/Filter` and `/DecodeParms` (or `/FFilter` and `/FDecodeParms`) - as single values
+/// if there is only one filter, and without `/DecodeParms` if none of the filters has parameters.
+fn write_filters<W: io::Write>(out: &mut W, filters: &[StreamFilter], filter_key: &str, params_key: &str)
+    -> io::Result<()>
+{
+    if filters.is_empty() {
+        return Ok(());
+    }
+    if filters.len() == 1 {
+        writeln!(out, "/{} /{}", filter_key, filters[0].name())?;
+    } else {
+        write!(out, "/{} [", filter_key)?;
+        for filter in filters {
+            write!(out, " /{}", filter.name())?;
+        }
+        writeln!(out, " ]")?;
+    }
+
+    let mut params = Vec::with_capacity(filters.len());
+    for filter in filters {
+        let mut buf = Vec::new();
+        filter.serialize_params(&mut buf)?;
+        params.push(buf);
+    }
+    if params.iter().all(|p| &p[..] == b"null") {
+        return Ok(());
+    }
+    write!(out, "/{} ", params_key)?;
+    if params.len() == 1 {
+        out.write_all(&params[0])?;
+    } else {
+        write!(out, "[")?;
+        for p in &params {
+            write!(out, " ")?;
+            out.write_all(p)?;
+        }
+        write!(out, " ]")?;
+    }
+    writeln!(out, "")
 }
+/// The entries that `Stream` writes itself, from its own fields.
+const STREAM_KEYS: &'static [&'static str] = &["Length", "Filter", "DecodeParms", "F", "FFilter", "FDecodeParms", "DL"];
+
+/// Follows an eventual reference. A missing entry is `Null`.
+fn resolve_primitive(p: Option<Primitive>, resolve: &Resolve) -> Result<Primitive> {
+    match p {
+        Some(Primitive::Reference (r)) => resolve.resolve(r),
+        Some(p) => Ok(p),
+        None => Ok(Primitive::Null),
+    }
+}
+/// `/DecodeParms` (or `/FDecodeParms`) is either a single dictionary (for a single filter) or an
+/// array with one dictionary or `null` per filter, and both the entry and the elements can be
+/// indirect. Filters that don't have parameters get an empty dictionary.
+fn decode_params(p: Option<Primitive>, resolve: &Resolve) -> Result<Vec<Dictionary>> {
+    let params = match resolve_primitive(p, resolve)? {
+        Primitive::Array (params) => params,
+        Primitive::Null => Vec::new(),
+        p => vec![p],
+    };
+    params.into_iter()
+        .map(|p| match resolve_primitive(Some(p), resolve)? {
+            Primitive::Null => Ok(Dictionary::default()),
+            p => Dictionary::from_primitive(p, resolve),
+        })
+        .collect()
+}
+
 impl<T: Object> Object for Stream<T> {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        unimplemented!();
+    /// Writes the stream dictionary - `/Length` (of the encoded data), the filters, `/F` and the
+    /// entries of `info` - followed by the (still encoded) data. Entries of `info` with the keys
+    /// of these fields are left out, so that each key is written once.
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut info = match self.info.to_primitive() {
+            Ok(Primitive::Dictionary (dict)) => dict,
+            Ok(_) => Dictionary::default(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+        };
+        for &key in STREAM_KEYS {
+            info.remove(key);
+        }
+
+        writeln!(out, "<<")?;
+        writeln!(out, "/Length {}", self.data.len())?;
+        write_filters(out, &self.filters, "Filter", "DecodeParms")?;
+        if let Some(ref file) = self.file {
+            write!(out, "/F ")?;
+            file.serialize(out)?;
+            writeln!(out, "")?;
+            write_filters(out, &self.file_filters, "FFilter", "FDecodeParms")?;
+        }
+        if let Some(dl) = self.decoded_length {
+            writeln!(out, "/DL {}", dl)?;
+        }
+        for (key, val) in info.iter() {
+            write_name(out, key)?;
+            write!(out, " ")?;
+            val.serialize(out)?;
+            writeln!(out, "")?;
+        }
+        writeln!(out, ">>")?;
+
+        writeln!(out, "stream")?;
+        out.write_all(&self.data)?;
+        writeln!(out, "\nendstream")
     }
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
         // (TODO) there are a lot of `clone()` here because we can't consume the dict before we
@@ -95,30 +306,39 @@ impl<T: Object> Object for Stream<T> {
         let mut stream = PdfStream::from_primitive(p, resolve)?;
         let dict = &mut stream.info;
 
-        let length = usize::from_primitive(
-            dict.remove("Length").ok_or(Error::from(ErrorKind::EntryNotFound{key:"Length"}))?,
-            resolve)?;
-        assert_eq!(length, stream.data.len());
+        // `Length` is often an indirect object (written after the stream, when the length is known)
+        let length = match dict.remove("Length") {
+            Some(Primitive::Reference (r)) => usize::from_primitive(resolve.resolve(r)?, resolve)?,
+            Some(p) => usize::from_primitive(p, resolve)?,
+            None => bail!(ErrorKind::EntryNotFound {key: "Length"}),
+        };
+        if length != stream.data.len() {
+            bail!(ErrorKind::StreamLength {length: length, found: stream.data.len()});
+        }
 
         let filters = Vec::<String>::from_primitive(
-            dict.remove("Filter").ok_or(Error::from(ErrorKind::EntryNotFound{key:"Filter"}))?,
+            resolve_primitive(dict.remove("Filter"), resolve)?,
             resolve)?;
 
-        let decode_params = Vec::<Dictionary>::from_primitive(
-            dict.remove("DecodeParms").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
+        let decode_params = decode_params(dict.remove("DecodeParms"), resolve)?;
 
-        let file = Option::<FileSpec>::from_primitive(
-            dict.remove("F").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
+        // A file specification can also be just the file name
+        let file = match dict.remove("F") {
+            Some(Primitive::String (name)) => Some(FileSpec::from_name(name)),
+            Some(p) => Option::<FileSpec>::from_primitive(p, resolve)?,
+            None => None
+        };
 
         let file_filters = Vec::<String>::from_primitive(
-            dict.remove("FFilter").or(Some(Primitive::Null)).unwrap(),
+            resolve_primitive(dict.remove("FFilter"), resolve)?,
             resolve)?;
 
-        let file_decode_params = Vec::<Dictionary>::from_primitive(
-            dict.remove("FDecodeParms").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
+        let file_decode_params = decode_params(dict.remove("FDecodeParms"), resolve)?;
+
+        let decoded_length = match dict.remove("DL") {
+            Some(p) => Option::<usize>::from_primitive(p, resolve)?,
+            None => Option::<usize>::from_missing(resolve)?,
+        };
 
 
         let mut new_filters = Vec::new();
@@ -144,12 +364,13 @@ impl<T: Object> Object for Stream<T> {
             filters: new_filters,
             file: file,
             file_filters: new_file_filters,
+            decoded_length: decoded_length,
             // Special
             info: T::from_primitive(Primitive::Dictionary (dict.clone()), resolve)?,
 
 
             // Data
-            data: stream.data,
+            data: Arc::new(stream.data),
         })
     }
 }
@@ -162,7 +383,7 @@ impl<T> Deref for Stream<T> {
 
 
 
-#[derive(Object, Default)]
+#[derive(Object, Default, Clone)]
 #[pdf(Type = "ObjStm")]
 pub struct ObjStmInfo {
 
@@ -182,37 +403,77 @@ pub struct ObjStmInfo {
 
     #[pdf(key = "Extends")]
     /// A reference to an eventual ObjectStream which this ObjectStream extends.
-    pub extends: Option<i32>,
+    pub extends: Option<PlainRef>,
 
 }
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct ObjectStream {
     stream: Stream<ObjStmInfo>,
-    /// Byte offset of each object. Index is the object number.
+    /// Object number of each contained object.
+    obj_nrs:    Vec<ObjNr>,
+    /// Byte offset of each object (relative to `/First`). Index is the index of the object in
+    /// the stream.
     offsets:    Vec<usize>,
     /// The object number of this object.
     id:         ObjNr,
 }
 impl Object for ObjectStream {
+    /// Writes the object stream (FlateDecode encoded), with the `N` pairs of object number and
+    /// offset before `/First`.
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
-        unimplemented!();
+        let mut data = Vec::new();
+        for (nr, offset) in self.obj_nrs.iter().zip(self.offsets.iter()) {
+            write!(data, "{} {} ", nr, offset)?;
+        }
+        data.push(b'\n');
+        let first = data.len();
+        match self.stream.data.get(self.stream.info.first as usize ..) {
+            Some(objects) => data.extend_from_slice(objects),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "/First of object stream is past its data")),
+        }
+
+        let mut stream = Stream {
+            filters: Vec::new(),
+            file: None,
+            file_filters: Vec::new(),
+            decoded_length: None,
+            info: ObjStmInfo {
+                num_objects: self.offsets.len() as i32,
+                first: first as i32,
+                extends: None,
+            },
+            data: Arc::new(data),
+        };
+        stream.encode(StreamFilter::FlateDecode (LZWFlateParams::default()))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        stream.serialize(out)
     }
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<ObjectStream> {
         let mut stream = Stream::<ObjStmInfo>::from_primitive(p, resolve)?;
-        stream.decode();
+        match resolve.decode_limits() {
+            Some(limits) => stream.decode_limited(limits)?,
+            None => stream.decode()?,
+        }
+        check_min("N", stream.info.num_objects, 0)?;
+        check_min("First", stream.info.first, 0)?;
+        check_max("First", stream.info.first as usize, stream.get_data_raw().len())?;
 
+        let mut obj_nrs = Vec::new();
         let mut offsets = Vec::new();
         {
-            let mut lexer = Lexer::new(&stream.get_data());
+            let mut lexer = Lexer::new(stream.get_data_raw());
             for _ in 0..(stream.info.num_objects as ObjNr) {
-                let _obj_nr = lexer.next()?.to::<ObjNr>()?;
+                let obj_nr = lexer.next()?.to::<ObjNr>()?;
                 let offset = lexer.next()?.to::<usize>()?;
+                obj_nrs.push(obj_nr);
                 offsets.push(offset);
             }
         }
         Ok(ObjectStream {
             stream: stream,
+            obj_nrs: obj_nrs,
             offsets: offsets,
             id: 0, // TODO
         })
@@ -231,21 +492,87 @@ impl ObjectStream {
         }
         */
     }
+    /// An empty object stream with object number `id`, to be filled with `add_object()`.
+    pub fn empty(id: ObjNr) -> ObjectStream {
+        ObjectStream {
+            stream: Stream::default(),
+            obj_nrs: Vec::new(),
+            offsets: Vec::new(),
+            id: id,
+        }
+    }
     pub fn id(&self) -> ObjNr {
         self.id
     }
+    /// Appends `obj` as the object with number `obj_nr`, and returns its index in the stream.
+    /// Streams and objects with a generation number other than 0 can't be compressed in object
+    /// streams.
+    pub fn add_object<T: Object>(&mut self, obj_nr: ObjNr, obj: &T) -> Result<usize> {
+        let data = Arc::make_mut(&mut self.stream.data);
+        let offset = data.len() - self.stream.info.first as usize;
+        obj.serialize(data)?;
+        data.push(b'\n');
+        self.obj_nrs.push(obj_nr);
+        self.offsets.push(offset);
+        Ok(self.offsets.len() - 1)
+    }
     pub fn get_object_slice(&self, index: usize) -> Result<&[u8]> {
         if index >= self.offsets.len() {
             bail!(ErrorKind::ObjStmOutOfBounds {index: index, max: self.offsets.len()});
         }
-        let start = self.stream.info.first as usize + self.offsets[index];
-        let end = if index == self.offsets.len() - 1 {
-            self.stream.data.len()
-        } else {
-            self.stream.info.first as usize + self.offsets[index + 1]
+        // (the offsets are read from the stream: they may be too large, or not in order)
+        let first = self.stream.info.first as usize;
+        let start = first.checked_add(self.offsets[index]);
+        let end = match self.offsets.get(index + 1) {
+            Some(&offset) => first.checked_add(offset),
+            None => Some(self.stream.data.len()),
         };
-
-        Ok(&self.stream.data[start..end])
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end && end <= self.stream.data.len() =>
+                Ok(&self.stream.data[start .. end]),
+            _ => bail!(ErrorKind::ObjStmOutOfBounds {index: index, max: self.offsets.len()})
+        }
+    }
+    /// Parses the object at `index`.
+    pub fn parse_object(&self, index: usize, resolve: &Resolve) -> Result<Primitive> {
+        parse(self.get_object_slice(index)?, resolve)
+    }
+    /// Object number of the object at `index`.
+    pub fn get_object_nr(&self, index: usize) -> Option<ObjNr> {
+        self.obj_nrs.get(index).cloned()
+    }
+    /// Index of the object with number `obj_nr`, if it is in this stream.
+    pub fn index_of(&self, obj_nr: ObjNr) -> Option<usize> {
+        self.obj_nrs.iter().position(|&nr| nr == obj_nr)
+    }
+    /// Parses the object with number `obj_nr`. If it's not in this stream, the object streams
+    /// that this one extends (`/Extends`) are searched.
+    pub fn get_object(&self, obj_nr: ObjNr, resolve: &Resolve) -> Result<Primitive> {
+        if let Some(index) = self.index_of(obj_nr) {
+            return self.parse_object(index, resolve);
+        }
+        let mut visited = Vec::new();
+        let mut next = self.stream.info.extends;
+        while let Some(r) = next {
+            if visited.contains(&r) {
+                bail!("Cycle in /Extends chain of object stream {}", self.id);
+            }
+            visited.push(r);
+            let parent = ObjectStream::from_primitive(resolve.resolve(r)?, resolve)?;
+            if let Some(index) = parent.index_of(obj_nr) {
+                return parent.parse_object(index, resolve);
+            }
+            next = parent.stream.info.extends;
+        }
+        bail!(ErrorKind::ObjStmObjectNotFound {obj_nr: obj_nr, stream_id: self.id})
+    }
+    /// Parses the object with number `obj_nr`, which the xref table puts at `index`. If it isn't
+    /// there, it is looked up by number (see `get_object()`).
+    pub fn get_object_at(&self, obj_nr: ObjNr, index: usize, resolve: &Resolve) -> Result<Primitive> {
+        match self.get_object_nr(index) {
+            Some(nr) if nr == obj_nr => self.parse_object(index, resolve),
+            _ => self.get_object(obj_nr, resolve)
+        }
     }
     /// Returns the number of contained objects
     pub fn n_objects(&self) -> usize {
@@ -0,0 +1,362 @@
+//! The structure tree of Tagged PDF (PDF reference, sections 10.6 and 10.7): the logical
+//! structure of the document - headings, paragraphs, lists, tables and figures - and which
+//! content belongs to which structure element.
+
+use std::collections::{BTreeMap, HashSet};
+use object::*;
+use err::*;
+
+/// The structure type `/S` of an element - the standard types of the PDF reference, or another
+/// type (that `/RoleMap` doesn't map to a standard type).
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructType {
+    // grouping elements
+    Document, Part, Art, Sect, Div, BlockQuote, Caption, TOC, TOCI, Index, NonStruct, Private,
+    // block-level elements
+    P, H, H1, H2, H3, H4, H5, H6, L, LI, Lbl, LBody, Table, TR, TH, TD, THead, TBody, TFoot,
+    // inline-level elements
+    Span, Quote, Note, Reference, BibEntry, Code, Link, Annot, Ruby, RB, RT, RP, Warichu, WT, WP,
+    // illustrations
+    Figure, Formula, Form,
+    Other (String),
+}
+
+macro_rules! struct_types {
+    ($($name:ident),*) => (
+        impl StructType {
+            /// The type named `name` (`Other` if it is not a standard type).
+            pub fn from_name(name: &str) -> StructType {
+                match name {
+                    $(stringify!($name) => StructType::$name,)*
+                    _ => StructType::Other (name.into()),
+                }
+            }
+            pub fn name(&self) -> &str {
+                match *self {
+                    $(StructType::$name => stringify!($name),)*
+                    StructType::Other (ref name) => name,
+                }
+            }
+        }
+    )
+}
+struct_types!(Document, Part, Art, Sect, Div, BlockQuote, Caption, TOC, TOCI, Index, NonStruct, Private,
+    P, H, H1, H2, H3, H4, H5, H6, L, LI, Lbl, LBody, Table, TR, TH, TD, THead, TBody, TFoot,
+    Span, Quote, Note, Reference, BibEntry, Code, Link, Annot, Ruby, RB, RT, RP, Warichu, WT, WP,
+    Figure, Formula, Form);
+
+impl StructType {
+    pub fn is_standard(&self) -> bool {
+        match *self {
+            StructType::Other (_) => false,
+            _ => true
+        }
+    }
+    /// Whether the type is an inline-level element (PDF reference, section 10.7.3) - a part
+    /// of the text of a block-level element, like a link or a quote.
+    pub fn is_inline(&self) -> bool {
+        match *self {
+            StructType::Span | StructType::Quote | StructType::Note | StructType::Reference |
+            StructType::BibEntry | StructType::Code | StructType::Link | StructType::Annot |
+            StructType::Ruby | StructType::RB | StructType::RT | StructType::RP |
+            StructType::Warichu | StructType::WT | StructType::WP => true,
+            _ => false
+        }
+    }
+    /// The level of a heading: 1 to 6 for `H1` to `H6`, and 0 for `H`.
+    pub fn heading_level(&self) -> Option<u8> {
+        match *self {
+            StructType::H => Some(0),
+            StructType::H1 => Some(1),
+            StructType::H2 => Some(2),
+            StructType::H3 => Some(3),
+            StructType::H4 => Some(4),
+            StructType::H5 => Some(5),
+            StructType::H6 => Some(6),
+            _ => None
+        }
+    }
+}
+
+/// A structure element.
+#[derive(Debug, Clone)]
+pub struct StructElem {
+    /// The object of the element dictionary (elements that are direct objects have none)
+    pub id:             Option<PlainRef>,
+    /// `/S` as it is in the document
+    pub struct_type:    String,
+    /// `/S`, mapped to a standard type with the role map if it can be
+    pub role:           StructType,
+    /// `/ID`: the key in the ID tree
+    pub element_id:     Option<PdfString>,
+    /// `/Pg`: the page of the content (inherited by the children)
+    pub page:           Option<PlainRef>,
+    /// `/T`
+    pub title:          Option<String>,
+    /// `/Lang`: the language of the content, like `en-US`
+    pub lang:           Option<String>,
+    /// `/Alt`: a description, for figures and formulas
+    pub alt:            Option<String>,
+    /// `/ActualText`: the text that the content stands for, like for ligatures or text in
+    /// images
+    pub actual_text:    Option<String>,
+    /// `/E`: the expanded form of an abbreviation
+    pub expansion:      Option<String>,
+    /// `/A`: attribute objects, like the `/Layout` or `/Table` attributes
+    pub attributes:     Vec<Dictionary>,
+    /// `/C`: attribute classes (in `/ClassMap`)
+    pub classes:        Vec<String>,
+    /// `/K`, in order
+    pub children:       Vec<StructChild>,
+    /// The element dictionary
+    pub dict:           Dictionary,
+}
+
+/// An item of `/K` of a structure element.
+#[derive(Debug, Clone)]
+pub enum StructChild {
+    Element (StructElem),
+    /// A marked content sequence (`BDC` with `/MCID`) of the page `page` - or, with `stream`,
+    /// of that content stream (like a form XObject)
+    MarkedContent {
+        mcid:   i32,
+        page:   Option<PlainRef>,
+        stream: Option<PlainRef>,
+    },
+    /// A whole object, like an annotation or an XObject
+    Object {
+        object: PlainRef,
+        page:   Option<PlainRef>,
+    },
+}
+
+impl StructElem {
+    /// The element and its descendants, in document order.
+    pub fn all(&self) -> Vec<&StructElem> {
+        let mut all = vec![self];
+        for child in &self.children {
+            if let StructChild::Element (ref elem) = *child {
+                all.extend(elem.all());
+            }
+        }
+        all
+    }
+}
+
+/// `/StructTreeRoot` of the catalog.
+#[derive(Debug, Clone)]
+pub struct StructTreeRoot {
+    /// `/K`: the top-level elements (usually one `Document`)
+    pub children:       Vec<StructElem>,
+    /// `/RoleMap`: structure types to the types they stand for
+    pub role_map:       BTreeMap<String, String>,
+    /// `/ClassMap`: attribute classes to attribute objects
+    pub class_map:      Dictionary,
+    /// `/ParentTree`: for content, the structure elements it belongs to - see `mcid_parent`
+    /// and `object_parent`
+    pub parent_tree:    Option<NumberTree<Primitive>>,
+    /// `/IDTree`: the elements by `/ID`
+    pub id_tree:        Option<NameTree<Primitive>>,
+}
+
+impl StructTreeRoot {
+    pub fn from_dict(dict: &Dictionary, resolve: &Resolve) -> Result<StructTreeRoot> {
+        let mut role_map = BTreeMap::new();
+        if let Some(map) = dict.get_as::<Dictionary>("RoleMap", resolve)? {
+            for (key, value) in map.iter() {
+                if let Primitive::Name (ref role) = *value {
+                    role_map.insert(key.clone(), role.clone());
+                }
+            }
+        }
+        let mut root = StructTreeRoot {
+            children:       Vec::new(),
+            role_map:       role_map,
+            class_map:      dict.get_as("ClassMap", resolve)?.unwrap_or_default(),
+            parent_tree:    dict.get_as("ParentTree", resolve)?,
+            id_tree:        dict.get_as("IDTree", resolve)?,
+        };
+        let mut visited = HashSet::new();
+        let mut children = Vec::new();
+        if let Some(k) = dict.get("K") {
+            for child in root.read_kids(k, None, &mut visited, resolve)? {
+                match child {
+                    StructChild::Element (elem) => children.push(elem),
+                    _ => bail!(ErrorKind::InvalidEntry {key: "K", reason: "content in the structure tree root".into()}),
+                }
+            }
+        }
+        root.children = children;
+        Ok(root)
+    }
+
+    /// The standard type that the type `name` stands for, following the role map.
+    pub fn role(&self, name: &str) -> StructType {
+        let mut name = name;
+        // (the role map may have cycles)
+        for _ in 0 .. self.role_map.len() + 1 {
+            let t = StructType::from_name(name);
+            if t.is_standard() {
+                return t;
+            }
+            match self.role_map.get(name) {
+                Some(role) => name = role.as_str(),
+                None => break,
+            }
+        }
+        StructType::Other (name.into())
+    }
+
+    /// All elements, in document order.
+    pub fn all(&self) -> Vec<&StructElem> {
+        self.children.iter().flat_map(|elem| elem.all()).collect()
+    }
+    /// The element in the object `id`.
+    pub fn find(&self, id: PlainRef) -> Option<&StructElem> {
+        self.all().into_iter().find(|elem| elem.id == Some(id))
+    }
+
+    /// The element that the marked content `mcid` belongs to, in the content of the page (or
+    /// content stream) with `/StructParents key`.
+    pub fn mcid_parent(&self, key: i32, mcid: i32, resolve: &Resolve) -> Result<Option<PlainRef>> {
+        let parents = match self.parent_tree_entry(key, resolve)? {
+            Some(Primitive::Array (parents)) => parents,
+            Some(p) => bail!(ErrorKind::UnexpectedPrimitive {expected: "Array", found: p.get_debug_name()}),
+            None => return Ok(None),
+        };
+        match parents.get(mcid as usize) {
+            Some(&Primitive::Reference (r)) if mcid >= 0 => Ok(Some(r)),
+            _ => Ok(None)
+        }
+    }
+    /// The element that the object (annotation or XObject) with `/StructParent key` belongs to.
+    pub fn object_parent(&self, key: i32, resolve: &Resolve) -> Result<Option<PlainRef>> {
+        match self.parent_tree.as_ref() {
+            Some(tree) => match tree.get(key, resolve)? {
+                Some(Primitive::Reference (r)) => Ok(Some(r)),
+                _ => Ok(None)
+            },
+            None => Ok(None)
+        }
+    }
+    /// The entry `key` of the parent tree, resolved.
+    fn parent_tree_entry(&self, key: i32, resolve: &Resolve) -> Result<Option<Primitive>> {
+        let entry = match self.parent_tree.as_ref() {
+            Some(tree) => tree.get(key, resolve)?,
+            None => None
+        };
+        match entry {
+            Some(Primitive::Reference (r)) => Ok(Some(resolve.resolve(r)?)),
+            entry => Ok(entry)
+        }
+    }
+
+    /// The children in `/K` (a single child or an array) of an element with the page `page`.
+    fn read_kids(&self, k: &Primitive, page: Option<PlainRef>, visited: &mut HashSet<PlainRef>, resolve: &Resolve) -> Result<Vec<StructChild>> {
+        let kids = match *k {
+            Primitive::Array (ref kids) => kids.clone(),
+            Primitive::Reference (r) => match resolve.resolve(r)? {
+                Primitive::Array (kids) => kids,
+                _ => vec![k.clone()],
+            },
+            _ => vec![k.clone()],
+        };
+        let mut children = Vec::with_capacity(kids.len());
+        for kid in kids {
+            let child = self.read_kid(kid, page, visited, resolve);
+            match child {
+                Ok(Some(child)) => children.push(child),
+                Ok(None) => {}
+                Err(e) => {
+                    // (left out, if not strict)
+                    if resolve.options().strict {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(children)
+    }
+    fn read_kid(&self, kid: Primitive, page: Option<PlainRef>, visited: &mut HashSet<PlainRef>, resolve: &Resolve) -> Result<Option<StructChild>> {
+        let (id, dict) = match kid {
+            Primitive::Integer (mcid) => return Ok(Some(StructChild::MarkedContent {mcid: mcid, page: page, stream: None})),
+            Primitive::Null => return Ok(None),
+            Primitive::Reference (r) => {
+                if !visited.insert(r) {
+                    bail!(ErrorKind::CircularReference {obj_nr: r.id});
+                }
+                (Some(r), Dictionary::from_primitive(Primitive::Reference (r), resolve)?)
+            }
+            p => (None, Dictionary::from_primitive(p, resolve)?),
+        };
+        let own_page = match dict.get("Pg") {
+            Some(&Primitive::Reference (r)) => Some(r),
+            _ => page
+        };
+        match dict.get("Type") {
+            Some(&Primitive::Name (ref t)) if t == "MCR" => return Ok(Some(StructChild::MarkedContent {
+                mcid:   dict.expect("MCID", resolve)?,
+                page:   own_page,
+                stream: reference(&dict, "Stm"),
+            })),
+            Some(&Primitive::Name (ref t)) if t == "OBJR" => return Ok(Some(match reference(&dict, "Obj") {
+                Some(object) => StructChild::Object {object: object, page: own_page},
+                None => bail!(ErrorKind::EntryNotFound {key: "Obj"}),
+            })),
+            _ => {}
+        }
+
+        let struct_type: String = dict.expect("S", resolve)?;
+        let (title, lang, alt, actual_text, expansion) = {
+            let text = |key| -> Result<Option<String>> {
+                Ok(dict.get_as::<PdfString>(key, resolve)?.map(|s| s.to_string_lossy()))
+            };
+            (text("T")?, text("Lang")?, text("Alt")?, text("ActualText")?, text("E")?)
+        };
+        // `/A` and `/C`: one or an array, with revision numbers after the entries
+        let attributes = match dict.get_as::<Primitive>("A", resolve)? {
+            Some(Primitive::Array (items)) => items.into_iter()
+                .filter(|item| match *item {
+                    Primitive::Integer (_) => false,
+                    _ => true
+                })
+                .map(|item| Dictionary::from_primitive(item, resolve))
+                .collect::<Result<Vec<_>>>()?,
+            Some(item) => vec![Dictionary::from_primitive(item, resolve)?],
+            None => Vec::new()
+        };
+        let classes = match dict.get_as::<Primitive>("C", resolve)? {
+            Some(Primitive::Array (items)) => items.into_iter().filter_map(|item| item.to_name().ok()).collect(),
+            Some(item) => vec![item.to_name()?],
+            None => Vec::new()
+        };
+        let children = match dict.get("K") {
+            Some(k) => self.read_kids(k, own_page, visited, resolve)?,
+            None => Vec::new()
+        };
+        Ok(Some(StructChild::Element (StructElem {
+            id:             id,
+            role:           self.role(&struct_type),
+            struct_type:    struct_type,
+            element_id:     dict.get_as("ID", resolve)?,
+            page:           own_page,
+            title:          title,
+            lang:           lang,
+            alt:            alt,
+            actual_text:    actual_text,
+            expansion:      expansion,
+            attributes:     attributes,
+            classes:        classes,
+            children:       children,
+            dict:           dict,
+        })))
+    }
+}
+
+/// The entry `key` of `dict`, if it is a reference.
+fn reference(dict: &Dictionary, key: &str) -> Option<PlainRef> {
+    match dict.get(key) {
+        Some(&Primitive::Reference (r)) => Some(r),
+        _ => None
+    }
+}
@@ -1,6 +1,7 @@
 //! Models of PDF types
 
 use std::io;
+use std::collections::HashSet;
 use object::*;
 use err::*;
 
@@ -18,9 +19,22 @@ impl Object for PagesNode {
         }
     }
     fn from_primitive(p: Primitive, r: &Resolve) -> Result<PagesNode> {
+        // The kids of a node may (wrongly) contain the node itself
+        if let Primitive::Reference (id) = p {
+            let p = r.resolve(id)?;
+            return guard_cycle(id, || PagesNode::from_primitive(p, r));
+        }
         let dict = Dictionary::from_primitive(p, r)?;
+        let ty = match dict.get("Type") {
+            Some(ty) if r.options().type_check != TypeCheck::Skip => ty.clone().to_name()?,
+            // Only a page tree node has kids
+            _ if r.options().type_check != TypeCheck::Error => {
+                String::from(if dict.get("Kids").is_some() { "Pages" } else { "Page" })
+            }
+            _ => bail!(ErrorKind::EntryNotFound {key: "Type"}),
+        };
         Ok(
-        match dict["Type"].clone().to_name()?.as_str() {
+        match ty.as_str() {
             "Page" => PagesNode::Leaf (Page::from_primitive(Primitive::Dictionary(dict), r)?),
             "Pages" => PagesNode::Tree (PageTree::from_primitive(Primitive::Dictionary(dict), r)?),
             other => bail!(ErrorKind::WrongDictionaryType {expected: "Page or Pages".into(), found: other.into()}),
@@ -30,33 +44,61 @@ impl Object for PagesNode {
 }
 
 
+/// The document catalog (`/Root`, PDF reference section 3.6.1). The entries that are not
+/// needed for every document are `Lazy` - read with `File::get`.
 #[derive(Object, Default)]
 pub struct Catalog {
-// Version: Name,
+    /// Overrides the version in the header if it is later (PDF 1.4).
+    #[pdf(key="Version")]
+    pub version: Option<String>,
     #[pdf(key="Pages")]
     pub pages: PageTree,
-// PageLabels: number_tree,
+    #[pdf(key="PageLabels")]
+    pub page_labels: Option<Lazy<NumberTree<PageLabel>>>,
     #[pdf(key="Names")]
     pub names: Option<NameDictionary>,
-// Dests: Dict
-// ViewerPreferences: dict
-// PageLayout: name
-// PageMode: name
-// Outlines: dict
+    /// Named destinations (PDF 1.1) - later documents have them in the `Dests` name tree of
+    /// `/Names`.
+    #[pdf(key="Dests")]
+    pub dests: Option<Lazy<Dictionary>>,
+    /// Read with `File::viewer_preferences`
+    #[pdf(key="ViewerPreferences")]
+    pub viewer_preferences: Option<Lazy<ViewerPreferences>>,
+    /// See `page_layout()`
+    #[pdf(key="PageLayout")]
+    pub page_layout: Option<String>,
+    /// See `page_mode()`
+    #[pdf(key="PageMode")]
+    pub page_mode: Option<String>,
+    #[pdf(key="Outlines")]
+    pub outlines: Option<Lazy<Dictionary>>,
 // Threads: array
-// OpenAction: array or dict
-// AA: dict
+    /// A destination (array) or an action (dictionary), for when the document is opened - see
+    /// `File::open_action`
+    #[pdf(key="OpenAction")]
+    pub open_action: Option<Lazy<Primitive>>,
+    /// Additional actions, for `DOCUMENT_TRIGGERS` - see `File::actions`
+    #[pdf(key="AA")]
+    pub additional_actions: Option<Dictionary>,
 // URI: dict
-// AcroForm: dict
-// Metadata: stream
+    #[pdf(key="AcroForm")]
+    pub acro_form: Option<Lazy<Dictionary>>,
+    /// XMP metadata of the document (PDF 1.4)
+    #[pdf(key="Metadata")]
+    pub metadata: Option<Lazy<Stream<Dictionary>>>,
+    /// The structure tree of Tagged PDF (PDF 1.3) - read with `File::struct_tree`
     #[pdf(key="StructTreeRoot")]
-    pub struct_tree_root: Option<StructTreeRoot>,
-// MarkInfo: dict
-// Lang: text string
+    pub struct_tree_root: Option<Lazy<Dictionary>>,
+    #[pdf(key="MarkInfo")]
+    pub mark_info: Option<MarkInformation>,
+    #[pdf(key="Lang")]
+    pub lang: Option<PdfString>,
 // SpiderInfo: dict
 // OutputIntents: array
 // PieceInfo: dict
-// OCProperties: dict
+    /// Optional content (PDF 1.5)
+    #[pdf(key="OCProperties")]
+    pub oc_properties: Option<Lazy<Dictionary>>,
 // Perms: dict
 // Legal: dict
 // Requirements: array
@@ -75,18 +117,124 @@ pub struct PageTree {
     #[pdf(key="Count")]
     pub count:  i32,
 
+    // Attributes inherited by the pages below - see `inherit`
 
-    /// Exists to be inherited to a 'Page' object. Note: *Inheritable*.
-    // Note about inheritance... if we wanted to 'inherit' things at the time of reading, we would
-    // want Option<Ref<Resources>> here most likely.
     #[pdf(key="Resources")]
-    pub resources: Option<Resources>,
+    pub resources: Option<MaybeRef<Resources>>,
+
+    #[pdf(key="MediaBox")]
+    pub media_box:  Option<Rect>,
+
+    #[pdf(key="CropBox")]
+    pub crop_box:   Option<Rect>,
+
+    #[pdf(key="Rotate")]
+    pub rotate:     Option<i32>,
 }
 
+/// Keys of a page that it inherits from the page tree nodes above it if it doesn't have them
+/// (PDF reference, section 3.6.2).
+pub const INHERITABLE_PAGE_ATTRIBUTES: &'static [&'static str] = &["Resources", "MediaBox", "CropBox", "Rotate"];
 
 impl PageTree {
-    fn inherit_resources(&mut self) {
+    /// Gives the inheritable attributes of this node to the kids that don't have them, and so
+    /// on down the tree - so every page has the values of its nearest ancestor. (`File` does this
+    /// for the page tree of the document.)
+    pub fn inherit(&mut self) {
+        for kid in &mut self.kids {
+            match *kid {
+                PagesNode::Tree (ref mut tree) => {
+                    inherit(&mut tree.resources, &self.resources);
+                    inherit(&mut tree.media_box, &self.media_box);
+                    inherit(&mut tree.crop_box, &self.crop_box);
+                    inherit(&mut tree.rotate, &self.rotate);
+                    tree.inherit();
+                }
+                PagesNode::Leaf (ref mut page) => {
+                    inherit(&mut page.resources, &self.resources);
+                    inherit(&mut page.media_box, &self.media_box);
+                    inherit(&mut page.crop_box, &self.crop_box);
+                    inherit(&mut page.rotate, &self.rotate);
+                }
+            }
+        }
+    }
+}
+impl PageTree {
+    /// The pages below this node, in order.
+    pub fn pages(&self) -> Pages {
+        Pages {
+            stack:  vec![self.kids.iter()],
+        }
+    }
+    /// Checks that `/Count` of this node and the nodes below is the number of pages they have
+    /// (which `File::get_page` relies on). Returns the number of pages.
+    pub fn check_count(&self) -> Result<i32> {
+        let mut found = 0;
+        for kid in &self.kids {
+            found += match *kid {
+                PagesNode::Tree (ref tree) => tree.check_count()?,
+                PagesNode::Leaf (_) => 1,
+            };
+        }
+        if found != self.count {
+            bail!(ErrorKind::WrongPageCount {count: self.count, found: found});
+        }
+        Ok(found)
+    }
+}
+
+/// Iterator over the pages of a page tree (see `PageTree::pages`), depth first.
+pub struct Pages<'a> {
+    /// The kids not visited yet of each node on the way from the root
+    stack:  Vec<::std::slice::Iter<'a, PagesNode>>,
+}
+impl<'a> Iterator for Pages<'a> {
+    type Item = &'a Page;
+    fn next(&mut self) -> Option<&'a Page> {
+        loop {
+            let next = match self.stack.last_mut() {
+                Some(kids) => kids.next(),
+                None => return None,
+            };
+            match next {
+                Some(&PagesNode::Leaf (ref page)) => return Some(page),
+                Some(&PagesNode::Tree (ref tree)) => self.stack.push(tree.kids.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+fn inherit<T: Clone>(attribute: &mut Option<T>, from: &Option<T>) {
+    if attribute.is_none() {
+        *attribute = from.clone();
+    }
+}
+
+/// Adds the inheritable attributes that the page dictionary `dict` doesn't have, from the nodes
+/// found by following `/Parent` - for reading a page without the page tree above it.
+pub fn inherit_page_attributes(dict: &mut Dictionary, resolve: &Resolve) -> Result<()> {
+    let mut visited = Vec::new();
+    let mut parent = dict.get("Parent").cloned();
+    while let Some(Primitive::Reference (r)) = parent {
+        if visited.contains(&r) {
+            bail!(ErrorKind::CircularReference {obj_nr: r.id});
+        }
+        visited.push(r);
+        let node = Dictionary::from_primitive(resolve.resolve(r)?, resolve)?;
+        for &key in INHERITABLE_PAGE_ATTRIBUTES {
+            if dict.get(key).is_none() {
+                if let Some(val) = node.get(key) {
+                    dict.insert(key, val.clone());
+                }
+            }
+        }
+        parent = node.get("Parent").cloned();
     }
+    Ok(())
 }
 
 #[derive(Object, Debug)]
@@ -95,7 +243,7 @@ pub struct Page {
     pub parent: Ref<PageTree>,
 
     #[pdf(key="Resources")]
-    pub resources: Option<Resources>,
+    pub resources: Option<MaybeRef<Resources>>,
     
     #[pdf(key="MediaBox")]
     pub media_box:  Option<Rect>,
@@ -103,11 +251,34 @@ pub struct Page {
     #[pdf(key="CropBox")]
     pub crop_box:   Option<Rect>,
     
+    #[pdf(key="BleedBox")]
+    pub bleed_box:  Option<Rect>,
+
     #[pdf(key="TrimBox")]
     pub trim_box:   Option<Rect>,
-    
-    //#[pdf(key="Contents")]
-    //pub contents:   Option<PlainRef>
+
+    #[pdf(key="ArtBox")]
+    pub art_box:    Option<Rect>,
+
+    /// Clockwise rotation in degrees (a multiple of 90)
+    #[pdf(key="Rotate")]
+    pub rotate:     Option<i32>,
+
+    #[pdf(key="Annots")]
+    pub annots:     Option<Lazy<Vec<Primitive>>>,
+
+    /// The key of the page in the parent tree of the structure tree (see
+    /// `StructTreeRoot::mcid_parent`)
+    #[pdf(key="StructParents")]
+    pub struct_parents: Option<i32>,
+
+    /// A content stream, or an array of them - read with `File::page_content`
+    #[pdf(key="Contents")]
+    pub contents:   Option<Lazy<Primitive>>,
+
+    /// Additional actions, for `PAGE_TRIGGERS` - see `File::actions`
+    #[pdf(key="AA")]
+    pub additional_actions: Option<Dictionary>,
 }
 
 impl Page {
@@ -116,13 +287,116 @@ impl Page {
             parent:     parent,
             media_box:  None,
             crop_box:   None,
+            bleed_box:  None,
             trim_box:   None,
+            art_box:    None,
+            rotate:     None,
             resources:  None,
+            annots:     None,
+            struct_parents: None,
+            contents:   None,
+            additional_actions: None,
         }
     }
+    /// `/MediaBox` - required, but it may be inherited.
+    pub fn media_box(&self) -> Result<Rect> {
+        match self.media_box {
+            Some(rect) => Ok(rect),
+            None => bail!(ErrorKind::EntryNotFound {key: "MediaBox"}),
+        }
+    }
+    /// `/CropBox` - the visible region of the page - by default the media box. (As for the
+    /// other boxes, the part outside the media box is left out; so is a box that doesn't
+    /// meet the media box at all.)
+    pub fn crop_box(&self) -> Result<Rect> {
+        self.clip_box(self.crop_box, self.media_box()?)
+    }
+    /// `/BleedBox` - the region to clip to in production - by default the crop box.
+    pub fn bleed_box(&self) -> Result<Rect> {
+        self.clip_box(self.bleed_box, self.crop_box()?)
+    }
+    /// `/TrimBox` - the page after trimming - by default the crop box.
+    pub fn trim_box(&self) -> Result<Rect> {
+        self.clip_box(self.trim_box, self.crop_box()?)
+    }
+    /// `/ArtBox` - the meaningful content of the page - by default the crop box.
+    pub fn art_box(&self) -> Result<Rect> {
+        self.clip_box(self.art_box, self.crop_box()?)
+    }
+    /// `rect` in the media box, or else `default`.
+    fn clip_box(&self, rect: Option<Rect>, default: Rect) -> Result<Rect> {
+        match rect {
+            Some(rect) => Ok(rect.intersection(&self.media_box()?).unwrap_or(default)),
+            None => Ok(default),
+        }
+    }
+    /// `/Rotate`, by default 0 - between 0 and 270 (so -90 is 270).
+    pub fn rotate(&self) -> i32 {
+        (self.rotate.unwrap_or(0) % 360 + 360) % 360
+    }
+    /// The width and height of the page as it is displayed: of the crop box, rotated.
+    pub fn visible_size(&self) -> Result<(f32, f32)> {
+        let crop_box = self.crop_box()?;
+        match self.rotate() {
+            90 | 270 => Ok((crop_box.height(), crop_box.width())),
+            _ => Ok((crop_box.width(), crop_box.height())),
+        }
+    }
+    /// The transformation from user space to the space of the page as it is displayed: with
+    /// the lower left corner of the (rotated) crop box at the origin, and the size
+    /// `visible_size`. Its inverse goes the other way - see `Matrix::inverse`.
+    pub fn display_matrix(&self) -> Result<Matrix> {
+        let crop_box = self.crop_box()?;
+        let (width, height) = (crop_box.width(), crop_box.height());
+        let rotation = match self.rotate() {
+            90 => Matrix {a: 0., b: -1., c: 1., d: 0., e: 0., f: width},
+            180 => Matrix {a: -1., b: 0., c: 0., d: -1., e: width, f: height},
+            270 => Matrix {a: 0., b: 1., c: -1., d: 0., e: height, f: 0.},
+            _ => Matrix::identity(),
+        };
+        Ok(Matrix::translate(-crop_box.llx, -crop_box.lly).then(&rotation))
+    }
+    /// The point `(x, y)` of user space, in the space of the displayed page (see
+    /// `display_matrix`).
+    pub fn user_to_display(&self, x: f32, y: f32) -> Result<(f32, f32)> {
+        Ok(self.display_matrix()?.transform_point(x, y))
+    }
+    /// The point `(x, y)` of the displayed page, in user space.
+    pub fn display_to_user(&self, x: f32, y: f32) -> Result<(f32, f32)> {
+        match self.display_matrix()?.inverse() {
+            Some(inverse) => Ok(inverse.transform_point(x, y)),
+            None => bail!("The crop box is empty"),
+        }
+    }
+    pub fn resources(&self) -> Option<&Resources> {
+        self.resources.as_ref().map(|r| r.get())
+    }
+    /// The annotations of the page. Annotations that can't be read are an error - or, if not
+    /// strict, skipped.
+    pub fn annotations(&self, resolve: &Resolve) -> Result<Vec<Annotation>> {
+        let annots = match self.annots {
+            Some(ref annots) => annots.get(resolve)?,
+            None => return Ok(Vec::new()),
+        };
+        let mut annotations = Vec::with_capacity(annots.len());
+        for annot in annots {
+            match Annotation::from_primitive(annot, resolve) {
+                Ok(annotation) => annotations.push(annotation),
+                Err(e) => {
+                    // (skipped, if not strict)
+                    if resolve.options().strict {
+                        return Err(e).chain_err(|| "Reading an annotation");
+                    }
+                }
+            }
+        }
+        Ok(annotations)
+    }
 }
 
-#[derive(Object)]
+/// A page label dictionary: how the pages from one key of `/PageLabels` up to the next one are
+/// numbered.
+#[derive(Object, Debug, Clone)]
 pub struct PageLabel {
     #[pdf(key="S")]
     style:  Option<Counter>,
@@ -133,8 +407,32 @@ pub struct PageLabel {
     #[pdf(key="St")]
     start:  Option<usize>
 }
+impl PageLabel {
+    /// The numbering style - `None` for labels that are just the prefix.
+    pub fn style(&self) -> Option<Counter> {
+        self.style
+    }
+    pub fn prefix(&self) -> Option<&PdfString> {
+        self.prefix.as_ref()
+    }
+    /// The number of the first page of the range (1 if not given).
+    pub fn start(&self) -> usize {
+        self.start.unwrap_or(1)
+    }
+    /// The label of the page `offset` pages after the first page of the range.
+    pub fn label(&self, offset: usize) -> String {
+        let mut label = match self.prefix {
+            Some(ref prefix) => prefix.to_string_lossy(),
+            None => String::new()
+        };
+        if let Some(style) = self.style {
+            label.push_str(&style.format(self.start() + offset));
+        }
+        label
+    }
+}
 
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Clone)]
 #[pdf(Type=false)]
 pub struct Resources {
     #[pdf(key="ExtGState")]
@@ -143,51 +441,93 @@ pub struct Resources {
     // pattern: Option<Pattern>,
     // shading: Option<Shading>,
     #[pdf(key="XObject")]
-    xobject: Option<BTreeMap<String, XObject>>
+    xobject: Option<BTreeMap<String, XObject>>,
+    /// The fonts (font dictionaries), by name
+    #[pdf(key="Font")]
+    pub font: Option<Dictionary>,
     // /XObject is a dictionary that map arbitrary names to XObjects
+
+    /// Property lists of marked content (`BDC /Tag /Name`), like optional content groups
+    #[pdf(key="Properties")]
+    pub properties: Option<Dictionary>,
+}
+impl Resources {
+    /// The XObject `name` (as in the `Do` operator).
+    pub fn xobject(&self, name: &str) -> Option<&XObject> {
+        self.xobject.as_ref().and_then(|xobjects| xobjects.get(name))
+    }
+    pub fn xobjects(&self) -> Option<&BTreeMap<String, XObject>> {
+        self.xobject.as_ref()
+    }
 }
 
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Clone)]
 #[pdf(Type = "ExtGState")]
 /// `ExtGState`
 pub struct GraphicsStateParameters {
     //TODO
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum XObject {
     Postscript (PostScriptXObject),
     Image (ImageXObject),
     Form (FormXObject),
 }
 
+impl XObject {
+    /// `/OC`: the optional content that the XObject belongs to (not for PostScript XObjects).
+    pub fn oc(&self) -> Option<&Primitive> {
+        match *self {
+            XObject::Image (ref image) => image.info.oc.as_ref(),
+            XObject::Form (ref form) => form.info.oc.as_ref(),
+            XObject::Postscript (_) => None,
+        }
+    }
+}
+
 impl Object for XObject {
     // Subtype==Image => ImageDictionary
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        unimplemented!();
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            XObject::Postscript (ref stream) => stream.serialize(out),
+            XObject::Image (ref stream) => stream.serialize(out),
+            XObject::Form (ref stream) => stream.serialize(out),
+        }
     }
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
         let mut stream = PdfStream::from_primitive(p, resolve)?;
 
-        let ty = stream.info.get("Type")
-            .ok_or(Error::from(ErrorKind::EntryNotFound { key: "Type" }))?.clone()
-            .to_name()?;
-        if ty != "XObject" {
-            bail!("XObject: /Type != XObject");
-        }
+        check_type(&stream.info, "Type", "XObject", resolve.options())?;
 
-        let subty = stream.info.get("Subtype")
-            .ok_or(Error::from(ErrorKind::EntryNotFound { key: "Subtype"}))?.clone()
-            .to_name()?;
+        let subty = stream.info.get_name("Subtype")?.to_string();
         Ok(match subty.as_str() {
             "PS" => XObject::Postscript (PostScriptXObject::from_primitive(Primitive::Stream(stream), resolve)?),
-            "Image" => XObject::Image (ImageXObject::from_primitive(Primitive::Stream(stream), resolve)?),
+            "Image" => {
+                check_image_dict(&stream.info, resolve)?;
+                XObject::Image (ImageXObject::from_primitive(Primitive::Stream(stream), resolve)?)
+            }
             "Form" => XObject::Form (FormXObject::from_primitive(Primitive::Stream(stream), resolve)?),
             s => bail!("XObject: invalid /Subtype {}", s),
         })
     }
 }
 
+/// The values of an image dictionary that `ImageDict` doesn't check: the size is at least 1x1,
+/// and `/BitsPerComponent` one of 1, 2, 4, 8 and 16.
+fn check_image_dict(dict: &Dictionary, resolve: &Resolve) -> Result<()> {
+    if let Some(width) = dict.get_as::<i32>("Width", resolve)? {
+        check_min("Width", width, 1)?;
+    }
+    if let Some(height) = dict.get_as::<i32>("Height", resolve)? {
+        check_min("Height", height, 1)?;
+    }
+    if let Some(bits) = dict.get_as::<i32>("BitsPerComponent", resolve)? {
+        check_one_of("BitsPerComponent", &bits, &[1, 2, 4, 8, 16])?;
+    }
+    Ok(())
+}
+
 /// A variant of XObject
 pub type PostScriptXObject = Stream<PostScriptDict>;
 /// A variant of XObject
@@ -195,7 +535,7 @@ pub type ImageXObject = Stream<ImageDict>;
 /// A variant of XObject
 pub type FormXObject = Stream<FormDict>;
 
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Clone)]
 #[pdf(Type="XObject", Subtype="PS")]
 pub struct PostScriptDict {
     // TODO
@@ -204,7 +544,7 @@ pub struct PostScriptDict {
 
 
 
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Clone)]
 #[pdf(Type="XObject", Subtype="Image")]
 /// A variant of XObject
 pub struct ImageDict {
@@ -215,7 +555,6 @@ pub struct ImageDict {
     // ColorSpace: name or array
     #[pdf(key="BitsPerComponent")]
     bits_per_component: i32,
-    // Note: only allowed values are 1, 2, 4, 8, 16. Enum?
     
     #[pdf(key="Intent")]
     intent: Option<RenderingIntent>,
@@ -250,27 +589,44 @@ pub struct ImageDict {
 
     // OPI: dict
     // Metadata: stream
-    // OC: dict
+
+    /// The optional content group or membership dictionary that the image belongs to
+    #[pdf(key="OC")]
+    pub oc: Option<Primitive>,
     
 }
 
 
-#[derive(Object, Debug)]
-pub enum RenderingIntent {
-    AbsoluteColorimetric,
-    RelativeColorimetric,
-    Saturation,
-    Perceptual,
+pdf_name_enum! {
+    /// `/Intent` of an image. Viewers use `RelativeColorimetric` for the intents they don't know.
+    pub enum RenderingIntent {
+        AbsoluteColorimetric,
+        RelativeColorimetric,
+        Saturation,
+        Perceptual,
+    }
 }
 
 
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Clone)]
 #[pdf(Type="XObject", Subtype="Form")]
 pub struct FormDict {
+    #[pdf(key="BBox")]
+    pub bbox:   Option<Rect>,
+
+    /// Maps form space to user space
+    #[pdf(key="Matrix")]
+    pub matrix: Option<Matrix>,
+
+    /// The optional content group or membership dictionary that the form belongs to
+    #[pdf(key="OC")]
+    pub oc:     Option<Primitive>,
     // TODO
 }
 
 
+/// The numbering style of page labels
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Counter {
     Arabic,
     RomanUpper,
@@ -278,6 +634,40 @@ pub enum Counter {
     AlphaUpper,
     AlphaLower
 }
+impl Counter {
+    /// `n` in this style: `4` is `4`, `IV` or `iv`, `D` or `d`. After `Z` come `AA` to `ZZ`,
+    /// then `AAA` and so on.
+    pub fn format(&self, n: usize) -> String {
+        match *self {
+            Counter::Arabic => n.to_string(),
+            Counter::RomanUpper => roman(n),
+            Counter::RomanLower => roman(n).to_lowercase(),
+            Counter::AlphaUpper => alpha(n),
+            Counter::AlphaLower => alpha(n).to_lowercase(),
+        }
+    }
+}
+fn roman(mut n: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut s = String::new();
+    for &(value, numeral) in NUMERALS {
+        while n >= value {
+            s.push_str(numeral);
+            n -= value;
+        }
+    }
+    s
+}
+fn alpha(n: usize) -> String {
+    if n == 0 {
+        return String::new();
+    }
+    let letter = (b'A' + ((n - 1) % 26) as u8) as char;
+    ::std::iter::repeat(letter).take((n - 1) / 26 + 1).collect()
+}
 impl Object for Counter {
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
         let style_code = match *self {
@@ -287,11 +677,18 @@ impl Object for Counter {
             Counter::AlphaLower => "a",
             Counter::AlphaUpper => "A"
         };
-        out.write_all(style_code.as_bytes())?;
-        Ok(())
+        write_name(out, style_code)
     }
-    fn from_primitive(_: Primitive, _: &Resolve) -> Result<Self> {
-        unimplemented!();
+    fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
+        let name = p.to_name()?;
+        Ok(match name.as_str() {
+            "D" => Counter::Arabic,
+            "r" => Counter::RomanLower,
+            "R" => Counter::RomanUpper,
+            "a" => Counter::AlphaLower,
+            "A" => Counter::AlphaUpper,
+            _ => bail!(ErrorKind::InvalidEntry {key: "S", reason: format!("unknown numbering style {}", name)}),
+        })
     }
 }
 
@@ -306,17 +703,120 @@ pub enum NameTreeNode<T> {
 }
 /// Note: The PDF concept of 'root' node is an intermediate or leaf node which has no 'Limits'
 /// entry. Hence, `limits`
+///
+/// Only the node itself is read by `from_primitive`; its kids are read when they are searched.
 pub struct NameTree<T> {
     limits: Option<(PdfString, PdfString)>,
     node: NameTreeNode<T>,
 }
 
+impl<T: Object> NameTree<T> {
+    /// The smallest and the largest key in the tree of this node (not in the root node).
+    pub fn limits(&self) -> Option<(&PdfString, &PdfString)> {
+        self.limits.as_ref().map(|&(ref min, ref max)| (min, max))
+    }
+    pub fn node(&self) -> &NameTreeNode<T> {
+        &self.node
+    }
+    /// Could `key` be in the tree of this node?
+    fn may_contain(&self, key: &[u8]) -> bool {
+        match self.limits {
+            Some((ref min, ref max)) => min.as_bytes() <= key && key <= max.as_bytes(),
+            None => true
+        }
+    }
+    /// Runs `f` with the node `kid` (guarded against kids that contain their parent).
+    fn with_kid<R, F>(kid: &Ref<NameTree<T>>, resolve: &Resolve, f: F) -> Result<R>
+        where F: FnOnce(NameTree<T>) -> Result<R>
+    {
+        let r = kid.get_inner();
+        let p = resolve.resolve(r)?;
+        guard_cycle(r, || f(NameTree::from_primitive(p, resolve)?))
+    }
+
+    /// The value of `key`, reading only the nodes whose limits contain it.
+    pub fn get(&self, key: &[u8], resolve: &Resolve) -> Result<Option<T>> where T: Clone {
+        match self.node {
+            NameTreeNode::Leaf (ref names) => {
+                Ok(names.iter().find(|&&(ref name, _)| name.as_bytes() == key).map(|&(_, ref value)| value.clone()))
+            }
+            NameTreeNode::Intermediate (ref kids) => {
+                for kid in kids {
+                    let value = NameTree::with_kid(kid, resolve, |kid| {
+                        if kid.may_contain(key) {
+                            kid.get(key, resolve)
+                        } else {
+                            Ok(None)
+                        }
+                    })?;
+                    if value.is_some() {
+                        return Ok(value);
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+    /// Calls `f` with all entries of the tree, in the order of the tree (the keys are sorted).
+    pub fn walk(&self, resolve: &Resolve, f: &mut FnMut(&PdfString, &T) -> Result<()>) -> Result<()> {
+        match self.node {
+            NameTreeNode::Leaf (ref names) => {
+                for &(ref name, ref value) in names {
+                    f(name, value)?;
+                }
+            }
+            NameTreeNode::Intermediate (ref kids) => {
+                for kid in kids {
+                    NameTree::with_kid(kid, resolve, |kid| kid.walk(resolve, f))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// All entries of the tree, in order.
+    pub fn entries(&self, resolve: &Resolve) -> Result<Vec<(PdfString, T)>> where T: Clone {
+        let mut entries = Vec::new();
+        self.walk(resolve, &mut |name, value| {
+            entries.push((name.clone(), value.clone()));
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+}
+
 impl<T: Object> Object for NameTree<T> {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        unimplemented!();
+    /// Writes this node only (`/Kids` are references).
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "<<")?;
+        if let Some((ref min, ref max)) = self.limits {
+            write!(out, " /Limits [")?;
+            min.serialize(out)?;
+            write!(out, " ")?;
+            max.serialize(out)?;
+            write!(out, "]")?;
+        }
+        match self.node {
+            NameTreeNode::Intermediate (ref kids) => {
+                write!(out, " /Kids [")?;
+                for kid in kids {
+                    write!(out, " ")?;
+                    kid.serialize(out)?;
+                }
+            }
+            NameTreeNode::Leaf (ref names) => {
+                write!(out, " /Names [")?;
+                for &(ref name, ref value) in names {
+                    write!(out, " ")?;
+                    name.serialize(out)?;
+                    write!(out, " ")?;
+                    value.serialize(out)?;
+                }
+            }
+        }
+        write!(out, " ] >>")
     }
     fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
-        let mut dict = p.to_dictionary(resolve).chain_err(|| "NameTree<T>")?;
+        let mut dict = Dictionary::from_primitive(p, resolve).chain_err(|| "NameTree<T>")?;
         // Quite long function...
         let limits = match dict.remove("Limits") {
             Some(limits) => {
@@ -351,9 +851,15 @@ impl<T: Object> Object for NameTree<T> {
                 match names {
                     Some(names) => {
                         let names = names.to_array(resolve)?;
+                        if names.len() % 2 != 0 {
+                            bail!("Error reading NameTree: 'Names' has a key without value");
+                        }
                         let mut new_names = Vec::new();
                         for pair in names.chunks(2) {
-                            let name = pair[0].clone().to_string()?;
+                            let name = match pair[0].clone() {
+                                Primitive::Reference (r) => resolve.resolve(r)?.to_string()?,
+                                p => p.to_string()?
+                            };
                             let value = T::from_primitive(pair[1].clone(), resolve)?;
                             new_names.push((name, value));
                         }
@@ -371,16 +877,175 @@ impl<T: Object> Object for NameTree<T> {
 
 
 
+pub enum NumberTreeNode<T> {
+    Intermediate (Vec<Ref<NumberTree<T>>>),
+    Leaf (Vec<(i32, T)>),
+}
+/// A number tree (PDF reference, section 3.8.6), as `NameTree`, with integer keys.
+pub struct NumberTree<T> {
+    limits: Option<(i32, i32)>,
+    node: NumberTreeNode<T>,
+}
+
+impl<T: Object> NumberTree<T> {
+    /// The smallest and the largest key in the tree of this node (not in the root node).
+    pub fn limits(&self) -> Option<(i32, i32)> {
+        self.limits
+    }
+    pub fn node(&self) -> &NumberTreeNode<T> {
+        &self.node
+    }
+    /// Runs `f` with the node `kid` (guarded against kids that contain their parent).
+    fn with_kid<R, F>(kid: &Ref<NumberTree<T>>, resolve: &Resolve, f: F) -> Result<R>
+        where F: FnOnce(NumberTree<T>) -> Result<R>
+    {
+        let r = kid.get_inner();
+        let p = resolve.resolve(r)?;
+        guard_cycle(r, || f(NumberTree::from_primitive(p, resolve)?))
+    }
+
+    /// The value of `key`.
+    pub fn get(&self, key: i32, resolve: &Resolve) -> Result<Option<T>> where T: Clone {
+        Ok(match self.get_range(key, resolve)? {
+            Some((k, value)) if k == key => Some(value),
+            _ => None
+        })
+    }
+    /// The entry with the largest key up to `key` - for trees whose entries apply to ranges
+    /// of numbers, such as page labels.
+    pub fn get_range(&self, key: i32, resolve: &Resolve) -> Result<Option<(i32, T)>> where T: Clone {
+        match self.node {
+            NumberTreeNode::Leaf (ref nums) => {
+                Ok(nums.iter().take_while(|&&(k, _)| k <= key).last().cloned())
+            }
+            NumberTreeNode::Intermediate (ref kids) => {
+                // the entry is in the last kid that starts at or before `key`
+                let mut found = None;
+                for kid in kids {
+                    let (past, entry) = NumberTree::with_kid(kid, resolve, |kid| {
+                        match kid.limits {
+                            Some((min, _)) if min > key => Ok((true, None)),
+                            _ => Ok((false, kid.get_range(key, resolve)?))
+                        }
+                    })?;
+                    if past {
+                        break;
+                    }
+                    if entry.is_some() {
+                        found = entry;
+                    }
+                }
+                Ok(found)
+            }
+        }
+    }
+    /// Calls `f` with all entries of the tree, in the order of the tree (the keys are sorted).
+    pub fn walk(&self, resolve: &Resolve, f: &mut FnMut(i32, &T) -> Result<()>) -> Result<()> {
+        match self.node {
+            NumberTreeNode::Leaf (ref nums) => {
+                for &(key, ref value) in nums {
+                    f(key, value)?;
+                }
+            }
+            NumberTreeNode::Intermediate (ref kids) => {
+                for kid in kids {
+                    NumberTree::with_kid(kid, resolve, |kid| kid.walk(resolve, f))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// All entries of the tree, in order.
+    pub fn entries(&self, resolve: &Resolve) -> Result<Vec<(i32, T)>> where T: Clone {
+        let mut entries = Vec::new();
+        self.walk(resolve, &mut |key, value| {
+            entries.push((key, value.clone()));
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+}
+
+impl<T: Object> Object for NumberTree<T> {
+    /// Writes this node only (`/Kids` are references).
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "<<")?;
+        if let Some((min, max)) = self.limits {
+            write!(out, " /Limits [{} {}]", min, max)?;
+        }
+        match self.node {
+            NumberTreeNode::Intermediate (ref kids) => {
+                write!(out, " /Kids [")?;
+                for kid in kids {
+                    write!(out, " ")?;
+                    kid.serialize(out)?;
+                }
+            }
+            NumberTreeNode::Leaf (ref nums) => {
+                write!(out, " /Nums [")?;
+                for &(key, ref value) in nums {
+                    write!(out, " {} ", key)?;
+                    value.serialize(out)?;
+                }
+            }
+        }
+        write!(out, " ] >>")
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        let mut dict = Dictionary::from_primitive(p, resolve).chain_err(|| "NumberTree<T>")?;
+        let limits = match dict.remove("Limits") {
+            Some(limits) => {
+                let limits = limits.to_array(resolve)?;
+                if limits.len() != 2 {
+                    bail!("Error reading NumberTree: 'Limits' is not of length 2");
+                }
+                Some((limits[0].as_integer()?, limits[1].as_integer()?))
+            }
+            None => None
+        };
+
+        let node = match (dict.remove("Kids"), dict.remove("Nums")) {
+            (Some(kids), _) => {
+                let kids = kids.to_array(resolve)?.into_iter()
+                    .map(|kid| Ref::<NumberTree<T>>::from_primitive(kid, resolve))
+                    .collect::<Result<Vec<_>>>()?;
+                NumberTreeNode::Intermediate (kids)
+            }
+            (None, Some(nums)) => {
+                let nums = nums.to_array(resolve)?;
+                if nums.len() % 2 != 0 {
+                    bail!("Error reading NumberTree: 'Nums' has a key without value");
+                }
+                let mut entries = Vec::new();
+                for pair in nums.chunks(2) {
+                    let key = i32::from_primitive(pair[0].clone(), resolve)?;
+                    let value = T::from_primitive(pair[1].clone(), resolve)?;
+                    entries.push((key, value));
+                }
+                NumberTreeNode::Leaf (entries)
+            }
+            (None, None) => bail!("Neither Kids nor Nums present in NumberTree node.")
+        };
+        Ok(NumberTree {
+            limits: limits,
+            node: node,
+        })
+    }
+}
+
+
 /// There is one `NameDictionary` associated with each PDF file.
 #[derive(Object)]
 pub struct NameDictionary {
-    /*
+    /// Named destinations (PDF 1.2), read with `File::resolve_destination`
     #[pdf(key="Dests")]
-    ap: NameTree<T>,
+    pub dests: Option<NameTree<Primitive>>,
+    /// Document-level JavaScript (PDF 1.3): JavaScript actions, run when the document is opened
+    #[pdf(key="JavaScript")]
+    pub javascript: Option<NameTree<Dictionary>>,
+    /*
     #[pdf(key="AP")]
     ap: NameTree<T>,
-    #[pdf(key="JavaScript")]
-    javascript: NameTree<T>,
     #[pdf(key="Pages")]
     pages: NameTree<T>,
     #[pdf(key="Templates")]
@@ -390,8 +1055,10 @@ pub struct NameDictionary {
     #[pdf(key="URLS")]
     urls: NameTree<T>,
     */
+    /// Attachments (PDF 1.4): file specification dictionaries, read with
+    /// `File::attachments`
     #[pdf(key="EmbeddedFiles")]
-    embedded_files: Option<NameTree<FileSpec>>,
+    pub embedded_files: Option<NameTree<Dictionary>>,
     /*
     #[pdf(key="AlternativePresentations")]
     alternate_presentations: NameTree<AlternatePresentation>,
@@ -409,6 +1076,12 @@ pub struct NameDictionary {
 
 #[derive(Object, Debug, Clone)]
 pub struct FileSpec {
+    /// File name (in a platform-independent form).
+    #[pdf(key="F")]
+    pub f: Option<PdfString>,
+    /// Unicode file name.
+    #[pdf(key="UF")]
+    pub uf: Option<PdfString>,
     #[pdf(key="EF")]
     ef: Option<Files<EmbeddedFile>>,
     /*
@@ -417,6 +1090,23 @@ pub struct FileSpec {
     */
 }
 
+impl FileSpec {
+    /// A file specification that consists of just the file name (a string instead of a
+    /// dictionary in the PDF).
+    pub fn from_name(name: PdfString) -> FileSpec {
+        FileSpec {
+            f: Some(name),
+            uf: None,
+            ef: None,
+        }
+    }
+    /// The file name - `/UF` if present, otherwise `/F`.
+    pub fn file_name(&self) -> Option<String> {
+        self.uf.as_ref().or(self.f.as_ref())
+            .map(|s| String::from_utf8_lossy(s.as_bytes()).into_owned())
+    }
+}
+
 /// Used only as elements in `FileSpec`
 #[derive(Object, Debug, Clone)]
 pub struct Files<T: Object> {
@@ -447,12 +1137,11 @@ pub struct EmbeddedFile {
 pub struct EmbeddedFileParamDict {
     #[pdf(key="Size")]
     size: Option<i32>,
-    /*
-    // TODO need Date type
     #[pdf(key="CreationDate")]
-    creationdate: T,
+    creationdate: Option<Date>,
     #[pdf(key="ModDate")]
-    moddate: T,
+    moddate: Option<Date>,
+    /*
     #[pdf(key="Mac")]
     mac: T,
     #[pdf(key="CheckSum")]
@@ -474,7 +1163,7 @@ pub fn write_list<'a, W, T: 'a, I>(out: &mut W, mut iter: I) -> io::Result<()>
         first.serialize(out)?;
         
         for other in iter {
-            out.write_all(b", ")?;
+            out.write_all(b" ")?;
             other.serialize(out)?;
         }
     }
@@ -488,29 +1177,553 @@ pub struct Outlines {
     pub count:  usize
 }
 
-#[derive(Debug)]
+/// The page of a destination: a page object, or - in destinations in other documents - a page
+/// number (from 0).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DestinationPage {
+    Ref (PlainRef),
+    Number (i32),
+}
+impl DestinationPage {
+    fn to_primitive(&self) -> Primitive {
+        match *self {
+            DestinationPage::Ref (r) => Primitive::Reference (r),
+            DestinationPage::Number (n) => Primitive::Integer (n),
+        }
+    }
+}
+
+/// An explicit destination (PDF reference, section 8.2.1): a page and how to show it.
+///
+/// Coordinates are in the default user space of the page. `None` (`null`) keeps the current
+/// value of the viewer - as does a zoom of 0.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    /// Position `(left, top)` at the upper left corner of the window, with the zoom factor `zoom`
+    XYZ {page: DestinationPage, left: Option<f32>, top: Option<f32>, zoom: Option<f32>},
+    /// The whole page in the window
+    Fit {page: DestinationPage},
+    /// The width of the page in the window, with `top` at the top
+    FitH {page: DestinationPage, top: Option<f32>},
+    /// The height of the page in the window, with `left` at the left
+    FitV {page: DestinationPage, left: Option<f32>},
+    /// The rectangle in the window
+    FitR {page: DestinationPage, rect: Rect},
+    /// The bounding box of the page contents in the window
+    FitB {page: DestinationPage},
+    FitBH {page: DestinationPage, top: Option<f32>},
+    FitBV {page: DestinationPage, left: Option<f32>},
+}
+impl Destination {
+    pub fn page(&self) -> DestinationPage {
+        match *self {
+            Destination::XYZ {page, ..} | Destination::Fit {page} | Destination::FitH {page, ..}
+            | Destination::FitV {page, ..} | Destination::FitR {page, ..} | Destination::FitB {page}
+            | Destination::FitBH {page, ..} | Destination::FitBV {page, ..} => page
+        }
+    }
+    /// The index of the page, with `pages` the page objects in order (see `File::page_refs`) -
+    /// `None` if it isn't one of them, or the page number is out of range.
+    pub fn page_index(&self, pages: &[PlainRef]) -> Option<usize> {
+        match self.page() {
+            DestinationPage::Ref (r) => pages.iter().position(|&page| page == r),
+            DestinationPage::Number (n) if n >= 0 && (n as usize) < pages.len() => Some(n as usize),
+            DestinationPage::Number (_) => None,
+        }
+    }
+    /// The destination array
+    fn to_array(&self) -> Vec<Primitive> {
+        fn number(n: Option<f32>) -> Primitive {
+            match n {
+                Some(n) => Primitive::Number (n),
+                None => Primitive::Null,
+            }
+        }
+        let page = self.page().to_primitive();
+        let name = |name: &str| Primitive::Name (name.into());
+        match *self {
+            Destination::XYZ {left, top, zoom, ..} => vec![page, name("XYZ"), number(left), number(top), number(zoom)],
+            Destination::Fit {..} => vec![page, name("Fit")],
+            Destination::FitH {top, ..} => vec![page, name("FitH"), number(top)],
+            Destination::FitV {left, ..} => vec![page, name("FitV"), number(left)],
+            Destination::FitR {rect, ..} => vec![page, name("FitR"),
+                Primitive::Number (rect.llx), Primitive::Number (rect.lly), Primitive::Number (rect.urx), Primitive::Number (rect.ury)],
+            Destination::FitB {..} => vec![page, name("FitB")],
+            Destination::FitBH {top, ..} => vec![page, name("FitBH"), number(top)],
+            Destination::FitBV {left, ..} => vec![page, name("FitBV"), number(left)],
+        }
+    }
+}
+/// The next parameter of a destination array - `None` if it is `null` or missing.
+fn dest_param(params: &mut ::std::vec::IntoIter<Primitive>, resolve: &Resolve) -> Result<Option<f32>> {
+    match params.next() {
+        Some(p) => Option::<f32>::from_primitive(p, resolve),
+        None => Ok(None)
+    }
+}
+impl Object for Destination {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        Primitive::Array (self.to_array()).serialize(out)
+    }
+    /// Also reads the value of a named destination, which can be a dictionary with the
+    /// destination in `/D`.
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        let array = match p {
+            Primitive::Reference (r) => return Destination::from_primitive(resolve.resolve(r)?, resolve),
+            Primitive::Dictionary (mut dict) => match dict.remove("D") {
+                Some(dest) => return Destination::from_primitive(dest, resolve),
+                None => bail!(ErrorKind::EntryNotFound {key: "D"}),
+            },
+            p => p.to_array(resolve)?
+        };
+        let mut params = array.into_iter();
+        let page = match params.next() {
+            Some(Primitive::Reference (r)) => DestinationPage::Ref (r),
+            Some(Primitive::Integer (n)) => DestinationPage::Number (n),
+            Some(p) => bail!(ErrorKind::UnexpectedPrimitive {expected: "Reference or Integer", found: p.get_debug_name()}),
+            None => bail!("Empty destination"),
+        };
+        let kind = match params.next() {
+            Some(kind) => String::from_primitive(kind, resolve)?,
+            None => bail!("Destination without type"),
+        };
+        let p = &mut params;
+        Ok(match kind.as_str() {
+            "XYZ" => Destination::XYZ {
+                page:   page,
+                left:   dest_param(p, resolve)?,
+                top:    dest_param(p, resolve)?,
+                zoom:   dest_param(p, resolve)?.and_then(|zoom| if zoom == 0. { None } else { Some(zoom) }),
+            },
+            "Fit" => Destination::Fit {page: page},
+            "FitH" => Destination::FitH {page: page, top: dest_param(p, resolve)?},
+            "FitV" => Destination::FitV {page: page, left: dest_param(p, resolve)?},
+            "FitR" => {
+                let (left, bottom) = (dest_param(p, resolve)?, dest_param(p, resolve)?);
+                let (right, top) = (dest_param(p, resolve)?, dest_param(p, resolve)?);
+                match (left, bottom, right, top) {
+                    (Some(left), Some(bottom), Some(right), Some(top)) => Destination::FitR {
+                        page:   page,
+                        rect:   Rect::new(left, bottom, right, top),
+                    },
+                    _ => bail!("FitR destination without rectangle"),
+                }
+            }
+            "FitB" => Destination::FitB {page: page},
+            "FitBH" => Destination::FitBH {page: page, top: dest_param(p, resolve)?},
+            "FitBV" => Destination::FitBV {page: page, left: dest_param(p, resolve)?},
+            _ => bail!("Unknown destination type {}", kind),
+        })
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Array (self.to_array()))
+    }
+}
+
+/// A destination as outline items, links and go-to actions have it: an explicit destination,
+/// or the name of one - see `File::resolve_target`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Explicit (Destination),
+    /// A name in the `Dests` name tree (a string) or - in PDF 1.1 - in the `/Dests` dictionary
+    /// of the catalog (a name object)
+    Named (Vec<u8>),
+}
+impl Object for Target {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            Target::Explicit (ref dest) => dest.serialize(out),
+            Target::Named (ref name) => PdfString::new(name.clone()).serialize(out),
+        }
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        let p = match p {
+            Primitive::Reference (r) => resolve.resolve(r)?,
+            p => p
+        };
+        match p {
+            Primitive::String (name) => Ok(Target::Named (name.into_bytes())),
+            Primitive::Name (name) => Ok(Target::Named (name.into_bytes())),
+            p @ Primitive::Array (_) | p @ Primitive::Dictionary (_) => Ok(Target::Explicit (Destination::from_primitive(p, resolve)?)),
+            p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Array or String", found: p.get_debug_name()}),
+        }
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        match *self {
+            Target::Explicit (ref dest) => dest.to_primitive(),
+            Target::Named (ref name) => Ok(Primitive::String (PdfString::new(name.clone()))),
+        }
+    }
+}
+
+/// An item of the document outline (a bookmark), with the items below it.
+#[derive(Debug, Clone)]
+pub struct OutlineItem {
+    /// `/Title`, decoded
+    pub title:      String,
+    /// `/Dest`: where to go when the item is activated
+    pub dest:       Option<Target>,
+    /// `/A`: the action to perform when the item is activated (instead of `dest`)
+    pub action:     Option<Action>,
+    /// Whether the children are shown (a positive `/Count`)
+    pub open:       bool,
+    pub children:   Vec<OutlineItem>,
+}
+
+/// Reads the items of the outline dictionary `outlines` (`/Outlines` of the catalog), following
+/// the `/First` and `/Next` links.
+///
+/// Every item is read once: an item that is reached again - through a cycle in the links - is
+/// an error. In lenient mode, the list it is in ends there.
+pub fn read_outline(outlines: &Dictionary, resolve: &Resolve) -> Result<Vec<OutlineItem>> {
+    let mut visited = HashSet::new();
+    read_outline_items(outlines, resolve, &mut visited)
+}
+
+/// The children of `parent` - the outline dictionary or an item.
+fn read_outline_items(parent: &Dictionary, resolve: &Resolve, visited: &mut HashSet<PlainRef>) -> Result<Vec<OutlineItem>> {
+    let mut items = Vec::new();
+    let mut next = parent.get("First").cloned();
+    while let Some(p) = next {
+        let r = match p {
+            Primitive::Reference (r) => r,
+            Primitive::Null => break,
+            p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Reference", found: p.get_debug_name()}),
+        };
+        if !visited.insert(r) {
+            if resolve.options().strict {
+                bail!(ErrorKind::CircularReference {obj_nr: r.id});
+            }
+            break;
+        }
+        let dict = resolve.resolve(r)
+            .and_then(|p| p.to_dictionary(resolve))
+            .chain_err(|| format!("Outline item {} {} R", r.id, r.gen))?;
+
+        let title = match dict.get("Title") {
+            Some(title) => PdfString::from_primitive(title.clone(), resolve)?.to_string_lossy(),
+            None if resolve.options().strict => bail!(ErrorKind::EntryNotFound {key: "Title"}),
+            None => String::new()
+        };
+        // (a destination that can't be read is left out, if not strict)
+        let dest = match dict.get("Dest") {
+            Some(dest) => match Target::from_primitive(dest.clone(), resolve) {
+                Ok(dest) => Some(dest),
+                Err(e) => {
+                    if resolve.options().strict {
+                        return Err(e).chain_err(|| format!("Outline item {} {} R", r.id, r.gen));
+                    }
+                    None
+                }
+            },
+            None => None
+        };
+        let action = match dict.get("A") {
+            Some(action) => Some(Action::from_primitive(action.clone(), resolve)
+                .chain_err(|| format!("Outline item {} {} R", r.id, r.gen))?),
+            None => None
+        };
+        let open = match dict.get("Count") {
+            Some(count) => i32::from_primitive(count.clone(), resolve)? > 0,
+            None => false
+        };
+        let children = read_outline_items(&dict, resolve, visited)?;
+        items.push(OutlineItem {
+            title:      title,
+            dest:       dest,
+            action:     action,
+            open:       open,
+            children:   children,
+        });
+        next = dict.get("Next").cloned();
+    }
+    Ok(items)
+}
+
+/// The document information dictionary (`/Info` of the trailer). Other entries than those with
+/// accessors are kept as they are.
+#[derive(Debug, Clone, Default)]
+pub struct InfoDict {
+    dict:   Dictionary,
+}
+impl InfoDict {
+    pub fn new() -> InfoDict {
+        InfoDict::default()
+    }
+    pub fn title(&self) -> Option<String> {
+        self.text("Title")
+    }
+    pub fn author(&self) -> Option<String> {
+        self.text("Author")
+    }
+    pub fn subject(&self) -> Option<String> {
+        self.text("Subject")
+    }
+    pub fn keywords(&self) -> Option<String> {
+        self.text("Keywords")
+    }
+    /// The application the document was created with (before it was converted to PDF)
+    pub fn creator(&self) -> Option<String> {
+        self.text("Creator")
+    }
+    /// The application that converted the document to PDF
+    pub fn producer(&self) -> Option<String> {
+        self.text("Producer")
+    }
+    pub fn creation_date(&self) -> Result<Option<Date>> {
+        self.date("CreationDate")
+    }
+    pub fn mod_date(&self) -> Result<Option<Date>> {
+        self.date("ModDate")
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.set_text("Title", title);
+    }
+    pub fn set_author(&mut self, author: &str) {
+        self.set_text("Author", author);
+    }
+    pub fn set_subject(&mut self, subject: &str) {
+        self.set_text("Subject", subject);
+    }
+    pub fn set_keywords(&mut self, keywords: &str) {
+        self.set_text("Keywords", keywords);
+    }
+    pub fn set_creator(&mut self, creator: &str) {
+        self.set_text("Creator", creator);
+    }
+    pub fn set_producer(&mut self, producer: &str) {
+        self.set_text("Producer", producer);
+    }
+    pub fn set_creation_date(&mut self, date: &Date) {
+        self.set_text("CreationDate", &date.to_string());
+    }
+    pub fn set_mod_date(&mut self, date: &Date) {
+        self.set_text("ModDate", &date.to_string());
+    }
+
+    pub fn as_dictionary(&self) -> &Dictionary {
+        &self.dict
+    }
+    pub fn as_dictionary_mut(&mut self) -> &mut Dictionary {
+        &mut self.dict
+    }
+
+    /// The text string `key`, decoded. (A value that is not a string is ignored.)
+    fn text(&self, key: &str) -> Option<String> {
+        match self.dict.get(key) {
+            Some(&Primitive::String (ref s)) => Some(s.to_string_lossy()),
+            _ => None
+        }
+    }
+    fn set_text(&mut self, key: &str, text: &str) {
+        self.dict.insert(key, Primitive::String (PdfString::from_text(text)));
+    }
+    fn date(&self, key: &str) -> Result<Option<Date>> {
+        match self.dict.get(key) {
+            Some(&Primitive::String (ref s)) => Ok(Some(Date::parse(s.as_bytes())?)),
+            Some(p) => bail!(ErrorKind::UnexpectedPrimitive {expected: "String", found: p.get_debug_name()}),
+            None => Ok(None)
+        }
+    }
+}
+impl Object for InfoDict {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.dict.serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        Ok(InfoDict {
+            dict:   Dictionary::from_primitive(p, resolve)?,
+        })
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Dictionary (self.dict.clone()))
+    }
+}
+
+/// A rectangle, as in `/MediaBox`: `[llx lly urx ury]`. The corners are normalized, so that
+/// `llx <= urx` and `lly <= ury`, as the PDF reference asks readers to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rect {
-    pub left:   f32,
-    pub right:  f32,
-    pub top:    f32,
-    pub bottom: f32
+    /// lower left x
+    pub llx:    f32,
+    /// lower left y
+    pub lly:    f32,
+    /// upper right x
+    pub urx:    f32,
+    /// upper right y
+    pub ury:    f32,
+}
+impl Rect {
+    /// The rectangle with the corners `(x1, y1)` and `(x2, y2)`, in any order.
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Rect {
+        Rect {
+            llx:    x1.min(x2),
+            lly:    y1.min(y2),
+            urx:    x1.max(x2),
+            ury:    y1.max(y2),
+        }
+    }
+    pub fn width(&self) -> f32 {
+        self.urx - self.llx
+    }
+    pub fn height(&self) -> f32 {
+        self.ury - self.lly
+    }
+    /// The part of the rectangle that is also in `other` - `None` if they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            llx:    self.llx.max(other.llx),
+            lly:    self.lly.max(other.lly),
+            urx:    self.urx.min(other.urx),
+            ury:    self.ury.min(other.ury),
+        };
+        if rect.llx <= rect.urx && rect.lly <= rect.ury {
+            Some(rect)
+        } else {
+            None
+        }
+    }
 }
 impl Object for Rect {
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
-        write!(out, "[{} {} {} {}]", self.left, self.top, self.right, self.bottom)
+        write!(out, "[{} {} {} {}]", self.llx, self.lly, self.urx, self.ury)
     }
     fn from_primitive(p: Primitive, r: &Resolve) -> Result<Self> {
         let arr = p.to_array(r)?;
         if arr.len() != 4 {
-            bail!("len != 4");
+            bail!("Rectangle with {} numbers instead of 4", arr.len());
+        }
+        let mut numbers = [0.; 4];
+        for (n, p) in numbers.iter_mut().zip(arr) {
+            *n = match p {
+                Primitive::Reference (id) => r.resolve(id)?.as_number()?,
+                p => p.as_number()?
+            };
+        }
+        Ok(Rect::new(numbers[0], numbers[1], numbers[2], numbers[3]))
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Array (vec![self.llx, self.lly, self.urx, self.ury].into_iter().map(Primitive::Number).collect()))
+    }
+}
+
+/// An affine transformation `[a b c d e f]` (PDF reference, section 4.2.3) - as in `/Matrix`
+/// entries and the `cm` operator. It maps `(x, y)` to `(a x + c y + e, b x + d y + f)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    pub a:  f32,
+    pub b:  f32,
+    pub c:  f32,
+    pub d:  f32,
+    pub e:  f32,
+    pub f:  f32,
+}
+impl Matrix {
+    pub fn identity() -> Matrix {
+        Matrix {a: 1., b: 0., c: 0., d: 1., e: 0., f: 0.}
+    }
+    pub fn translate(x: f32, y: f32) -> Matrix {
+        Matrix {a: 1., b: 0., c: 0., d: 1., e: x, f: y}
+    }
+    pub fn scale(x: f32, y: f32) -> Matrix {
+        Matrix {a: x, b: 0., c: 0., d: y, e: 0., f: 0.}
+    }
+    /// Rotation by `angle` radians counterclockwise
+    pub fn rotate(angle: f32) -> Matrix {
+        let (sin, cos) = angle.sin_cos();
+        Matrix {a: cos, b: sin, c: -sin, d: cos, e: 0., f: 0.}
+    }
+    /// The transformation that applies `self` first and then `other`. (`cm` with `self`
+    /// changes the current transformation matrix `ctm` to `self.then(&ctm)`.)
+    pub fn then(&self, other: &Matrix) -> Matrix {
+        Matrix {
+            a:  self.a * other.a + self.b * other.c,
+            b:  self.a * other.b + self.b * other.d,
+            c:  self.c * other.a + self.d * other.c,
+            d:  self.c * other.b + self.d * other.d,
+            e:  self.e * other.a + self.f * other.c + other.e,
+            f:  self.e * other.b + self.f * other.d + other.f,
         }
-        Ok(Rect {
-            left:   arr[0].as_number()?,
-            right:  arr[1].as_number()?,
-            top:    arr[2].as_number()?,
-            bottom: arr[3].as_number()?
+    }
+    /// The transformation that undoes this one - `None` if it isn't invertible.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0. {
+            return None;
+        }
+        Some(Matrix {
+            a:  self.d / det,
+            b:  -self.b / det,
+            c:  -self.c / det,
+            d:  self.a / det,
+            e:  (self.c * self.f - self.d * self.e) / det,
+            f:  (self.b * self.e - self.a * self.f) / det,
         })
     }
+    pub fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+    /// The bounding box of the transformed rectangle (as for the `/BBox` of a form).
+    pub fn transform_rect(&self, rect: &Rect) -> Rect {
+        let corners = [
+            self.transform_point(rect.llx, rect.lly),
+            self.transform_point(rect.llx, rect.ury),
+            self.transform_point(rect.urx, rect.lly),
+            self.transform_point(rect.urx, rect.ury),
+        ];
+        let (x0, y0) = corners[0];
+        let mut bbox = Rect::new(x0, y0, x0, y0);
+        for &(x, y) in &corners[1 ..] {
+            bbox.llx = bbox.llx.min(x);
+            bbox.lly = bbox.lly.min(y);
+            bbox.urx = bbox.urx.max(x);
+            bbox.ury = bbox.ury.max(y);
+        }
+        bbox
+    }
+}
+impl Default for Matrix {
+    fn default() -> Matrix {
+        Matrix::identity()
+    }
+}
+impl ::std::ops::Mul for Matrix {
+    type Output = Matrix;
+    /// `self.then(&other)`: transformations are applied from left to right, as row vectors
+    /// are multiplied in the PDF reference.
+    fn mul(self, other: Matrix) -> Matrix {
+        self.then(&other)
+    }
+}
+impl Object for Matrix {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "[{} {} {} {} {} {}]", self.a, self.b, self.c, self.d, self.e, self.f)
+    }
+    fn from_primitive(p: Primitive, r: &Resolve) -> Result<Self> {
+        let arr = p.to_array(r)?;
+        if arr.len() != 6 {
+            bail!("Matrix with {} numbers instead of 6", arr.len());
+        }
+        let mut numbers = [0.; 6];
+        for (n, p) in numbers.iter_mut().zip(arr) {
+            *n = match p {
+                Primitive::Reference (id) => r.resolve(id)?.as_number()?,
+                p => p.as_number()?
+            };
+        }
+        Ok(Matrix {
+            a:  numbers[0],
+            b:  numbers[1],
+            c:  numbers[2],
+            d:  numbers[3],
+            e:  numbers[4],
+            f:  numbers[5],
+        })
+    }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Array (vec![self.a, self.b, self.c, self.d, self.e, self.f].into_iter().map(Primitive::Number).collect()))
+    }
 }
 
 
@@ -529,42 +1742,3 @@ pub struct MarkInformation { // TODO no /Type
     pub suspects: bool,
 }
 
-#[derive(Object)]
-#[pdf(Type = "StructTreeRoot")]
-pub struct StructTreeRoot {
-    #[pdf(key="K")]
-    pub children: Vec<StructElem>,
-}
-#[derive(Object)]
-pub struct StructElem {
-    #[pdf(key="S")]
-    /// `S`
-    struct_type: StructType,
-    #[pdf(key="P")]
-    /// `P`
-    parent: Ref<StructElem>,
-    #[pdf(key="ID")]
-    /// `ID`
-    id: Option<PdfString>,
-    #[pdf(key="Pg")]
-    /// `Pg`: A page object representing a page on which some or all of the content items designated by the K entry are rendered.
-    page: Ref<Page>,
-}
-
-
-#[derive(Object)]
-pub enum StructType {
-    Document,
-    Part,
-    Art,
-    Sect,
-    Div,
-    BlockQuote,
-    Caption,
-    TOC,
-    TOCI,
-    Index,
-    NonStruct,
-    Private,
-}
-
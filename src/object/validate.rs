@@ -0,0 +1,42 @@
+//! Checks of dictionary entries beyond their type, with errors that name the entry
+//! (`ErrorKind::InvalidEntry`).
+//!
+//! They are meant as the checks behind declarative attributes of `#[derive(Object)]` fields
+//! (like a minimum or a set of allowed names), and are used by hand-written `from_primitive`s -
+//! such as that of `XObject`, for image dictionaries.
+
+use std::fmt::Display;
+use err::*;
+use primitive::Dictionary;
+
+/// `value` of `key` is at least `min`.
+pub fn check_min<T: PartialOrd + Display>(key: &'static str, value: T, min: T) -> Result<()> {
+    if value < min {
+        bail!(ErrorKind::InvalidEntry {key: key, reason: format!("{} is less than {}", value, min)});
+    }
+    Ok(())
+}
+/// `value` of `key` is at most `max`.
+pub fn check_max<T: PartialOrd + Display>(key: &'static str, value: T, max: T) -> Result<()> {
+    if value > max {
+        bail!(ErrorKind::InvalidEntry {key: key, reason: format!("{} is greater than {}", value, max)});
+    }
+    Ok(())
+}
+/// `value` of `key` is one of `allowed` - e.g. the names a key may have.
+pub fn check_one_of<T: PartialEq + Display>(key: &'static str, value: &T, allowed: &[T]) -> Result<()> {
+    if !allowed.contains(value) {
+        let allowed: Vec<String> = allowed.iter().map(|a| a.to_string()).collect();
+        bail!(ErrorKind::InvalidEntry {key: key, reason: format!("{} is not one of {}", value, allowed.join(", "))});
+    }
+    Ok(())
+}
+/// Either all or none of `keys` are in `dict` (like `/Width` and `/Height`).
+pub fn check_together(dict: &Dictionary, keys: &[&'static str]) -> Result<()> {
+    let present: Vec<&'static str> = keys.iter().cloned().filter(|&k| dict.get(k).is_some()).collect();
+    if !present.is_empty() && present.len() != keys.len() {
+        let missing = keys.iter().cloned().find(|&k| dict.get(k).is_none()).unwrap();
+        bail!(ErrorKind::InvalidEntry {key: missing, reason: format!("missing, but /{} is given", present.join(", /"))});
+    }
+    Ok(())
+}
@@ -0,0 +1,287 @@
+//! How the document should be presented (PDF reference, sections 3.6.1 and 8.1): the viewer
+//! preferences, the page layout and mode, and what to show when the document is opened.
+//!
+//! Names that aren't known are ignored - as viewers are asked to - and the defaults used.
+
+use object::*;
+use err::*;
+
+/// An enum of the values of a name entry, with `from_name` and `name`.
+macro_rules! name_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($(#[$variant_meta:meta])* $variant:ident),* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant),*
+        }
+        impl $name {
+            /// The value called `name` - `None` if there is none.
+            pub fn from_name(name: &str) -> Option<$name> {
+                match name {
+                    $(stringify!($variant) => Some($name::$variant),)*
+                    _ => None
+                }
+            }
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $($name::$variant => stringify!($variant)),*
+                }
+            }
+        }
+    }
+}
+
+name_enum! {
+    /// `/PageLayout` of the catalog: how pages are arranged.
+    pub enum PageLayout {
+        /// One page at a time (the default)
+        SinglePage,
+        /// The pages in a column
+        OneColumn,
+        /// The pages in two columns, the odd-numbered pages on the left
+        TwoColumnLeft,
+        /// The pages in two columns, the odd-numbered pages on the right
+        TwoColumnRight,
+        /// Two pages at a time, the odd-numbered pages on the left (PDF 1.5)
+        TwoPageLeft,
+        /// Two pages at a time, the odd-numbered pages on the right (PDF 1.5)
+        TwoPageRight
+    }
+}
+impl Default for PageLayout {
+    fn default() -> PageLayout {
+        PageLayout::SinglePage
+    }
+}
+
+name_enum! {
+    /// `/PageMode` of the catalog: what is shown besides the pages.
+    pub enum PageMode {
+        /// Nothing (the default)
+        UseNone,
+        /// The outline
+        UseOutlines,
+        /// The thumbnail images of the pages
+        UseThumbs,
+        /// Full screen: no menu bar, window controls or other windows
+        FullScreen,
+        /// The optional content groups (PDF 1.5)
+        UseOC,
+        /// The attachments (PDF 1.6)
+        UseAttachments
+    }
+}
+impl Default for PageMode {
+    fn default() -> PageMode {
+        PageMode::UseNone
+    }
+}
+
+name_enum! {
+    /// `/Direction` of the viewer preferences: the reading order of the text, which sets how
+    /// pages are put side by side.
+    pub enum Direction {
+        /// Left to right (the default)
+        L2R,
+        /// Right to left, as for Arabic or Hebrew - and vertical writing systems like Chinese
+        R2L
+    }
+}
+impl Default for Direction {
+    fn default() -> Direction {
+        Direction::L2R
+    }
+}
+
+name_enum! {
+    /// `/Duplex` of the viewer preferences: how to print on both sides of the paper.
+    pub enum Duplex {
+        /// On one side
+        Simplex,
+        /// On both sides, flipping on the short edge
+        DuplexFlipShortEdge,
+        /// On both sides, flipping on the long edge
+        DuplexFlipLongEdge
+    }
+}
+
+name_enum! {
+    /// `/PrintScaling` of the viewer preferences.
+    pub enum PrintScaling {
+        /// No scaling
+        None,
+        /// The scaling of the viewer (the default)
+        AppDefault
+    }
+}
+impl Default for PrintScaling {
+    fn default() -> PrintScaling {
+        PrintScaling::AppDefault
+    }
+}
+
+name_enum! {
+    /// A page boundary - as in `/ViewArea` and the like of the viewer preferences.
+    pub enum PageBox {
+        MediaBox,
+        CropBox,
+        BleedBox,
+        TrimBox,
+        ArtBox
+    }
+}
+
+impl Page {
+    /// The page boundary `page_box`, as in `crop_box` and the like.
+    pub fn page_box(&self, page_box: PageBox) -> Result<Rect> {
+        match page_box {
+            PageBox::MediaBox => self.media_box(),
+            PageBox::CropBox => self.crop_box(),
+            PageBox::BleedBox => self.bleed_box(),
+            PageBox::TrimBox => self.trim_box(),
+            PageBox::ArtBox => self.art_box(),
+        }
+    }
+}
+
+impl Catalog {
+    /// `/PageLayout`, by default `SinglePage`.
+    pub fn page_layout(&self) -> PageLayout {
+        self.page_layout.as_ref().and_then(|name| PageLayout::from_name(name)).unwrap_or_default()
+    }
+    /// `/PageMode`, by default `UseNone`.
+    pub fn page_mode(&self) -> PageMode {
+        self.page_mode.as_ref().and_then(|name| PageMode::from_name(name)).unwrap_or_default()
+    }
+}
+
+/// `/ViewerPreferences` of the catalog (PDF 1.2) - read with `File::viewer_preferences`. The
+/// name entries are kept as they are; the methods give their values.
+#[derive(Object, Debug, Clone, Default)]
+pub struct ViewerPreferences {
+    #[pdf(key="HideToolbar")]
+    pub hide_toolbar:       Option<bool>,
+    #[pdf(key="HideMenubar")]
+    pub hide_menubar:       Option<bool>,
+    /// Whether to hide the scroll bars and navigation controls, showing only the pages
+    #[pdf(key="HideWindowUI")]
+    pub hide_window_ui:     Option<bool>,
+    /// Whether to fit the window to the first page
+    #[pdf(key="FitWindow")]
+    pub fit_window:         Option<bool>,
+    #[pdf(key="CenterWindow")]
+    pub center_window:      Option<bool>,
+    /// Whether the title bar shows `/Title` of the document information (or of the XMP
+    /// metadata) rather than the file name (PDF 1.4)
+    #[pdf(key="DisplayDocTitle")]
+    pub display_doc_title:  Option<bool>,
+    #[pdf(key="NonFullScreenPageMode")]
+    pub non_full_screen_page_mode: Option<String>,
+    #[pdf(key="Direction")]
+    pub direction:          Option<String>,
+    #[pdf(key="ViewArea")]
+    pub view_area:          Option<String>,
+    #[pdf(key="ViewClip")]
+    pub view_clip:          Option<String>,
+    #[pdf(key="PrintArea")]
+    pub print_area:         Option<String>,
+    #[pdf(key="PrintClip")]
+    pub print_clip:         Option<String>,
+    /// (PDF 1.6)
+    #[pdf(key="PrintScaling")]
+    pub print_scaling:      Option<String>,
+    /// (PDF 1.7)
+    #[pdf(key="Duplex")]
+    pub duplex:             Option<String>,
+    /// Whether to pick the paper tray by the size of the pages, rather than in the print
+    /// dialog (PDF 1.7)
+    #[pdf(key="PickTrayByPDFSize")]
+    pub pick_tray_by_pdf_size: Option<bool>,
+    /// Pairs of the first and last page numbers (from 1) of the ranges to print - see
+    /// `print_page_ranges` (PDF 1.7)
+    #[pdf(key="PrintPageRange")]
+    pub print_page_range:   Option<Vec<i32>>,
+    /// The number of copies to print (PDF 1.7)
+    #[pdf(key="NumCopies")]
+    pub num_copies:         Option<i32>,
+}
+
+impl ViewerPreferences {
+    /// `/NonFullScreenPageMode`: the page mode after leaving full screen mode - by default
+    /// `UseNone`. (It is ignored unless the page mode is `FullScreen`.)
+    pub fn non_full_screen_page_mode(&self) -> PageMode {
+        match self.non_full_screen_page_mode.as_ref().and_then(|name| PageMode::from_name(name)) {
+            // (full screen mode isn't allowed here)
+            Some(PageMode::FullScreen) | None => PageMode::UseNone,
+            Some(mode) => mode,
+        }
+    }
+    pub fn direction(&self) -> Direction {
+        self.direction.as_ref().and_then(|name| Direction::from_name(name)).unwrap_or_default()
+    }
+    /// `/ViewArea`: the region of the pages to show - by default the crop box.
+    pub fn view_area(&self) -> PageBox {
+        page_box(&self.view_area)
+    }
+    /// `/ViewClip`: the region to clip the pages to when they are shown - by default the crop box.
+    pub fn view_clip(&self) -> PageBox {
+        page_box(&self.view_clip)
+    }
+    /// `/PrintArea`: the region of the pages to print - by default the crop box.
+    pub fn print_area(&self) -> PageBox {
+        page_box(&self.print_area)
+    }
+    /// `/PrintClip`: the region to clip the pages to when they are printed - by default the
+    /// crop box.
+    pub fn print_clip(&self) -> PageBox {
+        page_box(&self.print_clip)
+    }
+    pub fn print_scaling(&self) -> PrintScaling {
+        self.print_scaling.as_ref().and_then(|name| PrintScaling::from_name(name)).unwrap_or_default()
+    }
+    /// `/Duplex` - `None` if the print dialog should decide.
+    pub fn duplex(&self) -> Option<Duplex> {
+        self.duplex.as_ref().and_then(|name| Duplex::from_name(name))
+    }
+    /// The page ranges of `/PrintPageRange`, as the indices (from 0) of the first and last
+    /// pages. Ranges that are out of order are left out, as is the last number if there is an
+    /// odd number of them.
+    pub fn print_page_ranges(&self) -> Vec<(i32, i32)> {
+        let numbers = match self.print_page_range {
+            Some(ref numbers) => numbers,
+            None => return Vec::new(),
+        };
+        numbers.chunks(2)
+            .filter(|range| range.len() == 2 && 1 <= range[0] && range[0] <= range[1])
+            .map(|range| (range[0] - 1, range[1] - 1))
+            .collect()
+    }
+}
+
+fn page_box(name: &Option<String>) -> PageBox {
+    name.as_ref().and_then(|name| PageBox::from_name(name)).unwrap_or(PageBox::CropBox)
+}
+
+/// `/OpenAction` of the catalog: the destination to show, or the action to perform, when the
+/// document is opened - read with `File::open_action`.
+#[derive(Debug, Clone)]
+pub enum OpenAction {
+    Destination (Destination),
+    Action (Action),
+}
+
+impl OpenAction {
+    pub fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<OpenAction> {
+        let (r, p) = match p {
+            Primitive::Reference (r) => (Some(r), resolve.resolve(r)?),
+            p => (None, p)
+        };
+        match p {
+            // (an action is read by reference, so that it isn't repeated by a cycle in `/Next`)
+            Primitive::Dictionary (_) if r.is_some() => Ok(OpenAction::Action (Action::from_primitive(Primitive::Reference (r.unwrap()), resolve)?)),
+            p @ Primitive::Dictionary (_) => Ok(OpenAction::Action (Action::from_primitive(p, resolve)?)),
+            p @ Primitive::Array (_) => Ok(OpenAction::Destination (Destination::from_primitive(p, resolve)?)),
+            p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Array or Dictionary", found: p.get_debug_name()}),
+        }
+    }
+}
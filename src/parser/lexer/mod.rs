@@ -6,11 +6,29 @@ use std::str::FromStr;
 use std::ops::Range;
 use std::io::SeekFrom;
 
+use memchr::{memchr, memchr2};
 use err::*;
 
 mod str;
 pub use self::str::StringLexer;
 
+/// Position of the first occurence of `needle` in `data`.
+pub fn find_substr(data: &[u8], needle: &[u8]) -> Option<usize> {
+    let first = match needle.first() {
+        Some(&b) => b,
+        None => return Some(0),
+    };
+    let mut pos = 0;
+    while let Some(i) = memchr(first, &data[pos ..]) {
+        pos += i;
+        if data[pos ..].starts_with(needle) {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
 
 /// `Lexer` has functionality to jump around and traverse the PDF lexemes of a string in any direction.
 #[derive(Copy, Clone)]
@@ -74,9 +92,20 @@ impl<'a> Lexer<'a> {
     // TODO ^ backward case is actually not tested or.. thought about that well.
     fn next_word(&self, forward: bool) -> Result<(Substr<'a>, usize)> {
         let mut pos = self.pos;
-        // Move away from eventual whitespace
-        while self.is_whitespace(pos) {
-            pos = self.advance_pos(pos, forward)?;
+        // Move away from eventual whitespace - and comments, which go from `%` to the end of the
+        // line. (Comments can only be recognized reading forward.)
+        loop {
+            while self.is_whitespace(pos) {
+                pos = self.advance_pos(pos, forward)?;
+            }
+            if forward && self.is_comment_start(pos) {
+                pos = match memchr2(b'\n', b'\r', &self.buf[pos ..]) {
+                    Some(i) => pos + i,
+                    None => self.buf.len(),
+                };
+            } else {
+                break;
+            }
         }
         let start_pos = pos;
 
@@ -84,8 +113,8 @@ impl<'a> Lexer<'a> {
         //  - except << and >> which go together
         if self.is_delimiter(pos) {
             // TODO +- 1
-            if self.buf[pos] == b'<' && self.buf[pos+1] == b'<'
-                || self.buf[pos] == b'>' && self.buf[pos+1] == b'>' {
+            if pos + 1 < self.buf.len() && (self.buf[pos] == b'<' && self.buf[pos+1] == b'<'
+                || self.buf[pos] == b'>' && self.buf[pos+1] == b'>') {
                 pos = self.advance_pos(pos, forward)?;
 
             }
@@ -135,6 +164,15 @@ impl<'a> Lexer<'a> {
         self.pos
     }
 
+    /// The position right after the previous lexeme - before the whitespace that `next` skipped.
+    pub fn get_lexeme_end(&self) -> usize {
+        let mut pos = self.pos;
+        while pos > 0 && self.is_whitespace(pos - 1) {
+            pos -= 1;
+        }
+        pos
+    }
+
     pub fn new_substr(&self, mut range: Range<usize>) -> Substr<'a> {
         // if the range is backward, fix it
         // start is inclusive, end is exclusive. keep that in mind
@@ -259,7 +297,7 @@ impl<'a> Lexer<'a> {
     }
 
     /// Returns slice from current position to end.
-    pub fn get_remaining_slice(&self) -> &[u8] {
+    pub fn get_remaining_slice(&self) -> &'a [u8] {
         &self.buf[self.pos..]
     }
 
@@ -272,30 +310,21 @@ impl<'a> Lexer<'a> {
         }
     }
     fn is_whitespace(&self, pos: usize) -> bool {
-        if pos >= self.buf.len() {
-            false
-        } else {
-            self.buf[pos] == b' ' ||
-            self.buf[pos] == b'\r' ||
-            self.buf[pos] == b'\n' ||
-            self.buf[pos] == b'\t'
+        match self.buf.get(pos) {
+            Some(&b' ') | Some(&b'\r') | Some(&b'\n') | Some(&b'\t') | Some(&b'\x0C') | Some(&b'\0') => true,
+            _ => false
         }
     }
 
+    fn is_comment_start(&self, pos: usize) -> bool {
+        pos < self.buf.len() && self.buf[pos] == b'%'
+    }
+
     fn is_delimiter(&self, pos: usize) -> bool {
-        if pos >= self.buf.len() {
-            false
-        } else {
-            self.buf[pos] == b'(' ||
-            self.buf[pos] == b')' ||
-            self.buf[pos] == b'<' ||
-            self.buf[pos] == b'>' ||
-            self.buf[pos] == b'[' ||
-            self.buf[pos] == b']' ||
-            self.buf[pos] == b'{' ||
-            self.buf[pos] == b'}' ||
-            self.buf[pos] == b'/' ||
-            self.buf[pos] == b'%'
+        match self.buf.get(pos) {
+            Some(&b'(') | Some(&b')') | Some(&b'<') | Some(&b'>') | Some(&b'[') | Some(&b']') |
+            Some(&b'{') | Some(&b'}') | Some(&b'/') | Some(&b'%') => true,
+            _ => false
         }
     }
 }
@@ -323,17 +352,18 @@ impl<'a> Substr<'a> {
                     word: String::from(self.as_str())
                 }.into())
     }
-    pub fn is_integer(&self) -> bool {
-        match self.to::<i32>() {
-            Ok(_) => true,
+    /// Unlike `to`, doesn't allocate an error if this is not a number.
+    fn parses_as<T: FromStr>(&self) -> bool {
+        match std::str::from_utf8(self.slice) {
+            Ok(s) => s.parse::<T>().is_ok(),
             Err(_) => false,
         }
     }
+    pub fn is_integer(&self) -> bool {
+        self.parses_as::<i32>()
+    }
     pub fn is_real_number(&self) -> bool {
-        match self.to::<f32>() {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.parses_as::<f32>()
     }
 
     
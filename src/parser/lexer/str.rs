@@ -1,6 +1,5 @@
 use std::iter::Iterator;
 use err::*;
-use num_traits::int::PrimInt;
 
 /// A lexer for PDF strings. Breaks the string up into single characters (`u8`)
 /// It's also possible to get the number of indices of the original array that was traversed by the
@@ -46,6 +45,11 @@ impl<'a> StringLexer<'a> {
     }
 
     /// (mostly just used by Iterator, but might be useful)
+    ///
+    /// Returns `None` at the closing `)`. Escapes and line breaks are converted as in the PDF
+    /// reference (section 3.2.3): an end-of-line in the string (`\r`, `\n` or `\r\n`) is a
+    /// `\n`, a backslash before an end-of-line continues the string on the next line, and a
+    /// backslash before an unknown character is ignored.
     pub fn next_lexeme(&mut self) -> Result<Option<u8>> {
         let c = self.next_byte()?;
         match c {
@@ -56,41 +60,43 @@ impl<'a> StringLexer<'a> {
                     b'n' => Some(b'\n'),
                     b'r' => Some(b'\r'),
                     b't' => Some(b'\t'),
-                    b'b' => unimplemented!(),
-                    b'f' => unimplemented!(),
+                    b'b' => Some(b'\x08'),
+                    b'f' => Some(b'\x0C'),
                     b'(' => Some(b'('),
                     b')' => Some(b')'),
-                    b'\n' => self.next_lexeme()?, // ignore \\\n
                     b'\\' => Some(b'\\'),
-
-                    _ => {
-                        self.back()?;
-                        // A character code must follow. 1-3 numbers.
-                        let mut char_code: u8 = 0;
-                        let mut octal_digits = Vec::new();
-                        for _ in 0..3 {
-                            let c = self.peek_byte()?;
-                            if c >= b'0' && c <= b'9' {
-                                self.next_byte()?;
-                                octal_digits.push(c - b'0');
-                            } else {
-                                break;
-                            }
+                    // line continuation
+                    b'\n' => self.next_lexeme()?,
+                    b'\r' => {
+                        if self.peek_byte() == Some(b'\n') {
+                            self.pos += 1;
                         }
-                        if octal_digits.is_empty() {
-                            bail!("Wrong character following `\\`: {}", self.peek_byte()?);
-                        }
-                        // Convert string of octal digits to number
-                        octal_digits.reverse(); // little-endian
-                        for (i, digit) in octal_digits.iter().enumerate() {
-                            char_code += digit * 8.pow(i as u32);
+                        self.next_lexeme()?
+                    }
+                    b'0' ... b'7' => {
+                        // A character code: 1-3 octal digits (high-order overflow is ignored)
+                        let mut char_code = c - b'0';
+                        for _ in 0..2 {
+                            match self.peek_byte() {
+                                Some(d @ b'0' ... b'7') => {
+                                    self.pos += 1;
+                                    char_code = char_code.wrapping_mul(8).wrapping_add(d - b'0');
+                                }
+                                _ => break
+                            }
                         }
-
                         Some(char_code)
                     }
+                    c => Some(c)
                 }
                 )
             },
+            b'\r' => {
+                if self.peek_byte() == Some(b'\n') {
+                    self.pos += 1;
+                }
+                Ok(Some(b'\n'))
+            }
 
             b'(' => {
                 self.nested += 1;
@@ -111,27 +117,16 @@ impl<'a> StringLexer<'a> {
     }
 
     fn next_byte(&mut self) -> Result<u8> {
-        if self.pos < self.buf.len() - 1 {
+        if self.pos < self.buf.len() {
             self.pos += 1;
             Ok(self.buf[self.pos-1])
         } else {
             bail!(ErrorKind::EOF);
         }
     }
-    fn back(&mut self) -> Result<()> {
-        if self.pos > 0 {
-            self.pos -= 1;
-            Ok(())
-        } else {
-            bail!(ErrorKind::EOF);
-        }
-    }
-    fn peek_byte(&mut self) -> Result<u8> {
-        if self.pos < self.buf.len() - 1 {
-            Ok(self.buf[self.pos + 1])
-        } else {
-            bail!(ErrorKind::EOF);
-        }
+    /// The byte that `next_byte` would return.
+    fn peek_byte(&self) -> Option<u8> {
+        self.buf.get(self.pos).cloned()
     }
 }
 
@@ -2,15 +2,61 @@
 mod lexer;
 mod parse_object;
 mod parse_xref;
+mod sequential;
 
 pub use self::lexer::*;
 pub use self::parse_object::*;
 pub use self::parse_xref::*;
+pub use self::sequential::*;
 
+use std::{fmt, str};
 use err::*;
 use self::lexer::{StringLexer};
 use primitive::{Primitive, Dictionary, PdfStream, PdfString};
 use object::{ObjNr, GenNr, PlainRef, Resolve};
+use enc::{decode, StreamFilter};
+use memchr::memchr;
+
+/// Options for parsing a document.
+#[derive(Copy, Clone, Debug)]
+pub struct ParseOptions {
+    /// If false, recoverable violations of the PDF syntax (such as junk tokens inside a
+    /// dictionary, a missing stream `/Length`, or optional entries or `/Type` keys with
+    /// wrong values) are parsed as well as possible - skipped or left out - instead of failing.
+    pub strict: bool,
+    /// What to do if `/Type` or `/Subtype` of a dictionary is missing or not the expected one.
+    /// (Many producers leave out `/Type` where it is optional in practice, e.g. of XObjects.)
+    /// This applies to the checks of `object::check_type`, and so to those of `#[pdf(Type="...")]`.
+    pub type_check: TypeCheck,
+}
+impl ParseOptions {
+    pub fn strict() -> ParseOptions {
+        ParseOptions {strict: true, type_check: TypeCheck::Error}
+    }
+    pub fn lenient() -> ParseOptions {
+        ParseOptions {strict: false, type_check: TypeCheck::Tolerate}
+    }
+    /// These options with `type_check` instead.
+    pub fn with_type_check(self, type_check: TypeCheck) -> ParseOptions {
+        ParseOptions {type_check: type_check, .. self}
+    }
+}
+
+/// Policy for `/Type` and `/Subtype` checks - see `object::check_type`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TypeCheck {
+    /// Fail with `WrongDictionaryType` (or `EntryNotFound`)
+    Error,
+    /// Convert the dictionary anyway
+    Tolerate,
+    /// Don't look at the entry
+    Skip,
+}
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions::strict()
+    }
+}
 
 /// Can parse stream but only if its dictionary does not contain indirect references.
 /// Use `parse_stream` if this is insufficient.
@@ -29,11 +75,13 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<Primitive> {
             // Expect a Name (and Object) or the '>>' delimiter
             let delimiter = lexer.next()?;
             if delimiter.equals(b"/") {
-                let key = lexer.next()?.to_string();
+                let key = decode_name(lexer.next()?.as_slice(), r)?;
                 let obj = parse_with_lexer(lexer, r)?;
                 dict.insert(key, obj);
             } else if delimiter.equals(b">>") {
                 break;
+            } else if !r.options().strict {
+                // (junk, which is skipped)
             } else {
                 bail!(ErrorKind::UnexpectedLexeme{ pos: lexer.get_pos(), lexeme: delimiter.to_string(), expected: "/ or >>"});
             }
@@ -43,20 +91,19 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<Primitive> {
             lexer.next()?;
 
             let length = match dict.get("Length") {
-                Some(&Primitive::Integer (n)) => n,
-                Some(&Primitive::Reference (n)) => r.resolve(n)?.as_integer()?,
+                Some(&Primitive::Integer (n)) => n as usize,
+                Some(&Primitive::Reference (n)) => resolve_length(n, r)?,
+                // `read_stream_data` will look for `endstream`
+                _ if !r.options().strict => usize::max_value(),
                 _ => bail!(ErrorKind::EntryNotFound {key: "Length"}),
             };
 
             
-            let stream_substr = lexer.offset_pos(length as usize);
-
-            // Finish
-            lexer.next_expect("endstream")?;
+            let data = read_stream_data(lexer, length, &mut dict, r.options())?;
 
             Primitive::Stream(PdfStream {
                 info: dict,
-                data: stream_substr.to_vec(),
+                data: data,
             })
         } else {
             Primitive::Dictionary (dict)
@@ -91,19 +138,14 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<Primitive> {
         Primitive::Number (first_lexeme.to::<f32>()?)
     } else if first_lexeme.equals(b"/") {
         // Name
-        let s = lexer.next()?.to_string();
-        Primitive::Name(s)
+        Primitive::Name (decode_name(lexer.next()?.as_slice(), r)?)
     } else if first_lexeme.equals(b"[") {
         let mut array = Vec::new();
         // Array
-        loop {
+        // Exit at the closing delimiter (the array may be empty)
+        while !lexer.peek()?.equals(b"]") {
             let element = parse_with_lexer(lexer, r)?;
-            array.push(element.clone());
-
-            // Exit if closing delimiter
-            if lexer.peek()?.equals(b"]") {
-                break;
-            }
+            array.push(element);
         }
         lexer.next()?; // Move beyond closing delimiter
 
@@ -125,9 +167,13 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<Primitive> {
 
         Primitive::String (PdfString::new(string))
     } else if first_lexeme.equals(b"<") {
-        let hex_str = lexer.next()?.to_vec();
-        lexer.next_expect(">")?;
-        Primitive::String (PdfString::new(hex_str))
+        // Hex string - may contain whitespace, and an odd last digit is padded with 0
+        let len = match memchr(b'>', lexer.get_remaining_slice()) {
+            Some(i) => i + 1,
+            None => bail!(ErrorKind::EOF),
+        };
+        let data = decode(lexer.offset_pos(len).as_slice(), &StreamFilter::ASCIIHexDecode)?;
+        Primitive::String (PdfString::new(data))
     } else if first_lexeme.equals(b"true") {
         Primitive::Boolean (true)
     } else if first_lexeme.equals(b"false") {
@@ -145,6 +191,130 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<Primitive> {
 }
 
 
+/// Decodes the `#xx` escapes (two hexadecimal digits) of a name. A `#` that is not followed by two
+/// hexadecimal digits stands for itself, as in PDF before 1.2.
+///
+/// Names that aren't UTF-8 are an error if strict - else the invalid bytes are replaced.
+fn decode_name(name: &[u8], r: &Resolve) -> Result<String> {
+    fn hex(b: u8) -> Option<u8> {
+        match b {
+            b'0' ... b'9' => Some(b - b'0'),
+            b'a' ... b'f' => Some(b - b'a' + 10),
+            b'A' ... b'F' => Some(b - b'A' + 10),
+            _ => None
+        }
+    }
+    let mut decoded = Vec::with_capacity(name.len());
+    let mut i = 0;
+    while i < name.len() {
+        if name[i] == b'#' && i + 2 < name.len() {
+            if let (Some(h), Some(l)) = (hex(name[i + 1]), hex(name[i + 2])) {
+                decoded.push(h << 4 | l);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(name[i]);
+        i += 1;
+    }
+    if r.options().strict {
+        Ok(String::from_utf8(decoded)?)
+    } else {
+        Ok(String::from_utf8_lossy(&decoded).into_owned())
+    }
+}
+
+/// Version of the PDF syntax that a file uses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PdfVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+impl PdfVersion {
+    pub fn new(major: u8, minor: u8) -> PdfVersion {
+        PdfVersion {
+            major: major,
+            minor: minor,
+        }
+    }
+    /// Parses `M.m`, as in the header and the `/Version` of the catalog.
+    pub fn parse(s: &str) -> Result<PdfVersion> {
+        let mut parts = s.trim().splitn(2, '.');
+        match (parts.next(), parts.next()) {
+            (Some(major), Some(minor)) => Ok(PdfVersion::new(major.parse()?, minor.parse()?)),
+            _ => bail!("Invalid PDF version {:?}", s),
+        }
+    }
+}
+impl fmt::Display for PdfVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Parses the header (`%PDF-M.m`) at the start of `data`. Some files have junk before it, so
+/// it is looked for in the first 1024 bytes.
+pub fn parse_header(data: &[u8]) -> Result<PdfVersion> {
+    let data = &data[.. ::std::cmp::min(data.len(), 1024)];
+    let start = match find_substr(data, b"%PDF-") {
+        Some(pos) => pos + 5,
+        None => bail!(ErrorKind::NotFound {word: "%PDF-".into()}),
+    };
+    let len = data[start ..].iter().take_while(|&&b| b == b'.' || b'0' <= b && b <= b'9').count();
+    PdfVersion::parse(str::from_utf8(&data[start .. start + len])?)
+}
+
+/// Resolves an indirect stream `/Length`. If it can't be resolved (e.g. because the object
+/// comes later in a file that is read sequentially) and parsing isn't strict,
+/// `read_stream_data` will look for `endstream` instead.
+fn resolve_length(length: PlainRef, r: &Resolve) -> Result<usize> {
+    match r.resolve(length) {
+        Ok(p) => Ok(p.as_integer()? as usize),
+        Err(_) if !r.options().strict => Ok(usize::max_value()),
+        Err(e) => Err(e).chain_err(|| format!("/Length {} {} R of a stream", length.id, length.gen))
+    }
+}
+
+/// Reads `length` bytes of stream data at the position of `lexer` (just after `stream`), and
+/// the `endstream` keyword.
+///
+/// If `endstream` doesn't follow, `/Length` is wrong (common in broken files): that is a
+/// `StreamLength` error if strict. Else the data extends up to the next `endstream` instead,
+/// and `/Length` in `dict` is corrected.
+fn read_stream_data(lexer: &mut Lexer, length: usize, dict: &mut Dictionary, options: &ParseOptions) -> Result<Vec<u8>> {
+    let start = lexer.get_pos();
+    if length <= lexer.get_remaining_slice().len() {
+        let data = lexer.offset_pos(length).to_vec();
+        if lexer.next_expect("endstream").is_ok() {
+            return Ok(data);
+        }
+        lexer.set_pos(start);
+    }
+
+    let end = match find_substr(lexer.get_remaining_slice(), b"endstream") {
+        Some(end) => end,
+        None => bail!(ErrorKind::NotFound {word: "endstream".into()}),
+    };
+    let data = {
+        let data = &lexer.get_remaining_slice()[.. end];
+        // the EOL before `endstream` is not part of the data
+        let data = if data.ends_with(b"\r\n") {
+            &data[.. end - 2]
+        } else if data.ends_with(b"\n") || data.ends_with(b"\r") {
+            &data[.. end - 1]
+        } else {
+            data
+        };
+        data.to_vec()
+    };
+    if options.strict {
+        bail!(ErrorKind::StreamLength {length: length, found: data.len()});
+    }
+    lexer.set_pos(start + end + 9);
+    dict.insert("Length", Primitive::Integer (data.len() as i32));
+    Ok(data)
+}
+
 pub fn parse_stream(data: &[u8], resolve: &Resolve) -> Result<PdfStream> {
     parse_stream_with_lexer(&mut Lexer::new(data), resolve)
 }
@@ -159,11 +329,13 @@ fn parse_stream_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<PdfStream>
             // Expect a Name (and Object) or the '>>' delimiter
             let delimiter = lexer.next()?;
             if delimiter.equals(b"/") {
-                let key = lexer.next()?.to_string();
+                let key = decode_name(lexer.next()?.as_slice(), r)?;
                 let obj = parse_with_lexer(lexer, r)?;
                 dict.insert(key, obj);
             } else if delimiter.equals(b">>") {
                 break;
+            } else if !r.options().strict {
+                // (junk, which is skipped)
             } else {
                 bail!(ErrorKind::UnexpectedLexeme{ pos: lexer.get_pos(), lexeme: delimiter.to_string(), expected: "/ or >>"});
             }
@@ -174,20 +346,20 @@ fn parse_stream_with_lexer(lexer: &mut Lexer, r: &Resolve) -> Result<PdfStream>
 
             // Get length - look up in `resolve_fn` if necessary
             let length = match dict.get("Length") {
-                Some(&Primitive::Reference (reference)) => r.resolve(reference)?.as_integer()?,
-                Some(&Primitive::Integer (n)) => n,
+                Some(&Primitive::Reference (reference)) => resolve_length(reference, r)?,
+                Some(&Primitive::Integer (n)) => n as usize,
+                // `read_stream_data` will look for `endstream`
+                _ if !r.options().strict => usize::max_value(),
                 Some(other) => bail!(ErrorKind::UnexpectedPrimitive {expected: "Integer or Reference", found: other.get_debug_name()}),
                 None => bail!(ErrorKind::EntryNotFound {key: "Length"}),
             };
 
             
-            let stream_substr = lexer.offset_pos(length as usize);
-            // Finish
-            lexer.next_expect("endstream")?;
+            let data = read_stream_data(lexer, length, &mut dict, r.options())?;
 
             PdfStream {
                 info: dict,
-                data: stream_substr.to_vec(),
+                data: data,
             }
         } else {
             bail!(ErrorKind::UnexpectedPrimitive { expected: "Stream", found: "Dictionary" });
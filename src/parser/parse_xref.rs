@@ -1,72 +1,18 @@
 use err::*;
-use num_traits::PrimInt;
 use parser::lexer::Lexer;
-use xref::{XRef, XRefSection};
-use file::XRefInfo;
+use xref::{XRefSection, XRefStream};
 use primitive::{Primitive, Dictionary};
 use object::*;
 use parser::{parse_with_lexer};
 use parser::parse_object::{parse_indirect_stream};
 
 
-// Just the part of Parser which reads xref sections from xref stream.
-/// Takes `&mut &[u8]` so that it can "consume" data as it reads
-fn parse_xref_section_from_stream(first_id: i32, num_entries: i32, width: &[i32], data: &mut &[u8]) -> Result<XRefSection> {
-    let mut entries = Vec::new();
-    for _ in 0..num_entries {
-        // println!("{:?}", &data[.. width.iter().map(|&i| i as usize).sum()]);
-         // TODO Check if width[i] are 0. Use default values from the PDF references.
-        let _type = read_u64_from_stream(width[0], data);
-        let field1 = read_u64_from_stream(width[1], data);
-        let field2 = read_u64_from_stream(width[2], data);
-
-        let entry =
-        match _type {
-            0 => XRef::Free {next_obj_nr: field1 as ObjNr, gen_nr: field2 as GenNr},
-            1 => XRef::Raw {pos: field1 as usize, gen_nr: field2 as GenNr},
-            2 => XRef::Stream {stream_id: field1 as ObjNr, index: field2 as usize},
-            _ => bail!(ErrorKind::XRefStreamType {found: _type}), // TODO: Should actually just be seen as a reference to the null object
-        };
-        entries.push(entry);
-    }
-    Ok(XRefSection {
-        first_id: first_id as u32,
-        entries: entries,
-    })
-}
-/// Helper to read an integer with a certain amount of bits `width` from stream.
-fn read_u64_from_stream(width: i32, data: &mut &[u8]) -> u64 {
-    let mut result = 0;
-    for i in 0..width {
-        let i = width - 1 - i; // (width, 0]
-        let c: u8 = data[0];
-        *data = &data[1..]; // Consume byte
-        result += u64::from(c) * 256.pow(i as u32);
-    }
-    result
-}
-
-
 /// Reads xref sections (from stream) and trailer starting at the position of the Lexer.
 pub fn parse_xref_stream_and_trailer(lexer: &mut Lexer, resolve: &Resolve) -> Result<(Vec<XRefSection>, Dictionary)> {
     let xref_stream = parse_indirect_stream(lexer, resolve).chain_err(|| "Reading Xref stream")?.1;
     let trailer = xref_stream.info.clone();
-    let mut xref_stream = Stream::<XRefInfo>::from_primitive(Primitive::Stream(xref_stream), resolve)?;
-    xref_stream.decode();
-    let mut data_left = &xref_stream.get_data()[..];
-
-    let width = &xref_stream.info.w;
-
-    let index = &xref_stream.info.index;
-    
-
-    let mut sections = Vec::new();
-    for (first_id, num_objects) in index.chunks(2).map(|c| (c[0], c[1])) {
-        let section = parse_xref_section_from_stream(first_id, num_objects, width, &mut data_left)?;
-        sections.push(section);
-    }
-
-    Ok((sections, trailer))
+    let xref_stream = XRefStream::from_primitive(Primitive::Stream(xref_stream), resolve)?;
+    Ok((xref_stream.sections()?, trailer))
 }
 
 
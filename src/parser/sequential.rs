@@ -0,0 +1,182 @@
+//! Sequential parsing of a PDF file from an `io::Read` source, such as piped input.
+
+use std::io::Read;
+use std::collections::HashMap;
+use err::*;
+use parser::{Lexer, ParseOptions, find_substr, parse_with_lexer, parse_indirect_object};
+use primitive::{Primitive, Dictionary};
+use object::{ObjNr, PlainRef, Resolver};
+
+/// Size of the first read for each object. Doubled every time the buffered data turns out to
+/// be too short.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What `ObjectReader::step` found.
+enum Step {
+    Object (PlainRef, Primitive),
+    Trailer (Dictionary),
+    Skipped,
+    End,
+}
+
+/// Reads the indirect objects of a PDF file one after another, buffering only the data of the
+/// current object. The xref table is skipped, so no random access is needed.
+///
+/// Indirect stream lengths that aren't known yet (they usually follow the stream) are found by
+/// looking for `endstream` - which needs lenient options (as of `new`); with strict options,
+/// such streams are errors.
+///
+/// ```ignore
+/// let mut reader = ObjectReader::new(io::stdin());
+/// for object in reader.by_ref() {
+///     let (id, primitive) = object?;
+/// }
+/// let trailer = reader.trailer();
+/// ```
+pub struct ObjectReader<R: Read> {
+    reader:     R,
+    buf:        Vec<u8>,
+    /// Position in `buf` of the data not parsed yet
+    pos:        usize,
+    eof:        bool,
+    options:    ParseOptions,
+    /// Integer objects read so far (possibly stream lengths)
+    integers:   HashMap<ObjNr, i32>,
+    trailer:    Option<Dictionary>,
+}
+
+impl<R: Read> ObjectReader<R> {
+    pub fn new(reader: R) -> ObjectReader<R> {
+        ObjectReader::with_options(reader, ParseOptions::lenient())
+    }
+    pub fn with_options(reader: R, options: ParseOptions) -> ObjectReader<R> {
+        ObjectReader {
+            reader:     reader,
+            buf:        Vec::new(),
+            pos:        0,
+            eof:        false,
+            options:    options,
+            integers:   HashMap::new(),
+            trailer:    None,
+        }
+    }
+    /// The last `trailer` dictionary read so far. (Files with an xref stream instead of a table
+    /// have their trailer in the xref stream object.)
+    pub fn trailer(&self) -> Option<&Dictionary> {
+        self.trailer.as_ref()
+    }
+
+    /// Returns the next indirect object, or `None` at the end of the input.
+    pub fn next_object(&mut self) -> Result<Option<(PlainRef, Primitive)>> {
+        let mut chunk_size = CHUNK_SIZE;
+        loop {
+            let result = {
+                let mut lexer = Lexer::new(&self.buf[self.pos ..]);
+                self.step(&mut lexer).map(|step| (step, lexer.get_pos()))
+            };
+            match result {
+                Ok((step, len)) => {
+                    self.pos += len;
+                    chunk_size = CHUNK_SIZE;
+                    match step {
+                        Step::Object (id, obj) => {
+                            if let Primitive::Integer (n) = obj {
+                                self.integers.insert(id.id, n);
+                            }
+                            return Ok(Some((id, obj)));
+                        }
+                        Step::Trailer (dict) => self.trailer = Some(dict),
+                        Step::Skipped => {}
+                        Step::End => return Ok(None),
+                    }
+                }
+                // At the end of the input the error is real, otherwise more data may help.
+                Err(e) => {
+                    if self.eof {
+                        // don't try again on the next call
+                        self.pos = self.buf.len();
+                        return Err(e);
+                    }
+                    self.fill(chunk_size)?;
+                    chunk_size *= 2;
+                }
+            }
+        }
+    }
+
+    fn step(&self, lexer: &mut Lexer) -> Result<Step> {
+        let word = lexer.peek()?;
+        if word.as_slice().is_empty() {
+            if self.eof {
+                return Ok(Step::End);
+            }
+            bail!(ErrorKind::EOF);
+        }
+        if word.is_integer() {
+            let integers = &self.integers;
+            let resolve = Resolver::new(|r: PlainRef| match integers.get(&r.id) {
+                Some(&n) => Ok(Primitive::Integer (n)),
+                None => Err(ErrorKind::FollowReference.into())
+            }, self.options);
+            let (id, obj) = parse_indirect_object(lexer, &resolve)?;
+            Ok(Step::Object (id, obj))
+        } else if word.equals(b"xref") {
+            // The table isn't needed - skip to the trailer.
+            let end = match find_substr(lexer.get_remaining_slice(), b"trailer") {
+                Some(end) => end,
+                None => bail!(ErrorKind::NotFound {word: "trailer".into()}),
+            };
+            lexer.offset_pos(end);
+            Ok(Step::Skipped)
+        } else if word.equals(b"trailer") {
+            lexer.next()?;
+            let resolve = Resolver::new(|_| Err(ErrorKind::FollowReference.into()), self.options);
+            match parse_with_lexer(lexer, &resolve)? {
+                Primitive::Dictionary (dict) => Ok(Step::Trailer (dict)),
+                p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Dictionary", found: p.get_debug_name()}),
+            }
+        } else if word.equals(b"startxref") {
+            lexer.next()?;
+            lexer.next()?;
+            Ok(Step::Skipped)
+        } else if !self.options.strict {
+            // (junk between objects)
+            lexer.next()?;
+            Ok(Step::Skipped)
+        } else {
+            bail!(ErrorKind::UnexpectedLexeme {pos: lexer.get_pos(), lexeme: word.to_string(), expected: "indirect object"});
+        }
+    }
+
+    /// Reads up to `size` more bytes into the buffer, first dropping the parsed data.
+    fn fill(&mut self, size: usize) -> Result<()> {
+        self.buf.drain(.. self.pos);
+        self.pos = 0;
+
+        let start = self.buf.len();
+        self.buf.resize(start + size, 0);
+        let mut len = start;
+        while len < self.buf.len() {
+            match self.reader.read(&mut self.buf[len ..])? {
+                0 => {
+                    self.eof = true;
+                    break;
+                }
+                n => len += n,
+            }
+        }
+        self.buf.truncate(len);
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for ObjectReader<R> {
+    type Item = Result<(PlainRef, Primitive)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_object() {
+            Ok(Some(object)) => Some(Ok(object)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
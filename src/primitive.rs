@@ -2,7 +2,7 @@ use err::*;
 
 use std::collections::{btree_map, BTreeMap};
 use std::{str, fmt, io};
-use std::ops::{Index, Range};
+use std::ops::Index;
 use object::{PlainRef, Resolve, Object};
 use chrono::{DateTime, FixedOffset};
 use std::ops::Deref;
@@ -35,8 +35,84 @@ impl Dictionary {
     pub fn get(&self, key: &str) -> Option<&Primitive> {
         self.dict.get(key)
     }
-    pub fn insert(&mut self, key: String, val: Primitive) -> Option<Primitive> {
-        self.dict.insert(key, val)
+    pub fn insert<K: Into<String>, V: Into<Primitive>>(&mut self, key: K, val: V) -> Option<Primitive> {
+        self.dict.insert(key.into(), val.into())
+    }
+    /// `insert` for building dictionaries:
+    ///
+    /// ```ignore
+    /// let dict = Dictionary::new()
+    ///     .with("Type", Primitive::Name ("XObject".into()))
+    ///     .with("Width", 100);
+    /// ```
+    pub fn with<K: Into<String>, V: Into<Primitive>>(mut self, key: K, val: V) -> Dictionary {
+        self.insert(key, val);
+        self
+    }
+
+    /// The entry `key`, or `EntryNotFound`.
+    pub fn require(&self, key: &'static str) -> Result<&Primitive> {
+        match self.dict.get(key) {
+            Some(p) => Ok(p),
+            None => bail!(ErrorKind::EntryNotFound {key: key}),
+        }
+    }
+    /// Converts the entry `key` to `T` (resolving references), with the key in the error.
+    pub fn expect<T: Object>(&self, key: &'static str, resolve: &Resolve) -> Result<T> {
+        T::from_primitive(self.require(key)?.clone(), resolve)
+            .chain_err(|| format!("Dictionary entry /{}", key))
+    }
+    /// Like `expect`, but a missing entry is `None`.
+    pub fn get_as<T: Object>(&self, key: &'static str, resolve: &Resolve) -> Result<Option<T>> {
+        match self.dict.get(key) {
+            Some(p) => T::from_primitive(p.clone(), resolve).map(Some)
+                .chain_err(|| format!("Dictionary entry /{}", key)),
+            None => Ok(None)
+        }
+    }
+    /// Converts the entry `key` to `T`, or - if it is missing - uses `T::from_missing`, like
+    /// derived `Object`s do.
+    pub fn get_entry<T: Object>(&self, key: &'static str, resolve: &Resolve) -> Result<T> {
+        match self.dict.get(key) {
+            Some(p) => T::from_primitive(p.clone(), resolve)
+                .chain_err(|| format!("Dictionary entry /{}", key)),
+            None => T::from_missing(resolve),
+        }
+    }
+    /// The entry `key`, if it is an integer. Like the other `get_*` accessors below, this doesn't
+    /// resolve a Reference - use `get_entry` for entries that may be indirect.
+    pub fn get_int(&self, key: &'static str) -> Result<i32> {
+        self.require(key)?.as_integer().chain_err(|| format!("Dictionary entry /{}", key))
+    }
+    pub fn get_number(&self, key: &'static str) -> Result<f32> {
+        self.require(key)?.as_number().chain_err(|| format!("Dictionary entry /{}", key))
+    }
+    pub fn get_bool(&self, key: &'static str) -> Result<bool> {
+        self.require(key)?.as_bool().chain_err(|| format!("Dictionary entry /{}", key))
+    }
+    pub fn get_name(&self, key: &'static str) -> Result<&str> {
+        match *self.require(key)? {
+            Primitive::Name (ref name) => Ok(name),
+            ref p => unexpected(key, "Name", p)
+        }
+    }
+    pub fn get_string(&self, key: &'static str) -> Result<&PdfString> {
+        match *self.require(key)? {
+            Primitive::String (ref s) => Ok(s),
+            ref p => unexpected(key, "String", p)
+        }
+    }
+    pub fn get_array(&self, key: &'static str) -> Result<&[Primitive]> {
+        match *self.require(key)? {
+            Primitive::Array (ref a) => Ok(a),
+            ref p => unexpected(key, "Array", p)
+        }
+    }
+    pub fn get_dictionary(&self, key: &'static str) -> Result<&Dictionary> {
+        match *self.require(key)? {
+            Primitive::Dictionary (ref d) => Ok(d),
+            ref p => unexpected(key, "Dictionary", p)
+        }
     }
     pub fn iter(&self) -> btree_map::Iter<String, Primitive> {
         self.dict.iter()
@@ -45,6 +121,11 @@ impl Dictionary {
         self.dict.remove(key)
     }
 }
+/// `UnexpectedPrimitive` for the entry `key`
+fn unexpected<T>(key: &str, expected: &'static str, found: &Primitive) -> Result<T> {
+    Err(Error::from(ErrorKind::UnexpectedPrimitive {expected: expected, found: found.get_debug_name()}))
+        .chain_err(|| format!("Dictionary entry /{}", key))
+}
 impl Deref for Dictionary {
     type Target = BTreeMap<String, Primitive>;
     fn deref(&self) -> &BTreeMap<String, Primitive> {
@@ -81,15 +162,13 @@ pub struct PdfStream {
     pub data: Vec<u8>,
 }
 impl Object for PdfStream {
+    /// Writes the dictionary - with `/Length` set to the length of the data - and the data.
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()>  {
-        writeln!(out, "<<")?;
-        for (k, v) in &self.info {
-            write!(out, "  {} ", k)?;
-            v.serialize(out)?;
-            writeln!(out, "")?;
-        }
-        writeln!(out, ">>")?;
-        
+        let mut info = self.info.clone();
+        info.insert("Length", self.data.len() as i32);
+        info.serialize(out)?;
+        writeln!(out, "")?;
+
         writeln!(out, "stream")?;
         out.write_all(&self.data)?;
         writeln!(out, "\nendstream")
@@ -101,6 +180,9 @@ impl Object for PdfStream {
             p => bail!(ErrorKind::UnexpectedPrimitive {expected: "Stream", found: p.get_debug_name()})
         }
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::Stream (self.clone()))
+    }
 }
 
 
@@ -114,6 +196,19 @@ macro_rules! unexpected_primitive {
     )
 }
 
+/// Writes `name` as name object: with a slash, and the characters that can't be part of a name
+/// (whitespace, delimiters, `#` and non-ASCII bytes) as `#xx`.
+pub fn write_name<W: io::Write>(out: &mut W, name: &str) -> io::Result<()> {
+    out.write_all(b"/")?;
+    for &b in name.as_bytes() {
+        match b {
+            b'!' ... b'~' if !b"#%()/<>[]{}".contains(&b) => out.write_all(&[b])?,
+            b => write!(out, "#{:02X}", b)?,
+        }
+    }
+    Ok(())
+}
+
 /// Primitive String type.
 #[derive(Clone)]
 pub struct PdfString {
@@ -134,17 +229,32 @@ impl fmt::Debug for PdfString {
     }
 }
 impl Object for PdfString {
+    /// Writes a literal string `(...)` if all bytes are printable ASCII (or can be escaped,
+    /// like `\n`), else a hex string `<...>`.
     fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
-        write!(out, r"\")?;
-        for &b in &self.data {
-            match b {
-                b'\\' | b'(' | b')' => write!(out, r"\")?,
-                c if c > b'~' => panic!("only ASCII"),
-                _ => ()
+        let literal = self.data.iter().all(|&b| b >= b' ' && b <= b'~' || b"\n\r\t\x08\x0C".contains(&b));
+        if literal {
+            out.write_all(b"(")?;
+            for &b in &self.data {
+                match b {
+                    b'\\' | b'(' | b')' => out.write_all(&[b'\\', b])?,
+                    // (a line break in the string would be read as `\n`, whatever it is)
+                    b'\n' => out.write_all(b"\\n")?,
+                    b'\r' => out.write_all(b"\\r")?,
+                    b'\t' => out.write_all(b"\\t")?,
+                    b'\x08' => out.write_all(b"\\b")?,
+                    b'\x0C' => out.write_all(b"\\f")?,
+                    b => out.write_all(&[b])?,
+                }
+            }
+            out.write_all(b")")
+        } else {
+            out.write_all(b"<")?;
+            for &b in &self.data {
+                write!(out, "{:02X}", b)?;
             }
-            write!(out, "{}", b)?;
+            out.write_all(b">")
         }
-        Ok(())
     }
     fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
         match p {
@@ -152,6 +262,9 @@ impl Object for PdfString {
             _ => unexpected_primitive!(String, p.get_debug_name()),
         }
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::String (self.clone()))
+    }
 }
 
 impl PdfString {
@@ -172,6 +285,67 @@ impl PdfString {
     pub fn into_string(self) -> Result<String> {
         Ok(String::from_utf8(self.data)?)
     }
+    /// If this is a text string in UTF-8 - marked by the byte order mark EF BB BF (PDF 2.0) -
+    /// returns the text after the mark.
+    pub fn as_utf8_text(&self) -> Option<&str> {
+        if self.data.starts_with(b"\xEF\xBB\xBF") {
+            str::from_utf8(&self.data[3 ..]).ok()
+        } else {
+            None
+        }
+    }
+    /// Decodes this as text string (PDF reference, section 3.8.1): UTF-16BE if it starts
+    /// with the byte order mark FE FF, UTF-8 if it starts with EF BB BF, and else
+    /// PDFDocEncoding. Invalid or undefined characters become U+FFFD.
+    pub fn to_string_lossy(&self) -> String {
+        if self.data.starts_with(b"\xFE\xFF") {
+            let units: Vec<u16> = self.data[2 ..].chunks(2)
+                .map(|c| if c.len() == 2 { (c[0] as u16) << 8 | c[1] as u16 } else { 0xFFFD })
+                .collect();
+            String::from_utf16_lossy(&units)
+        } else if self.data.starts_with(b"\xEF\xBB\xBF") {
+            String::from_utf8_lossy(&self.data[3 ..]).into_owned()
+        } else {
+            self.data.iter().map(|&b| pdf_doc_char(b)).collect()
+        }
+    }
+    /// Encodes `text` as text string: as is if it is printable ASCII (which is the same in
+    /// PDFDocEncoding), and else as UTF-16BE with byte order mark.
+    pub fn from_text(text: &str) -> PdfString {
+        if text.bytes().all(|b| b >= 0x20 && b < 0x7F || b == b'\t' || b == b'\n' || b == b'\r') {
+            return PdfString::new(text.as_bytes().to_vec());
+        }
+        let mut data = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            data.push((unit >> 8) as u8);
+            data.push(unit as u8);
+        }
+        PdfString::new(data)
+    }
+}
+
+/// Characters 0x80 to 0x9F of PDFDocEncoding (0x9F is undefined).
+const PDF_DOC_80: [char; 32] = [
+    '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+    '\u{2039}', '\u{203A}', '\u{2212}', '\u{2030}', '\u{201E}', '\u{201C}', '\u{201D}', '\u{2018}',
+    '\u{2019}', '\u{201A}', '\u{2122}', '\u{FB01}', '\u{FB02}', '\u{0141}', '\u{0152}', '\u{0160}',
+    '\u{0178}', '\u{017D}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017E}', '\u{FFFD}',
+];
+/// Characters 0x18 to 0x1F of PDFDocEncoding
+const PDF_DOC_18: [char; 8] = [
+    '\u{02D8}', '\u{02C7}', '\u{02C6}', '\u{02D9}', '\u{02DD}', '\u{02DB}', '\u{02DA}', '\u{02DC}',
+];
+
+/// The character of `b` in PDFDocEncoding (PDF reference, appendix D), which is Latin-1 except
+/// for some characters.
+fn pdf_doc_char(b: u8) -> char {
+    match b {
+        0x18 ... 0x1F => PDF_DOC_18[(b - 0x18) as usize],
+        0x7F | 0xAD => '\u{FFFD}',
+        0x80 ... 0x9F => PDF_DOC_80[(b - 0x80) as usize],
+        0xA0 => '\u{20AC}',
+        b => b as char
+    }
 }
 
 
@@ -311,11 +485,12 @@ impl From<String> for Primitive {
 
 
 impl<T: Object> Object for Option<T> {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        // TODO: the Option here is most often or always about whether the entry exists in a
-        // dictionary. Hence it should probably be more up to the Dictionary impl of serialize, to
-        // handle Options. 
-        unimplemented!();
+    /// `None` is `null` - which in a dictionary is the same as a missing entry.
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            Some(ref t) => t.serialize(out),
+            None => write!(out, "null"),
+        }
     }
     fn from_primitive(p: Primitive, r: &Resolve) -> Result<Self> {
         match p {
@@ -324,58 +499,162 @@ impl<T: Object> Object for Option<T> {
                 Ok(p) => Ok(Some(p)),
                 // References to non-existing objects ought not to be an error
                 Err(Error(ErrorKind::NullRef {..}, _)) => Ok(None),
-                Err(e) => bail!(e),
+                Err(e) => {
+                    if r.options().strict {
+                        bail!(e);
+                    }
+                    Ok(None)
+                }
             }
         }
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        match *self {
+            Some(ref t) => t.to_primitive(),
+            None => Ok(Primitive::Null),
+        }
+    }
 }
 
-fn parse_or<T: str::FromStr + Clone>(buffer: &str, range: Range<usize>, default: T) -> T {
-    buffer.get(range)
-        .map(|s| str::parse::<T>(s).unwrap_or(default.clone()))
-        .unwrap_or(default)
+/// A date (PDF reference, section 3.8.3), as in `/CreationDate`: `D:YYYYMMDDHHmmSSOHH'mm'`.
+/// The components after the year are optional.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub year:       u16,
+    pub month:      u8,
+    pub day:        u8,
+    pub hour:       u8,
+    pub minute:     u8,
+    pub second:     u8,
+    /// Offset of the local time from UT in minutes, if known
+    pub utc_offset: Option<i32>,
 }
 
-impl Object for DateTime<FixedOffset> {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> io::Result<()> {
-        // TODO: smal/avg amount of work.
-        unimplemented!();
-    }
-    fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
-        use chrono::{NaiveDateTime, NaiveDate, NaiveTime};
-        match p {
-            Primitive::String (PdfString {data}) => {
-                let s = str::from_utf8(&data)?;
-                let len = s.len();
-                if len > 2 && &s[0..2] == "D:" {
-
-                    let year = match s.get(2..6) {
-                        Some(year) => {
-                            str::parse::<i32>(year)?
-                        }
-                        None => bail!("Missing obligatory year in date")
-                    };
-                    let month = parse_or(s, 6..8, 1);
-                    let day = parse_or(s, 8..10, 1);
-                    let hour = parse_or(s, 10..12, 0);
-                    let minute = parse_or(s, 12..14, 0);
-                    let second = parse_or(s, 14..16, 0);
-                    let tz_hour = parse_or(s, 16..18, 0);
-                    let tz_minute = parse_or(s, 19..21, 0);
-                    let tz = FixedOffset::east(tz_hour * 60 + tz_minute);
-
-                    Ok(DateTime::from_utc(
-                            NaiveDateTime::new(NaiveDate::from_ymd(year, month, day),
-                                               NaiveTime::from_hms(hour, minute, second)),
-                          tz
-                      ))
+/// The number of the `n` ASCII digits at `*pos`, if they are digits. Advances `pos` past them.
+fn parse_digits(data: &[u8], pos: &mut usize, n: usize) -> Option<u32> {
+    let digits = match data.get(*pos .. *pos + n) {
+        Some(digits) => digits,
+        None => return None
+    };
+    if !digits.iter().all(|&b| b >= b'0' && b <= b'9') {
+        return None;
+    }
+    *pos += n;
+    Some(digits.iter().fold(0, |n, &b| n * 10 + (b - b'0') as u32))
+}
 
-                } else {
-                    bail!("Failed parsing date");
+impl Date {
+    /// Parses a date string. The `D:` prefix, which some producers leave out, is optional too,
+    /// as are the apostrophes of the time zone.
+    pub fn parse(data: &[u8]) -> Result<Date> {
+        let data = if data.starts_with(b"D:") { &data[2 ..] } else { data };
+        let mut pos = 0;
+        let year = match parse_digits(data, &mut pos, 4) {
+            Some(year) => year as u16,
+            None => bail!("Date without year: {:?}", String::from_utf8_lossy(data))
+        };
+        // month, day, hour, minute, second
+        let mut fields = [1, 1, 0, 0, 0];
+        for field in &mut fields {
+            match parse_digits(data, &mut pos, 2) {
+                Some(n) => *field = n,
+                None => break
+            }
+        }
+        let utc_offset = match data.get(pos) {
+            Some(&b'Z') => Some(0),
+            Some(&sign) if sign == b'+' || sign == b'-' => {
+                pos += 1;
+                let hours = parse_digits(data, &mut pos, 2).unwrap_or(0);
+                if data.get(pos) == Some(&b'\'') {
+                    pos += 1;
+                }
+                let minutes = parse_digits(data, &mut pos, 2).unwrap_or(0);
+                if hours > 23 || minutes > 59 {
+                    bail!("Invalid time zone in date {:?}", String::from_utf8_lossy(data));
                 }
+                let offset = (hours * 60 + minutes) as i32;
+                Some(if sign == b'-' { -offset } else { offset })
             }
+            _ => None
+        };
+        let (month, day, hour, minute, second) = (fields[0], fields[1], fields[2], fields[3], fields[4]);
+        if month < 1 || month > 12 || day < 1 || day > 31 || hour > 23 || minute > 59 || second > 59 {
+            bail!("Invalid date {:?}", String::from_utf8_lossy(data));
+        }
+        Ok(Date {
+            year:       year,
+            month:      month as u8,
+            day:        day as u8,
+            hour:       hour as u8,
+            minute:     minute as u8,
+            second:     second as u8,
+            utc_offset: utc_offset,
+        })
+    }
+    /// The date as `chrono` type, unless the day doesn't exist (e.g. February 30). Dates whose
+    /// time zone is not known are taken to be UT.
+    pub fn to_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        use chrono::{NaiveDate, TimeZone};
+        let tz = match FixedOffset::east_opt(self.utc_offset.unwrap_or(0) * 60) {
+            Some(tz) => tz,
+            None => return None
+        };
+        NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+            .and_then(|date| date.and_hms_opt(self.hour as u32, self.minute as u32, self.second as u32))
+            .and_then(|local| tz.from_local_datetime(&local).single())
+    }
+}
+impl From<DateTime<FixedOffset>> for Date {
+    fn from(date: DateTime<FixedOffset>) -> Date {
+        use chrono::{Datelike, Timelike};
+        Date {
+            year:       date.year() as u16,
+            month:      date.month() as u8,
+            day:        date.day() as u8,
+            hour:       date.hour() as u8,
+            minute:     date.minute() as u8,
+            second:     date.second() as u8,
+            utc_offset: Some(date.offset().local_minus_utc() / 60),
+        }
+    }
+}
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "D:{:04}{:02}{:02}{:02}{:02}{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)?;
+        match self.utc_offset {
+            Some(0) => write!(f, "Z"),
+            Some(offset) => write!(f, "{}{:02}'{:02}'", if offset < 0 { '-' } else { '+' }, offset.abs() / 60, offset.abs() % 60),
+            None => Ok(())
+        }
+    }
+}
+impl Object for Date {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        // (no characters that need escaping)
+        write!(out, "({})", self)
+    }
+    fn from_primitive(p: Primitive, _: &Resolve) -> Result<Self> {
+        match p {
+            Primitive::String (string) => Date::parse(string.as_bytes()),
             _ => unexpected_primitive!(String, p.get_debug_name()),
         }
     }
+    fn to_primitive(&self) -> Result<Primitive> {
+        Ok(Primitive::String (PdfString::new(self.to_string().into_bytes())))
+    }
+}
+
+impl Object for DateTime<FixedOffset> {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        Date::from(*self).serialize(out)
+    }
+    fn from_primitive(p: Primitive, r: &Resolve) -> Result<Self> {
+        let date = Date::from_primitive(p, r)?;
+        match date.to_datetime() {
+            Some(date) => Ok(date),
+            None => bail!("Invalid date {}", date),
+        }
+    }
 }
 
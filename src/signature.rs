@@ -0,0 +1,330 @@
+//! Digital signatures (PDF reference, section 8.7): the signature dictionaries of the signature
+//! fields, and checking that the signed bytes are those of the file.
+//!
+//! The certificates and the signature value of the CMS (PKCS#7) blob are not checked here - that
+//! is left to a cryptography library, with `Signature::cms`. Likewise `File::sign` leaves making
+//! the CMS blob to a callback.
+
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use crypto::sha2::{Sha256, Sha384, Sha512};
+use err::*;
+use object::*;
+use primitive::{Primitive, Dictionary, PdfString, Date};
+use backend::Revision;
+
+/// The signature dictionary of a signature field
+#[derive(Debug, Clone)]
+pub struct Signature {
+    /// Fully qualified name of the signature field
+    pub field:          String,
+    /// `/Filter`: the preferred signature handler, like `Adobe.PPKLite`
+    pub filter:         String,
+    /// `/SubFilter`: the format of `/Contents`, like `adbe.pkcs7.detached` or `ETSI.CAdES.detached`
+    pub sub_filter:     Option<String>,
+    /// `/Contents`: the signature - for the CMS formats, the DER-encoded CMS blob (with the
+    /// padding after it)
+    pub contents:       Vec<u8>,
+    /// `/ByteRange`: the signed parts of the file, as offset and length
+    pub byte_range:     Vec<(usize, usize)>,
+    /// `/M`: the signing time (not trustworthy - the time in the CMS blob or its time stamp is)
+    pub signing_time:   Option<Date>,
+    /// `/Name`: the signer
+    pub name:           Option<String>,
+    pub reason:         Option<String>,
+    pub location:       Option<String>,
+    pub contact_info:   Option<String>,
+}
+
+/// The hash function of a signature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+impl DigestAlgorithm {
+    /// The digest of `parts`, one after another.
+    pub fn digest(&self, parts: &[&[u8]]) -> Vec<u8> {
+        let mut hasher: Box<Digest> = match *self {
+            DigestAlgorithm::Sha1 => Box::new(Sha1::new()),
+            DigestAlgorithm::Sha256 => Box::new(Sha256::new()),
+            DigestAlgorithm::Sha384 => Box::new(Sha384::new()),
+            DigestAlgorithm::Sha512 => Box::new(Sha512::new()),
+        };
+        for part in parts {
+            hasher.input(part);
+        }
+        let mut digest = vec![0; hasher.output_bytes()];
+        hasher.result(&mut digest);
+        digest
+    }
+}
+
+/// What `Signature::verify` found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub algorithm:          DigestAlgorithm,
+    /// Whether the digest of the signed bytes is the one in the CMS blob (the `messageDigest`
+    /// attribute)
+    pub digest_matches:     bool,
+    /// The revision that the signature covers (the index in `File::revisions`), if the signed
+    /// bytes end where a revision ends
+    pub revision:           Option<usize>,
+    /// Whether the signature covers the whole file - if not, the file has been updated after
+    /// signing, and the updates are not signed
+    pub covers_whole_file:  bool,
+}
+
+/// DER encoding of the object identifier of the `messageDigest` attribute (1.2.840.113549.1.9.4)
+const OID_MESSAGE_DIGEST: &[u8] = b"\x06\x09\x2A\x86\x48\x86\xF7\x0D\x01\x09\x04";
+/// DER encodings of the object identifiers of the hash functions
+const OID_DIGESTS: &[(&[u8], DigestAlgorithm)] = &[
+    (b"\x06\x05\x2B\x0E\x03\x02\x1A", DigestAlgorithm::Sha1),
+    (b"\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x01", DigestAlgorithm::Sha256),
+    (b"\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x02", DigestAlgorithm::Sha384),
+    (b"\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x03", DigestAlgorithm::Sha512),
+];
+
+impl Signature {
+    /// Reads the signature dictionary `dict` of the field `field`.
+    pub fn from_dict(field: &str, dict: &Dictionary, resolve: &Resolve) -> Result<Signature> {
+        let text = |key| -> Result<Option<String>> {
+            Ok(dict.get_as::<PdfString>(key, resolve)?.map(|s| s.to_string_lossy()))
+        };
+        let range: Vec<i32> = dict.expect("ByteRange", resolve)?;
+        if range.len() % 2 != 0 || range.iter().any(|&n| n < 0) {
+            bail!(ErrorKind::InvalidEntry {key: "ByteRange", reason: format!("{:?} are not pairs of offset and length", range)});
+        }
+        Ok(Signature {
+            field:          field.to_string(),
+            filter:         dict.expect("Filter", resolve)?,
+            sub_filter:     dict.get_as("SubFilter", resolve)?,
+            contents:       dict.expect::<PdfString>("Contents", resolve)?.into_bytes(),
+            byte_range:     range.chunks(2).map(|pair| (pair[0] as usize, pair[1] as usize)).collect(),
+            signing_time:   dict.get_as("M", resolve)?,
+            name:           text("Name")?,
+            reason:         text("Reason")?,
+            location:       text("Location")?,
+            contact_info:   text("ContactInfo")?,
+        })
+    }
+
+    /// The CMS blob of `/Contents`, without the padding after it - for checking the certificates
+    /// and the signature value.
+    pub fn cms(&self) -> Result<&[u8]> {
+        match der_element(&self.contents, 0) {
+            Some((_, _, end)) => Ok(&self.contents[.. end]),
+            None => bail!(ErrorKind::InvalidEntry {key: "Contents", reason: "not DER-encoded".into()}),
+        }
+    }
+
+    /// Checks the byte range of the signature against `data` - the file - and the digest of
+    /// the bytes in it against the digest in the CMS blob. `revisions` are those of the file.
+    ///
+    /// The byte range has to start at the start of the file and leave out just the
+    /// `/Contents` string; else it is an error. Only the CMS sub filters are supported
+    /// (`adbe.pkcs7.detached` and `ETSI.CAdES.detached`).
+    pub fn verify(&self, data: &[u8], revisions: &[Revision]) -> Result<Verification> {
+        match self.sub_filter.as_ref().map(|s| s.as_str()) {
+            Some("adbe.pkcs7.detached") | Some("ETSI.CAdES.detached") => {}
+            sub_filter => bail!("Signatures with /SubFilter {:?} are not supported", sub_filter),
+        }
+        let invalid_range = |reason: &str| -> Error {
+            ErrorKind::InvalidEntry {key: "ByteRange", reason: reason.into()}.into()
+        };
+        let mut parts = Vec::with_capacity(self.byte_range.len());
+        let mut pos = 0;
+        for &(offset, len) in &self.byte_range {
+            if pos != 0 {
+                // the gap has to be the hex string of /Contents
+                if offset <= pos || data.get(pos) != Some(&b'<') || data.get(offset - 1) != Some(&b'>') {
+                    return Err(invalid_range("it leaves out more than /Contents"));
+                }
+            } else if offset != 0 {
+                return Err(invalid_range("it doesn't start at the start of the file"));
+            }
+            match data.get(offset .. offset + len) {
+                Some(part) => parts.push(part),
+                None => return Err(invalid_range("it is outside of the file")),
+            }
+            pos = offset + len;
+        }
+
+        let cms = self.cms()?;
+        // the first digest algorithm - that of `digestAlgorithms` of the SignedData
+        let first_digest = OID_DIGESTS.iter()
+            .filter_map(|&(oid, algorithm)| find(cms, oid).map(|pos| (pos, algorithm)))
+            .min_by_key(|&(pos, _)| pos);
+        let algorithm = match first_digest {
+            Some((_, algorithm)) => algorithm,
+            None => bail!("CMS signature with unknown digest algorithm"),
+        };
+        // messageDigest ::= SET { OCTET STRING }
+        let expected = match find(cms, OID_MESSAGE_DIGEST) {
+            Some(oid) => {
+                let set = der_element(cms, oid + OID_MESSAGE_DIGEST.len());
+                let octets = set.and_then(|(_, start, _)| der_element(cms, start));
+                match octets {
+                    Some((0x04, start, end)) => &cms[start .. end],
+                    _ => bail!("CMS signature with invalid messageDigest attribute"),
+                }
+            }
+            None => bail!("CMS signature without signed attributes"),
+        };
+        Ok(Verification {
+            algorithm:          algorithm,
+            digest_matches:     algorithm.digest(&parts) == expected,
+            revision:           revisions.iter().position(|revision| revision.end == pos),
+            covers_whole_file:  pos == data.len(),
+        })
+    }
+}
+
+/// The signature that `File::sign` adds.
+#[derive(Debug, Clone)]
+pub struct SigningOptions {
+    /// The name of the new signature field
+    pub field_name:     String,
+    /// `/SubFilter`: the format of the CMS blob
+    pub sub_filter:     String,
+    /// The space reserved for the CMS blob, in bytes - it has to fit, with the certificates
+    /// and time stamps in it
+    pub contents_size:  usize,
+    pub signing_time:   Option<Date>,
+    pub name:           Option<String>,
+    pub reason:         Option<String>,
+    pub location:       Option<String>,
+    pub contact_info:   Option<String>,
+}
+impl Default for SigningOptions {
+    fn default() -> SigningOptions {
+        SigningOptions {
+            field_name:     "Signature1".into(),
+            sub_filter:     "adbe.pkcs7.detached".into(),
+            contents_size:  8192,
+            signing_time:   None,
+            name:           None,
+            reason:         None,
+            location:       None,
+            contact_info:   None,
+        }
+    }
+}
+
+/// The `/ByteRange` written first - with numbers as long as the real ones can be.
+const BYTE_RANGE_PLACEHOLDER: [i32; 4] = [0, 1000000000, 1000000000, 1000000000];
+
+impl SigningOptions {
+    /// The signature dictionary, with placeholders for `/ByteRange` and `/Contents`.
+    pub fn signature_dict(&self) -> Dictionary {
+        let mut dict = Dictionary::new()
+            .with("Type", Primitive::Name ("Sig".into()))
+            .with("Filter", Primitive::Name ("Adobe.PPKLite".into()))
+            .with("SubFilter", Primitive::Name (self.sub_filter.clone()))
+            .with("ByteRange", self.byte_range_placeholder())
+            .with("Contents", self.contents_placeholder());
+        if let Some(ref date) = self.signing_time {
+            dict.insert("M", PdfString::new(date.to_string().into_bytes()));
+        }
+        let text = [("Name", &self.name), ("Reason", &self.reason), ("Location", &self.location), ("ContactInfo", &self.contact_info)];
+        for &(key, value) in &text {
+            if let Some(ref value) = *value {
+                dict.insert(key, PdfString::from_text(value));
+            }
+        }
+        dict
+    }
+    fn byte_range_placeholder(&self) -> Primitive {
+        Primitive::Array (BYTE_RANGE_PLACEHOLDER.iter().map(|&n| Primitive::Integer (n)).collect())
+    }
+    fn contents_placeholder(&self) -> PdfString {
+        PdfString::new(vec![0; self.contents_size])
+    }
+
+    /// Fills in the placeholders of the signature dictionary in `data` - the file, with the
+    /// signature dictionary after `start` - and the CMS blob that `signer` makes.
+    pub fn sign_placeholders<F>(&self, data: &mut Vec<u8>, start: usize, signer: F) -> Result<()>
+        where F: FnOnce(&[u8]) -> Result<Vec<u8>>
+    {
+        let mut range_placeholder = Vec::new();
+        self.byte_range_placeholder().serialize(&mut range_placeholder)?;
+        let mut contents_placeholder = Vec::new();
+        self.contents_placeholder().serialize(&mut contents_placeholder)?;
+        let range_pos = match find(&data[start ..], &range_placeholder) {
+            Some(pos) => start + pos,
+            None => bail!("The /ByteRange of the signature is not in the file"),
+        };
+        let contents_start = match find(&data[start ..], &contents_placeholder) {
+            Some(pos) => start + pos,
+            None => bail!("The /Contents of the signature is not in the file"),
+        };
+        let contents_end = contents_start + contents_placeholder.len();
+
+        let range = Primitive::Array (vec![
+            Primitive::Integer (0),
+            Primitive::Integer (contents_start as i32),
+            Primitive::Integer (contents_end as i32),
+            Primitive::Integer ((data.len() - contents_end) as i32),
+        ]);
+        let mut range_data = Vec::new();
+        range.serialize(&mut range_data)?;
+        // (the placeholder has the longest numbers, so this fits)
+        range_data.resize(range_placeholder.len(), b' ');
+        data[range_pos .. range_pos + range_data.len()].copy_from_slice(&range_data);
+
+        let signed: Vec<u8> = data[.. contents_start].iter().chain(&data[contents_end ..]).cloned().collect();
+        let cms = signer(&signed)?;
+        if cms.len() > self.contents_size {
+            bail!("The signature is {} bytes, but only {} are reserved", cms.len(), self.contents_size);
+        }
+        let hex: String = cms.iter().map(|b| format!("{:02X}", b)).collect();
+        data[contents_start + 1 .. contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+        Ok(())
+    }
+}
+
+/// The signatures of the signature fields of `form` that are signed.
+pub fn read_signatures(form: &AcroForm, resolve: &Resolve) -> Result<Vec<Signature>> {
+    let mut signatures = Vec::new();
+    for field in form.all_fields() {
+        if field.field_type != Some(FieldType::Signature) {
+            continue;
+        }
+        if let Some(Primitive::Dictionary (ref dict)) = field.value {
+            let signature = Signature::from_dict(&field.full_name, dict, resolve)
+                .chain_err(|| format!("Signature of field {}", field.full_name))?;
+            signatures.push(signature);
+        }
+    }
+    Ok(signatures)
+}
+
+/// The position of `needle` in `data`.
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|window| window == needle)
+}
+
+/// The DER element at `pos` of `data`: its tag, and the start and end of its contents.
+fn der_element(data: &[u8], pos: usize) -> Option<(u8, usize, usize)> {
+    if pos + 2 > data.len() {
+        return None;
+    }
+    let (tag, first) = (data[pos], data[pos + 1] as usize);
+    let (len, start) = if first < 0x80 {
+        (first, pos + 2)
+    } else {
+        // long form: the number of length bytes, then the length
+        let n = first & 0x7F;
+        if n == 0 || n > 4 || pos + 2 + n > data.len() {
+            return None;
+        }
+        let len = data[pos + 2 .. pos + 2 + n].iter().fold(0, |len, &b| len << 8 | b as usize);
+        (len, pos + 2 + n)
+    };
+    if start + len > data.len() {
+        return None;
+    }
+    Some((tag, start, start + len))
+}
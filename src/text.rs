@@ -0,0 +1,745 @@
+//! Text extraction. For a Tagged PDF, `extract_text` gives the text in the logical reading
+//! order of the structure tree - the order a screen reader reads it in - with the roles of the
+//! elements (headings, paragraphs, list items, table cells...). Pages that aren't tagged get
+//! their text in layout order: line by line, from the top.
+//!
+//! Characters are mapped to Unicode with the `/ToUnicode` CMap of the font, or else with
+//! `WinAnsiEncoding` (`/Differences` of the encoding are not used). Glyph widths come from
+//! `/Widths` (or `/W` of the descendant font). Form XObjects are not looked into.
+
+use std::collections::HashMap;
+use std::cmp::Ordering;
+use err::*;
+use object::*;
+use primitive::{Primitive, Dictionary, PdfString};
+use backend::Backend;
+use file::File;
+use content::Content;
+
+/// Text shown by one text showing operation (`Tj`, `TJ`, `'` or `"`).
+#[derive(Debug, Clone)]
+pub struct TextFragment {
+    pub text:   String,
+    /// The position of the start of the text (on the baseline), in default user space
+    pub x:      f32,
+    pub y:      f32,
+    /// The font size, in default user space
+    pub size:   f32,
+    /// The width of the text (estimated from the glyph widths), in default user space
+    pub width:  f32,
+    /// The marked content identifier of the innermost marked content the text is in
+    pub mcid:   Option<i32>,
+}
+
+/// A block of text: the text of a block-level structure element - with the text of the
+/// inline-level elements in it - or, for untagged pages, a line.
+#[derive(Debug, Clone)]
+pub struct TextBlock {
+    /// The standard structure type of the element (following the role map) - `None` for
+    /// untagged text
+    pub role:           Option<StructType>,
+    /// The structure type of the element as in `/S`
+    pub struct_type:    Option<String>,
+    /// The depth of the element in the structure tree (0 for the top-level elements)
+    pub depth:          usize,
+    /// The index of the page the text starts on
+    pub page:           Option<usize>,
+    /// `/Lang` of the element or of the closest ancestor that has one
+    pub lang:           Option<String>,
+    pub text:           String,
+}
+
+/// The text of the document: in structure order if it is a Tagged PDF (with the text of
+/// untagged pages after it, in page order), else in layout order. Content that is not part of
+/// the structure tree on a tagged page - like artifacts: page headers, footers and
+/// decorations - is left out.
+pub fn extract_text<B: Backend>(file: &File<B>) -> Result<Vec<TextBlock>> {
+    let tree = file.struct_tree()?;
+    let page_refs = file.page_refs()?;
+    let mut marked = HashMap::new();
+    let mut untagged = Vec::new();
+    for i in 0 .. file.get_num_pages()? {
+        let page = file.get_page(i)?;
+        let fragments = page_text_fragments(file, page).chain_err(|| format!("Page {}", i))?;
+        if tree.is_some() && page.struct_parents.is_some() {
+            for fragment in fragments {
+                if let Some(mcid) = fragment.mcid {
+                    marked.entry((i as usize, mcid)).or_insert_with(Vec::new).push(fragment);
+                }
+            }
+        } else {
+            untagged.push((i as usize, fragments));
+        }
+    }
+
+    let mut blocks = Vec::new();
+    if let Some(ref tree) = tree {
+        let mut reader = StructText {
+            tree:   tree,
+            pages:  page_refs.iter().enumerate().map(|(i, &r)| (r, i)).collect(),
+            marked: marked,
+            blocks: Vec::new(),
+        };
+        for elem in &tree.children {
+            reader.block(elem, 0, None);
+        }
+        blocks = reader.blocks;
+    }
+    for (page, fragments) in untagged {
+        for line in layout_lines(&fragments) {
+            blocks.push(TextBlock {
+                role:           None,
+                struct_type:    None,
+                depth:          0,
+                page:           Some(page),
+                lang:           None,
+                text:           line,
+            });
+        }
+    }
+    Ok(blocks)
+}
+
+/// The text of `fragments` in layout order: lines from the top of the page, each from the
+/// left. Fragments are on the same line if their baselines are less than half the font size
+/// apart.
+pub fn layout_lines(fragments: &[TextFragment]) -> Vec<String> {
+    let mut sorted: Vec<&TextFragment> = fragments.iter().collect();
+    sorted.sort_by(|a, b| b.y.partial_cmp(&a.y).unwrap_or(Ordering::Equal));
+
+    let mut lines: Vec<Vec<&TextFragment>> = Vec::new();
+    for fragment in sorted {
+        let same_line = match lines.last() {
+            Some(line) => (line[0].y - fragment.y).abs() < 0.5 * line[0].size.max(fragment.size),
+            None => false
+        };
+        if same_line {
+            lines.last_mut().unwrap().push(fragment);
+        } else {
+            lines.push(vec![fragment]);
+        }
+    }
+
+    let mut texts = Vec::with_capacity(lines.len());
+    for mut line in lines {
+        line.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal));
+        let mut text = String::new();
+        let mut end: Option<f32> = None;
+        for fragment in line {
+            // a gap of more than a fifth of the font size is a space
+            if let Some(end) = end {
+                if fragment.x - end > 0.2 * fragment.size {
+                    append(&mut text, &fragment.text);
+                } else {
+                    text.push_str(&fragment.text);
+                }
+            } else {
+                text.push_str(&fragment.text);
+            }
+            end = Some(fragment.x + fragment.width);
+        }
+        let text = text.trim();
+        if !text.is_empty() {
+            texts.push(text.to_string());
+        }
+    }
+    texts
+}
+
+/// Appends `part` to `text`, with a space in between unless there is already whitespace.
+fn append(text: &mut String, part: &str) {
+    if part.is_empty() {
+        return;
+    }
+    if !text.is_empty() && !text.ends_with(char::is_whitespace) && !part.starts_with(char::is_whitespace) {
+        text.push(' ');
+    }
+    text.push_str(part);
+}
+
+/// Collects the blocks of the structure tree.
+struct StructText<'a> {
+    tree:   &'a StructTreeRoot,
+    /// The page objects, to the indices of the pages
+    pages:  HashMap<PlainRef, usize>,
+    /// The fragments of the marked content of the tagged pages, by page index and MCID
+    marked: HashMap<(usize, i32), Vec<TextFragment>>,
+    blocks: Vec<TextBlock>,
+}
+
+impl<'a> StructText<'a> {
+    /// Adds the blocks of the block-level element `elem`: its own text - with that of the
+    /// inline-level elements in it - and the blocks of the block-level elements in it, in order.
+    fn block(&mut self, elem: &StructElem, depth: usize, lang: Option<&str>) {
+        let role = self.tree.role(&elem.struct_type);
+        let lang = elem.lang.as_ref().map(|lang| lang.as_str()).or(lang);
+
+        let mut text = String::new();
+        let mut page = None;
+        if let Some(ref actual_text) = elem.actual_text {
+            // (still looking into the content for the page)
+            for child in &elem.children {
+                self.inline(child, &mut String::new(), &mut page);
+            }
+            self.push(elem, &role, depth, lang, actual_text.clone(), page);
+            return;
+        }
+        for child in &elem.children {
+            match *child {
+                StructChild::Element (ref child) if !self.tree.role(&child.struct_type).is_inline() => {
+                    let own_text = ::std::mem::replace(&mut text, String::new());
+                    self.push(elem, &role, depth, lang, own_text, page.take());
+                    self.block(child, depth + 1, lang);
+                }
+                ref child => self.inline(child, &mut text, &mut page),
+            }
+        }
+        // a figure or formula without text is read by its alternate description
+        if text.trim().is_empty() {
+            match role {
+                StructType::Figure | StructType::Formula => if let Some(ref alt) = elem.alt {
+                    text = alt.clone();
+                },
+                _ => {}
+            }
+        }
+        self.push(elem, &role, depth, lang, text, page);
+    }
+
+    /// Appends the text of `child` - marked content, or an inline-level element - to `text`.
+    /// `page` is set to the page of the first text, if it isn't already.
+    fn inline(&self, child: &StructChild, text: &mut String, page: &mut Option<usize>) {
+        match *child {
+            StructChild::MarkedContent {mcid, page: Some(page_ref), stream: None} => {
+                let index = match self.pages.get(&page_ref) {
+                    Some(&index) => index,
+                    None => return,
+                };
+                if let Some(fragments) = self.marked.get(&(index, mcid)) {
+                    append(text, &layout_lines(fragments).join(" "));
+                    if page.is_none() {
+                        *page = Some(index);
+                    }
+                }
+            }
+            StructChild::Element (ref elem) => {
+                let mut inner = String::new();
+                for child in &elem.children {
+                    self.inline(child, &mut inner, page);
+                }
+                if let Some(ref actual_text) = elem.actual_text {
+                    inner = actual_text.clone();
+                } else if inner.trim().is_empty() {
+                    if let Some(ref alt) = elem.alt {
+                        inner = alt.clone();
+                    }
+                }
+                append(text, &inner);
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a block of `elem`, unless the text is empty.
+    fn push(&mut self, elem: &StructElem, role: &StructType, depth: usize, lang: Option<&str>, text: String, page: Option<usize>) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        self.blocks.push(TextBlock {
+            role:           Some(role.clone()),
+            struct_type:    Some(elem.struct_type.clone()),
+            depth:          depth,
+            page:           page,
+            lang:           lang.map(|lang| lang.to_string()),
+            text:           text.to_string(),
+        });
+    }
+}
+
+/// The text shown in the content of `page`, in content order.
+pub fn page_text_fragments<B: Backend>(file: &File<B>, page: &Page) -> Result<Vec<TextFragment>> {
+    let content = file.page_content(page)?;
+    let mut extractor = TextExtractor {
+        file:       file,
+        resources:  page.resources(),
+        fonts:      HashMap::new(),
+        state:      TextState::new(),
+        saved:      Vec::new(),
+        marked:     Vec::new(),
+        fragments:  Vec::new(),
+    };
+    for operation in &content.operations {
+        let result = extractor.operation(&operation.operator, &operation.operands)
+            .chain_err(|| format!("Operation {}", operation.operator));
+        if let Err(e) = result {
+            // (ignored, if not strict)
+            if file.get_parse_options().strict {
+                return Err(e);
+            }
+        }
+    }
+    Ok(extractor.fragments)
+}
+
+/// The parts of the graphics state that text extraction needs.
+#[derive(Clone)]
+struct TextState {
+    ctm:            Matrix,
+    text_matrix:    Matrix,
+    line_matrix:    Matrix,
+    font:           Option<String>,
+    size:           f32,
+    leading:        f32,
+    char_spacing:   f32,
+    word_spacing:   f32,
+    /// `Tz` / 100
+    scale:          f32,
+}
+
+impl TextState {
+    fn new() -> TextState {
+        TextState {
+            ctm:            Matrix::identity(),
+            text_matrix:    Matrix::identity(),
+            line_matrix:    Matrix::identity(),
+            font:           None,
+            size:           0.0,
+            leading:        0.0,
+            char_spacing:   0.0,
+            word_spacing:   0.0,
+            scale:          1.0,
+        }
+    }
+    /// Moves to the start of the next line, offset by `tx`, `ty` from the start of this one.
+    fn next_line(&mut self, tx: f32, ty: f32) {
+        self.line_matrix = Matrix::translate(tx, ty).then(&self.line_matrix);
+        self.text_matrix = self.line_matrix;
+    }
+    /// Moves `tx` (in text space) along the line.
+    fn advance(&mut self, tx: f32) {
+        self.text_matrix = Matrix::translate(tx, 0.0).then(&self.text_matrix);
+    }
+    /// The text matrix in default user space.
+    fn device_matrix(&self) -> Matrix {
+        self.text_matrix.then(&self.ctm)
+    }
+}
+
+/// A marked content section (`BMC` or `BDC` up to `EMC`).
+struct MarkedSection {
+    mcid:           Option<i32>,
+    /// `/ActualText` of the properties: the replacement for the text in the section
+    actual_text:    Option<String>,
+    /// The index of the first fragment in the section
+    start:          usize,
+}
+
+struct TextExtractor<'a, B: Backend + 'a> {
+    file:       &'a File<B>,
+    resources:  Option<&'a Resources>,
+    fonts:      HashMap<String, FontDecoder>,
+    state:      TextState,
+    /// The states saved by `q`
+    saved:      Vec<TextState>,
+    marked:     Vec<MarkedSection>,
+    fragments:  Vec<TextFragment>,
+}
+
+/// The operand `i` as a number.
+fn number(operands: &[Primitive], i: usize) -> Result<f32> {
+    match operands.get(i) {
+        Some(operand) => operand.as_number(),
+        None => bail!("Missing operand"),
+    }
+}
+fn matrix(operands: &[Primitive]) -> Result<Matrix> {
+    Ok(Matrix {
+        a:  number(operands, 0)?,
+        b:  number(operands, 1)?,
+        c:  number(operands, 2)?,
+        d:  number(operands, 3)?,
+        e:  number(operands, 4)?,
+        f:  number(operands, 5)?,
+    })
+}
+
+impl<'a, B: Backend + 'a> TextExtractor<'a, B> {
+    fn operation(&mut self, operator: &str, operands: &[Primitive]) -> Result<()> {
+        match operator {
+            "q" => self.saved.push(self.state.clone()),
+            "Q" => if let Some(saved) = self.saved.pop() {
+                // (the text matrix isn't part of the graphics state)
+                let (text_matrix, line_matrix) = (self.state.text_matrix, self.state.line_matrix);
+                self.state = saved;
+                self.state.text_matrix = text_matrix;
+                self.state.line_matrix = line_matrix;
+            },
+            "cm" => self.state.ctm = matrix(operands)?.then(&self.state.ctm),
+            "BT" => {
+                self.state.text_matrix = Matrix::identity();
+                self.state.line_matrix = Matrix::identity();
+            }
+            "Tf" => {
+                let name = match operands.first() {
+                    Some(&Primitive::Name (ref name)) => name.clone(),
+                    _ => bail!("Missing font name"),
+                };
+                self.state.size = number(operands, 1)?;
+                self.load_font(&name)?;
+                self.state.font = Some(name);
+            }
+            "TL" => self.state.leading = number(operands, 0)?,
+            "Tc" => self.state.char_spacing = number(operands, 0)?,
+            "Tw" => self.state.word_spacing = number(operands, 0)?,
+            "Tz" => self.state.scale = number(operands, 0)? / 100.0,
+            "Td" => self.state.next_line(number(operands, 0)?, number(operands, 1)?),
+            "TD" => {
+                let ty = number(operands, 1)?;
+                self.state.leading = -ty;
+                self.state.next_line(number(operands, 0)?, ty);
+            }
+            "Tm" => {
+                let m = matrix(operands)?;
+                self.state.text_matrix = m;
+                self.state.line_matrix = m;
+            }
+            "T*" => {
+                let leading = self.state.leading;
+                self.state.next_line(0.0, -leading);
+            }
+            "Tj" => self.show(operands)?,
+            "'" => {
+                let leading = self.state.leading;
+                self.state.next_line(0.0, -leading);
+                self.show(operands)?;
+            }
+            "\"" => {
+                self.state.word_spacing = number(operands, 0)?;
+                self.state.char_spacing = number(operands, 1)?;
+                let leading = self.state.leading;
+                self.state.next_line(0.0, -leading);
+                self.show(&operands[2 ..])?;
+            }
+            "TJ" => match operands.first() {
+                Some(&Primitive::Array (ref items)) => self.show(items)?,
+                _ => bail!("Missing array"),
+            },
+            "BMC" => self.marked.push(MarkedSection {mcid: None, actual_text: None, start: self.fragments.len()}),
+            "BDC" => self.begin_marked_content(operands)?,
+            "EMC" => self.end_marked_content(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Reads the font `name` of the resources, if it isn't already. A font that can't be read
+    /// is an error - or, if not strict, a font without `/ToUnicode`.
+    fn load_font(&mut self, name: &str) -> Result<()> {
+        if self.fonts.contains_key(name) {
+            return Ok(());
+        }
+        let resolve = self.file.resolver();
+        let font = self.resources.and_then(|r| r.font.as_ref()).and_then(|fonts| fonts.get(name).cloned());
+        let decoder = match font {
+            Some(font) => Dictionary::from_primitive(font, &resolve)
+                .and_then(|font| FontDecoder::new(self.file, &font)),
+            None => Err(format!("Font {} not in the resources", name).into()),
+        };
+        let decoder = match decoder {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                if resolve.options().strict {
+                    return Err(e).chain_err(|| format!("Font {}", name));
+                }
+                FontDecoder {code_len: 1, default_width: 500.0, .. FontDecoder::default()}
+            }
+        };
+        self.fonts.insert(name.into(), decoder);
+        Ok(())
+    }
+
+    /// Shows the strings of `items` - with, in between, the adjustments of `TJ` (in
+    /// thousandths of the font size; those of more than a quarter of it are spaces).
+    fn show(&mut self, items: &[Primitive]) -> Result<()> {
+        let name = match self.state.font {
+            Some(ref name) => name.clone(),
+            None => bail!("Text shown without a font"),
+        };
+        let mut text = String::new();
+        // in text space
+        let mut advance = 0.0;
+        {
+            let font = &self.fonts[&name];
+            let state = &self.state;
+            for item in items {
+                match *item {
+                    Primitive::String (ref s) => {
+                        for (code_text, width, is_space) in font.decode(s.as_bytes()) {
+                            text.push_str(&code_text);
+                            let spacing = state.char_spacing + if is_space { state.word_spacing } else { 0.0 };
+                            advance += (width / 1000.0 * state.size + spacing) * state.scale;
+                        }
+                    }
+                    Primitive::Integer (_) | Primitive::Number (_) => {
+                        let adjustment = item.as_number()?;
+                        if adjustment < -250.0 && !text.is_empty() && !text.ends_with(' ') {
+                            text.push(' ');
+                        }
+                        advance -= adjustment / 1000.0 * state.size * state.scale;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let m = self.state.device_matrix();
+        let fragment = TextFragment {
+            text:   text,
+            x:      m.e,
+            y:      m.f,
+            size:   self.state.size * m.c.hypot(m.d),
+            width:  advance * m.a.hypot(m.b),
+            mcid:   self.mcid(),
+        };
+        self.state.advance(advance);
+        if !fragment.text.is_empty() {
+            self.fragments.push(fragment);
+        }
+        Ok(())
+    }
+
+    /// The MCID of the innermost marked content section that has one.
+    fn mcid(&self) -> Option<i32> {
+        self.marked.iter().rev().filter_map(|section| section.mcid).next()
+    }
+
+    /// `BDC`: the properties are in the operation, or in `/Properties` of the resources.
+    fn begin_marked_content(&mut self, operands: &[Primitive]) -> Result<()> {
+        let resolve = self.file.resolver();
+        let properties = match operands.get(1) {
+            Some(&Primitive::Dictionary (ref dict)) => Some(dict.clone()),
+            Some(&Primitive::Name (ref name)) => {
+                match self.resources.and_then(|r| r.properties.as_ref()).and_then(|p| p.get(name)) {
+                    Some(properties) => Some(Dictionary::from_primitive(properties.clone(), &resolve)?),
+                    None => None
+                }
+            }
+            _ => None
+        };
+        let (mcid, actual_text) = match properties {
+            Some(ref properties) => (
+                properties.get_as("MCID", &resolve)?,
+                properties.get_as::<PdfString>("ActualText", &resolve)?.map(|s| s.to_string_lossy())
+            ),
+            None => (None, None)
+        };
+        self.marked.push(MarkedSection {mcid: mcid, actual_text: actual_text, start: self.fragments.len()});
+        Ok(())
+    }
+
+    /// `EMC`: the text of a section with `/ActualText` is replaced.
+    fn end_marked_content(&mut self) {
+        let section = match self.marked.pop() {
+            Some(section) => section,
+            None => return,
+        };
+        let text = match section.actual_text {
+            Some(text) => text,
+            None => return,
+        };
+        let (x, y, size) = match self.fragments.get(section.start) {
+            Some(fragment) => (fragment.x, fragment.y, fragment.size),
+            None => {
+                let m = self.state.device_matrix();
+                (m.e, m.f, self.state.size * m.c.hypot(m.d))
+            }
+        };
+        let width = self.fragments[section.start ..].iter().map(|fragment| fragment.width).sum();
+        self.fragments.truncate(section.start);
+        let mcid = section.mcid.or(self.mcid());
+        self.fragments.push(TextFragment {text: text, x: x, y: y, size: size, width: width, mcid: mcid});
+    }
+}
+
+/// Maps the character codes of a font to text and widths.
+#[derive(Debug, Default)]
+struct FontDecoder {
+    /// The length of the codes - 2 for Type0 fonts (unless the `/ToUnicode` CMap says
+    /// otherwise), else 1
+    code_len:       usize,
+    /// From `/ToUnicode`
+    to_unicode:     HashMap<u32, String>,
+    /// The glyph widths, in thousandths of the font size
+    widths:         HashMap<u32, f32>,
+    default_width:  f32,
+}
+
+impl FontDecoder {
+    fn new<B: Backend>(file: &File<B>, font: &Dictionary) -> Result<FontDecoder> {
+        let resolve = file.resolver();
+        let subtype: Option<String> = font.get_as("Subtype", &resolve)?;
+        let composite = subtype.as_ref().map_or(false, |subtype| subtype == "Type0");
+        let mut decoder = FontDecoder {
+            code_len:       if composite { 2 } else { 1 },
+            to_unicode:     HashMap::new(),
+            widths:         HashMap::new(),
+            default_width:  if composite { 1000.0 } else { 500.0 },
+        };
+        if let Some(to_unicode) = font.get("ToUnicode") {
+            if let Primitive::Name (_) = *to_unicode {
+                // (like /Identity-H - not a mapping to Unicode)
+            } else {
+                let mut stream = Stream::<Dictionary>::from_primitive(to_unicode.clone(), &resolve)?;
+                file.decode_stream(&mut stream)?;
+                let (code_len, map) = parse_cmap(stream.get_data_raw()).chain_err(|| "ToUnicode CMap")?;
+                if let Some(code_len) = code_len {
+                    decoder.code_len = code_len;
+                }
+                decoder.to_unicode = map;
+            }
+        }
+
+        if composite {
+            let descendants: Vec<Primitive> = font.get_as("DescendantFonts", &resolve)?.unwrap_or_default();
+            if let Some(descendant) = descendants.into_iter().next() {
+                let descendant = Dictionary::from_primitive(descendant, &resolve)?;
+                if let Some(width) = descendant.get_as("DW", &resolve)? {
+                    decoder.default_width = width;
+                }
+                let w: Vec<Primitive> = descendant.get_as("W", &resolve)?.unwrap_or_default();
+                decoder.read_cid_widths(w, &resolve)?;
+            }
+        } else if let Some(widths) = font.get_as::<Vec<f32>>("Widths", &resolve)? {
+            // (only the standard 14 fonts may leave out all three)
+            check_together(font, &["FirstChar", "LastChar", "Widths"])?;
+            let first: i32 = font.get_as("FirstChar", &resolve)?.unwrap_or(0);
+            check_min("FirstChar", first, 0)?;
+            check_max("FirstChar", first, 255)?;
+            for (i, width) in widths.into_iter().enumerate() {
+                decoder.widths.insert((first + i as i32) as u32, width);
+            }
+        }
+        Ok(decoder)
+    }
+
+    /// `/W` of a CIDFont: `c [w1 w2 ...]` - the widths from `c` on - or `c_first c_last w`.
+    fn read_cid_widths(&mut self, w: Vec<Primitive>, resolve: &Resolve) -> Result<()> {
+        let mut items = w.into_iter();
+        while let Some(first) = items.next() {
+            let first = i32::from_primitive(first, resolve)? as u32;
+            match items.next() {
+                Some(Primitive::Array (widths)) => {
+                    for (i, width) in widths.into_iter().enumerate() {
+                        self.widths.insert(first + i as u32, f32::from_primitive(width, resolve)?);
+                    }
+                }
+                Some(last) => {
+                    let last = i32::from_primitive(last, resolve)? as u32;
+                    let width = match items.next() {
+                        Some(width) => f32::from_primitive(width, resolve)?,
+                        None => bail!(ErrorKind::InvalidEntry {key: "W", reason: "missing width".into()}),
+                    };
+                    // (guarding against huge ranges)
+                    for code in (first .. last.saturating_add(1)).take(0x10000) {
+                        self.widths.insert(code, width);
+                    }
+                }
+                None => bail!(ErrorKind::InvalidEntry {key: "W", reason: "missing widths".into()}),
+            }
+        }
+        Ok(())
+    }
+
+    /// The text, width and whether it is a space (for word spacing), of each code in `data`.
+    fn decode(&self, data: &[u8]) -> Vec<(String, f32, bool)> {
+        let code_len = if self.code_len == 0 { 1 } else { self.code_len };
+        data.chunks(code_len).map(|bytes| {
+            let code = code(bytes);
+            let text = match self.to_unicode.get(&code) {
+                Some(text) => text.clone(),
+                None if code_len == 1 => win_ansi_char(bytes[0]).to_string(),
+                None => '\u{FFFD}'.to_string(),
+            };
+            let width = self.widths.get(&code).cloned().unwrap_or(self.default_width);
+            (text, width, code_len == 1 && code == 32)
+        }).collect()
+    }
+}
+
+/// The code of the (big-endian) bytes.
+fn code(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, &b| code << 8 | b as u32)
+}
+fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks(2).map(|c| if c.len() == 2 { (c[0] as u16) << 8 | c[1] as u16 } else { 0xFFFD }).collect()
+}
+
+/// Reads a `/ToUnicode` CMap: the length of the codes (in the first `codespacerange`) and
+/// the text of the codes (`bfchar` and `bfrange`).
+fn parse_cmap(data: &[u8]) -> Result<(Option<usize>, HashMap<u32, String>)> {
+    // (a CMap is a PostScript program - close enough to a content stream)
+    let cmap = Content::parse_from(data)?;
+    let mut code_len = None;
+    let mut map = HashMap::new();
+    for operation in &cmap.operations {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "endcodespacerange" => {
+                if let Some(&Primitive::String (ref low)) = operands.first() {
+                    code_len = code_len.or(Some(low.as_bytes().len()));
+                }
+            }
+            "endbfchar" => {
+                for pair in operands.chunks(2) {
+                    if let (Some(&Primitive::String (ref src)), Some(&Primitive::String (ref dst))) = (pair.get(0), pair.get(1)) {
+                        map.insert(code(src.as_bytes()), String::from_utf16_lossy(&utf16_units(dst.as_bytes())));
+                    }
+                }
+            }
+            "endbfrange" => {
+                for range in operands.chunks(3) {
+                    if range.len() < 3 {
+                        continue;
+                    }
+                    let (low, high) = match (&range[0], &range[1]) {
+                        (&Primitive::String (ref low), &Primitive::String (ref high)) => (code(low.as_bytes()), code(high.as_bytes())),
+                        _ => continue,
+                    };
+                    for (i, c) in (low .. high.saturating_add(1)).take(0x10000).enumerate() {
+                        let text = match range[2] {
+                            // the last unit is incremented along the range
+                            Primitive::String (ref dst) => {
+                                let mut units = utf16_units(dst.as_bytes());
+                                if let Some(last) = units.last_mut() {
+                                    *last = last.wrapping_add(i as u16);
+                                }
+                                String::from_utf16_lossy(&units)
+                            }
+                            Primitive::Array (ref dsts) => match dsts.get(i) {
+                                Some(&Primitive::String (ref dst)) => String::from_utf16_lossy(&utf16_units(dst.as_bytes())),
+                                _ => continue,
+                            },
+                            _ => continue,
+                        };
+                        map.insert(c, text);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((code_len, map))
+}
+
+/// `WinAnsiEncoding` from 0x80 to 0x9F (the rest is Latin-1).
+const WIN_ANSI_80: [char; 32] = [
+    '€', '\u{FFFD}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{FFFD}', 'Ž', '\u{FFFD}',
+    '\u{FFFD}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{FFFD}', 'ž', 'Ÿ',
+];
+
+fn win_ansi_char(b: u8) -> char {
+    match b {
+        0x80 ... 0x9F => WIN_ANSI_80[(b - 0x80) as usize],
+        b => b as char,
+    }
+}
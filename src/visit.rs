@@ -0,0 +1,74 @@
+//! Walking the object graph of a document - everything reachable from the trailer - for
+//! auditing, statistics or finding the unused objects.
+
+use std::collections::HashSet;
+use err::*;
+use object::{PlainRef, Resolve};
+use primitive::Primitive;
+
+/// The callbacks of `walk`. They do nothing by default.
+pub trait Visitor {
+    /// Called once for every indirect object that is reached, before its contents. If it returns
+    /// `false`, the references in the object are not followed (from here).
+    fn visit_object(&mut self, _r: PlainRef, _object: &Primitive) -> Result<bool> {
+        Ok(true)
+    }
+    /// Called for every primitive: the indirect objects and all primitives in them, outer first.
+    /// `object` is the indirect object that `p` is part of (`None` for the start of the walk).
+    fn visit_primitive(&mut self, _p: &Primitive, _object: Option<PlainRef>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Visits `start` and all objects reachable from it, resolving each indirect object once - so
+/// objects that refer to each other are fine.
+///
+/// References to free or non-existing objects are `null` (PDF reference, section 3.2.9), so
+/// they are skipped.
+pub fn walk<V: Visitor>(start: &Primitive, resolve: &Resolve, visitor: &mut V) -> Result<()> {
+    let mut visited = HashSet::new();
+    let mut pending = Vec::new();
+    visit_direct(start, None, visitor, &mut pending)?;
+
+    while let Some(r) = pending.pop() {
+        if !visited.insert(r) {
+            continue;
+        }
+        let object = match resolve.resolve(r) {
+            Ok(p) => p,
+            Err(Error(ErrorKind::FreeObject {..}, _))
+            | Err(Error(ErrorKind::NullRef {..}, _))
+            | Err(Error(ErrorKind::UnspecifiedXRefEntry {..}, _)) => continue,
+            Err(e) => return Err(e).chain_err(|| format!("Visiting object {} {} R", r.id, r.gen)),
+        };
+        if visitor.visit_object(r, &object)? {
+            visit_direct(&object, Some(r), visitor, &mut pending)?;
+        }
+    }
+    Ok(())
+}
+
+/// Visits `p` and the primitives in it, and adds the references in it to `pending`.
+fn visit_direct<V: Visitor>(p: &Primitive, object: Option<PlainRef>, visitor: &mut V, pending: &mut Vec<PlainRef>) -> Result<()> {
+    visitor.visit_primitive(p, object)?;
+    match *p {
+        Primitive::Array (ref array) => {
+            for p in array {
+                visit_direct(p, object, visitor, pending)?;
+            }
+        }
+        Primitive::Dictionary (ref dict) => {
+            for (_, p) in dict.iter() {
+                visit_direct(p, object, visitor, pending)?;
+            }
+        }
+        Primitive::Stream (ref stream) => {
+            for (_, p) in stream.info.iter() {
+                visit_direct(p, object, visitor, pending)?;
+            }
+        }
+        Primitive::Reference (r) => pending.push(r),
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! XMP metadata (the `/Metadata` stream of the catalog): a minimal view of the properties in the
+//! Dublin Core (`dc:`), XMP basic (`xmp:`) and Adobe PDF (`pdf:`) schemas.
+//!
+//! This is no XML parser - it reads the simple properties of the RDF serialization that XMP
+//! writers use, and ignores everything else.
+
+use std::collections::BTreeMap;
+
+/// The namespace prefixes whose properties are read
+const PREFIXES: &[&str] = &["dc:", "xmp:", "pdf:"];
+
+/// The properties of the XMP packet `xml`, by qualified name (like `dc:title` or
+/// `xmp:CreateDate`).
+///
+/// Properties can be attributes of `rdf:Description` or elements. The items of arrays
+/// (`rdf:Seq`, `rdf:Bag` and `rdf:Alt`) are joined with `"; "` - so a `dc:title` in several
+/// languages is all of them.
+pub fn properties(xml: &[u8]) -> BTreeMap<String, String> {
+    let xml = String::from_utf8_lossy(xml);
+    let mut properties = BTreeMap::new();
+    // the property element we are in, and the text found in it
+    let mut current: Option<(String, Vec<String>)> = None;
+
+    let mut rest: &str = &xml;
+    while let Some(start) = rest.find('<') {
+        if let Some((_, ref mut values)) = current {
+            let text = rest[.. start].trim();
+            if !text.is_empty() {
+                values.push(unescape(text));
+            }
+        }
+        rest = &rest[start ..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1 .. end];
+        rest = &rest[end + 1 ..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if tag.starts_with('/') {
+            let name = tag[1 ..].trim();
+            let done = match current {
+                Some((ref current, _)) => current == name,
+                None => false
+            };
+            if done {
+                if let Some((name, values)) = current.take() {
+                    properties.insert(name, values.join("; "));
+                }
+            }
+            continue;
+        }
+        let empty = tag.ends_with('/');
+        let tag = tag.trim_right_matches('/');
+        let name = tag.split_whitespace().next().unwrap_or("");
+        for (key, value) in attributes(&tag[name.len() ..]) {
+            if is_property(key) {
+                properties.insert(key.to_string(), unescape(value));
+            }
+        }
+        if current.is_none() && !empty && is_property(name) {
+            current = Some((name.to_string(), Vec::new()));
+        }
+    }
+    properties
+}
+
+fn is_property(name: &str) -> bool {
+    PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// The `key="value"` (or `key='value'`) pairs in `s`.
+fn attributes(mut s: &str) -> Vec<(&str, &str)> {
+    let mut attributes = Vec::new();
+    while let Some(eq) = s.find('=') {
+        let key = s[.. eq].trim();
+        let after = s[eq + 1 ..].trim_left();
+        let quote = match after.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => break,
+        };
+        let value_end = match after[1 ..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        attributes.push((key, &after[1 .. value_end]));
+        s = &after[value_end + 1 ..];
+    }
+    attributes
+}
+
+/// Replaces the predefined entities and character references.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[.. amp]);
+        rest = &rest[amp ..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let c = match &rest[1 .. end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity if entity.starts_with("#x") => u32::from_str_radix(&entity[2 ..], 16).ok().and_then(::std::char::from_u32),
+            entity if entity.starts_with('#') => entity[1 ..].parse().ok().and_then(::std::char::from_u32),
+            _ => None
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1 ..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1 ..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
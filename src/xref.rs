@@ -1,7 +1,10 @@
 use err::*;
 use std;
+use std::{io, cmp};
 use std::fmt::{Debug, Formatter};
 use object::*;
+use primitive::Primitive;
+use file::XRefInfo;
 
 ///////////////////////////
 // Cross-reference table //
@@ -47,16 +50,26 @@ impl XRef {
 pub struct XRefTable {
     // None means that it's not specified, and should result in an error if used
     // Thought: None could also mean Free?
-    entries: Vec<XRef>
+    entries: Vec<XRef>,
+    revisions: usize,
+    /// Object numbers from this one on are ignored, see `with_max_id`
+    max_id: ObjNr,
 }
 
 
 impl XRefTable {
     pub fn new(num_objects: ObjNr) -> XRefTable {
+        XRefTable::with_max_id(num_objects, ObjNr::max_value())
+    }
+    /// A table that ignores the object numbers from `max_id` on - for reading a file, whose
+    /// length bounds the number of objects: so that a damaged one can't make the table huge.
+    pub fn with_max_id(num_objects: ObjNr, max_id: ObjNr) -> XRefTable {
         let mut entries = Vec::new();
-        entries.resize(num_objects as usize, XRef::Invalid);
+        entries.resize(cmp::min(num_objects, max_id) as usize, XRef::Invalid);
         XRefTable {
             entries: entries,
+            revisions: 0,
+            max_id: max_id,
         }
     }
 
@@ -87,22 +100,114 @@ impl XRefTable {
         self.entries.len()
     }
 
-    pub fn add_entries_from(&mut self, section: XRefSection) {
-        for (i, entry) in section.entries.iter().enumerate() {
-            // Early return if the entry we have has larger or equal generation number
-            let should_be_updated = match self.entries[i] {
-                XRef::Raw { gen_nr: gen, .. } | XRef::Free { gen_nr: gen, .. }
-                    => entry.get_gen_nr() > gen,
-                XRef::Stream { .. } | XRef::Invalid
-                    => true,
-                x => panic!("found {:?}", x)
-            };
-            let dst = &mut self.entries[section.first_id as usize + i];
-            if should_be_updated {
-                *dst = *entry;
+    /// Adds the sections of one revision of the document. Revisions have to be added from the
+    /// newest to the oldest: entries that were set by a newer revision are not changed.
+    pub fn add_revision(&mut self, sections: Vec<XRefSection>) {
+        for section in sections {
+            for (i, entry) in section.entries.into_iter().enumerate() {
+                self.set_if_invalid((section.first_id as ObjNr).saturating_add(i as ObjNr), entry);
+            }
+        }
+        self.revisions += 1;
+    }
+    /// Sets the entry of `id` unless it's already specified (or `id` is too large, see
+    /// `with_max_id`).
+    pub fn set_if_invalid(&mut self, id: ObjNr, entry: XRef) {
+        if id >= self.max_id {
+            return;
+        }
+        let id = id as usize;
+        if id >= self.entries.len() {
+            self.entries.resize(id + 1, XRef::Invalid);
+        }
+        if let XRef::Invalid = self.entries[id] {
+            self.entries[id] = entry;
+        }
+    }
+    /// Object numbers and generation numbers of the free objects, following the chain of free
+    /// entries that starts at object 0. The generation number is the one for reusing the object.
+    pub fn free_list(&self) -> Vec<(ObjNr, GenNr)> {
+        let mut list = Vec::new();
+        let mut next = match self.entries.first() {
+            Some(&XRef::Free {next_obj_nr, ..}) => next_obj_nr,
+            _ => return list
+        };
+        while next != 0 {
+            if list.iter().any(|&(id, _)| id == next) {
+                break; // broken chain
+            }
+            match self.entries.get(next as usize) {
+                Some(&XRef::Free {next_obj_nr, gen_nr}) => {
+                    list.push((next, gen_nr));
+                    next = next_obj_nr;
+                }
+                _ => break
+            }
+        }
+        list
+    }
+    /// Reserves an object number for a new object, and marks it `Promised`. Free objects are
+    /// reused (with their next generation number), except those whose generation number has
+    /// reached the maximum of 65535.
+    pub fn allocate(&mut self) -> PlainRef {
+        let free = self.entries.iter().enumerate().skip(1).filter_map(|(id, entry)| match *entry {
+            XRef::Free {gen_nr, ..} if gen_nr < 65535 => Some((id, gen_nr)),
+            _ => None
+        }).next();
+        match free {
+            Some((id, gen_nr)) => {
+                self.unlink_free(id as ObjNr);
+                self.entries[id] = XRef::Promised;
+                PlainRef {id: id as ObjNr, gen: gen_nr}
+            }
+            None => {
+                self.entries.push(XRef::Promised);
+                PlainRef {id: self.entries.len() as ObjNr - 1, gen: 0}
+            }
+        }
+    }
+    /// Removes `id` from the chain of free entries.
+    fn unlink_free(&mut self, id: ObjNr) {
+        let next = match self.entries[id as usize] {
+            XRef::Free {next_obj_nr, ..} => next_obj_nr,
+            _ => return
+        };
+        for entry in &mut self.entries {
+            if let XRef::Free {ref mut next_obj_nr, ..} = *entry {
+                if *next_obj_nr == id {
+                    *next_obj_nr = next;
+                }
             }
         }
     }
+    /// Frees the object `id`: its entry is put at the start of the free list, with the
+    /// generation number incremented (for references to it after its reuse).
+    pub fn free(&mut self, id: ObjNr) {
+        let gen_nr = match self.get(id) {
+            _ if id == 0 || id >= self.max_id => return,
+            Ok(XRef::Free {..}) => return,
+            Ok(XRef::Raw {gen_nr, ..}) => gen_nr.saturating_add(1),
+            Ok(XRef::Stream {..}) => 1,
+            _ => 0
+        };
+        let head = match self.entries.first() {
+            Some(&XRef::Free {next_obj_nr, ..}) => next_obj_nr,
+            _ => 0
+        };
+        if self.entries.is_empty() {
+            self.entries.push(XRef::Free {next_obj_nr: 0, gen_nr: 65535});
+        }
+        if id as usize >= self.entries.len() {
+            self.entries.resize(id as usize + 1, XRef::Invalid);
+        }
+        self.entries[id as usize] = XRef::Free {next_obj_nr: head, gen_nr: gen_nr};
+        self.entries[0] = XRef::Free {next_obj_nr: id, gen_nr: 65535};
+    }
+    /// Number of revisions added with `add_revision` - 1 for files that were never incrementally
+    /// updated.
+    pub fn num_revisions(&self) -> usize {
+        self.revisions
+    }
 }
 
 impl Debug for XRefTable {
@@ -130,6 +235,33 @@ impl Debug for XRefTable {
     }
 }
 
+/// Where an object is stored in the file (see `File::layout`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectLocation {
+    /// `len` bytes at `offset`, from the object header up to and including `endobj`
+    Raw {offset: usize, len: usize},
+    /// Object `index` of the object stream `stream_id`
+    Stream {stream_id: ObjNr, index: usize},
+    Free,
+}
+
+/// An entry of the layout of a file (see `File::layout`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectLayout {
+    pub id:         ObjNr,
+    /// The generation number (of free objects: the one for reusing the number)
+    pub gen:        GenNr,
+    pub location:   ObjectLocation,
+}
+impl ObjectLayout {
+    pub fn in_object_stream(&self) -> bool {
+        match self.location {
+            ObjectLocation::Stream {..} => true,
+            _ => false
+        }
+    }
+}
+
 /// As found in PDF files
 #[derive(Debug)]
 pub struct XRefSection {
@@ -154,6 +286,78 @@ impl XRefSection {
 }
 
 
+/// Cross-reference stream (PDF 1.5): the xref sections encoded as binary entries in a stream,
+/// whose dictionary is also the trailer.
+#[derive(Debug)]
+pub struct XRefStream {
+    pub stream: Stream<XRefInfo>,
+}
+impl Object for XRefStream {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.stream.serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &Resolve) -> Result<Self> {
+        Ok(XRefStream {
+            stream: Stream::from_primitive(p, resolve)?,
+        })
+    }
+}
+impl XRefStream {
+    /// Decodes the entries: `/W` gives the width in bytes of the three fields of each entry, and
+    /// `/Index` the object numbers of the sections.
+    pub fn sections(&self) -> Result<Vec<XRefSection>> {
+        let info = &self.stream.info;
+        if info.w.len() != 3 || info.w.iter().any(|&w| w < 0 || w > 8) {
+            bail!("Invalid /W {:?} in xref stream", info.w);
+        }
+        if info.index.len() % 2 != 0 {
+            bail!("Odd number of elements in /Index of xref stream");
+        }
+        let data = self.stream.get_data()?;
+        let mut data_left = &data[..];
+
+        let mut sections = Vec::new();
+        for (first_id, num_objects) in info.index.chunks(2).map(|c| (c[0], c[1])) {
+            sections.push(read_section(first_id as u32, num_objects as usize, &info.w, &mut data_left)?);
+        }
+        Ok(sections)
+    }
+}
+
+/// Reads an integer of `width` bytes (big endian) from `data`, which it consumes.
+fn read_field(width: i32, data: &mut &[u8]) -> u64 {
+    let (field, rest) = data.split_at(width as usize);
+    *data = rest;
+    field.iter().fold(0, |n, &b| n << 8 | b as u64)
+}
+
+fn read_section(first_id: u32, num_entries: usize, width: &[i32], data: &mut &[u8]) -> Result<XRefSection> {
+    let entry_len = width.iter().sum::<i32>() as usize;
+    if data.len() < num_entries * entry_len {
+        bail!("Xref stream is too short for /Index");
+    }
+    let mut section = XRefSection::new(first_id);
+    for _ in 0 .. num_entries {
+        // A missing type field means type 1
+        let kind = match width[0] {
+            0 => 1,
+            w => read_field(w, data)
+        };
+        let field1 = read_field(width[1], data);
+        let field2 = read_field(width[2], data);
+
+        let entry = match kind {
+            0 => XRef::Free {next_obj_nr: field1 as ObjNr, gen_nr: field2 as GenNr},
+            1 => XRef::Raw {pos: field1 as usize, gen_nr: field2 as GenNr},
+            2 => XRef::Stream {stream_id: field1 as ObjNr, index: field2 as usize},
+            // Other types are references to the null object
+            _ => XRef::Invalid,
+        };
+        section.entries.push(entry);
+    }
+    Ok(section)
+}
+
 /// Iterates over the used object numbers in this xref table, skips the free objects.
 pub struct ObjectNrIter<'a> {
     xref_table: &'a XRefTable,
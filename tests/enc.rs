@@ -0,0 +1,242 @@
+extern crate pdf;
+
+use pdf::enc::*;
+use pdf::primitive::Dictionary;
+use pdf::object::{Object, Stream, NO_RESOLVE};
+use pdf::parser::parse;
+use std::sync::Arc;
+
+fn flate(predictor: i32, colors: i32, bits_per_component: i32, columns: i32) -> StreamFilter {
+    StreamFilter::FlateDecode (LZWFlateParams {
+        predictor: predictor,
+        n_components: colors,
+        bits_per_component: bits_per_component,
+        columns: columns,
+        .. LZWFlateParams::default()
+    })
+}
+
+#[test]
+fn flate_roundtrip() {
+    let data: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+    let filter = flate(1, 1, 8, 1);
+    let encoded = encode(&data, &filter).unwrap();
+    assert_eq!(decode(&encoded, &filter).unwrap(), data);
+}
+
+#[test]
+fn flate_predictors_roundtrip() {
+    // 3 colors, 8 bit, 10 columns = 30 bytes per row, the last row is incomplete
+    let data: Vec<u8> = (0..1000).map(|i| (i * 13 % 256) as u8).collect();
+    for &predictor in &[2, 10, 11, 12, 13, 14, 15] {
+        let filter = flate(predictor, 3, 8, 10);
+        let encoded = encode(&data, &filter).unwrap();
+        assert_eq!(decode(&encoded, &filter).unwrap(), data, "Predictor {}", predictor);
+    }
+    // TIFF predictor with sub-byte and 16 bit samples
+    for &bits in &[1, 2, 4, 16] {
+        let filter = flate(2, 1, bits, 16);
+        let len = (bits * 16 / 8 * 20) as usize;
+        let encoded = encode(&data[.. len], &filter).unwrap();
+        assert_eq!(decode(&encoded, &filter).unwrap(), &data[.. len], "BitsPerComponent {}", bits);
+    }
+}
+
+#[test]
+fn lzw_roundtrip() {
+    // Long enough to fill the code table a few times
+    let data: Vec<u8> = (0..100000u32).map(|i| ((i * i) >> 5) as u8).collect();
+    for &early_change in &[0, 1] {
+        let filter = StreamFilter::LZWDecode (LZWFlateParams {
+            early_change: early_change,
+            .. LZWFlateParams::default()
+        });
+        let encoded = encode(&data, &filter).unwrap();
+        assert_eq!(decode(&encoded, &filter).unwrap(), data, "EarlyChange {}", early_change);
+    }
+}
+
+#[test]
+fn lzw_decode_spec_example() {
+    // Example from section 3.3.3 of the PDF reference: "-----A---B" with EarlyChange 1
+    let encoded = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+    let filter = StreamFilter::LZWDecode (LZWFlateParams::default());
+    assert_eq!(decode(&encoded, &filter).unwrap(), b"-----A---B");
+}
+
+#[test]
+fn ascii_hex() {
+    let filter = StreamFilter::ASCIIHexDecode;
+    assert_eq!(decode(b"48 65 6C6c\n6F>", &filter).unwrap(), b"Hello");
+    // odd number of digits: the last one is padded with 0
+    assert_eq!(decode(b"7>", &filter).unwrap(), b"\x70");
+    let data: Vec<u8> = (0..256).map(|i| i as u8).collect();
+    assert_eq!(decode(&encode(&data, &filter).unwrap(), &filter).unwrap(), data);
+}
+
+#[test]
+fn ascii_85() {
+    let filter = StreamFilter::ASCII85Decode;
+    assert_eq!(decode(b"87cURD]i,\"Ebo80~>", &filter).unwrap(), b"Hello World!");
+    assert_eq!(decode(b"87cUR\nD]i,\"Eb o7~>", &filter).unwrap(), b"Hello World");
+    assert_eq!(decode(b"z~>", &filter).unwrap(), &[0, 0, 0, 0]);
+    for len in 0..10 {
+        let data: Vec<u8> = (0..len).map(|i| i * 40).collect();
+        assert_eq!(decode(&encode(&data, &filter).unwrap(), &filter).unwrap(), data);
+    }
+}
+
+#[test]
+fn filter_chain() {
+    // [/ASCII85Decode /FlateDecode]
+    let filters = [StreamFilter::ASCII85Decode, flate(1, 1, 8, 1)];
+    let data = b"BT /F1 12 Tf 72 712 Td (A stream) Tj ET".to_vec();
+    let encoded = filters.iter().rev().fold(data.clone(), |d, f| encode(&d, f).unwrap());
+    let decoded = filters.iter().fold(encoded, |d, f| decode(&d, f).unwrap());
+    assert_eq!(decoded, data);
+}
+
+#[test]
+fn dct_image_info() {
+    let jpeg = [
+        0xFF, 0xD8,                                 // SOI
+        0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00,         // APP0 (truncated)
+        0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x10,   // SOF0: 8 bit, height 16
+        0x00, 0x20, 0x03,                           // width 32, 3 components
+        1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1,
+        0xFF, 0xDA,                                 // SOS
+    ];
+    let filter = StreamFilter::DCTDecode (DCTDecodeParams { color_transform: None });
+    assert!(filter.is_image_codec());
+    assert_eq!(image_info(&jpeg, &filter).unwrap(), ImageInfo {
+        width: 32,
+        height: 16,
+        components: 3,
+        bits_per_component: 8,
+    });
+}
+
+#[test]
+fn ccitt_fax() {
+    // 16x6 pixels:
+    //   ................
+    //   ..####....####..
+    //   ..####....####..
+    //   .......##.......
+    //   #..............#
+    //   .##############.
+    let expected = [0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0x7F, 0x7F, 0xFE, 0x80, 0x01];
+    let g4: &[u8] = &[0x97, 0x66, 0xDF, 0xE2, 0x08, 0xFE, 0x4D, 0x50, 0xAB, 0x50, 0x01, 0x00, 0x10];
+    let g3: &[u8] = &[0xA9, 0xDD, 0xB7, 0x77, 0x6D, 0xFF, 0xF3, 0x55, 0xA2, 0x1C, 0x1C, 0x70];
+    let g3_2d: &[u8] = &[0xD5, 0x77, 0x6D, 0xEE, 0xED, 0xBF, 0xFF, 0x9A, 0xAD, 0x14, 0x70, 0x71, 0xC0];
+
+    for &(k, data) in &[(-1, g4), (0, g3), (1, g3_2d)] {
+        let filter = StreamFilter::CCITTFaxDecode (CCITTFaxDecodeParams {
+            k: k,
+            end_of_line: false,
+            encoded_byte_align: false,
+            columns: 16,
+            rows: 6,
+            end_of_block: true,
+            black_is_1: false,
+            damaged_rows_before_error: 0,
+        });
+        assert_eq!(decode(data, &filter).unwrap(), &expected, "K = {}", k);
+    }
+}
+
+#[test]
+fn jpx_image_info() {
+    let jp2 = [
+        0, 0, 0, 12, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A,   // signature box
+        0, 0, 0, 30, b'j', b'p', b'2', b'h',                            // header superbox
+        0, 0, 0, 22, b'i', b'h', b'd', b'r',
+        0, 0, 0, 48, 0, 0, 0, 64, 0, 3, 7, 7, 0, 0,                     // 64x48, 3 components, 8 bit
+    ];
+    let filter = StreamFilter::JPXDecode;
+    assert!(filter.is_image_codec());
+    assert_eq!(image_info(&jp2, &filter).unwrap(), ImageInfo {
+        width: 64,
+        height: 48,
+        components: 3,
+        bits_per_component: 8,
+    });
+}
+
+#[test]
+fn decode_reader_matches_decode() {
+    use std::io::Read;
+    let data: Vec<u8> = (0..5000).map(|i| (i * 13 % 256) as u8).collect();
+    for filter in &[flate(1, 1, 8, 1), flate(2, 3, 8, 10), flate(15, 3, 8, 10), StreamFilter::ASCII85Decode] {
+        let encoded = encode(&data, filter).unwrap();
+        let mut decoded = Vec::new();
+        decode_reader(Box::new(&encoded[..]), filter).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data, "{:?}", filter);
+    }
+}
+
+#[test]
+fn predictor_row_length() {
+    use std::io::Read;
+    // Colors * BitsPerComponent * Columns doesn't fit
+    let encoded = encode(b"data", &flate(1, 1, 8, 1)).unwrap();
+    for &predictor in &[2, 10] {
+        let filter = flate(predictor, 1 << 16, 16, 1 << 16);
+        assert!(decode(&encoded, &filter).is_err());
+        assert!(decode_reader(Box::new(&encoded[..]), &filter).read_to_end(&mut Vec::new()).is_err());
+    }
+    // Rows much longer than the data (and a pixel longer than it), without allocating them
+    let data: Vec<u8> = (0..100).map(|i| (i * 13 % 256) as u8).collect();
+    for &(predictor, colors, bits, len) in &[(2, 1, 8, 100), (10, 1, 8, 100), (15, 1, 8, 100), (15, 3, 16, 3)] {
+        let filter = flate(predictor, colors, bits, 1 << 24);
+        let encoded = encode(&data[.. len], &filter).unwrap();
+        assert_eq!(decode(&encoded, &filter).unwrap(), &data[.. len], "{:?}", filter);
+        let mut decoded = Vec::new();
+        decode_reader(Box::new(&encoded[..]), &filter).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, &data[.. len], "{:?}", filter);
+    }
+}
+
+struct Xor;
+impl CustomFilter for Xor {
+    fn decode(&self, data: &[u8], _: &Dictionary) -> pdf::Result<Vec<u8>> {
+        Ok(data.iter().map(|b| b ^ 0x55).collect())
+    }
+}
+
+#[test]
+fn custom_filter_registry() {
+    assert!(StreamFilter::from_kind_and_params("XorDecode", Dictionary::new(), NO_RESOLVE).is_err());
+    register_filter("XorDecode", Arc::new(Xor));
+    let filter = StreamFilter::from_kind_and_params("XorDecode", Dictionary::new(), NO_RESOLVE).unwrap();
+    assert_eq!(filter.name(), "XorDecode");
+    assert_eq!(decode(&[0x55 ^ b'a', 0x55 ^ b'b'], &filter).unwrap(), b"ab");
+    assert!(encode(b"ab", &filter).is_err());
+}
+
+#[test]
+fn decode_limits() {
+    let data = vec![0; 100000];
+    let filter = flate(1, 1, 8, 1);
+    let encoded = encode(&data, &filter).unwrap();
+    assert!(decode_limited(&encoded, &filter, 1000).is_err());
+    assert_eq!(decode_limited(&encoded, &filter, 100000).unwrap(), data);
+
+    let limits = DecodeLimits::new(100000, 150000);
+    assert_eq!(limits.stream_limit(), 100000);
+    limits.add(100000).unwrap();
+    assert_eq!(limits.stream_limit(), 50000);
+    assert!(limits.add(100000).is_err());
+
+    // also for the decoded data of streams
+    let mut object = format!("<< /Length {} /Filter /FlateDecode >>\nstream\n", encoded.len()).into_bytes();
+    object.extend_from_slice(&encoded);
+    object.extend_from_slice(b"\nendstream");
+    let p = parse(&object, NO_RESOLVE).unwrap();
+    let stream = Stream::<Dictionary>::from_primitive(p, NO_RESOLVE).unwrap();
+    assert!(stream.get_data_limited(&DecodeLimits::new(1000, 1000)).is_err());
+    let limits = DecodeLimits::new(100000, 150000);
+    assert_eq!(&*stream.get_data_limited(&limits).unwrap(), &data[..]);
+    assert_eq!(limits.total(), 100000);
+    assert!(stream.get_data_limited(&limits).is_err());
+}
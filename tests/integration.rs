@@ -18,7 +18,8 @@ macro_rules! file_path {
 #[test]
 fn open_file() {
     let _ = File::<Vec<u8>>::open(file_path!("example.pdf")).unwrap_or_else(|e| print_err(e));
-    let _ = File::<Mmap>::open(file_path!("example.pdf")).unwrap_or_else(|e| print_err(e));
+    let file = File::<Mmap>::open(file_path!("example.pdf")).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.version().to_string(), "1.7");
 }
 
 #[test]
@@ -56,3 +57,1772 @@ fn parse_objects_from_stream() {
 }
 
 // TODO test decoding
+
+#[test]
+fn read_objects_sequentially() {
+    use std::fs;
+    use pdf::parser::ObjectReader;
+    let mut reader = ObjectReader::new(fs::File::open(file_path!("example.pdf")).unwrap());
+    let mut n = 0;
+    for object in reader.by_ref() {
+        object.unwrap_or_else(|e| print_err(e));
+        n += 1;
+    }
+    assert!(n > 0);
+    assert!(reader.trailer().unwrap().get("Root").is_some());
+}
+
+#[test]
+fn backends() {
+    use pdf::backend::{Backend, FileBackend};
+    let path = file_path!("example.pdf");
+    let file = FileBackend::open(path).unwrap_or_else(|e| print_err(e));
+    let data = Vec::<u8>::open(path).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.len(), data.len());
+    assert_eq!(&*file.read_at(10, 100).unwrap(), &*data.read_at(10, 100).unwrap());
+    // reads are cut off at the end of the file
+    assert_eq!(file.read_at(data.len() - 5, 100).unwrap().len(), 5);
+    assert!(file.read_at(data.len() + 1, 1).is_err());
+
+    let _ = File::<FileBackend>::open(path).unwrap_or_else(|e| print_err(e));
+}
+
+#[test]
+fn object_cache() {
+    let file = File::<Vec<u8>>::open(file_path!("xelatex.pdf")).unwrap_or_else(|e| print_err(e));
+    let r = PlainRef {id: 13, gen: 0};
+    let first = file.deref(Ref::<ObjectStream>::new(r)).unwrap_or_else(|e| print_err(e));
+    file.clear_cache();
+    file.set_cache_limit(0);
+    let second = file.deref(Ref::<ObjectStream>::new(r)).unwrap_or_else(|e| print_err(e));
+    assert_eq!(first.n_objects(), second.n_objects());
+}
+
+#[test]
+fn object_stream_limits() {
+    use pdf::enc::DecodeLimits;
+    use pdf::{Error, ErrorKind};
+    let mut file = File::<Vec<u8>>::open(file_path!("xelatex.pdf")).unwrap_or_else(|e| print_err(e));
+    // object streams are decoded within the limits of the document
+    file.set_decode_limits(DecodeLimits::new(10, 10));
+    let r = PlainRef {id: 13, gen: 0};
+    match ObjectStream::from_primitive(file.resolver().resolve(r).unwrap(), &file.resolver()) {
+        Err(Error(ErrorKind::DecodeLimit {..}, _)) => {}
+        Err(e) => panic!("expected DecodeLimit, found {}", e),
+        Ok(_) => panic!("expected DecodeLimit"),
+    }
+    file.set_decode_limits(DecodeLimits::default());
+    ObjectStream::from_primitive(file.resolver().resolve(r).unwrap(), &file.resolver()).unwrap_or_else(|e| print_err(e));
+    assert!(file.get_decode_limits().total() > 0);
+}
+
+/// A PDF file with the given objects (numbered from 1) and an xref table. Object 1 is the
+/// catalog.
+fn build_pdf(objects: &[&str]) -> Vec<u8> {
+    build_pdf_with_trailer(objects, "")
+}
+/// Like `build_pdf`, with more `trailer` entries.
+fn build_pdf_with_trailer(objects: &[&str], trailer: &str) -> Vec<u8> {
+    let mut data = b"%PDF-1.7\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push(data.len());
+        data.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, obj).as_bytes());
+    }
+    let xref = data.len();
+    data.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        data.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    data.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R {} >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, trailer, xref).as_bytes());
+    data
+}
+/// Replaces `placeholder` in `data` with `bytes` of the same length - for binary data in the
+/// objects of `build_pdf`.
+fn replace_bytes(data: &mut [u8], placeholder: &str, bytes: &[u8]) {
+    assert_eq!(placeholder.len(), bytes.len());
+    let pos = data.windows(bytes.len()).position(|w| w == placeholder.as_bytes()).expect("placeholder not found");
+    data[pos .. pos + bytes.len()].copy_from_slice(bytes);
+}
+
+#[test]
+fn circular_reference() {
+    use pdf::parser::ParseOptions;
+    use pdf::{Error, ErrorKind};
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [2 0 R] /Count 1 >>",
+    ]);
+    match File::from_backend(data, ParseOptions::strict()) {
+        Err(Error(ErrorKind::CircularReference {obj_nr: 2}, _)) => {}
+        Err(e) => panic!("expected CircularReference, found {}", e),
+        Ok(_) => panic!("expected CircularReference"),
+    }
+
+    // an object that is a reference to itself
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 3 0 R >>",
+        "3 0 R",
+    ]);
+    assert!(File::from_backend(data, ParseOptions::strict()).is_err());
+}
+
+#[test]
+fn generation_mismatch() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::Dictionary;
+    use pdf::{Error, ErrorKind};
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.deref(Ref::<Dictionary>::new(PlainRef {id: 2, gen: 0})).is_ok());
+    match file.deref(Ref::<Dictionary>::new(PlainRef {id: 2, gen: 1})) {
+        Err(Error(ErrorKind::GenerationMismatch {obj_nr: 2, gen: 1, found: 0}, _)) => {}
+        Err(e) => panic!("expected GenerationMismatch, found {}", e),
+        Ok(_) => panic!("expected GenerationMismatch"),
+    }
+
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert!(file.deref(Ref::<Dictionary>::new(PlainRef {id: 2, gen: 1})).is_ok());
+}
+
+#[test]
+fn trailer_entries() {
+    use pdf::parser::ParseOptions;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Title (Test) >>",
+    ];
+    let data = build_pdf_with_trailer(&objects, "/Info 3 0 R /ID [<01020304> <0A0B>]");
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.get_size(), 4);
+    assert_eq!(file.get_id(), Some((&[1u8, 2, 3, 4][..], &[10u8, 11][..])));
+    assert_eq!(file.get_info_ref(), Some(PlainRef {id: 3, gen: 0}));
+    assert_eq!(file.get_encrypt_ref(), None);
+
+    let updated = build_pdf_with_trailer(&objects, "/ID [<01020304> <0C0D>]");
+    let updated = File::from_backend(updated, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.same_document(&updated));
+    let other = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(!file.same_document(&other));
+}
+
+#[test]
+fn update_object() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::Dictionary;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Title (Test) >>",
+    ];
+    let mut file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let r = Ref::<Dictionary>::from_id(3);
+    let mut info = file.deref(r).unwrap();
+    info.insert("Pages", 0);
+    file.update_object(r, &info).unwrap();
+    assert_eq!(file.deref(r).unwrap().get_int("Pages").unwrap(), 0);
+
+    // entries that `Page` doesn't read are kept
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /StructParents 1 /Tabs /S /UserUnit 2 >>",
+    ];
+    let mut file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let r = Ref::<Page>::from_id(3);
+    let mut page = file.deref(r).unwrap();
+    page.rotate = Some(90);
+    page.struct_parents = None;
+    file.update_object(r, &page).unwrap();
+    let dict = file.deref(Ref::<Dictionary>::from_id(3)).unwrap();
+    assert_eq!(dict.get_int("Rotate").unwrap(), 90);
+    assert_eq!(dict.get_name("Tabs").unwrap(), "S");
+    assert_eq!(dict.get_int("UserUnit").unwrap(), 2);
+    assert_eq!(dict.get_name("Type").unwrap(), "Page");
+    // (but not the ones that it does read)
+    assert_eq!(dict.get_as::<i32>("StructParents", NO_RESOLVE).unwrap(), None);
+}
+
+#[test]
+fn inherited_page_attributes() {
+    use pdf::parser::ParseOptions;
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 2 /MediaBox [0 0 612 792] /Rotate 90 >>",
+        "<< /Type /Pages /Parent 2 0 R /Kids [4 0 R 5 0 R] /Count 2 /MediaBox [0 0 100 200] >>",
+        "<< /Type /Page /Parent 3 0 R >>",
+        "<< /Type /Page /Parent 3 0 R /MediaBox [0 0 10 20] /Rotate 0 >>",
+    ]);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let first = file.get_page(0).unwrap();
+    assert_eq!(first.media_box().unwrap(), Rect::new(0., 0., 100., 200.));
+    assert_eq!(first.crop_box().unwrap(), Rect::new(0., 0., 100., 200.));
+    assert_eq!(first.rotate(), 90);
+    let second = file.get_page(1).unwrap();
+    assert_eq!(second.media_box().unwrap(), Rect::new(0., 0., 10., 20.));
+    assert_eq!(second.rotate(), 0);
+}
+
+#[test]
+fn page_geometry() {
+    use pdf::parser::ParseOptions;
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /MediaBox [0 0 200 100] /Rotate -270 >>",
+        "<< /Type /Page /Parent 2 0 R /CropBox [10 10 210 90] /BleedBox [5 5 195 95] /ArtBox [300 300 400 400] >>",
+        "<< /Type /Page /Parent 2 0 R /Rotate 180 >>",
+    ]);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let page = file.get_page(0).unwrap();
+    assert_eq!(page.rotate(), 90);
+    // boxes are clipped to the media box, and default to the crop box
+    assert_eq!(page.crop_box().unwrap(), Rect::new(10., 10., 200., 90.));
+    assert_eq!(page.bleed_box().unwrap(), Rect::new(5., 5., 195., 95.));
+    assert_eq!(page.trim_box().unwrap(), page.crop_box().unwrap());
+    assert_eq!(page.art_box().unwrap(), page.crop_box().unwrap());
+    assert_eq!(page.visible_size().unwrap(), (80., 190.));
+
+    // turned clockwise: the upper left corner is on the upper right
+    assert_eq!(page.user_to_display(10., 90.).unwrap(), (80., 190.));
+    assert_eq!(page.user_to_display(10., 10.).unwrap(), (0., 190.));
+    let (x, y) = page.display_to_user(80., 190.).unwrap();
+    assert!((x - 10.).abs() < 1e-4 && (y - 90.).abs() < 1e-4);
+
+    let page = file.get_page(1).unwrap();
+    assert_eq!(page.visible_size().unwrap(), (200., 100.));
+    assert_eq!(page.user_to_display(0., 0.).unwrap(), (200., 100.));
+}
+
+#[test]
+fn walk_objects() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::Primitive;
+    use pdf::visit::Visitor;
+    struct Audit {
+        objects:    Vec<u64>,
+        javascript: Vec<Option<u64>>,
+    }
+    impl Visitor for Audit {
+        fn visit_object(&mut self, r: PlainRef, _: &Primitive) -> pdf::Result<bool> {
+            self.objects.push(r.id);
+            Ok(true)
+        }
+        fn visit_primitive(&mut self, p: &Primitive, object: Option<PlainRef>) -> pdf::Result<()> {
+            if let Primitive::Dictionary (ref dict) = *p {
+                if dict.get_name("S").ok() == Some("JavaScript") {
+                    self.javascript.push(object.map(|r| r.id));
+                }
+            }
+            Ok(())
+        }
+    }
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /OpenAction << /S /JavaScript /JS (app.alert) >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /Annots [4 0 R 9 0 R] >>",
+        "<< /A << /S /JavaScript /JS (x) >> /P 3 0 R >>",
+        "<< /Unused true >>",
+    ]);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let mut audit = Audit {objects: Vec::new(), javascript: Vec::new()};
+    file.walk(&mut audit).unwrap();
+    audit.objects.sort();
+    assert_eq!(audit.objects, vec![1, 2, 3, 4]);
+    audit.javascript.sort();
+    assert_eq!(audit.javascript, vec![Some(1), Some(4)]);
+}
+
+#[test]
+fn copy_objects() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::{Primitive, Dictionary};
+    use pdf::copy::Copier;
+    let source = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /Resources 5 0 R /MediaBox [0 0 612 792] >>",
+        "<< /Type /Page /Parent 2 0 R /Annots [6 0 R] >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        "<< /Font << /F1 7 0 R >> >>",
+        "<< /Subtype /Link /P 3 0 R >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let mut dest = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+
+    let mut copier = Copier::new(&source);
+    let first = copier.copy_page(PlainRef {id: 3, gen: 0}, &mut dest).unwrap();
+    let second = copier.copy_page(PlainRef {id: 4, gen: 0}, &mut dest).unwrap();
+    assert!(first.id >= 3 && second.id >= 3 && first != second);
+    assert_eq!(copier.copy_page(PlainRef {id: 3, gen: 0}, &mut dest).unwrap(), first);
+
+    let page = dest.deref(Ref::<Dictionary>::new(first)).unwrap();
+    assert!(page.get("Parent").is_none());
+    assert!(page.get("MediaBox").is_some());
+    // the resources are shared, and the annotation refers to the copy of the page
+    let resources = page.get("Resources").cloned().unwrap().to_reference().unwrap();
+    let other = dest.deref(Ref::<Dictionary>::new(second)).unwrap();
+    assert_eq!(other.get("Resources").cloned().unwrap().to_reference().unwrap(), resources);
+    let annot = match page.get_array("Annots").unwrap()[0] {
+        Primitive::Reference (r) => dest.deref(Ref::<Dictionary>::new(r)).unwrap(),
+        ref p => panic!("expected Reference, found {:?}", p),
+    };
+    assert_eq!(annot.get("P").cloned().unwrap().to_reference().unwrap(), first);
+    assert_eq!(copier.get_copy(PlainRef {id: 2, gen: 0}), None);
+}
+
+#[test]
+fn collect_garbage() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::{Primitive, Dictionary};
+    let mut file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 3 0 R >>",
+        "<< /Unused true >>",
+        "<< /Type /Pages /Kids [5 0 R] /Count 1 >>",
+        "<< /Unused 2 0 R >>",
+        "<< /Type /Page /Parent 3 0 R /Resources << /Font 9 0 R >> >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    file.add_primitive(Primitive::Integer (42));
+    assert_eq!(file.reachable_objects().unwrap().len(), 3);
+
+    assert_eq!(file.collect_garbage().unwrap(), 3);
+    assert_eq!(file.get_size(), 4);
+    let pages = file.deref(Ref::<Dictionary>::from_id(2)).unwrap();
+    assert_eq!(pages.get_name("Type").unwrap(), "Pages");
+    let page = file.deref(Ref::<Dictionary>::from_id(3)).unwrap();
+    assert_eq!(page.get_dictionary("Resources").unwrap().get("Font").map(|p| p.get_debug_name()), Some("Null"));
+    assert_eq!(file.get_num_pages().unwrap(), 1);
+
+    let mut saved = Vec::new();
+    file.save_to(&mut saved).unwrap();
+    let saved = File::from_backend(saved, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(saved.get_num_pages().unwrap(), 1);
+    assert_eq!(saved.reachable_objects().unwrap().len(), 3);
+}
+
+#[test]
+fn diff_files() {
+    use pdf::parser::ParseOptions;
+    use pdf::diff::{diff, ObjectDiff};
+    let old = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Length 3 >>\nstream\nabc\nendstream",
+        "<< /Title (Old) /Keywords [1 2] >>",
+        "<< /Removed true >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let new = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Length 7 /Filter /ASCIIHexDecode >>\nstream\n616263>\nendstream",
+        "<< /Title (New) /Keywords [1.0 3] /Author (A) >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+
+    let diffs = diff(&old, &new).unwrap();
+    assert_eq!(diffs.len(), 2, "{:?}", diffs);
+    match diffs[0] {
+        ObjectDiff::Changed (r, ref differences) => {
+            assert_eq!(r.id, 4);
+            let paths: Vec<&str> = differences.iter().map(|d| d.path.as_str()).collect();
+            assert_eq!(paths, vec!["/Author", "/Keywords[1]", "/Title"]);
+            assert!(differences[0].old.is_none());
+        }
+        ref d => panic!("expected Changed, found {:?}", d),
+    }
+    match diffs[1] {
+        ObjectDiff::Removed (r) => assert_eq!(r.id, 5),
+        ref d => panic!("expected Removed, found {:?}", d),
+    }
+}
+
+#[test]
+fn page_iterator() {
+    use pdf::parser::ParseOptions;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R 5 0 R] /Count 4 >>",
+        "<< /Type /Page /Parent 2 0 R /Rotate 90 >>",
+        "<< /Type /Pages /Parent 2 0 R /Kids [6 0 R 7 0 R] /Count 2 >>",
+        "<< /Type /Page /Parent 2 0 R /Rotate 270 >>",
+        "<< /Type /Page /Parent 4 0 R /Rotate 180 >>",
+        "<< /Type /Page /Parent 4 0 R >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let rotations: Vec<i32> = file.pages().unwrap().map(|page| page.rotate()).collect();
+    assert_eq!(rotations, vec![90, 180, 0, 270]);
+
+    let mut objects = objects;
+    objects[1] = "<< /Type /Pages /Kids [3 0 R 4 0 R 5 0 R] /Count 3 >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.pages().is_err());
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.pages().unwrap().count(), 4);
+}
+
+#[test]
+fn catalog_entries() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::Primitive;
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /PageMode /UseOutlines /Outlines 3 0 R /OpenAction [4 0 R /Fit] /Lang (en) /AcroForm 5 0 R >>",
+        "<< /Type /Pages /Kids [4 0 R] /Count 1 >>",
+        "<< /Type /Outlines /Count 0 >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        // not read unless asked for
+        "<< /Fields 6 0 R >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let root = file.get_root();
+    assert_eq!(root.page_mode.as_ref().map(|s| s.as_str()), Some("UseOutlines"));
+    assert!(root.metadata.is_none());
+
+    let outlines = root.outlines.as_ref().unwrap();
+    assert_eq!(outlines.get_ref().map(|r| r.get_inner().id), Some(3));
+    assert_eq!(file.get(outlines).unwrap().get("Count").unwrap().as_integer().unwrap(), 0);
+    match file.get(root.open_action.as_ref().unwrap()).unwrap() {
+        Primitive::Array (ref dest) => assert_eq!(dest.len(), 2),
+        p => panic!("OpenAction: {:?}", p),
+    }
+    assert!(file.get(root.acro_form.as_ref().unwrap()).is_ok());
+}
+
+#[test]
+fn viewer_preferences() {
+    use pdf::parser::ParseOptions;
+    use pdf::object::{PageLayout, PageMode, Direction, Duplex, PrintScaling, PageBox, OpenAction, Destination, DestinationPage};
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /PageLayout /TwoColumnRight /PageMode /FullScreen /ViewerPreferences 4 0 R /OpenAction 5 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+        "<< /HideToolbar true /DisplayDocTitle true /NonFullScreenPageMode /UseOutlines /Direction /R2L \
+            /ViewArea /ArtBox /PrintScaling /None /Duplex /DuplexFlipLongEdge /PrintPageRange [1 1 3 2 5] /NumCopies 2 >>",
+        "[3 0 R /FitH 100]",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let root = file.get_root();
+    assert_eq!(root.page_layout(), PageLayout::TwoColumnRight);
+    assert_eq!(root.page_mode(), PageMode::FullScreen);
+    let preferences = file.viewer_preferences().unwrap();
+    assert_eq!(preferences.hide_toolbar, Some(true));
+    assert_eq!(preferences.hide_menubar, None);
+    assert_eq!(preferences.display_doc_title, Some(true));
+    assert_eq!(preferences.non_full_screen_page_mode(), PageMode::UseOutlines);
+    assert_eq!(preferences.direction(), Direction::R2L);
+    assert_eq!(preferences.view_area(), PageBox::ArtBox);
+    assert_eq!(preferences.view_clip(), PageBox::CropBox);
+    assert_eq!(preferences.print_scaling(), PrintScaling::None);
+    assert_eq!(preferences.duplex(), Some(Duplex::DuplexFlipLongEdge));
+    // (the range that is out of order and the odd number are left out)
+    assert_eq!(preferences.print_page_ranges(), vec![(0, 0)]);
+    assert_eq!(preferences.num_copies, Some(2));
+    match file.open_action().unwrap() {
+        Some(OpenAction::Destination (dest)) => assert_eq!(dest, Destination::FitH {page: DestinationPage::Ref (PlainRef {id: 3, gen: 0}), top: Some(100.)}),
+        action => panic!("expected a destination, found {:?}", action),
+    }
+
+    // the defaults, and names that aren't known
+    let mut objects = objects;
+    objects[0] = "<< /Type /Catalog /Pages 2 0 R /PageLayout /Spread /OpenAction << /S /Named /N /NextPage >> >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let root = file.get_root();
+    assert_eq!(root.page_layout(), PageLayout::SinglePage);
+    assert_eq!(root.page_mode(), PageMode::UseNone);
+    let preferences = file.viewer_preferences().unwrap();
+    assert_eq!(preferences.direction(), Direction::L2R);
+    assert_eq!(preferences.print_scaling(), PrintScaling::AppDefault);
+    assert_eq!(preferences.duplex(), None);
+    match file.open_action().unwrap() {
+        Some(OpenAction::Action (action)) => match action.kind {
+            ActionKind::Named {ref name} => assert_eq!(name, "NextPage"),
+            ref kind => panic!("expected a named action, found {:?}", kind),
+        },
+        action => panic!("expected an action, found {:?}", action),
+    }
+}
+
+#[test]
+fn actions() {
+    use pdf::parser::ParseOptions;
+    let script = "app.launchURL('http://example.com/x');";
+    let script_stream = format!("<< /Length {} >>\nstream\n{}\nendstream", script.len(), script);
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /OpenAction 4 0 R /AA << /WC << /S /JavaScript /JS (closing) >> >> \
+            /Outlines 9 0 R /Names << /JavaScript << /Names [(init) 12 0 R] >> >> /AcroForm << /Fields [8 0 R] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [7 0 R 8 0 R] /AA << /O << /S /Named /N /FirstPage >> >> >>",
+        // a script, then a link and a named action
+        "<< /S /JavaScript /JS (app.alert\\(1\\)) /Next [5 0 R 6 0 R] >>",
+        "<< /S /URI /URI (http://example.com/a) >>",
+        "<< /S /Launch /Win << /F (cmd.exe) /P (/c calc) >> >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] /A << /S /URI /URI (http://example.com/b) /IsMap true >> >>",
+        "<< /Type /Annot /Subtype /Widget /Rect [0 20 10 30] /FT /Tx /T (amount) /AA << /K << /S /JavaScript /JS 11 0 R >> /E << /S /GoTo /D [3 0 R /Fit] >> >> >>",
+        "<< /Type /Outlines /First 10 0 R /Last 10 0 R /Count 1 >>",
+        "<< /Title (Home) /Parent 9 0 R /A << /S /GoTo /D (home) >> >>",
+        &script_stream,
+        "<< /S /JavaScript /JS (var init = 1;) >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let actions = file.actions().unwrap();
+    let sources: Vec<_> = actions.iter().map(|&(ref source, ref action)| (source.clone(), action.action_type.as_str())).collect();
+    assert_eq!(sources, vec![
+        (ActionSource::OpenAction, "JavaScript"),
+        (ActionSource::Document ("WC".into()), "JavaScript"),
+        (ActionSource::NamedJavaScript ("init".into()), "JavaScript"),
+        (ActionSource::Outline ("Home".into()), "GoTo"),
+        (ActionSource::Page (0, "O".into()), "Named"),
+        (ActionSource::Annotation {page: 0, annotation: 0, trigger: None}, "URI"),
+        (ActionSource::Annotation {page: 0, annotation: 1, trigger: Some("E".into())}, "GoTo"),
+        (ActionSource::Field ("amount".into(), "K".into()), "JavaScript"),
+    ]);
+
+    // the chain of the open action
+    let chain = actions[0].1.all();
+    assert_eq!(chain.len(), 3);
+    match chain[0].kind {
+        ActionKind::JavaScript {ref script} => assert_eq!(script, "app.alert(1)"),
+        ref kind => panic!("expected JavaScript, found {:?}", kind),
+    }
+    match chain[2].kind {
+        ActionKind::Launch {ref file, ref parameters, ..} => {
+            assert_eq!(file.as_ref().unwrap(), "cmd.exe");
+            assert_eq!(parameters.as_ref().unwrap(), "/c calc");
+        }
+        ref kind => panic!("expected Launch, found {:?}", kind),
+    }
+
+    // all the URIs and scripts, as for a security scan
+    let mut uris = Vec::new();
+    let mut scripts = Vec::new();
+    for &(_, ref action) in &actions {
+        for action in action.all() {
+            match action.kind {
+                ActionKind::URI {ref uri, ..} => uris.push(uri.clone()),
+                ActionKind::JavaScript {ref script} => scripts.push(script.clone()),
+                _ => {}
+            }
+        }
+    }
+    assert_eq!(uris, vec!["http://example.com/a", "http://example.com/b"]);
+    assert_eq!(scripts, vec!["app.alert(1)", "closing", "var init = 1;", script]);
+    match file.outline().unwrap()[0].action.as_ref().unwrap().kind {
+        ActionKind::GoTo {ref dest} => assert_eq!(*dest, Target::Named (b"home".to_vec())),
+        ref kind => panic!("expected GoTo, found {:?}", kind),
+    }
+
+    // a chain with a cycle is an error - or, if not strict, ends there
+    let mut objects = objects;
+    objects[5] = "<< /S /Named /N /NextPage /Next 4 0 R >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.open_action().is_err());
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    match file.open_action().unwrap() {
+        Some(OpenAction::Action (action)) => assert_eq!(action.all().len(), 3),
+        action => panic!("expected an action, found {:?}", action),
+    }
+}
+
+#[test]
+fn outline() {
+    use pdf::parser::ParseOptions;
+    let mut objects = [
+        "<< /Type /Catalog /Pages 2 0 R /Outlines 3 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Type /Outlines /First 4 0 R /Last 5 0 R /Count 3 >>",
+        "<< /Title (Chapter 1) /Parent 3 0 R /Next 5 0 R /First 6 0 R /Last 6 0 R /Count 1 /Dest [2 0 R /Fit] >>",
+        "<< /Title <FEFF0043006800200032> /Parent 3 0 R /Prev 4 0 R /A << /S /URI /URI (http://example.com) >> >>",
+        "<< /Title (Section 1.1) /Parent 4 0 R /Dest (sec1) >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let items = file.outline().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].title, "Chapter 1");
+    assert!(items[0].open);
+    assert_eq!(items[0].children[0].title, "Section 1.1");
+    assert_eq!(items[1].title, "Ch 2");
+    assert!(items[1].dest.is_none() && items[1].action.is_some());
+
+    // the last item links back to the first
+    objects[4] = "<< /Title (Chapter 2) /Parent 3 0 R /Prev 4 0 R /Next 4 0 R >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.outline().is_err());
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.outline().unwrap().len(), 2);
+}
+
+#[test]
+fn named_destinations() {
+    use pdf::parser::ParseOptions;
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /Names << /Dests 4 0 R >> /Dests << /old [3 0 R /FitH 700] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        "<< /Kids [5 0 R] >>",
+        "<< /Limits [(a) (z)] /Names [(chapter) [3 0 R /XYZ 72 null 0] (section) << /D [3 0 R /FitR 0 100 50 0] >>] >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let page = DestinationPage::Ref (PlainRef {id: 3, gen: 0});
+    assert_eq!(file.resolve_destination(b"chapter").unwrap(),
+        Some(Destination::XYZ {page: page, left: Some(72.), top: None, zoom: None}));
+    assert_eq!(file.resolve_destination(b"section").unwrap(),
+        Some(Destination::FitR {page: page, rect: Rect::new(0., 0., 50., 100.)}));
+    assert_eq!(file.resolve_destination(b"old").unwrap(), Some(Destination::FitH {page: page, top: Some(700.)}));
+    assert_eq!(file.resolve_destination(b"none").unwrap(), None);
+}
+
+#[test]
+fn destination_pages() {
+    use pdf::parser::ParseOptions;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /Outlines 5 0 R /Names << /Dests << /Names [(end) [4 0 R /Fit]] >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>",
+        "<< /Type /Page /Parent 2 0 R /Annots [8 0 R 9 0 R] >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        "<< /Type /Outlines /First 6 0 R /Last 7 0 R /Count 2 >>",
+        "<< /Title (End) /Parent 5 0 R /Next 7 0 R /Dest (end) >>",
+        "<< /Title (Nowhere) /Parent 5 0 R /Prev 6 0 R /Dest (none) >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] /Dest [4 0 R /FitH 700] >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 20 10 30] /A << /S /GoTo /D [3 0 R /XYZ null null 2] >> >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+
+    // named destinations, the second without destination
+    let items = file.outline().unwrap();
+    assert_eq!(items[0].dest, Some(Target::Named (b"end".to_vec())));
+    assert_eq!(file.target_page(items[0].dest.as_ref().unwrap()).unwrap(), Some(1));
+    assert_eq!(file.target_page(items[1].dest.as_ref().unwrap()).unwrap(), None);
+
+    // an explicit destination, and one of a go-to action
+    let page = file.get_page(0).unwrap();
+    let annotations = file.annotations(&page).unwrap();
+    match annotations[0].kind {
+        AnnotationKind::Link {dest: Some(ref dest), ..} => {
+            assert_eq!(*dest, Target::Explicit (Destination::FitH {page: DestinationPage::Ref (PlainRef {id: 4, gen: 0}), top: Some(700.)}));
+            assert_eq!(file.target_page(dest).unwrap(), Some(1));
+        }
+        ref kind => panic!("expected a link with a destination, found {:?}", kind),
+    }
+    match annotations[1].kind {
+        AnnotationKind::Link {action: Some(ref action), ..} => match action.kind {
+            ActionKind::GoTo {ref dest} => assert_eq!(file.target_page(dest).unwrap(), Some(0)),
+            ref kind => panic!("expected GoTo, found {:?}", kind),
+        },
+        ref kind => panic!("expected a link with an action, found {:?}", kind),
+    }
+
+    // page numbers (as in remote go-to actions), and a page that isn't in the document
+    assert_eq!(file.destination_page(&Destination::Fit {page: DestinationPage::Number (1)}).unwrap(), Some(1));
+    assert_eq!(file.destination_page(&Destination::Fit {page: DestinationPage::Number (2)}).unwrap(), None);
+    assert_eq!(file.destination_page(&Destination::Fit {page: DestinationPage::Ref (PlainRef {id: 5, gen: 0})}).unwrap(), None);
+}
+
+#[test]
+fn page_labels() {
+    use pdf::parser::ParseOptions;
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /PageLabels 3 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Nums [0 << /S /r >> 4 << /S /D >> 7 << /S /D /P (A-) /St 7 >> 9 << /S /A /St 26 >> 11 << /P (cover) >>] >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let labels: Vec<String> = (0 .. 12).map(|i| file.page_label(i).unwrap()).collect();
+    assert_eq!(labels, vec!["i", "ii", "iii", "iv", "1", "2", "3", "A-7", "A-8", "Z", "AA", "cover"]);
+
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.page_label(0).unwrap(), "1");
+}
+
+#[test]
+fn permissions() {
+    use pdf::parser::ParseOptions;
+    use pdf::crypt::Permissions;
+    // RC4, 128 bits, with an empty user password: may print, annotate and fill in forms, and
+    // extract text for accessibility
+    let file = File::from_backend(build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Filter /Standard /V 2 /Length 128 /R 3 /P -3100 \
+            /O <566FA873EE33C797CD3B904FDADF814AFA34DF9A38F6ED41B984E2C6DA2AA6F5> \
+            /U <B4D73B4A983C3A1129632F187AE4EA2100000000000000000000000000000000> >>",
+    ], "/Encrypt 3 0 R /ID [<0123456789ABCDEF0123456789ABCDEF> <0123456789ABCDEF0123456789ABCDEF>]"),
+        ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let encryption = file.encryption().unwrap().unwrap();
+    assert_eq!((encryption.v, encryption.bits, encryption.revision), (2, 128, 3));
+    let permissions = file.permissions().unwrap();
+    assert!(permissions.can_print && !permissions.can_print_high_quality);
+    assert!(!permissions.can_modify && !permissions.can_copy && !permissions.can_assemble);
+    assert!(permissions.can_annotate && permissions.can_fill_forms && permissions.can_extract_for_accessibility);
+
+    // revision 2 has only 4 flags
+    let permissions = Permissions::from_flags(-44, 2);
+    assert!(permissions.can_print && permissions.can_print_high_quality && !permissions.can_modify);
+    assert!(permissions.can_copy && permissions.can_extract_for_accessibility && !permissions.can_fill_forms);
+
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.encryption().unwrap().is_none());
+    assert_eq!(file.permissions().unwrap(), Permissions::all());
+}
+
+#[test]
+fn decryption() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::{Primitive, Dictionary};
+    let id = "/ID [<0123456789ABCDEF0123456789ABCDEF> <0123456789ABCDEF0123456789ABCDEF>]";
+    // (objects are decrypted as they are read)
+    let string_of = |file: &File<Vec<u8>>, r: PlainRef| match file.resolver().resolve(r).unwrap() {
+        Primitive::String (s) => s.into_bytes(),
+        p => panic!("expected a string, found {}", p.get_debug_name()),
+    };
+    let string_ref = PlainRef {id: 4, gen: 0};
+
+    // RC4, 128 bits
+    let file = File::from_backend(build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Filter /Standard /V 2 /Length 128 /R 3 /P -3100 \
+            /O <566FA873EE33C797CD3B904FDADF814AFA34DF9A38F6ED41B984E2C6DA2AA6F5> \
+            /U <B4D73B4A983C3A1129632F187AE4EA2100000000000000000000000000000000> >>",
+        "<67929BA2DB099E8A2E717060>",
+    ], &format!("/Encrypt 3 0 R {}", id)), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(string_of(&file, string_ref), b"Secret title");
+
+    // AES-128, with the initialization vector 0, 1, ... 15
+    let encrypted = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\xCF\xBC\xA6\x91\x84\x04\xD4\xEA\xAB\x7A\x54\xC5\xB3\xEB\x03\xC9\xC8\xAA\xBA\xBD\xCC\x41\xA3\x26\x35\x55\x99\x0E\xCE\x7E\x85\x92";
+    let placeholder = "X".repeat(encrypted.len());
+    let stream = format!("<< /Length {} >>\nstream\n{}\nendstream", encrypted.len(), placeholder);
+    let mut data = build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Filter /Standard /V 4 /R 4 /P -4 /CF << /StdCF << /CFM /AESV2 /AuthEvent /DocOpen /Length 16 >> >> \
+            /StmF /StdCF /StrF /StdCF \
+            /O <566FA873EE33C797CD3B904FDADF814AFA34DF9A38F6ED41B984E2C6DA2AA6F5> \
+            /U <2C1687A9517CFF099BEC62B8A7EDDB7600000000000000000000000000000000> >>",
+        "<000102030405060708090A0B0C0D0E0F0A126BFA35269F212BA28D5473D0F2AD>",
+        &stream,
+    ], &format!("/Encrypt 3 0 R {}", id));
+    replace_bytes(&mut data, &placeholder, encrypted);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(string_of(&file, string_ref), b"Secret title");
+    let stream_ref = PlainRef {id: 5, gen: 0};
+    // (the decrypted stream is shorter: /Length is corrected)
+    match file.resolver().resolve(stream_ref).unwrap() {
+        Primitive::Stream (stream) => {
+            assert_eq!(stream.data.len(), 26);
+            assert_eq!(stream.info.get("Length").unwrap().as_integer().unwrap(), 26);
+        }
+        p => panic!("expected a stream, found {}", p.get_debug_name()),
+    }
+    let stream = Stream::<Dictionary>::from_primitive(file.resolver().resolve(stream_ref).unwrap(), &file.resolver()).unwrap();
+    assert_eq!(&*stream.get_data().unwrap(), &b"BT /F1 12 Tf (Hello) Tj ET"[..]);
+
+    // AES-256 (revision 6, and the older revision 5 with SHA-256 for the hashes), with the file
+    // key 32, 33, ... 63
+    let encrypted = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x72\x13\xF2\x76\x8C\xCE\x3E\xB2\xA8\xED\x2A\x87\xC0\xC6\xA2\x97\x81\x91\x38\x8E\xC3\x7B\x22\x22\x7F\xAC\x3B\x94\xCF\x3A\xAC\x90";
+    let r6 = "<< /Filter /Standard /V 5 /R 6 /Length 256 /P -1028 /CF << /StdCF << /CFM /AESV3 /Length 32 >> >> \
+        /StmF /StdCF /StrF /StdCF /Perms <E5C4869C501ED88EB165CE83615C49B0> \
+        /U <C6687B07EBEC578A4529888460021E69E21D1B01EF382BD90C405101BC40C56A757673616C747576756B657973616C74> \
+        /UE <B02EB79859F6B80225CA2E80987D9FCA483E5F56BA29BB671237A117D41A2DC3> \
+        /O <5077E78B7BD40ED86082DA55A13DFA44DCD9BEAC0F3B70F3821D61166E9E9E3A6F7673616C746F766F6B657973616C74> \
+        /OE <C3E3F52EE0A28408DF4C3B770CDE0CB76F15D06D65C1EC3DA5FEB73D5BF16C99> >>";
+    let r5 = "<< /Filter /Standard /V 5 /R 5 /Length 256 /P -1028 /CF << /StdCF << /CFM /AESV3 /Length 32 >> >> \
+        /StmF /StdCF /StrF /StdCF /Perms <E5C4869C501ED88EB165CE83615C49B0> \
+        /U <510D2C576D1F50FA44CB2DA76B72A52ED2326195B8487F86DEB5DFC5A1AC5351757673616C747576756B657973616C74> \
+        /UE <0F06DBC2D9B8E6108B0575DA69EAE02ED330F8940CDD39DAE79D598D9998298A> \
+        /O <789F0307671B1E56C921CB06BC0FDAA365A5A7BDD1D18FF6A334EFDA9F6A5EC36F7673616C746F766F6B657973616C74> \
+        /OE <CCB91FE46E0FA1E91B0CEF5F66124DABAB2654A4B357C452C6BEB7B319EDF0D5> >>";
+    for encrypt_dict in &[r6, r5] {
+        let mut data = build_pdf_with_trailer(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [] /Count 0 >>",
+            *encrypt_dict,
+            "<000102030405060708090A0B0C0D0E0F4D6317746BDCE91355C1053790B7F794>",
+            &stream,
+        ], &format!("/Encrypt 3 0 R {}", id));
+        replace_bytes(&mut data, &placeholder, encrypted);
+        let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert_eq!(string_of(&file, string_ref), b"Secret title");
+        let stream = Stream::<Dictionary>::from_primitive(file.resolver().resolve(stream_ref).unwrap(), &file.resolver()).unwrap();
+        assert_eq!(&*stream.get_data().unwrap(), &b"BT /F1 12 Tf (Hello) Tj ET"[..]);
+    }
+
+    // AES-128 with metadata that isn't encrypted (`/EncryptMetadata false`)
+    let metadata = "<x:xmpmeta xmlns:x='adobe:ns:meta/'/>";
+    let file = File::from_backend(build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R /Metadata 5 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Filter /Standard /V 4 /R 4 /P -4 /CF << /StdCF << /CFM /AESV2 /AuthEvent /DocOpen /Length 16 >> >> \
+            /StmF /StdCF /StrF /StdCF /EncryptMetadata false \
+            /O <36451BD39D753B7C1D10922C28E6665AA4F3353FB0348B536893E3B1DB5C579B> \
+            /U <09C446473143E0EFED6BE8E873FD26AD00000000000000000000000000000000> >>",
+        "<< /Title <000102030405060708090A0B0C0D0E0F51B2C098537E1B3B33D729661872C7AA> >>",
+        &format!("<< /Type /Metadata /Subtype /XML /Length {} >>\nstream\n{}\nendstream", metadata.len(), metadata),
+    ], &format!("/Encrypt 3 0 R /Info 4 0 R {}", id)), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.info().unwrap().title().unwrap(), "Secret title");
+    let stream = Stream::<Dictionary>::from_primitive(file.resolver().resolve(stream_ref).unwrap(), &file.resolver()).unwrap();
+    assert_eq!(&*stream.get_data().unwrap(), metadata.as_bytes());
+
+    // not encrypted
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.security_handler().is_none());
+}
+
+#[test]
+fn passwords() {
+    use pdf::parser::ParseOptions;
+    use pdf::crypt::PasswordKind;
+    use pdf::ErrorKind;
+    let pages = "<< /Type /Pages /Kids [] /Count 0 >>";
+    let trailer = "/Encrypt 3 0 R /ID [<0123456789ABCDEF0123456789ABCDEF> <0123456789ABCDEF0123456789ABCDEF>]";
+    let documents = [
+        // RC4 and AES-256, with the user password "user" and the owner password "owner"
+        build_pdf_with_trailer(&["<< /Type /Catalog /Pages 2 0 R >>", pages,
+            "<< /Filter /Standard /V 2 /Length 128 /R 3 /P -3100 \
+                /O <0BA3835F88F90388E74E54584125CE142BE0DE24C6B0D37746E075B891756671> \
+                /U <16BE33C055D14C34915883B7A407D87300000000000000000000000000000000> >>",
+        ], trailer),
+        build_pdf_with_trailer(&["<< /Type /Catalog /Pages 2 0 R >>", pages,
+            "<< /Filter /Standard /V 5 /R 6 /Length 256 /P -1028 /CF << /StdCF << /CFM /AESV3 /Length 32 >> >> \
+                /StmF /StdCF /StrF /StdCF /Perms <E5C4869C501ED88EB165CE83615C49B0> \
+                /U <7F827A03B4E721EE3588831B7D044534B1373ED8EC846933F66D8682689F3DC2757673616C747576756B657973616C74> \
+                /UE <9C85C9209AFCD5680E811C340DE5D9B89869D5723297D871B561FDF7112556AB> \
+                /O <56660EE9569B764947B2413365D8763883D82463EA78229DEB8CF5E28C2539CD6F7673616C746F766F6B657973616C74> \
+                /OE <E89B472411743F6A46D60783B96D67DB044F60A881A6E400F159DF7F0E945156> >>",
+        ], trailer),
+    ];
+    for data in &documents {
+        match File::from_backend(data.clone(), ParseOptions::strict()) {
+            Err(ref e) => match *e.kind() {
+                ErrorKind::WrongPassword => {}
+                ref kind => panic!("expected WrongPassword, found {}", kind),
+            },
+            Ok(_) => panic!("expected WrongPassword"),
+        }
+        let file = File::from_backend_with_password(data.clone(), b"user", ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert_eq!(file.password_kind(), Some(PasswordKind::User));
+        assert!(!file.permissions().unwrap().can_copy);
+        let file = File::from_backend_with_password(data.clone(), b"owner", ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert_eq!(file.password_kind(), Some(PasswordKind::Owner));
+        assert!(file.permissions().unwrap().can_copy);
+
+        // asked until the right one
+        let mut asked = 0;
+        let file = File::from_backend_with_passwords(data.clone(), ParseOptions::strict(), || {
+            asked += 1;
+            Some(if asked < 3 { b"wrong".to_vec() } else { b"user".to_vec() })
+        }).unwrap_or_else(|e| print_err(e));
+        assert_eq!(asked, 3);
+        assert_eq!(file.password_kind(), Some(PasswordKind::User));
+    }
+
+    // a security handler that isn't supported
+    let data = build_pdf_with_trailer(&["<< /Type /Catalog /Pages 2 0 R >>", pages,
+        "<< /Filter /Adobe.PubSec /SubFilter /adbe.pkcs7.s5 /V 4 /R 4 /P -4 /O <> /U <> >>",
+    ], trailer);
+    match File::from_backend(data.clone(), ParseOptions::strict()) {
+        Err(ref e) => match *e.kind() {
+            ErrorKind::UnsupportedSecurityHandler {..} => {}
+            ref kind => panic!("expected UnsupportedSecurityHandler, found {}", kind),
+        },
+        Ok(_) => panic!("expected UnsupportedSecurityHandler"),
+    }
+    let file = File::from_backend(data, ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert!(file.security_handler().is_none() && file.password_kind().is_none());
+
+    // the 32-byte keys of /V 5 with a revision that makes keys with MD5
+    let data = build_pdf_with_trailer(&["<< /Type /Catalog /Pages 2 0 R >>", pages,
+        "<< /Filter /Standard /V 5 /R 4 /Length 256 /P -4 /CF << /StdCF << /CFM /AESV3 /Length 32 >> >> \
+            /StmF /StdCF /StrF /StdCF \
+            /O <566FA873EE33C797CD3B904FDADF814AFA34DF9A38F6ED41B984E2C6DA2AA6F5> \
+            /U <2C1687A9517CFF099BEC62B8A7EDDB7600000000000000000000000000000000> >>",
+    ], trailer);
+    match File::from_backend(data, ParseOptions::strict()) {
+        Err(ref e) => match *e.kind() {
+            ErrorKind::UnsupportedSecurityHandler {..} => {}
+            ref kind => panic!("expected UnsupportedSecurityHandler, found {}", kind),
+        },
+        Ok(_) => panic!("expected UnsupportedSecurityHandler"),
+    }
+}
+
+#[test]
+fn save_encrypted() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::{Primitive, Dictionary};
+    use pdf::crypt::{CryptMethod, EncryptionOptions, PasswordKind};
+    use pdf::ErrorKind;
+    let content = "BT /F1 12 Tf (Hello) Tj ET";
+    let stream = format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content);
+    let file = File::from_backend(build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Title (Secret title) >>",
+        &stream,
+    ], "/Info 3 0 R"), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+
+    for &method in &[CryptMethod::RC4, CryptMethod::AES128, CryptMethod::AES256] {
+        let mut options = EncryptionOptions::new(method, b"user", b"owner");
+        options.permissions.can_copy = false;
+        let mut data = Vec::new();
+        file.save_encrypted_to(&mut data, &options).unwrap();
+        // (neither in the clear)
+        assert!(!data.windows(6).any(|w| w == b"Secret" || w == b"Hello)"));
+
+        match File::from_backend(data.clone(), ParseOptions::strict()) {
+            Err(ref e) => match *e.kind() {
+                ErrorKind::WrongPassword => {}
+                ref kind => panic!("expected WrongPassword, found {}", kind),
+            },
+            Ok(_) => panic!("expected WrongPassword"),
+        }
+        let owner = File::from_backend_with_password(data.clone(), b"owner", ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert_eq!(owner.password_kind(), Some(PasswordKind::Owner));
+
+        let saved = File::from_backend_with_password(data, b"user", ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert_eq!(saved.password_kind(), Some(PasswordKind::User));
+        let permissions = saved.permissions().unwrap();
+        assert!(permissions.can_print && !permissions.can_copy);
+        assert!(saved.get_id().is_some());
+
+        let info = Dictionary::from_primitive(saved.resolver().resolve(PlainRef {id: 3, gen: 0}).unwrap(), &saved.resolver()).unwrap();
+        match info.get("Title") {
+            Some(&Primitive::String (ref title)) => assert_eq!(title.as_bytes(), b"Secret title"),
+            title => panic!("expected a string, found {:?}", title),
+        }
+        let stream = Stream::<Dictionary>::from_primitive(saved.resolver().resolve(PlainRef {id: 4, gen: 0}).unwrap(), &saved.resolver()).unwrap();
+        assert_eq!(&*stream.get_data().unwrap(), content.as_bytes());
+
+        // and written decrypted again
+        let mut data = Vec::new();
+        saved.save_to(&mut data).unwrap();
+        let decrypted = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert!(decrypted.security_handler().is_none());
+        assert_eq!(decrypted.info().unwrap().title().unwrap(), "Secret title");
+
+        // an incremental update is encrypted too
+        let mut saved = saved;
+        let mut info = saved.info().unwrap();
+        info.set_title("Changed title");
+        saved.set_info(&info);
+        let mut data = Vec::new();
+        saved.save_incremental(&mut data).unwrap();
+        assert!(!data.windows(7).any(|w| w == b"Changed"));
+        let updated = File::from_backend_with_password(data, b"user", ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+        assert_eq!(updated.info().unwrap().title().unwrap(), "Changed title");
+    }
+}
+
+#[test]
+fn crypt_filters() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::{Primitive, Dictionary};
+    // AES-128 for the embedded files only - and for a stream with its own `/Crypt` filter
+    let content = "BT /F1 12 Tf (Hello) Tj ET";
+    let embedded = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x05\x10\xBE\xE8\x1C\xD0\x61\x23\xCD\x98\x63\xF3\x2E\x9A\xCF\xC7";
+    let hex = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x2D\xBB\x8D\xE4\x80\x23\x88\x20\x05\x01\xD4\x1E\xEB\x01\xEF\xE9";
+    let (embedded_placeholder, hex_placeholder) = ("E".repeat(embedded.len()), "H".repeat(hex.len()));
+    let mut data = build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Filter /Standard /V 4 /R 4 /P -4 /CF << /StdCF << /CFM /AESV2 /AuthEvent /EFOpen /Length 16 >> >> \
+            /StmF /Identity /StrF /Identity /EFF /StdCF \
+            /O <566FA873EE33C797CD3B904FDADF814AFA34DF9A38F6ED41B984E2C6DA2AA6F5> \
+            /U <2C1687A9517CFF099BEC62B8A7EDDB7600000000000000000000000000000000> >>",
+        &format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content),
+        &format!("<< /Type /EmbeddedFile /Length {} >>\nstream\n{}\nendstream", embedded.len(), embedded_placeholder),
+        "(Plain title)",
+        &format!("<< /Filter [/Crypt /ASCIIHexDecode] /DecodeParms [<< /Name /StdCF >> null] /Length {} >>\nstream\n{}\nendstream",
+            hex.len(), hex_placeholder),
+    ], "/Encrypt 3 0 R /ID [<0123456789ABCDEF0123456789ABCDEF> <0123456789ABCDEF0123456789ABCDEF>]");
+    replace_bytes(&mut data, &embedded_placeholder, embedded);
+    replace_bytes(&mut data, &hex_placeholder, hex);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let stream_at = |id| Stream::<Dictionary>::from_primitive(file.resolver().resolve(PlainRef {id: id, gen: 0}).unwrap(), &file.resolver()).unwrap();
+
+    assert_eq!(&*stream_at(4).get_data().unwrap(), content.as_bytes());
+    assert_eq!(&*stream_at(5).get_data().unwrap(), &b"Attached file"[..]);
+    match file.resolver().resolve(PlainRef {id: 6, gen: 0}).unwrap() {
+        Primitive::String (s) => assert_eq!(s.as_bytes(), b"Plain title"),
+        p => panic!("expected a string, found {}", p.get_debug_name()),
+    }
+    // (the crypt filter is taken out of the filters)
+    let stream = stream_at(7);
+    assert_eq!(stream.get_filters().len(), 1);
+    assert_eq!(&*stream.get_data().unwrap(), &b"Hello"[..]);
+}
+
+#[test]
+fn info_dict() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::Date;
+    let mut file = File::from_backend(build_pdf_with_trailer(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Title <FEFF00DC0062006500720020> /Producer (pdf) /CreationDate (D:20170102030405+01'00') /Custom (kept) >>",
+    ], "/Info 3 0 R"), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let mut info = file.info().unwrap();
+    assert_eq!(info.title().unwrap(), "\u{DC}ber ");
+    assert_eq!(info.author(), None);
+    let date = info.creation_date().unwrap().unwrap();
+    assert_eq!((date.year, date.hour, date.utc_offset), (2017, 3, Some(60)));
+
+    info.set_author("Zoë");
+    info.set_mod_date(&date);
+    file.set_info(&info);
+    let mut out = Vec::new();
+    file.save_to(&mut out).unwrap();
+    let file = File::from_backend(out, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let info = file.info().unwrap();
+    assert_eq!(info.author().unwrap(), "Zoë");
+    assert_eq!(info.mod_date().unwrap().unwrap().to_string(), "D:20170102030405+01'00'");
+    assert!(info.as_dictionary().get("Custom").is_some());
+}
+
+#[test]
+fn xmp_metadata() {
+    use pdf::parser::ParseOptions;
+    use pdf::xmp;
+    let mut file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.metadata().unwrap().is_none());
+
+    let xml = concat!(
+        "<?xpacket begin='' id='W5M0MpCehiHzreSzNTczkc9d'?>",
+        "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>",
+        "<rdf:Description rdf:about='' xmp:CreateDate='2017-01-02T03:04:05+01:00' pdf:Producer='pdf &amp; co'>",
+        "<dc:title><rdf:Alt><rdf:li xml:lang='x-default'>A &lt;title&gt;</rdf:li></rdf:Alt></dc:title>",
+        "<dc:creator><rdf:Seq><rdf:li>Ann</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq></dc:creator>",
+        "</rdf:Description></rdf:RDF></x:xmpmeta><?xpacket end='w'?>");
+    file.set_metadata(xml.as_bytes()).unwrap();
+    let mut out = Vec::new();
+    file.save_to(&mut out).unwrap();
+    let file = File::from_backend(out, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let data = file.metadata().unwrap().unwrap();
+    assert_eq!(data, xml.as_bytes());
+
+    let properties = xmp::properties(&data);
+    assert_eq!(properties["dc:title"], "A <title>");
+    assert_eq!(properties["dc:creator"], "Ann; Bob");
+    assert_eq!(properties["xmp:CreateDate"], "2017-01-02T03:04:05+01:00");
+    assert_eq!(properties["pdf:Producer"], "pdf & co");
+}
+
+#[test]
+fn annotations() {
+    use pdf::parser::ParseOptions;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /Annots [4 0 R 5 0 R 6 0 R] >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] /A << /S /URI /URI (http://example.com) >> /F 4 >>",
+        "<< /Type /Annot /Subtype /Text /Rect [40 40 20 20] /Contents (A note) /Open true >>",
+        "<< /Type /Annot /Subtype /Widget /Rect [0 0 100 20] /AS /Off /AP << /N << /On 7 0 R /Off 8 0 R >> >> >>",
+        "<< /BBox [0 0 20 20] /Length 2 >>\nstream\non\nendstream",
+        "<< /BBox [0 0 20 20] /Length 3 >>\nstream\noff\nendstream",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let annots = file.annotations(file.get_page(0).unwrap()).unwrap();
+    assert_eq!(annots.len(), 3);
+    match annots[0].kind {
+        AnnotationKind::Link {ref action, ..} => assert!(action.is_some()),
+        ref kind => panic!("{:?}", kind),
+    }
+    assert!(annots[0].is_printed() && !annots[0].is_hidden());
+    match annots[1].kind {
+        AnnotationKind::Text {open, ..} => assert!(open),
+        ref kind => panic!("{:?}", kind),
+    }
+    assert_eq!(annots[1].contents.as_ref().map(|s| s.as_str()), Some("A note"));
+    assert_eq!(annots[1].rect, Rect::new(20., 20., 40., 40.));
+    let appearance = annots[2].normal_appearance(&file.resolver()).unwrap().unwrap();
+    assert_eq!(appearance.get_data_raw(), b"off");
+
+    // an annotation without /Rect
+    let mut objects = objects;
+    objects[3] = "<< /Type /Annot /Subtype /Link >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.annotations(file.get_page(0).unwrap()).is_err());
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.annotations(file.get_page(0).unwrap()).unwrap().len(), 2);
+}
+
+#[test]
+fn form_fields() {
+    use pdf::parser::ParseOptions;
+    let mut objects = [
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R 7 0 R] /DA (/Helv 0 Tf 0 g) >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /Annots [5 0 R 6 0 R 7 0 R] >>",
+        "<< /T (address) /FT /Tx /Ff 2 /Kids [5 0 R 6 0 R] >>",
+        // fields and widgets in one
+        "<< /T (city) /V (Berlin) /Parent 4 0 R /Type /Annot /Subtype /Widget /Rect [0 0 100 20] >>",
+        "<< /T (zip) /FT /Ch /DA (/Cour 10 Tf) /Parent 4 0 R /Type /Annot /Subtype /Widget /Rect [0 20 100 40] /Kids [] >>",
+        // a field with two widgets
+        "<< /T (agree) /FT /Btn /V /Yes /Kids [8 0 R 9 0 R] >>",
+        "<< /Parent 7 0 R /Type /Annot /Subtype /Widget /Rect [0 40 10 50] /AS /Yes >>",
+        "<< /Parent 7 0 R /Type /Annot /Subtype /Widget /Rect [0 50 10 60] /AS /Off >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let form = file.acro_form().unwrap().unwrap();
+    let names: Vec<&str> = form.all_fields().iter().map(|f| f.full_name.as_str()).collect();
+    assert_eq!(names, vec!["address", "address.city", "address.zip", "agree"]);
+
+    let city = form.field("address.city").unwrap();
+    assert_eq!(city.field_type, Some(FieldType::Text));
+    assert_eq!(city.flags, 2);
+    assert_eq!(city.default_appearance.as_ref().unwrap().as_bytes(), b"/Helv 0 Tf 0 g");
+    assert_eq!(city.widgets.len(), 1);
+    let zip = form.field("address.zip").unwrap();
+    assert_eq!(zip.field_type, Some(FieldType::Choice));
+    assert_eq!(zip.default_appearance.as_ref().unwrap().as_bytes(), b"/Cour 10 Tf");
+    assert!(zip.value.is_none());
+    let agree = form.field("agree").unwrap();
+    assert!(agree.children.is_empty());
+    assert_eq!(agree.widgets.iter().map(|w| w.id.unwrap().id).collect::<Vec<_>>(), vec![8, 9]);
+    assert!(form.field("city").is_none());
+
+    // a field that is its own grandchild
+    objects[5] = "<< /T (zip) /Parent 4 0 R /Kids [4 0 R] >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.acro_form().is_err());
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.acro_form().unwrap().unwrap().all_fields().len(), 4);
+}
+
+#[test]
+fn form_field_values() {
+    use pdf::parser::ParseOptions;
+    let file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [3 0 R 4 0 R 5 0 R 8 0 R 9 0 R 10 0 R 11 0 R 12 0 R 14 0 R] >> >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /T (name) /FT /Tx /V <FEFF004A00F6> >>",
+        "<< /T (agree) /FT /Btn /V /Off /AS /Off >>",
+        "<< /T (size) /FT /Btn /Ff 32768 /Kids [6 0 R 7 0 R] >>",
+        "<< /Parent 5 0 R /Type /Annot /Subtype /Widget /Rect [0 0 10 10] /AS /Off >>",
+        "<< /Parent 5 0 R /Type /Annot /Subtype /Widget /Rect [0 10 10 20] /AS /L >>",
+        "<< /T (colors) /FT /Ch /Ff 2097152 /V [(red) (blue)] >>",
+        "<< /T (country) /FT /Ch /Ff 131072 /V 13 0 R >>",
+        "<< /T (send) /FT /Btn /Ff 65536 >>",
+        "<< /T (sign) /FT /Sig >>",
+        "<< /T (newsletter) /FT /Btn /Type /Annot /Subtype /Widget /Rect [0 0 10 10] /AS /Yes >>",
+        "(Italy)",
+        "<< /T (notes) /FT /Tx /V 15 0 R >>",
+        "<< /Length 19 /Filter /ASCIIHexDecode >>\nstream\n4C6F6E67206E6F7465>\nendstream",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let form = file.acro_form().unwrap().unwrap();
+    let value = |name| form.field(name).unwrap().value(&file.resolver()).unwrap();
+    assert_eq!(value("name"), FieldValue::Text (Some("Jö".into())));
+    assert_eq!(value("agree"), FieldValue::CheckBox (false));
+    assert_eq!(value("newsletter"), FieldValue::CheckBox (true));
+    assert_eq!(value("size"), FieldValue::Radio (Some("L".into())));
+    assert_eq!(value("colors"), FieldValue::Choice (vec!["red".into(), "blue".into()]));
+    assert_eq!(value("country"), FieldValue::Choice (vec!["Italy".into()]));
+    assert_eq!(value("send"), FieldValue::PushButton);
+    assert_eq!(value("sign"), FieldValue::Signature (false));
+    assert_eq!(value("notes"), FieldValue::Text (Some("Long note".into())));
+    assert!(form.field("colors").unwrap().is_multi_select());
+    assert!(form.field("country").unwrap().is_combo_box());
+}
+
+#[test]
+fn fill_form() {
+    use pdf::parser::ParseOptions;
+    let mut file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm 3 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Fields [4 0 R 5 0 R 7 0 R] /DR << /Font << /Helv 8 0 R >> >> /DA (/Helv 0 Tf 0 g) >>",
+        "<< /T (name) /FT /Tx /Type /Annot /Subtype /Widget /Rect [0 0 200 20] >>",
+        "<< /T (agree) /FT /Btn /V /Off /Type /Annot /Subtype /Widget /Rect [0 0 10 10] /AS /Off /AP << /N << /Off 6 0 R /Checked 6 0 R >> >> >>",
+        "<< /Length 0 >>\nstream\n\nendstream",
+        "<< /T (color) /FT /Ch /Ff 131072 /Type /Annot /Subtype /Widget /Rect [0 0 100 20] /AP << /N 6 0 R >> >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    file.set_field_value("name", &FieldValue::Text (Some("Jane (Doe)".into()))).unwrap();
+    file.set_field_value("agree", &FieldValue::CheckBox (true)).unwrap();
+    assert!(file.set_field_value("agree", &FieldValue::Text (None)).is_err());
+    assert!(!file.acro_form().unwrap().unwrap().need_appearances);
+    file.set_field_value("color", &FieldValue::Choice (vec!["red".into()])).unwrap();
+
+    let mut out = Vec::new();
+    file.save_to(&mut out).unwrap();
+    let file = File::from_backend(out, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let form = file.acro_form().unwrap().unwrap();
+    assert!(form.need_appearances);
+    let name = form.field("name").unwrap();
+    assert_eq!(name.value(&file.resolver()).unwrap(), FieldValue::Text (Some("Jane (Doe)".into())));
+    let appearance = name.widgets[0].annotation.normal_appearance(&file.resolver()).unwrap().unwrap();
+    let content = String::from_utf8(appearance.get_data_raw().to_vec()).unwrap();
+    assert!(content.contains("/Helv 14 Tf 0 g"), content);
+    assert!(content.contains("(Jane \\(Doe\\)) Tj"), content);
+    let agree = form.field("agree").unwrap();
+    assert_eq!(agree.value(&file.resolver()).unwrap(), FieldValue::CheckBox (true));
+    assert_eq!(agree.widgets[0].annotation.appearance_state.as_ref().unwrap(), "Checked");
+    let color = form.field("color").unwrap();
+    assert_eq!(color.value(&file.resolver()).unwrap(), FieldValue::Choice (vec!["red".into()]));
+    // the old appearance is gone, viewers make a new one
+    assert!(color.widgets[0].annotation.normal_appearance(&file.resolver()).unwrap().is_none());
+}
+
+#[test]
+fn signature_digest() {
+    use pdf::parser::ParseOptions;
+    use pdf::signature::DigestAlgorithm;
+    let mut data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [3 0 R] >> >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /T (approval) /FT /Sig /V 4 0 R >>",
+        concat!("<< /Type /Sig /Filter /Adobe.PPKLite /SubFilter /adbe.pkcs7.detached /Reason (test) ",
+            "/ByteRange [0 0000000000 0000000000 0000000000] /Contents <",
+            "0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+            "> >>"),
+    ]);
+    // sign it: fill in the byte range, and a CMS blob with just the digest algorithm and the message digest
+    let text = String::from_utf8(data.clone()).unwrap();
+    let start = text.find("/Contents <").unwrap() + 10;
+    let end = start + 202;
+    let range = format!("[0 {:010} {:010} {:010}]", start, end, data.len() - end);
+    let range_pos = text.find("[0 0000000000").unwrap();
+    data[range_pos .. range_pos + range.len()].copy_from_slice(range.as_bytes());
+    let digest = DigestAlgorithm::Sha256.digest(&[&data[.. start], &data[end ..]]);
+    let mut cms = b"\x30\x3A\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x01\x06\x09\x2A\x86\x48\x86\xF7\x0D\x01\x09\x04\x31\x22\x04\x20".to_vec();
+    cms.extend_from_slice(&digest);
+    let hex: String = cms.iter().map(|b| format!("{:02X}", b)).collect();
+    data[start + 1 .. start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+
+    let file = File::from_backend(data.clone(), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let signatures = file.signatures().unwrap();
+    assert_eq!(signatures.len(), 1);
+    let signature = &signatures[0];
+    assert_eq!(signature.field, "approval");
+    assert_eq!(signature.reason.as_ref().unwrap(), "test");
+    assert_eq!(signature.cms().unwrap(), &cms[..]);
+    let verification = file.verify_signature(signature).unwrap();
+    assert_eq!(verification.algorithm, DigestAlgorithm::Sha256);
+    assert!(verification.digest_matches);
+    assert!(verification.covers_whole_file);
+    assert_eq!(verification.revision, Some(0));
+
+    // changed after signing
+    let mut changed = data.clone();
+    let reason = text.find("(test)").unwrap();
+    changed[reason + 1] = b'T';
+    assert!(!signature.verify(&changed, &[]).unwrap().digest_matches);
+    // updated after signing
+    let mut updated = data.clone();
+    updated.extend_from_slice(b"% an update\n");
+    let verification = signature.verify(&updated, &file.revisions().unwrap()).unwrap();
+    assert!(verification.digest_matches && !verification.covers_whole_file);
+    assert_eq!(verification.revision, Some(0));
+}
+
+#[test]
+fn sign() {
+    use pdf::parser::ParseOptions;
+    use pdf::signature::{DigestAlgorithm, SigningOptions};
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+    ]);
+    let mut file = File::from_backend(data.clone(), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let options = SigningOptions {
+        reason:         Some("approved".into()),
+        contents_size:  256,
+        .. SigningOptions::default()
+    };
+    let signed = file.sign(&options, |signed| {
+        let mut cms = b"\x30\x3A\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x01\x06\x09\x2A\x86\x48\x86\xF7\x0D\x01\x09\x04\x31\x22\x04\x20".to_vec();
+        cms.extend_from_slice(&DigestAlgorithm::Sha256.digest(&[signed]));
+        Ok(cms)
+    }).unwrap();
+    assert_eq!(&signed[.. data.len()], &data[..]);
+
+    let file = File::from_backend(signed, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.get_num_pages().unwrap(), 1);
+    let signatures = file.signatures().unwrap();
+    assert_eq!(signatures.len(), 1);
+    assert_eq!(signatures[0].field, "Signature1");
+    assert_eq!(signatures[0].reason.as_ref().unwrap(), "approved");
+    let verification = file.verify_signature(&signatures[0]).unwrap();
+    assert!(verification.digest_matches);
+    assert!(verification.covers_whole_file);
+    assert_eq!(verification.revision, Some(1));
+    let annotations = file.annotations(file.get_page(0).unwrap()).unwrap();
+    assert_eq!(annotations[0].subtype, "Widget");
+
+    // a CMS blob that doesn't fit
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+    ]);
+    let mut file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.sign(&options, |_| Ok(vec![0; 257])).is_err());
+}
+
+#[test]
+fn attachments() {
+    use pdf::parser::ParseOptions;
+    use pdf::object::AnnotationKind;
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /Names << /EmbeddedFiles << /Names [(factur-x.xml) 4 0 R] >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [6 0 R] >>",
+        "<< /Type /Filespec /F (factur-x.xml) /UF (factur-x.xml) /Desc (Invoice) /AFRelationship /Data /EF << /F 5 0 R >> >>",
+        concat!("<< /Type /EmbeddedFile /Subtype /text#2Fxml /Length 11 /Filter /ASCIIHexDecode ",
+            "/Params << /Size 5 /CheckSum <5D41402ABC4B2A76B9719D911017C592> >> >>\nstream\n68656C6C6F>\nendstream"),
+        "<< /Type /Annot /Subtype /FileAttachment /Rect [0 0 10 10] /Name /Paperclip /FS << /F (note.txt) /EF << /F 7 0 R >> >> >>",
+        "<< /Length 4 >>\nstream\nnote\nendstream",
+    ]);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let attachments = file.attachments().unwrap();
+    assert_eq!(attachments.len(), 2);
+    let invoice = &attachments[0];
+    assert_eq!(invoice.name.as_ref().unwrap(), "factur-x.xml");
+    assert_eq!(invoice.file_name.as_ref().unwrap(), "factur-x.xml");
+    assert_eq!(invoice.description.as_ref().unwrap(), "Invoice");
+    assert_eq!(invoice.mime_type.as_ref().unwrap(), "text/xml");
+    assert_eq!(invoice.relationship.as_ref().unwrap(), "Data");
+    assert_eq!(invoice.size, Some(5));
+    assert_eq!(file.attachment_data(invoice).unwrap(), b"hello");
+
+    let note = &attachments[1];
+    assert_eq!(note.name, None);
+    assert_eq!(note.page, Some(0));
+    assert_eq!(note.file_name.as_ref().unwrap(), "note.txt");
+    assert_eq!(note.check(b"note"), None);
+    assert_eq!(file.attachment_data(note).unwrap(), b"note");
+    match file.annotations(file.get_page(0).unwrap()).unwrap()[0].kind {
+        AnnotationKind::FileAttachment {ref icon, ..} => assert_eq!(icon.as_ref().unwrap(), "Paperclip"),
+        ref kind => panic!("expected FileAttachment, found {:?}", kind),
+    }
+
+    let invoice = file.attachment("factur-x.xml").unwrap().unwrap();
+    assert_eq!(invoice.check(b"hello"), Some(true));
+    assert_eq!(invoice.check(b"hallo"), Some(false));
+    assert!(file.attachment("missing.xml").unwrap().is_none());
+}
+
+#[test]
+fn add_attachment() {
+    use pdf::parser::ParseOptions;
+    use pdf::object::{AttachmentOptions, Ref, PlainRef};
+    use pdf::primitive::{Date, Primitive, Dictionary};
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /Names 3 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /EmbeddedFiles << /Names [(b.txt) << /F (b.txt) /EF << /F 4 0 R >> >>] >> >>",
+        "<< /Length 1 >>\nstream\nb\nendstream",
+    ]);
+    let mut file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let options = AttachmentOptions {
+        mime_type:      Some("text/xml".into()),
+        relationship:   Some("Alternative".into()),
+        mod_date:       Some(Date::parse(b"D:20240101120000Z").unwrap()),
+        .. AttachmentOptions::default()
+    };
+    let xml = b"<rsm:CrossIndustryInvoice/>";
+    let spec = file.add_attachment("factur-x.xml", xml, &options).unwrap();
+    file.add_attachment("a.txt", b"a", &AttachmentOptions::default()).unwrap();
+    // replaced
+    file.add_attachment("b.txt", b"bb", &AttachmentOptions::default()).unwrap();
+    let mut saved = Vec::new();
+    file.save_to(&mut saved).unwrap();
+
+    let file = File::from_backend(saved, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let attachments = file.attachments().unwrap();
+    let names: Vec<_> = attachments.iter().map(|a| a.name.clone().unwrap()).collect();
+    assert_eq!(names, ["a.txt", "b.txt", "factur-x.xml"]);
+    assert_eq!(file.attachment_data(&attachments[1]).unwrap(), b"bb");
+    let invoice = &attachments[2];
+    assert_eq!(invoice.mime_type.as_ref().unwrap(), "text/xml");
+    assert_eq!(invoice.relationship.as_ref().unwrap(), "Alternative");
+    assert_eq!(invoice.size, Some(xml.len()));
+    assert_eq!(invoice.mod_date.as_ref().unwrap().year, 2024);
+    let data = file.attachment_data(invoice).unwrap();
+    assert_eq!(data, &xml[..]);
+    assert_eq!(invoice.check(&data), Some(true));
+    // the objects keep their numbers
+    let catalog = file.deref(Ref::<Dictionary>::new(PlainRef {id: 1, gen: 0})).unwrap();
+    match catalog.get("AF") {
+        Some(&Primitive::Array (ref files)) => {
+            assert_eq!(files.len(), 1);
+            match files[0] {
+                Primitive::Reference (r) => assert_eq!(r, spec),
+                ref p => panic!("expected a reference, found {:?}", p),
+            }
+        }
+        af => panic!("expected /AF, found {:?}", af),
+    }
+}
+
+#[test]
+fn optional_content() {
+    use std::collections::HashSet;
+    use pdf::parser::ParseOptions;
+    use pdf::object::{OptionalContentMembership, PlainRef, VisibilityPolicy};
+    use pdf::primitive::Primitive;
+    use pdf::{Error, ErrorKind};
+    let data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /OCProperties << /OCGs [4 0 R 5 0 R] /D << /Name (Default) /OFF [5 0 R] /Locked [4 0 R] >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        concat!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 7 0 R /Annots [9 0 R] ",
+            "/Resources << /Properties << /L1 4 0 R /Both 6 0 R >> /XObject << /Im1 8 0 R >> >> >>"),
+        "<< /Type /OCG /Name (Text) >>",
+        "<< /Type /OCG /Name (Notes) /Intent /Design >>",
+        "<< /Type /OCMD /OCGs [4 0 R 5 0 R] /P /AllOn >>",
+        "<< /Length 77 >>\nstream\n/OC /L1 BDC 1 0 0 RG EMC /OC /Both BDC 0 g /Im1 Do EMC /Span <<>> BDC 2 w EMC\nendstream",
+        "<< /Type /XObject /Subtype /Form /BBox [0 0 1 1] /OC 5 0 R /Length 0 >>\nstream\n\nendstream",
+        "<< /Type /Annot /Subtype /Text /Rect [0 0 10 10] /OC 5 0 R >>",
+    ]);
+    let file = File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let oc = file.optional_content().unwrap().unwrap();
+    let text = PlainRef {id: 4, gen: 0};
+    let notes = PlainRef {id: 5, gen: 0};
+    assert_eq!(oc.groups.len(), 2);
+    assert_eq!(oc.group(text).unwrap().name, "Text");
+    assert_eq!(oc.group_by_name("Notes").unwrap().intent, ["Design"]);
+    assert_eq!(oc.default_config.name.as_ref().unwrap(), "Default");
+    assert_eq!(oc.default_config.locked, [text]);
+    let active = oc.default_active_groups();
+    assert!(active.contains(&text) && !active.contains(&notes));
+
+    let page = file.get_page(0).unwrap();
+    let layers = file.page_layers(page).unwrap();
+    assert_eq!(layers.xobjects.len(), 1);
+    assert_eq!(layers.xobjects[0].0, "Im1");
+    assert_eq!(layers.xobjects[0].1, OptionalContentMembership::Group (notes));
+    assert_eq!(layers.annotations.len(), 1);
+    assert_eq!(layers.annotations[0], (0, OptionalContentMembership::Group (notes)));
+    assert_eq!(layers.sections.len(), 2);
+    assert_eq!((layers.sections[0].0, layers.sections[0].1), (0, 2));
+    match layers.sections[1].2 {
+        OptionalContentMembership::Membership {ref groups, policy, ..} => {
+            assert_eq!(groups, &[text, notes]);
+            assert_eq!(policy, VisibilityPolicy::AllOn);
+        }
+        ref m => panic!("expected a membership dictionary, found {:?}", m),
+    }
+
+    let content = file.page_content(page).unwrap();
+    let operators = |active: HashSet<PlainRef>| -> Vec<String> {
+        let filtered = oc.filter_content(&content, page.resources(), &active, &file.resolver()).unwrap();
+        filtered.operations.iter().map(|op| op.operator.clone()).collect()
+    };
+    assert_eq!(operators(oc.default_active_groups()), ["BDC", "RG", "EMC", "BDC", "w", "EMC"]);
+    assert_eq!(operators(vec![text, notes].into_iter().collect()), ["BDC", "RG", "EMC", "BDC", "g", "Do", "EMC", "BDC", "w", "EMC"]);
+    assert_eq!(operators(vec![notes].into_iter().collect()), ["BDC", "w", "EMC"]);
+
+    // a visibility expression that contains itself
+    let objects = [
+        Primitive::Null,
+        parse(b"<< /Type /OCMD /VE 2 0 R >>", NO_RESOLVE).unwrap(),
+        parse(b"[/Not 2 0 R]", NO_RESOLVE).unwrap(),
+    ];
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+    match OptionalContentMembership::from_primitive(&Primitive::Reference (PlainRef {id: 1, gen: 0}), &resolve) {
+        Err(Error(ErrorKind::CircularReference {obj_nr: 2}, _)) => {}
+        Err(e) => panic!("expected CircularReference, found {}", e),
+        Ok(m) => panic!("expected CircularReference, found {:?}", m),
+    }
+}
+
+#[test]
+fn struct_tree() {
+    use pdf::parser::ParseOptions;
+    use pdf::object::{PlainRef, StructChild, StructType};
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /StructTreeRoot 4 0 R /MarkInfo << /Marked true >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /StructParents 0 >>",
+        "<< /Type /StructTreeRoot /K 5 0 R /RoleMap << /Title /Heading /Heading /H1 >> /ParentTree 10 0 R >>",
+        "<< /Type /StructElem /S /Document /P 4 0 R /K [6 0 R 7 0 R 8 0 R] /Lang (en) >>",
+        "<< /Type /StructElem /S /Title /P 5 0 R /Pg 3 0 R /K 0 /T (Intro) >>",
+        "<< /Type /StructElem /S /P /P 5 0 R /Pg 3 0 R /K [1 << /Type /MCR /MCID 2 >>] /A [<< /O /Layout /TextAlign /Center >> 0] >>",
+        "<< /Type /StructElem /S /Figure /P 5 0 R /Pg 3 0 R /Alt (A diagram) /K << /Type /OBJR /Obj 9 0 R >> >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] /StructParent 1 >>",
+        "<< /Nums [0 [6 0 R 7 0 R 7 0 R] 1 8 0 R] >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.get_root().mark_info.as_ref().unwrap().marked);
+    let tree = file.struct_tree().unwrap().unwrap();
+    assert_eq!(tree.role("Title"), StructType::H1);
+    assert_eq!(tree.role("Custom"), StructType::Other ("Custom".into()));
+    assert_eq!(tree.children.len(), 1);
+    let document = &tree.children[0];
+    assert_eq!(document.role, StructType::Document);
+    assert_eq!(document.lang.as_ref().unwrap(), "en");
+    let all = tree.all();
+    assert_eq!(all.len(), 4);
+
+    let title = all[1];
+    assert_eq!(title.struct_type, "Title");
+    assert_eq!(title.role, StructType::H1);
+    assert_eq!(title.role.heading_level(), Some(1));
+    assert_eq!(title.title.as_ref().unwrap(), "Intro");
+    let page = PlainRef {id: 3, gen: 0};
+    match title.children[0] {
+        StructChild::MarkedContent {mcid: 0, page: Some(p), stream: None} => assert_eq!(p, page),
+        ref child => panic!("expected MCID 0, found {:?}", child),
+    }
+    let paragraph = all[2];
+    assert_eq!(paragraph.children.len(), 2);
+    assert_eq!(paragraph.attributes.len(), 1);
+    let figure = all[3];
+    assert_eq!(figure.alt.as_ref().unwrap(), "A diagram");
+    match figure.children[0] {
+        StructChild::Object {object, ..} => assert_eq!(object, PlainRef {id: 9, gen: 0}),
+        ref child => panic!("expected an object, found {:?}", child),
+    }
+
+    let resolve = file.resolver();
+    assert_eq!(tree.mcid_parent(0, 2, &resolve).unwrap(), Some(PlainRef {id: 7, gen: 0}));
+    assert_eq!(tree.mcid_parent(0, 5, &resolve).unwrap(), None);
+    assert_eq!(tree.object_parent(1, &resolve).unwrap(), Some(PlainRef {id: 8, gen: 0}));
+    assert_eq!(tree.find(PlainRef {id: 6, gen: 0}).unwrap().struct_type, "Title");
+    assert_eq!(file.get_page(0).unwrap().struct_parents, Some(0));
+
+    // an element that is in the tree twice is an error - or, if not strict, left out
+    let mut objects = objects;
+    objects[7] = "<< /Type /StructElem /S /Figure /P 5 0 R /K [<< /Type /OBJR /Obj 9 0 R >> 7 0 R] >>";
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert!(file.struct_tree().is_err());
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    let tree = file.struct_tree().unwrap().unwrap();
+    assert_eq!(tree.all().len(), 4);
+    assert_eq!(tree.all()[3].children.len(), 1);
+}
+
+#[test]
+fn extract_text() {
+    use pdf::parser::ParseOptions;
+    use pdf::object::StructType;
+    use pdf::text::{extract_text, page_text_fragments};
+    let stream = |content: &str| format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content);
+    // the heading is drawn last, and the footer is an artifact
+    let content = stream("/Artifact BMC BT /F1 10 Tf 20 20 Td (Footer) Tj ET EMC\n\
+        /P <</MCID 1>> BDC BT /F1 12 Tf 20 150 Td (First line of) Tj 0 -14 Td (text) Tj ET EMC\n\
+        /Link <</MCID 2>> BDC BT /F1 12 Tf 60 136 Td (here) Tj ET EMC\n\
+        /P <</MCID 3>> BDC /Span <</ActualText (Office)>> BDC BT /F1 12 Tf 20 100 Td (O\\036ce) Tj ET EMC EMC\n\
+        /H1 <</MCID 0>> BDC BT /F1 24 Tf 20 180 Td [(Hea) -30 (ding)] TJ ET EMC");
+    // untagged, with the lines from the bottom
+    let untagged = stream("BT /F1 12 Tf 20 50 Td [(Bottom) -500 (line)] TJ 0 40 Td (Top line) Tj ET");
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /StructTreeRoot 6 0 R /MarkInfo << /Marked true >> >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources 10 0 R /Contents 5 0 R /StructParents 0 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources 10 0 R /Contents 11 0 R >>",
+        &content,
+        "<< /Type /StructTreeRoot /K 7 0 R >>",
+        "<< /Type /StructElem /S /Document /P 6 0 R /K [8 0 R 9 0 R 12 0 R 13 0 R] /Lang (en-US) >>",
+        "<< /Type /StructElem /S /H1 /P 7 0 R /Pg 3 0 R /K 0 >>",
+        "<< /Type /StructElem /S /P /P 7 0 R /Pg 3 0 R /K [1 14 0 R] >>",
+        "<< /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> >> >>",
+        &untagged,
+        "<< /Type /StructElem /S /P /P 7 0 R /Pg 3 0 R /K 3 >>",
+        "<< /Type /StructElem /S /Figure /P 7 0 R /Alt (A chart) >>",
+        "<< /Type /StructElem /S /Link /P 9 0 R /Pg 3 0 R /K 2 >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.page_refs().unwrap().iter().map(|r| r.id).collect::<Vec<_>>(), vec![3, 4]);
+
+    let fragments = page_text_fragments(&file, file.get_page(0).unwrap()).unwrap();
+    let texts: Vec<_> = fragments.iter().map(|f| (f.text.as_str(), f.mcid)).collect();
+    assert_eq!(texts, vec![("Footer", None), ("First line of", Some(1)), ("text", Some(1)),
+                           ("here", Some(2)), ("Office", Some(3)), ("Heading", Some(0))]);
+    let heading = &fragments[5];
+    assert_eq!((heading.x, heading.y, heading.size), (20.0, 180.0, 24.0));
+    // 7 glyphs of the default width, and the adjustment
+    assert!((heading.width - (7.0 * 0.5 * 24.0 + 0.03 * 24.0)).abs() < 0.01);
+
+    let blocks = extract_text(&file).unwrap();
+    let texts: Vec<_> = blocks.iter().map(|b| (b.role.clone(), b.text.as_str(), b.page)).collect();
+    assert_eq!(texts, vec![
+        (Some(StructType::H1), "Heading", Some(0)),
+        (Some(StructType::P), "First line of text here", Some(0)),
+        (Some(StructType::P), "Office", Some(0)),
+        (Some(StructType::Figure), "A chart", None),
+        (None, "Top line", Some(1)),
+        (None, "Bottom line", Some(1)),
+    ]);
+    assert_eq!(blocks[0].depth, 1);
+    assert_eq!(blocks[0].lang.as_ref().unwrap(), "en-US");
+    assert_eq!(blocks[1].struct_type.as_ref().unwrap(), "P");
+}
+
+#[test]
+fn reconstruction() {
+    use pdf::parser::ParseOptions;
+    let mut data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]);
+    // a broken xref offset - and an object number that can only be damage
+    let start = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+    data.truncate(start);
+    data.extend_from_slice(b"4000000000 0 obj\nnull\nendobj\nstartxref\n1\n%%EOF\n");
+
+    // (only if not strict)
+    assert!(File::from_backend(data.clone(), ParseOptions::strict()).is_err());
+    let file = File::from_backend(data, ParseOptions::lenient()).unwrap_or_else(|e| print_err(e));
+    assert_eq!(file.object_refs().len(), 2);
+    assert!(file.resolver().resolve(PlainRef {id: 4000000000, gen: 0}).is_err());
+    let pages = file.get_root().pages.count;
+    assert_eq!(pages, 0);
+
+    // if reconstructing fails too, the error of the xref table is kept
+    let data = b"%PDF-1.7\nstartxref\n1\n%%EOF\n".to_vec();
+    match File::from_backend(data, ParseOptions::lenient()) {
+        Err(e) => assert!(e.iter().count() > 1, "{}", e),
+        Ok(_) => panic!("expected an error"),
+    }
+}
+
+#[test]
+fn promised_objects() {
+    use pdf::parser::ParseOptions;
+    use pdf::file::PromisedRef;
+    use pdf::primitive::Dictionary;
+    use pdf::{Error, ErrorKind};
+    let mut file = File::from_backend(build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let promised: PromisedRef<Dictionary> = file.promise();
+    let r: PlainRef = (&promised).into();
+    match file.resolver().resolve(r) {
+        Err(Error(ErrorKind::PromisedObject {obj_nr}, _)) => assert_eq!(obj_nr, r.id),
+        Err(e) => panic!("expected PromisedObject, found {}", e),
+        Ok(p) => panic!("expected PromisedObject, found {:?}", p),
+    }
+}
+
+#[test]
+fn revisions() {
+    use pdf::parser::ParseOptions;
+    use pdf::primitive::Dictionary;
+    let mut data = build_pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ]);
+    let original_len = data.len();
+
+    // an incremental update that changes object 2
+    let prev = {
+        let text = str::from_utf8(&data).unwrap();
+        let start = text.rfind("startxref\n").unwrap() + 10;
+        text[start ..].lines().next().unwrap().to_string()
+    };
+    let pos = data.len();
+    data.extend_from_slice(b"2 0 obj\n<< /Type /Pages /Kids [] /Count 0 /Updated true >>\nendobj\n");
+    let xref = data.len();
+    data.extend_from_slice(format!("xref\n2 1\n{:010} 00000 n \ntrailer\n<< /Size 3 /Root 1 0 R /Prev {} >>\nstartxref\n{}\n%%EOF\n", pos, prev, xref).as_bytes());
+
+    let file = File::from_backend(data.clone(), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let revisions = file.revisions().unwrap_or_else(|e| print_err(e));
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0].end, original_len);
+    assert_eq!(revisions[1].end, data.len());
+    assert!(revisions[0].trailer.get("Prev").is_none());
+    assert!(revisions[1].trailer.get("Prev").is_some());
+
+    let pages = PlainRef {id: 2, gen: 0};
+    let latest = file.deref(Ref::<Dictionary>::new(pages)).unwrap_or_else(|e| print_err(e));
+    assert!(latest.get("Updated").is_some());
+
+    let original = File::from_backend_at_revision(data.clone(), 0, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let before = original.deref(Ref::<Dictionary>::new(pages)).unwrap_or_else(|e| print_err(e));
+    assert!(before.get("Updated").is_none());
+
+    assert!(File::from_backend_at_revision(data, 2, ParseOptions::strict()).is_err());
+}
+
+#[test]
+fn layout() {
+    use pdf::parser::ParseOptions;
+    use pdf::xref::ObjectLocation;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ];
+    let file = File::from_backend(build_pdf(&objects), ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+    let layout = file.layout().unwrap_or_else(|e| print_err(e));
+    assert_eq!(layout.len(), 3);
+    assert_eq!(layout[0].location, ObjectLocation::Free);
+    assert_eq!(layout[0].gen, 65535);
+    let first_len = format!("1 0 obj\n{}\nendobj", objects[0]).len();
+    assert_eq!(layout[1].location, ObjectLocation::Raw {offset: 9, len: first_len});
+    assert_eq!(layout[2].location, ObjectLocation::Raw {offset: 9 + first_len + 1, len: format!("2 0 obj\n{}\nendobj", objects[1]).len()});
+
+    let file = File::<Vec<u8>>::open(file_path!("xelatex.pdf")).unwrap_or_else(|e| print_err(e));
+    let layout = file.layout().unwrap_or_else(|e| print_err(e));
+    assert!(layout.iter().any(|object| object.in_object_stream()));
+}
+
+#[test]
+fn junk_after_eof() {
+    use pdf::parser::ParseOptions;
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+    ];
+    let mut data = build_pdf(&objects);
+    data.extend(std::iter::repeat(b'x').take(5000));
+    File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+
+    // no %%EOF at all
+    let mut data = build_pdf(&objects);
+    let len = data.len();
+    data.truncate(len - "\n%%EOF\n".len());
+    File::from_backend(data, ParseOptions::strict()).unwrap_or_else(|e| print_err(e));
+}
+
+#[test]
+fn page_offset_hints() {
+    use pdf::linearization::parse_page_offset_hints;
+    let mut data = vec![
+        0, 0, 0, 2,     0, 0, 0, 100,   0, 4,
+        0, 0, 0, 50,    0, 8,
+        0, 0, 0, 0,     0, 0,   0, 0, 0, 0,     0, 0,
+        0, 0,   0, 0,   0, 0,   0, 0,
+    ];
+    // number of objects (4 bits each), then page lengths (8 bits each)
+    data.extend_from_slice(&[0x13, 10, 20]);
+    let hints = parse_page_offset_hints(&data, 2, 1000).unwrap_or_else(|e| print_err(e));
+    assert_eq!(hints.pages[0].num_objects, 3);
+    assert_eq!(hints.pages[1].num_objects, 5);
+    assert_eq!(hints.pages[1].len, 70);
+    assert_eq!(hints.page_offsets().unwrap(), vec![100, 160]);
+
+    // more pages, or bytes, than the file has
+    assert!(parse_page_offset_hints(&data, 2000, 1000).is_err());
+    assert!(parse_page_offset_hints(&data, 2, 150).is_err());
+    // and the least length plus the bits beyond 32 bits
+    data[13] = 0xFF; data[12] = 0xFF; data[11] = 0xFF; data[10] = 0xFF;
+    assert!(parse_page_offset_hints(&data, 2, 1000).is_err());
+}
+
+#[test]
+fn linearization_dict() {
+    use pdf::linearization::read_linearization;
+    let data = b"%PDF-1.7\n4 0 obj\n<< /Linearized 1 /L 1000 /H [200 30] /O 6 /E 500 /N 2 /T 900 >>\nendobj\n".to_vec();
+    let (lin, _) = read_linearization(&data).unwrap_or_else(|e| print_err(e)).unwrap();
+    assert_eq!(lin.first_page, 6);
+    assert_eq!(lin.hint_stream, vec![200, 30]);
+
+    let file = File::<Vec<u8>>::open(file_path!("example.pdf")).unwrap_or_else(|e| print_err(e));
+    assert!(!file.is_linearized());
+}
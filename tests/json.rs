@@ -0,0 +1,39 @@
+#![cfg(feature = "serde")]
+extern crate pdf;
+extern crate serde_json;
+
+use pdf::json::{to_json, from_json};
+use pdf::parser::parse;
+use pdf::primitive::Primitive;
+use pdf::object::{PlainRef, NO_RESOLVE};
+
+#[test]
+fn round_trip() {
+    let p = parse(b"<< /Type /Page /Count 3 /Scale 0.5 /Title (Hello) /Id <FF00> /Kids [1 0 R null true] >>", NO_RESOLVE).unwrap();
+    let json = to_json(&p, NO_RESOLVE).unwrap();
+    assert_eq!(json, serde_json::from_str::<serde_json::Value>(r#"{
+        "/Type": "/Page",
+        "/Count": 3,
+        "/Scale": 0.5,
+        "/Title": {"string": "Hello"},
+        "/Id": {"hex": "FF00"},
+        "/Kids": [{"ref": [1, 0]}, null, true]
+    }"#).unwrap());
+
+    let back = from_json(&json).unwrap();
+    assert_eq!(to_json(&back, NO_RESOLVE).unwrap(), json);
+}
+
+#[test]
+fn resolve_references() {
+    let objects = vec![
+        parse(b"<< /Kids [1 0 R] >>", NO_RESOLVE).unwrap(),
+        parse(b"<< /Parent 0 0 R /N 7 >>", NO_RESOLVE).unwrap(),
+    ];
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+    let json = to_json(&Primitive::Reference (PlainRef {id: 0, gen: 0}), &resolve).unwrap();
+    // the parent refers back to the root, which is not expanded again
+    assert_eq!(json, serde_json::from_str::<serde_json::Value>(r#"{
+        "/Kids": [{"/Parent": {"ref": [0, 0]}, "/N": 7}]
+    }"#).unwrap());
+}
@@ -0,0 +1,637 @@
+extern crate pdf;
+#[macro_use]
+extern crate pdf_derive;
+
+use pdf::parser::parse;
+use pdf::primitive::{Primitive, Date};
+use pdf::parser::ParseOptions;
+use pdf::object::{Object, Resolver, NO_RESOLVE, PlainRef};
+
+#[test]
+fn comments() {
+    let p = parse(b"% leading comment\n<< /A 1 % comment\n /B [2 %x\r 3] >>", NO_RESOLVE).unwrap();
+    let dict = match p {
+        Primitive::Dictionary (dict) => dict,
+        p => panic!("expected dictionary, found {:?}", p),
+    };
+    assert_eq!(dict.get("A").unwrap().as_integer().unwrap(), 1);
+    match *dict.get("B").unwrap() {
+        Primitive::Array (ref a) => assert_eq!(a.len(), 2),
+        ref p => panic!("expected array, found {:?}", p),
+    }
+}
+
+fn string(data: &[u8]) -> Vec<u8> {
+    match parse(data, NO_RESOLVE).unwrap() {
+        Primitive::String (s) => s.as_bytes().to_vec(),
+        p => panic!("expected string, found {:?}", p),
+    }
+}
+
+#[test]
+fn hex_strings() {
+    assert_eq!(string(b"<48656C6C6F>"), b"Hello");
+    assert_eq!(string(b"<48 65 6C6c\n6F>"), b"Hello");
+    assert_eq!(string(b"<901FA>"), &[0x90, 0x1F, 0xA0]);
+    assert_eq!(string(b"<>"), b"");
+}
+
+#[test]
+fn literal_strings() {
+    assert_eq!(string(b"(Hello)"), b"Hello");
+    assert_eq!(string(b"(a (nested (pair)) b)"), b"a (nested (pair)) b");
+    assert_eq!(string(b"(\\n\\r\\t\\b\\f\\\\\\(\\))"), b"\n\r\t\x08\x0C\\()");
+    assert_eq!(string(b"(\\101\\0532\\7x)"), b"A+2\x07x");
+    assert_eq!(string(b"(split \\\nline, \\\r\nagain)"), b"split line, again");
+    assert_eq!(string(b"(crlf\r\nbecomes lf)"), b"crlf\nbecomes lf");
+    assert_eq!(string(b"(unknown \\q escape)"), b"unknown q escape");
+}
+
+#[test]
+fn lenient() {
+    let lenient = Resolver::new(|_: PlainRef| -> pdf::Result<Primitive> { Ok(Primitive::Null) }, ParseOptions::lenient());
+    let data = b"<< /A 1 junk /B 2 >>";
+    assert!(parse(data, NO_RESOLVE).is_err());
+    let dict = match parse(data, &lenient).unwrap() {
+        Primitive::Dictionary (dict) => dict,
+        p => panic!("expected dictionary, found {:?}", p),
+    };
+    assert_eq!(dict.get("B").unwrap().as_integer().unwrap(), 2);
+
+    // a stream without /Length
+    match parse(b"<< >> stream\ndata\nendstream", &lenient).unwrap() {
+        Primitive::Stream (s) => assert_eq!(s.data, b"data"),
+        p => panic!("expected stream, found {:?}", p),
+    }
+
+    // wrong types of optional values
+    assert!(Option::<i32>::from_primitive(Primitive::Name ("x".into()), NO_RESOLVE).is_err());
+    assert_eq!(Option::<i32>::from_primitive(Primitive::Name ("x".into()), &lenient).unwrap(), None);
+}
+
+#[test]
+fn whitespace() {
+    // form feed and null are whitespace too
+    match parse(b"[1\x0C2\x003]", NO_RESOLVE).unwrap() {
+        Primitive::Array (a) => assert_eq!(a.len(), 3),
+        p => panic!("expected array, found {:?}", p),
+    }
+}
+
+#[test]
+fn wrong_stream_length() {
+    use pdf::{Error, ErrorKind};
+    let data = b"<< /Length 2 >>\nstream\ndata\nendstream";
+    match parse(data, NO_RESOLVE) {
+        Err(Error(ErrorKind::StreamLength {length: 2, found: 4}, _)) => {}
+        r => panic!("expected StreamLength, found {:?}", r),
+    }
+    // (recovered, if not strict)
+    let lenient = Resolver::new(|_: PlainRef| -> pdf::Result<Primitive> { Ok(Primitive::Null) }, ParseOptions::lenient());
+    match parse(data, &lenient).unwrap() {
+        Primitive::Stream (stream) => {
+            assert_eq!(stream.data, b"data");
+            assert_eq!(stream.info.get("Length").unwrap().as_integer().unwrap(), 4);
+        }
+        p => panic!("expected stream, found {:?}", p),
+    }
+}
+
+#[test]
+fn find_substr() {
+    use pdf::parser::find_substr;
+    assert_eq!(find_substr(b"end endstream", b"endstream"), Some(4));
+    assert_eq!(find_substr(b"endstrea", b"endstream"), None);
+}
+
+#[test]
+fn names() {
+    match parse(b"/A#20B#2f#", NO_RESOLVE).unwrap() {
+        Primitive::Name (name) => assert_eq!(name, "A B/#"),
+        p => panic!("expected name, found {:?}", p),
+    }
+    // not UTF-8: an error if strict
+    assert!(parse(b"/A#FF", NO_RESOLVE).is_err());
+    let lenient = Resolver::new(|_: PlainRef| -> pdf::Result<Primitive> { Ok(Primitive::Null) }, ParseOptions::lenient());
+    match parse(b"/A#FF", &lenient).unwrap() {
+        Primitive::Name (name) => assert_eq!(name, "A\u{FFFD}"),
+        p => panic!("expected name, found {:?}", p),
+    }
+}
+
+#[test]
+fn header() {
+    use pdf::parser::{parse_header, PdfVersion};
+    assert_eq!(parse_header(b"%PDF-2.0\n%\xE2\xE3\xCF\xD3\n").unwrap(), PdfVersion::new(2, 0));
+    assert_eq!(parse_header(b"junk\n%PDF-1.4\n").unwrap(), PdfVersion::new(1, 4));
+    assert!(parse_header(b"1 0 obj").is_err());
+    assert!(PdfVersion::new(2, 0) > PdfVersion::new(1, 7));
+}
+
+#[test]
+fn dates() {
+    let date = Date::parse(b"D:20240131120000+01'00'").unwrap();
+    assert_eq!(date, Date {year: 2024, month: 1, day: 31, hour: 12, minute: 0, second: 0, utc_offset: Some(60)});
+    assert_eq!(date.to_string(), "D:20240131120000+01'00'");
+    let datetime = date.to_datetime().unwrap();
+    assert_eq!(datetime.to_rfc3339(), "2024-01-31T12:00:00+01:00");
+    assert_eq!(Date::from(datetime), date);
+
+    // optional components
+    assert_eq!(Date::parse(b"D:1999").unwrap(), Date {year: 1999, month: 1, day: 1, hour: 0, minute: 0, second: 0, utc_offset: None});
+    assert_eq!(Date::parse(b"D:199912312359Z").unwrap().utc_offset, Some(0));
+    assert_eq!(Date::parse(b"20010203040506-0530").unwrap().utc_offset, Some(-330));
+
+    assert!(Date::parse(b"D:2024").is_ok());
+    assert!(Date::parse(b"D:20241301").is_err());
+    assert!(Date::parse(b"D:").is_err());
+    assert!(Date::parse(b"D:20230230").unwrap().to_datetime().is_none());
+
+    let p = parse(b"(D:20240131120000Z)", NO_RESOLVE).unwrap();
+    assert_eq!(Date::from_primitive(p, NO_RESOLVE).unwrap().hour, 12);
+}
+
+#[test]
+fn text_strings() {
+    use pdf::primitive::PdfString;
+    let text = |data: &[u8]| PdfString::new(data.to_vec()).to_string_lossy();
+    assert_eq!(text(b"Hello"), "Hello");
+    // PDFDocEncoding
+    assert_eq!(text(b"\x80 caf\xE9 \xA0 \x93"), "\u{2022} caf\u{E9} \u{20AC} \u{FB01}");
+    // UTF-16BE, with a surrogate pair
+    assert_eq!(text(b"\xFE\xFF\x00H\x00i\xD8\x3D\xDE\x00"), "Hi\u{1F600}");
+    // unpaired surrogate and odd length
+    assert_eq!(text(b"\xFE\xFF\xD8\x3D\x00"), "\u{FFFD}\u{FFFD}");
+    assert_eq!(text(b"\xEF\xBB\xBFcaf\xC3\xA9"), "caf\u{E9}");
+}
+
+#[test]
+fn rect() {
+    use pdf::object::Rect;
+    let p = parse(b"[612 792.5 0 -10]", NO_RESOLVE).unwrap();
+    let rect = Rect::from_primitive(p, NO_RESOLVE).unwrap();
+    assert_eq!(rect, Rect {llx: 0., lly: -10., urx: 612., ury: 792.5});
+    assert_eq!(rect.width(), 612.);
+    assert_eq!(rect.height(), 802.5);
+
+    let mut out = Vec::new();
+    rect.serialize(&mut out).unwrap();
+    assert_eq!(out, b"[0 -10 612 792.5]");
+
+    assert!(Rect::from_primitive(parse(b"[0 0 1]", NO_RESOLVE).unwrap(), NO_RESOLVE).is_err());
+}
+
+#[test]
+fn matrix() {
+    use pdf::object::{Matrix, Rect};
+    let p = parse(b"[2 0 0 3 10 20]", NO_RESOLVE).unwrap();
+    let m = Matrix::from_primitive(p, NO_RESOLVE).unwrap();
+    assert_eq!(m, Matrix::scale(2., 3.).then(&Matrix::translate(10., 20.)));
+    assert_eq!(m.transform_point(1., 1.), (12., 23.));
+    assert_eq!(m * Matrix::identity(), m);
+
+    // scale, then move by (1, 0): not the same as the other way around
+    let a = Matrix::scale(2., 2.) * Matrix::translate(1., 0.);
+    let b = Matrix::translate(1., 0.) * Matrix::scale(2., 2.);
+    assert_eq!(a.transform_point(1., 1.), (3., 2.));
+    assert_eq!(b.transform_point(1., 1.), (4., 2.));
+
+    let rotated = Matrix::rotate(::std::f32::consts::FRAC_PI_2).transform_rect(&Rect::new(0., 0., 2., 1.));
+    assert!((rotated.llx + 1.).abs() < 1e-6 && rotated.urx.abs() < 1e-6);
+    assert!(rotated.lly.abs() < 1e-6 && (rotated.ury - 2.).abs() < 1e-6);
+}
+
+/// `objects` parsed, as objects 0, 1, ...
+fn objects(objects: &[&[u8]]) -> Vec<Primitive> {
+    objects.iter().map(|data| parse(data, NO_RESOLVE).unwrap()).collect()
+}
+
+#[test]
+fn name_tree() {
+    use pdf::object::NameTree;
+    let objects = objects(&[
+        b"<< /Kids [1 0 R 2 0 R] >>",
+        b"<< /Limits [(a) (c)] /Names [(a) 1 (c) 3] >>",
+        b"<< /Limits [(d) (f)] /Kids [3 0 R] >>",
+        b"<< /Limits [(d) (f)] /Names [(d) 4 (f) 6] >>",
+        b"<< /Kids [5 0 R] >>",
+        b"<< /Limits [(a) (z)] /Kids [5 0 R] >>",
+    ]);
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+
+    let tree = NameTree::<i32>::from_primitive(objects[0].clone(), &resolve).unwrap();
+    assert_eq!(tree.get(b"c", &resolve).unwrap(), Some(3));
+    assert_eq!(tree.get(b"f", &resolve).unwrap(), Some(6));
+    assert_eq!(tree.get(b"b", &resolve).unwrap(), None);
+    assert_eq!(tree.get(b"z", &resolve).unwrap(), None);
+
+    let entries = tree.entries(&resolve).unwrap();
+    let keys: Vec<&[u8]> = entries.iter().map(|&(ref key, _)| key.as_bytes()).collect();
+    assert_eq!(keys, vec![&b"a"[..], b"c", b"d", b"f"]);
+    assert_eq!(entries.iter().map(|&(_, value)| value).collect::<Vec<_>>(), vec![1, 3, 4, 6]);
+
+    // a node that is its own kid
+    let tree = NameTree::<i32>::from_primitive(objects[4].clone(), &resolve).unwrap();
+    assert!(tree.get(b"b", &resolve).is_err());
+}
+
+#[test]
+fn number_tree() {
+    use pdf::object::NumberTree;
+    let objects = objects(&[
+        b"<< /Kids [1 0 R 2 0 R] >>",
+        b"<< /Limits [0 5] /Nums [0 10 5 15] >>",
+        b"<< /Limits [10 20] /Nums [10 20 20 30] >>",
+    ]);
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+
+    let tree = NumberTree::<i32>::from_primitive(objects[0].clone(), &resolve).unwrap();
+    assert_eq!(tree.get(5, &resolve).unwrap(), Some(15));
+    assert_eq!(tree.get(6, &resolve).unwrap(), None);
+    assert_eq!(tree.get_range(7, &resolve).unwrap(), Some((5, 15)));
+    assert_eq!(tree.get_range(12, &resolve).unwrap(), Some((10, 20)));
+    assert_eq!(tree.get_range(100, &resolve).unwrap(), Some((20, 30)));
+    assert_eq!(tree.get_range(-1, &resolve).unwrap(), None);
+    assert_eq!(tree.entries(&resolve).unwrap(), vec![(0, 10), (5, 15), (10, 20), (20, 30)]);
+}
+
+#[test]
+fn tree_serialization() {
+    use pdf::object::{NameTree, NumberTree, NumberTreeNode};
+    let objects = objects(&[
+        b"<< /Kids [1 0 R 2 0 R] >>",
+        b"<< /Limits [0 5] /Nums [0 10 5 15] >>",
+        b"<< /Limits [(a) (b)] /Names [(a) 1 (b) 2] >>",
+    ]);
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+
+    let mut data = Vec::new();
+    let root = NumberTree::<i32>::from_primitive(objects[0].clone(), &resolve).unwrap();
+    root.serialize(&mut data).unwrap();
+    let root = NumberTree::<i32>::from_primitive(parse(&data, NO_RESOLVE).unwrap(), &resolve).unwrap();
+    assert!(root.limits().is_none());
+    match *root.node() {
+        NumberTreeNode::Intermediate (ref kids) => assert_eq!(kids.len(), 2),
+        _ => panic!("expected an intermediate node"),
+    }
+
+    let mut data = Vec::new();
+    let leaf = NumberTree::<i32>::from_primitive(objects[1].clone(), &resolve).unwrap();
+    leaf.serialize(&mut data).unwrap();
+    let leaf = NumberTree::<i32>::from_primitive(parse(&data, NO_RESOLVE).unwrap(), &resolve).unwrap();
+    assert_eq!(leaf.limits(), Some((0, 5)));
+    assert_eq!(leaf.entries(&resolve).unwrap(), vec![(0, 10), (5, 15)]);
+
+    let mut data = Vec::new();
+    let names = NameTree::<i32>::from_primitive(objects[2].clone(), &resolve).unwrap();
+    names.serialize(&mut data).unwrap();
+    let names = NameTree::<i32>::from_primitive(parse(&data, NO_RESOLVE).unwrap(), &resolve).unwrap();
+    assert_eq!(names.limits().map(|(min, max)| (min.as_bytes().to_vec(), max.as_bytes().to_vec())),
+               Some((b"a".to_vec(), b"b".to_vec())));
+    assert_eq!(names.get(b"b", &resolve).unwrap(), Some(2));
+}
+
+#[test]
+fn inline_images() {
+    use pdf::content::Content;
+    let image = |data: &[u8]| -> Vec<u8> {
+        let content = Content::parse_from(data).unwrap();
+        assert_eq!(content.operations.len(), 2);
+        assert_eq!(content.operations[0].operator, "BI");
+        assert_eq!(content.operations[1].operator, "Q");
+        match content.operations[0].operands[1] {
+            Primitive::String (ref s) => s.as_bytes().to_vec(),
+            ref p => panic!("expected string, found {:?}", p),
+        }
+    };
+    assert_eq!(image(b"BI /W 1 /H 1 /BPC 8 /CS /G ID \xFF EI Q"), b"\xFF");
+    assert_eq!(image(b"BI /W 0 /H 0 ID EI Q"), b"");
+    assert_eq!(image(b"BI /W 0 /H 0 ID\nEI Q"), b"");
+    assert!(Content::parse_from(b"BI /W 0 /H 0 ID").is_err());
+}
+
+#[test]
+fn typed_references() {
+    use pdf::object::Ref;
+    let objects = objects(&[b"null", b"42", b"(not a number)"]);
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+
+    let r = Ref::<i32>::from_id(1);
+    let copy = r;
+    assert_eq!(r.resolve(&resolve).unwrap(), 42);
+    assert!(copy == r);
+    assert!(Ref::<i32>::from_id(2).resolve(&resolve).is_err());
+}
+
+#[test]
+fn maybe_ref() {
+    use pdf::object::MaybeRef;
+    let objects = objects(&[b"null", b"42"]);
+    let resolve = |r: PlainRef| -> pdf::Result<Primitive> { Ok(objects[r.id as usize].clone()) };
+
+    let indirect = MaybeRef::<i32>::from_primitive(parse(b"1 0 R", NO_RESOLVE).unwrap(), &resolve).unwrap();
+    assert_eq!(*indirect, 42);
+    assert_eq!(indirect.get_ref().map(|r| r.get_inner()), Some(PlainRef {id: 1, gen: 0}));
+    let mut out = Vec::new();
+    indirect.serialize(&mut out).unwrap();
+    assert_eq!(out, b"1 0 R");
+
+    let direct = MaybeRef::<i32>::from_primitive(Primitive::Integer (7), &resolve).unwrap();
+    assert!(direct.get_ref().is_none());
+    let mut out = Vec::new();
+    direct.serialize(&mut out).unwrap();
+    assert_eq!(out, b"7");
+}
+
+#[test]
+fn dictionary_accessors() {
+    use pdf::primitive::{Dictionary, PdfString};
+    use pdf::{Error, ErrorKind};
+    let dict = Dictionary::new()
+        .with("Count", 3)
+        .with("Type", Primitive::Name ("Pages".into()))
+        .with("Title", PdfString::new(b"text".to_vec()))
+        .with("Kids", vec![Primitive::Reference (PlainRef {id: 1, gen: 0})]);
+    assert_eq!(dict.get_int("Count").unwrap(), 3);
+    assert_eq!(dict.get_name("Type").unwrap(), "Pages");
+    assert_eq!(dict.get_string("Title").unwrap().as_bytes(), b"text");
+    assert_eq!(dict.get_array("Kids").unwrap().len(), 1);
+    assert_eq!(dict.expect::<i32>("Count", NO_RESOLVE).unwrap(), 3);
+    assert_eq!(dict.get_as::<i32>("Missing", NO_RESOLVE).unwrap(), None);
+
+    match dict.get_int("Missing") {
+        Err(Error(ErrorKind::EntryNotFound {key: "Missing"}, _)) => {}
+        r => panic!("expected EntryNotFound, found {:?}", r),
+    }
+    let e = dict.get_int("Type").unwrap_err();
+    assert!(e.to_string().contains("/Type"));
+}
+
+fn serialized<T: Object>(object: &T) -> String {
+    let mut out = Vec::new();
+    object.serialize(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn write_primitives() {
+    use pdf::primitive::{PdfString, PdfStream, Dictionary};
+    assert_eq!(serialized(&Primitive::Name ("A B#1/".into())), "/A#20B#231#2F");
+    assert_eq!(serialized(&Primitive::String (PdfString::new(b"a(b)\\\n".to_vec()))), r"(a\(b\)\\\n)");
+    assert_eq!(serialized(&Primitive::String (PdfString::new(b"\x00\xFF".to_vec()))), "<00FF>");
+    assert_eq!(serialized(&Primitive::Number (0.5)), "0.5");
+    assert_eq!(serialized(&Primitive::Number (1e10)), "10000000000");
+    assert_eq!(serialized(&Primitive::Number (1e-3)), "0.001");
+    assert_eq!(serialized(&Primitive::Array (vec![Primitive::Integer (1), Primitive::Null])), "[1 null]");
+
+    let stream = PdfStream {info: Dictionary::new(), data: b"abc".to_vec()};
+    assert_eq!(serialized(&stream), "<< /Length 3 >>\nstream\nabc\nendstream\n");
+
+    // /Length is written once - for the data, even if `info` has one too
+    let mut stream = pdf::object::Stream::<Dictionary>::from_primitive(
+        parse(b"<< /Length 3 /A 1 >>\nstream\nabc\nendstream", NO_RESOLVE).unwrap(), NO_RESOLVE).unwrap();
+    stream.info.insert("Length", Primitive::Integer (99));
+    assert_eq!(serialized(&stream), "<<\n/Length 3\n/A 1\n>>\nstream\nabc\nendstream\n");
+
+    // what is written is read back the same
+    let data = b"<< /A [1 2.5 /N#20ame (s\\)) <00FF>] /B 3 0 R /C << /D true >> >>";
+    let written = serialized(&parse(data, NO_RESOLVE).unwrap());
+    assert_eq!(written, "<< /A [1 2.5 /N#20ame (s\\)) <00FF>] /B 3 0 R /C << /D true >> >>");
+    assert_eq!(serialized(&parse(written.as_bytes(), NO_RESOLVE).unwrap()), written);
+}
+
+#[test]
+fn to_primitive() {
+    use pdf::object::{Rect, Ref};
+    use pdf::primitive::Dictionary;
+    let rect = Rect::new(0., 0., 100., 200.);
+    let p = rect.to_primitive().unwrap();
+    assert_eq!(serialized(&p), "[0 0 100 200]");
+    assert_eq!(Rect::from_primitive(p, NO_RESOLVE).unwrap(), rect);
+
+    assert_eq!(serialized(&vec![Some(1), None].to_primitive().unwrap()), "[1 null]");
+    assert_eq!(serialized(&Ref::<i32>::from_id(3).to_primitive().unwrap()), "3 0 R");
+    let date = Date::parse(b"D:20170102030405+01'00'").unwrap();
+    assert_eq!(Date::from_primitive(date.to_primitive().unwrap(), NO_RESOLVE).unwrap(), date);
+
+    let dict = Dictionary::new().with("A", 1).with("B", Primitive::Name ("N".into()));
+    match dict.to_primitive().unwrap() {
+        Primitive::Dictionary (d) => assert_eq!(serialized(&d), serialized(&dict)),
+        p => panic!("expected Dictionary, found {:?}", p),
+    }
+}
+
+#[test]
+fn type_check() {
+    use pdf::object::check_type;
+    use pdf::parser::TypeCheck;
+    use pdf::primitive::Dictionary;
+    use pdf::{Error, ErrorKind};
+    let dict = Dictionary::new().with("Type", Primitive::Name ("XObject".into()));
+    let strict = ParseOptions::strict();
+    assert!(check_type(&dict, "Type", "XObject", &strict).is_ok());
+    match check_type(&dict, "Type", "ObjStm", &strict) {
+        Err(Error(ErrorKind::WrongDictionaryType {..}, _)) => {}
+        r => panic!("expected WrongDictionaryType, found {:?}", r),
+    }
+    match check_type(&dict, "Subtype", "Form", &strict) {
+        Err(Error(ErrorKind::EntryNotFound {key: "Subtype"}, _)) => {}
+        r => panic!("expected EntryNotFound, found {:?}", r),
+    }
+    assert!(check_type(&dict, "Type", "ObjStm", &ParseOptions::lenient()).is_ok());
+    assert!(check_type(&dict, "Subtype", "Form", &strict.with_type_check(TypeCheck::Skip)).is_ok());
+}
+
+#[test]
+fn derived_type_checks() {
+    use pdf::object::{Stream, ObjStmInfo, XObject};
+    use pdf::parser::TypeCheck;
+    use pdf::{Error, ErrorKind};
+    let missing: &[u8] = b"<< /N 0 /First 0 /Length 1 >>\nstream\nx\nendstream";
+    let wrong: &[u8] = b"<< /Type /XRef /N 0 /First 0 /Length 1 >>\nstream\nx\nendstream";
+    // (an XObject without /Type)
+    let image: &[u8] = b"<< /Subtype /Image /Width 1 /Height 1 /BitsPerComponent 8 /Length 1 >>\nstream\nx\nendstream";
+    for &type_check in &[TypeCheck::Error, TypeCheck::Tolerate, TypeCheck::Skip] {
+        let resolve = Resolver::new(|_: PlainRef| -> pdf::Result<Primitive> { Ok(Primitive::Null) },
+            ParseOptions::strict().with_type_check(type_check));
+        let obj_stm = |data: &[u8]| Stream::<ObjStmInfo>::from_primitive(parse(data, &resolve).unwrap(), &resolve);
+        let xobject = XObject::from_primitive(parse(image, &resolve).unwrap(), &resolve);
+        if type_check == TypeCheck::Error {
+            match obj_stm(missing) {
+                Err(Error(ErrorKind::EntryNotFound {key: "Type"}, _)) => {}
+                Err(e) => panic!("expected EntryNotFound, found {}", e),
+                Ok(_) => panic!("expected EntryNotFound"),
+            }
+            match obj_stm(wrong) {
+                Err(Error(ErrorKind::WrongDictionaryType {..}, _)) => {}
+                Err(e) => panic!("expected WrongDictionaryType, found {}", e),
+                Ok(_) => panic!("expected WrongDictionaryType"),
+            }
+            assert!(xobject.is_err());
+        } else {
+            assert!(obj_stm(missing).is_ok(), "{:?}", type_check);
+            assert!(obj_stm(wrong).is_ok(), "{:?}", type_check);
+            match xobject {
+                Ok(XObject::Image (_)) => {}
+                Ok(_) => panic!("expected an image"),
+                Err(e) => panic!("{:?}: {}", type_check, e),
+            }
+        }
+    }
+}
+
+#[test]
+fn numbers() {
+    use pdf::object::Number;
+    use pdf::{Error, ErrorKind};
+    let lenient = Resolver::new(|_| Err(ErrorKind::FollowReference.into()), ParseOptions::lenient());
+    assert_eq!(i32::from_primitive(Primitive::Number (3.0), NO_RESOLVE).unwrap(), 3);
+    assert!(i32::from_primitive(Primitive::Number (2.5), NO_RESOLVE).is_err());
+    assert_eq!(i32::from_primitive(Primitive::Number (2.6), &lenient).unwrap(), 3);
+    match i32::from_primitive(Primitive::Number (1e10), &lenient) {
+        Err(Error(ErrorKind::NumberOutOfRange {..}, _)) => {}
+        r => panic!("expected NumberOutOfRange, found {:?}", r),
+    }
+    assert!(usize::from_primitive(Primitive::Integer (-1), NO_RESOLVE).is_err());
+    assert_eq!(f32::from_primitive(Primitive::Integer (2), NO_RESOLVE).unwrap(), 2.);
+    assert_eq!(f64::from_primitive(Primitive::Number (0.5), NO_RESOLVE).unwrap(), 0.5);
+
+    let n = Number::from_primitive(Primitive::Integer (612), NO_RESOLVE).unwrap();
+    assert_eq!(n, Number (612.));
+    assert_eq!(n.to_primitive().unwrap().as_integer().unwrap(), 612);
+    assert_eq!(serialized(&Number (0.25)), "0.25");
+}
+
+#[test]
+fn null_entries() {
+    use pdf::object::Entry;
+    let dict = match parse(b"<< /A 1 /B null >>", NO_RESOLVE).unwrap() {
+        Primitive::Dictionary (dict) => dict,
+        p => panic!("expected Dictionary, found {:?}", p),
+    };
+    assert_eq!(dict.get_entry::<Entry<i32>>("A", NO_RESOLVE).unwrap(), Entry::Value (1));
+    assert_eq!(dict.get_entry::<Entry<i32>>("B", NO_RESOLVE).unwrap(), Entry::Null);
+    assert_eq!(dict.get_entry::<Entry<i32>>("C", NO_RESOLVE).unwrap(), Entry::Missing);
+    // for `Option` both are `None`
+    assert_eq!(dict.get_entry::<Option<i32>>("B", NO_RESOLVE).unwrap(), None);
+    assert_eq!(dict.get_entry::<Option<i32>>("C", NO_RESOLVE).unwrap(), None);
+    assert_eq!(serialized(&Entry::<i32>::Missing), "null");
+}
+
+#[test]
+fn object_stream_bounds() {
+    use pdf::object::ObjectStream;
+    use pdf::{Error, ErrorKind};
+    let object_stream = |first: i32, data: &str| {
+        let object = format!("<< /Type /ObjStm /N 2 /First {} /Length {} >>\nstream\n{}\nendstream", first, data.len(), data);
+        ObjectStream::from_primitive(parse(object.as_bytes(), NO_RESOLVE).unwrap(), NO_RESOLVE)
+    };
+    let stream = object_stream(8, "1 0 2 4\n(a) (b)").unwrap();
+    assert_eq!(stream.get_object_slice(1).unwrap(), b"(b)");
+
+    // a negative /First, or one past the data
+    assert!(object_stream(-5, "1 0 2 4\n(a) (b)").is_err());
+    assert!(object_stream(100, "1 0 2 4\n(a) (b)").is_err());
+    // offsets past the data, and not in order
+    for data in &["1 0 2 100\n(a) (b)", "1 5 2 0\n(a) (b)"] {
+        let stream = object_stream(10, data).unwrap();
+        match stream.get_object_slice(0) {
+            Err(Error(ErrorKind::ObjStmOutOfBounds {..}, _)) => {}
+            r => panic!("expected ObjStmOutOfBounds, found {:?}", r),
+        }
+    }
+}
+
+#[test]
+fn derived_objects() {
+    use pdf::object::ObjStmInfo;
+    use pdf::enc::LZWFlateParams;
+    // the type names and the fields are written - but not those that are `None`
+    let info = ObjStmInfo {num_objects: 2, first: 10, extends: None};
+    assert_eq!(serialized(&info), "<< /First 10 /N 2 /Type /ObjStm >>");
+    let read = ObjStmInfo::from_primitive(info.to_primitive().unwrap(), NO_RESOLVE).unwrap();
+    assert_eq!((read.num_objects, read.first, read.extends), (2, 10, None));
+
+    let params = LZWFlateParams {predictor: 12, columns: 5, .. LZWFlateParams::default()};
+    let written = serialized(&params);
+    let read = LZWFlateParams::from_primitive(parse(written.as_bytes(), NO_RESOLVE).unwrap(), NO_RESOLVE).unwrap();
+    assert_eq!((read.predictor, read.columns, read.n_components), (12, 5, 1));
+}
+
+/// A derived object with `#[pdf(other)]` (the derive needs these names in scope)
+mod derived {
+    use pdf::object::*;
+    use pdf::primitive::*;
+    use pdf::{Result, ResultExt};
+
+    #[derive(Object)]
+    #[pdf(Type="Example")]
+    pub struct Example {
+        #[pdf(key="Count")]
+        pub count: i32,
+        #[pdf(other)]
+        pub other: Dictionary,
+    }
+}
+
+#[test]
+fn other_entries() {
+    use derived::Example;
+    let p = parse(b"<< /Type /Example /Count 2 /Producer (x) /Custom [1 2] >>", NO_RESOLVE).unwrap();
+    let mut example = Example::from_primitive(p, NO_RESOLVE).unwrap();
+    assert_eq!(example.count, 2);
+    // (just the entries that aren't fields, or the type)
+    let keys: Vec<&str> = example.other.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, vec!["Custom", "Producer"]);
+
+    // and written back - the fields win over entries of `other` with the same key
+    example.other.insert("Count", Primitive::Integer (5));
+    assert_eq!(serialized(&example), "<< /Count 2 /Custom [1 2] /Producer (x) /Type /Example >>");
+}
+
+#[test]
+fn name_enums() {
+    use pdf::object::RenderingIntent;
+    let intent = |data: &[u8]| RenderingIntent::from_primitive(parse(data, NO_RESOLVE).unwrap(), NO_RESOLVE);
+    assert_eq!(intent(b"/Perceptual").unwrap(), RenderingIntent::Perceptual);
+    assert_eq!(intent(b"/Custom").unwrap(), RenderingIntent::Other ("Custom".into()));
+    assert!(intent(b"1").is_err());
+    assert_eq!(serialized(&RenderingIntent::Saturation), "/Saturation");
+    assert_eq!(serialized(&RenderingIntent::Other ("Custom".into())), "/Custom");
+    assert_eq!(RenderingIntent::Other ("Custom".into()).to_primitive().unwrap().to_name().unwrap(), "Custom");
+}
+
+#[test]
+fn validate_entries() {
+    use pdf::object::{check_min, check_max, check_one_of, check_together};
+    use pdf::primitive::Dictionary;
+    use pdf::{Error, ErrorKind};
+    assert!(check_min("Width", 1, 1).is_ok());
+    match check_min("Width", 0, 1) {
+        Err(Error(ErrorKind::InvalidEntry {key: "Width", ..}, _)) => {}
+        r => panic!("expected InvalidEntry, found {:?}", r),
+    }
+    assert!(check_max("Rotate", 360, 270).is_err());
+    assert!(check_one_of("BitsPerComponent", &8, &[1, 2, 4, 8, 16]).is_ok());
+    let e = check_one_of("BitsPerComponent", &3, &[1, 2, 4, 8, 16]).unwrap_err();
+    assert!(e.to_string().contains("/BitsPerComponent"));
+
+    let dict = Dictionary::new().with("Width", 10);
+    assert!(check_together(&dict, &["Width", "Height"]).is_err());
+    assert!(check_together(&dict.clone().with("Height", 5), &["Width", "Height"]).is_ok());
+    assert!(check_together(&Dictionary::new(), &["Width", "Height"]).is_ok());
+
+    // as checked by image XObjects
+    use pdf::object::XObject;
+    let image = |entries: &str| {
+        let data = format!("<< /Type /XObject /Subtype /Image {} /Length 1 >>\nstream\nx\nendstream", entries);
+        XObject::from_primitive(parse(data.as_bytes(), NO_RESOLVE).unwrap(), NO_RESOLVE)
+    };
+    assert!(image("/Width 1 /Height 1 /BitsPerComponent 8").is_ok());
+    match image("/Width 1 /Height 1 /BitsPerComponent 3") {
+        Err(Error(ErrorKind::InvalidEntry {key: "BitsPerComponent", ..}, _)) => {}
+        Err(e) => panic!("expected InvalidEntry, found {}", e),
+        Ok(_) => panic!("expected InvalidEntry"),
+    }
+    assert!(image("/Width 0 /Height 1 /BitsPerComponent 8").is_err());
+}
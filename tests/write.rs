@@ -12,12 +12,7 @@ fn main() {
     let page_tree_promise = file.promise();
     let mut page_tree = PageTree::root();
     let mut page = Page::new((&page_tree_promise).into());
-    page.media_box = Some(Rect {
-        left: 0.,
-        right: 100.,
-        top: 0.,
-        bottom: 200.
-    });
+    page.media_box = Some(Rect::new(0., 0., 100., 200.));
     
     // create the content stream
     let content = ObjectStream::new(&mut file);
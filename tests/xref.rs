@@ -0,0 +1,44 @@
+extern crate pdf;
+
+use pdf::xref::{XRef, XRefTable, XRefSection};
+use pdf::object::PlainRef;
+
+/// Objects 1 and 3 are free (in this order in the free list), 2 and 4 in use. Object 3 can't
+/// be reused.
+fn table() -> XRefTable {
+    let mut section = XRefSection::new(0);
+    section.add_free_entry(1, 65535);
+    section.add_free_entry(3, 2);
+    section.add_inuse_entry(100, 0);
+    section.add_free_entry(0, 65535);
+    section.add_inuse_entry(200, 0);
+    let mut refs = XRefTable::new(5);
+    refs.add_revision(vec![section]);
+    refs
+}
+
+#[test]
+fn free_list() {
+    let refs = table();
+    assert_eq!(refs.free_list(), vec![(1, 2), (3, 65535)]);
+}
+
+#[test]
+fn allocate() {
+    let mut refs = table();
+    assert_eq!(refs.allocate(), PlainRef {id: 1, gen: 2});
+    assert_eq!(refs.free_list(), vec![(3, 65535)]);
+    // object 3 is used up
+    assert_eq!(refs.allocate(), PlainRef {id: 5, gen: 0});
+}
+
+#[test]
+fn free() {
+    let mut refs = table();
+    refs.free(2);
+    assert_eq!(refs.free_list(), vec![(2, 1), (1, 2), (3, 65535)]);
+    match refs.get(2).unwrap() {
+        XRef::Free {gen_nr: 1, ..} => {}
+        e => panic!("expected free entry, found {:?}", e),
+    }
+}